@@ -0,0 +1,183 @@
+// Which of the script's alerts have already been told about, kept in
+// alerts.json so restarting `watch` or the daemon doesn't tell about them
+// again. Alerts go by id, the condition's name or the one the script gives,
+// so a message carrying the latest numbers is still the same alert. An alert
+// is told about when it's first raised; it's raised again, and so told about
+// again, once it has cleared for a refresh. With
+// `alert_repeat_hours` in config it's also repeated that often while it stays
+// raised.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::lock;
+use crate::profile;
+
+// Written out as just the message, which is all `stocks metrics` shows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(into = "String")]
+pub struct Alert {
+    pub id: String,
+    pub message: String,
+}
+
+impl Alert {
+    // an alert that says what it is, like a condition's name
+    pub fn named(name: &str) -> Self {
+        Alert {
+            id: name.to_string(),
+            message: name.to_string(),
+        }
+    }
+}
+
+impl From<Alert> for String {
+    fn from(a: Alert) -> Self {
+        a.message
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fired {
+    // raised since, without clearing in between
+    pub since: DateTime<Utc>,
+    pub notified: DateTime<Utc>,
+}
+
+fn alerts_path() -> String {
+    format!("{}/alerts.json", profile::data_dir())
+}
+
+pub fn load() -> BTreeMap<String, Fired> {
+    fs::read_to_string(alerts_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+// Brings the state up to date with what's raised now, returning the messages
+// to tell about, in the order raised. Anything no longer raised is dropped,
+// which re-arms it.
+pub fn update(
+    state: &mut BTreeMap<String, Fired>,
+    raised: &[Alert],
+    now: DateTime<Utc>,
+    repeat: Option<Duration>,
+) -> Vec<String> {
+    state.retain(|id, _| raised.iter().any(|a| &a.id == id));
+    let mut notify = vec![];
+    for (i, a) in raised.iter().enumerate() {
+        // the same id twice in one run is one alert
+        if raised[..i].iter().any(|b| b.id == a.id) {
+            continue;
+        }
+        match state.get_mut(&a.id) {
+            Some(f) if repeat.is_some_and(|r| now - f.notified >= r) => {
+                f.notified = now;
+                notify.push(a.message.clone());
+            }
+            Some(_) => {}
+            None => {
+                state.insert(
+                    a.id.clone(),
+                    Fired {
+                        since: now,
+                        notified: now,
+                    },
+                );
+                notify.push(a.message.clone());
+            }
+        }
+    }
+    notify
+}
+
+// update() against alerts.json, held locked so a watch and the daemon
+// running together agree on what's been told about
+pub fn fresh(raised: &[Alert], conf: &Config, now: DateTime<Utc>) -> Result<Vec<String>, String> {
+    let path = alerts_path();
+    fs::create_dir_all(profile::data_dir())
+        .map_err(|e| format!("could not create {}: {}", profile::data_dir(), e))?;
+    let _lock = lock::lock(&path)?;
+    let mut state = load();
+    let before = state.clone();
+    let repeat = conf.alert_repeat_hours.map(Duration::hours);
+    let notify = update(&mut state, raised, now, repeat);
+    if state != before {
+        lock::write_atomic(
+            &path,
+            serde_json::to_string_pretty(&state).unwrap().as_bytes(),
+        )
+        .map_err(|e| format!("could not write {}: {}", path, e))?;
+    }
+    Ok(notify)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn alerts_are_told_once_until_they_clear_or_repeat() {
+        let down = Alert::named("down 10%");
+        let big = Alert::named("NVDA over 20%");
+        let mut state = BTreeMap::new();
+
+        let now = [down.clone(), big.clone()];
+        assert_eq!(
+            update(&mut state, &now, at(1), None),
+            ["down 10%", "NVDA over 20%"]
+        );
+        assert!(update(&mut state, &now, at(2), None).is_empty());
+
+        // clearing re-arms it, and it's raised again from then
+        assert!(update(&mut state, &now[1..], at(3), None).is_empty());
+        assert_eq!(update(&mut state, &now, at(4), None), ["down 10%"]);
+        assert_eq!(state[&down.id].since, at(4));
+        assert_eq!(state[&big.id].since, at(1));
+
+        // with repeats every 3 hours, the one raised at 1 is due again
+        let repeat = Some(Duration::hours(3));
+        assert_eq!(update(&mut state, &now, at(5), repeat), ["NVDA over 20%"]);
+        assert_eq!(state[&big.id].notified, at(5));
+        assert!(update(&mut state, &now, at(6), repeat).is_empty());
+
+        let twice = [down.clone(), down];
+        assert_eq!(
+            update(&mut BTreeMap::new(), &twice, at(1), None),
+            ["down 10%"]
+        );
+    }
+
+    #[test]
+    fn alerts_go_by_id_while_the_message_changes() {
+        let down = |pct: f64| Alert {
+            id: "down".to_string(),
+            message: format!("down {}%", pct),
+        };
+        let mut state = BTreeMap::new();
+
+        assert_eq!(
+            update(&mut state, &[down(-10.5)], at(1), None),
+            ["down -10.5%"]
+        );
+        assert!(update(&mut state, &[down(-11.2)], at(2), None).is_empty());
+        assert_eq!(state["down"].since, at(1));
+
+        // repeats carry the latest numbers
+        let repeat = Some(Duration::hours(2));
+        assert_eq!(
+            update(&mut state, &[down(-12.0)], at(3), repeat),
+            ["down -12%"]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chart::size;
+use crate::glyph;
+use crate::money::Money;
+use crate::report::{sum, Report, Row};
+
+pub const CASH: &str = "Cash";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Slice {
+    pub name: String,
+    pub value: Money,
+    // of the account it's in, or of the portfolio for the combined view
+    pub pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountAlloc {
+    pub name: String,
+    pub total: Money,
+    // of the whole portfolio
+    pub pct: f64,
+    pub slices: Vec<Slice>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Allocation {
+    pub accounts: Vec<AccountAlloc>,
+    // a symbol held in several accounts counts once here
+    pub portfolio: Vec<Slice>,
+    pub total: Money,
+}
+
+fn largest_first(slices: &mut [Slice]) {
+    slices.sort_by(|a, b| b.value.amount.total_cmp(&a.value.amount));
+}
+
+pub fn build(report: &Report) -> Allocation {
+    build_by(report, |r| (r.symbol.clone(), r.label.clone()))
+}
+
+// Slices by what `group` puts each position under, e.g. its fund category,
+// rather than one per symbol
+pub fn build_by(report: &Report, group: impl Fn(&Row) -> (String, String)) -> Allocation {
+    let total = report.totals.total.clone();
+    let mut portfolio: Vec<(String, Slice)> = vec![];
+
+    let accounts = report
+        .accounts
+        .iter()
+        .map(|a| {
+            let account_total = a.total();
+            let mut slices: Vec<(String, Slice)> = vec![];
+            for r in a.rows.iter().filter(|r| !r.failed) {
+                let (key, name) = group(r);
+                add(&mut portfolio, &key, &name, &r.value, 0.0);
+                add(&mut slices, &key, &name, &r.value, r.account_weight);
+            }
+            if let Some(cash) = &a.cash {
+                add(&mut portfolio, CASH, CASH, cash, 0.0);
+                add(&mut slices, CASH, CASH, cash, cash.pct_of(&account_total));
+            }
+            let mut slices = slices.into_iter().map(|(_, s)| s).collect::<Vec<_>>();
+            largest_first(&mut slices);
+
+            AccountAlloc {
+                name: a.name.clone(),
+                pct: account_total.pct_of(&total),
+                total: account_total,
+                slices,
+            }
+        })
+        .collect();
+
+    let mut portfolio = portfolio
+        .into_iter()
+        .map(|(_, mut s)| {
+            s.pct = s.value.pct_of(&total);
+            s
+        })
+        .collect::<Vec<_>>();
+    largest_first(&mut portfolio);
+
+    Allocation {
+        accounts,
+        portfolio,
+        total,
+    }
+}
+
+fn add(slices: &mut Vec<(String, Slice)>, key: &str, name: &str, value: &Money, pct: f64) {
+    match slices.iter_mut().find(|(k, _)| k == key) {
+        Some((_, s)) => {
+            s.value = sum([s.value.clone(), value.clone()].into_iter());
+            s.pct += pct;
+        }
+        None => slices.push((
+            key.to_string(),
+            Slice {
+                name: name.to_string(),
+                value: value.clone(),
+                pct,
+            },
+        )),
+    }
+}
+
+fn slices(out: &mut dyn Write, slices: &[Slice], w: usize, room: usize) -> io::Result<()> {
+    for s in slices.iter() {
+        let len = if s.pct.is_finite() {
+            (s.pct.clamp(0.0, 100.0) / 100.0 * room as f64).round() as usize
+        } else {
+            0
+        };
+        writeln!(
+            out,
+            "\t{:<w$}  {:>10}  {:>6.2}%  {}",
+            s.name,
+            s.value,
+            s.pct,
+            glyph::pick("█", "#").repeat(len)
+        )?;
+    }
+    Ok(())
+}
+
+pub fn render(a: &Allocation, out: &mut dyn Write) -> io::Result<()> {
+    let w = a
+        .accounts
+        .iter()
+        .flat_map(|a| a.slices.iter())
+        .map(|s| s.name.chars().count())
+        .fold(6, usize::max);
+    let (cols, _) = size();
+    let room = cols.saturating_sub(w + 32).max(10);
+
+    for acct in a.accounts.iter() {
+        writeln!(
+            out,
+            "\x1b[1m{}\x1b[0m: {} ({:.2}% of the portfolio)",
+            acct.name, acct.total, acct.pct
+        )?;
+        slices(out, &acct.slices, w, room)?;
+    }
+
+    if a.accounts.len() > 1 {
+        writeln!(out, "\x1b[1mPortfolio\x1b[0m: {}", a.total)?;
+        slices(out, &a.portfolio, w, room)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+// What drove the return over a span: each symbol's, account's or sector's
+// contribution, its weight times its return, from the daily snapshots. Each
+// day's gains are on the shares held at the day before's close, so buying
+// and selling along the way doesn't count as return, and the days are
+// linked so the contributions add up to the portfolio's return.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::render::clr;
+use crate::snapshot::Snapshot;
+use crate::time;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Part {
+    pub name: String,
+    // average share of the portfolio over the span, in percent
+    pub weight: f64,
+    pub return_pct: f64,
+    // in percentage points of the portfolio's return
+    pub contribution: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attribution {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub return_pct: f64,
+    // largest contribution first
+    pub parts: Vec<Part>,
+}
+
+#[derive(Default)]
+struct Acc {
+    weight: f64,
+    growth: f64,
+    contribution: f64,
+}
+
+// From the last snapshot on or before `from` to the latest. `group` says what
+// a symbol counts towards, split by fractions adding up to 1.
+pub fn build(
+    snaps: &[Snapshot],
+    from: NaiveDate,
+    group: impl Fn(&str) -> Vec<(String, f64)>,
+) -> Result<Attribution, String> {
+    let mut days = BTreeMap::new();
+    for s in snaps.iter() {
+        days.insert(time::date(&s.time), s);
+    }
+    let start = days
+        .range(..=from)
+        .next_back()
+        .or(days.iter().next())
+        .map(|(d, _)| *d)
+        .ok_or("no history yet, each run of `stocks` records a snapshot")?;
+    let days = days.range(start..).collect::<Vec<_>>();
+    if days.len() < 2 {
+        return Err(format!(
+            "not enough history since {} to attribute, it needs two days of snapshots",
+            start
+        ));
+    }
+
+    let mut groups: BTreeMap<String, Acc> = BTreeMap::new();
+    // the portfolio's return so far, what each day's contribution compounds on
+    let mut growth = 1.0;
+    for pair in days.windows(2) {
+        let (prev, cur) = (pair[0].1, pair[1].1);
+        let total = prev
+            .symbols
+            .values()
+            .map(|h| h.amount * h.price)
+            .sum::<f64>();
+        if total <= 0.0 {
+            continue;
+        }
+
+        let mut day: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+        for (symbol, h) in prev.symbols.iter() {
+            let value = h.amount * h.price;
+            // gone the next day, sold at a price that wasn't recorded
+            let gain = cur
+                .symbols
+                .get(symbol)
+                .filter(|c| c.price > 0.0)
+                .map_or(0.0, |c| h.amount * (c.price - h.price));
+            for (name, share) in group(symbol) {
+                let g = day.entry(name).or_default();
+                g.0 += value * share;
+                g.1 += gain * share;
+            }
+        }
+
+        let mut day_return = 0.0;
+        for (name, (value, gain)) in day {
+            let g = groups.entry(name).or_insert(Acc {
+                growth: 1.0,
+                ..Default::default()
+            });
+            g.weight += value / total;
+            if value > 0.0 {
+                g.growth *= 1.0 + gain / value;
+            }
+            g.contribution += gain / total * growth;
+            day_return += gain / total;
+        }
+        growth *= 1.0 + day_return;
+    }
+
+    let n = (days.len() - 1) as f64;
+    let mut parts = groups
+        .into_iter()
+        .map(|(name, g)| Part {
+            name,
+            weight: g.weight * 100.0 / n,
+            return_pct: (g.growth - 1.0) * 100.0,
+            contribution: g.contribution * 100.0,
+        })
+        .collect::<Vec<_>>();
+    parts.sort_by(|a, b| b.contribution.total_cmp(&a.contribution));
+
+    Ok(Attribution {
+        from: start,
+        to: *days[days.len() - 1].0,
+        return_pct: (growth - 1.0) * 100.0,
+        parts,
+    })
+}
+
+pub fn render(a: &Attribution, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "from {} to {}, return {}{:+.2}%\x1b[0m",
+        a.from.format("%Y-%m-%d"),
+        a.to.format("%Y-%m-%d"),
+        clr(a.return_pct),
+        a.return_pct
+    )?;
+    if a.parts.is_empty() {
+        return writeln!(out, "no positions recorded over the span");
+    }
+    let w = a
+        .parts
+        .iter()
+        .map(|p| p.name.chars().count())
+        .fold(8, usize::max);
+    writeln!(
+        out,
+        "\x1b[1m\t{:<w$}  {:>8}  {:>9}  {:>12}\x1b[0m",
+        "", "Weight", "Return", "Contribution"
+    )?;
+    for p in a.parts.iter() {
+        writeln!(
+            out,
+            "\t{:<w$}  {:>7.2}%  {}{:>+8.2}%  {:>+12.2}\x1b[0m",
+            p.name,
+            p.weight,
+            clr(p.return_pct),
+            p.return_pct,
+            p.contribution
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Holding;
+    use chrono::{TimeZone, Utc};
+
+    fn snap(day: u32, symbols: &[(&str, f64, f64)]) -> Snapshot {
+        Snapshot {
+            time: Utc.with_ymd_and_hms(2024, 3, day, 15, 0, 0).unwrap(),
+            total: 0.0,
+            accounts: BTreeMap::new(),
+            symbols: symbols
+                .iter()
+                .map(|(s, n, p)| {
+                    (
+                        s.to_string(),
+                        Holding {
+                            amount: *n,
+                            price: *p,
+                        },
+                    )
+                })
+                .collect(),
+            currency: None,
+        }
+    }
+
+    #[test]
+    fn contributions_add_up_to_the_return() {
+        let snaps = [
+            snap(1, &[("VTI", 10.0, 100.0), ("BND", 10.0, 100.0)]),
+            snap(2, &[("VTI", 10.0, 110.0), ("BND", 10.0, 100.0)]),
+            // buying more BND isn't return
+            snap(3, &[("VTI", 10.0, 110.0), ("BND", 20.0, 90.0)]),
+            snap(4, &[("VTI", 10.0, 121.0), ("BND", 20.0, 90.0)]),
+        ];
+        let by_symbol = |s: &str| vec![(s.to_string(), 1.0)];
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let a = build(&snaps, day(1), by_symbol).unwrap();
+        assert_eq!((a.from, a.to), (day(1), day(4)));
+
+        let vti = &a.parts[0];
+        assert_eq!(vti.name, "VTI");
+        assert!((vti.return_pct - 21.0).abs() < 1e-9);
+        let sum = a.parts.iter().map(|p| p.contribution).sum::<f64>();
+        assert!((sum - a.return_pct).abs() < 1e-9);
+        let bnd = &a.parts[1];
+        assert!((bnd.return_pct + 10.0).abs() < 1e-9);
+        assert!(bnd.contribution < 0.0);
+
+        // put together, it's the whole portfolio
+        let a = build(&snaps, day(1), |_| vec![("All".to_string(), 1.0)]).unwrap();
+        assert!((a.parts[0].weight - 100.0).abs() < 1e-9);
+        assert!((a.parts[0].contribution - a.return_pct).abs() < 1e-9);
+
+        assert!(build(&snaps[..1], day(1), by_symbol).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+// Provider API keys kept in the OS keyring rather than config.toml. There is
+// no keyring crate here; the platform tools are used directly, secret-tool
+// for the Secret Service on Linux and security for the macOS Keychain.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+
+const SERVICE: &str = "stocks";
+
+// `api_keys` in config.toml wins, typically as "${SOME_KEY}", then the keyring
+pub fn api_key(conf: &Config, provider: &str) -> Option<String> {
+    if let Some(k) = conf.api_keys.get(provider) {
+        return Some(k.clone());
+    }
+    lookup(provider).ok().flatten()
+}
+
+fn run(cmd: &mut Command, input: Option<&str>) -> Result<String, String> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "could not run {}: {}",
+                cmd.get_program().to_string_lossy(),
+                e
+            )
+        })?;
+
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+
+    let out = child.wait_with_output().map_err(|e| e.to_string())?;
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+    }
+}
+
+// The key goes in on stdin, as a command to `security -i`, since -w with the
+// key would show it to anyone running ps. Errors there don't fail the exit
+// status, so it's read back to check.
+#[cfg(target_os = "macos")]
+pub fn store(provider: &str, key: &str) -> Result<(), String> {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let command = format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        quote(SERVICE),
+        quote(provider),
+        quote(key)
+    );
+    run(Command::new("security").arg("-i"), Some(&command))?;
+    match lookup(provider)? {
+        Some(k) if k == key => Ok(()),
+        _ => Err(format!("the keychain didn't keep the {} key", provider)),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn lookup(provider: &str) -> Result<Option<String>, String> {
+    // a missing item is a failed exit, which is an answer rather than an error
+    Ok(run(
+        Command::new("security").args([
+            "find-generic-password",
+            "-s",
+            SERVICE,
+            "-a",
+            provider,
+            "-w",
+        ]),
+        None,
+    )
+    .ok())
+}
+
+#[cfg(target_os = "macos")]
+pub fn delete(provider: &str) -> Result<(), String> {
+    run(
+        Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", provider]),
+        None,
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn store(provider: &str, key: &str) -> Result<(), String> {
+    let label = format!("stocks {} api key", provider);
+    run(
+        Command::new("secret-tool").args([
+            "store", "--label", &label, "service", SERVICE, "provider", provider,
+        ]),
+        Some(key),
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn lookup(provider: &str) -> Result<Option<String>, String> {
+    let res = run(
+        Command::new("secret-tool").args(["lookup", "service", SERVICE, "provider", provider]),
+        None,
+    );
+    // nothing stored fails quietly, a real problem says why
+    match res {
+        Ok(k) => Ok(Some(k).filter(|k| !k.is_empty())),
+        Err(e) if e.is_empty() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn delete(provider: &str) -> Result<(), String> {
+    run(
+        Command::new("secret-tool").args(["clear", "service", SERVICE, "provider", provider]),
+        None,
+    )
+    .map(|_| ())
+}
+
+// Reads the key from stdin, without echo when it's a terminal
+pub fn read_key(provider: &str) -> Result<String, String> {
+    let tty = io::stdin().is_terminal();
+    if tty {
+        eprint!("API key for {}: ", provider);
+        echo(false);
+    }
+
+    let mut key = String::new();
+    let res = io::stdin().lock().read_line(&mut key);
+
+    if tty {
+        echo(true);
+        eprintln!();
+    }
+    res.map_err(|e| format!("could not read the key: {}", e))?;
+
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err("no key given".to_string());
+    }
+    Ok(key)
+}
+
+fn echo(on: bool) {
+    let _ = Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .status();
+}
@@ -0,0 +1,183 @@
+// What the portfolio is measured against: one symbol, or a blend named in
+// config like `[benchmarks] balanced = { VTI = 70, BND = 30 }`, held at its
+// weights and rebalanced back to them every day.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDate;
+use futures::future::join_all;
+
+use crate::config::{Closes, Config};
+use crate::nasdaq::get_series;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Benchmark {
+    pub name: String,
+    // symbols and their share, adding up to 1
+    pub parts: Vec<(String, f64)>,
+}
+
+pub fn check(benchmarks: &BTreeMap<String, BTreeMap<String, f64>>) -> Result<(), String> {
+    for (name, parts) in benchmarks.iter() {
+        if parts.is_empty() {
+            return Err(format!("benchmark {} has no symbols", name));
+        }
+        if let Some((symbol, _)) = parts.iter().find(|(_, w)| **w <= 0.0) {
+            return Err(format!(
+                "benchmark {} needs a positive weight for {}",
+                name, symbol
+            ));
+        }
+    }
+    Ok(())
+}
+
+// A blend from config by name, otherwise the symbol itself; weights written
+// as 70/30 or 0.7/0.3 come out the same
+pub fn resolve(name: &str, conf: &Config) -> Benchmark {
+    let blend = conf
+        .benchmarks
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name));
+    match blend {
+        Some((n, parts)) => {
+            let total: f64 = parts.values().sum();
+            Benchmark {
+                name: n.clone(),
+                parts: parts
+                    .iter()
+                    .map(|(s, w)| (s.to_uppercase(), w / total))
+                    .collect(),
+            }
+        }
+        None => Benchmark {
+            name: name.to_uppercase(),
+            parts: vec![(name.to_uppercase(), 1.0)],
+        },
+    }
+}
+
+// One value a day for the blend, starting at 100, from each part's daily
+// closes in the same order as `parts`. A part with no close on some day, like
+// a foreign listing on a holiday, keeps its last one.
+pub fn blend(b: &Benchmark, closes: &[Vec<(NaiveDate, f64)>]) -> Vec<(NaiveDate, f64)> {
+    let series = closes
+        .iter()
+        .map(|c| c.iter().cloned().collect::<BTreeMap<_, _>>())
+        .collect::<Vec<_>>();
+    // only from when every part has a price
+    let start = match series
+        .iter()
+        .map(|s| s.keys().next())
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(firsts) => match firsts.into_iter().max() {
+            Some(d) => *d,
+            None => return vec![],
+        },
+        None => return vec![],
+    };
+    let dates = series
+        .iter()
+        .flat_map(|s| s.range(start..).map(|(d, _)| *d))
+        .collect::<BTreeSet<_>>();
+
+    let close = |s: &BTreeMap<_, f64>, d| s.range(..=d).next_back().map(|(_, v)| *v);
+    let mut value = 100.0;
+    let mut prev = start;
+    let mut out = vec![];
+    for d in dates {
+        if d != start {
+            let growth: f64 = b
+                .parts
+                .iter()
+                .zip(series.iter())
+                .map(|((_, w), s)| match (close(s, prev), close(s, d)) {
+                    (Some(p), Some(c)) if p > 0.0 => w * c / p,
+                    _ => *w,
+                })
+                .sum();
+            value *= growth;
+        }
+        out.push((d, value));
+        prev = d;
+    }
+    out
+}
+
+// The blend over a span, from each part's daily closes
+pub async fn series(
+    b: &Benchmark,
+    from: NaiveDate,
+    to: NaiveDate,
+    closes: Closes,
+) -> Result<Vec<(NaiveDate, f64)>, String> {
+    let parts = join_all(b.parts.iter().map(|(s, _)| get_series(s, from, to, closes))).await;
+    let closes = b
+        .parts
+        .iter()
+        .zip(parts)
+        .map(|((symbol, _), bars)| {
+            bars.map(|bars| bars.iter().map(|b| (b.date, b.close)).collect())
+                .ok_or_else(|| format!("no historical data for {}", symbol))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(blend(b, &closes))
+}
+
+// Scaled so it's worth `value` on `date`, or on its first day after, to
+// plot beside the portfolio in dollars
+pub fn rebase(series: &[(NaiveDate, f64)], date: NaiveDate, value: f64) -> Vec<(NaiveDate, f64)> {
+    let base = series
+        .iter()
+        .find(|(d, _)| *d >= date)
+        .or(series.last())
+        .map(|(_, v)| *v);
+    match base {
+        Some(b) if b > 0.0 => series.iter().map(|(d, v)| (*d, v * value / b)).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    #[test]
+    fn blends_rebalance_to_their_weights_each_day() {
+        let conf = Config {
+            benchmarks: BTreeMap::from([(
+                "balanced".to_string(),
+                BTreeMap::from([("vti".to_string(), 70.0), ("BND".to_string(), 30.0)]),
+            )]),
+            ..Default::default()
+        };
+        let b = resolve("Balanced", &conf);
+        assert_eq!(
+            b.parts,
+            [("BND".to_string(), 0.3), ("VTI".to_string(), 0.7)]
+        );
+        assert_eq!(resolve("spy", &conf).parts, [("SPY".to_string(), 1.0)]);
+
+        let bnd = vec![(day(1), 50.0), (day(2), 50.0), (day(3), 55.0)];
+        // no close on the 2nd, so it stays at 100 that day
+        let vti = vec![(day(1), 100.0), (day(3), 110.0)];
+        let series = blend(&b, &[bnd, vti]);
+
+        assert_eq!(series[0], (day(1), 100.0));
+        assert_eq!(series[1], (day(2), 100.0));
+        assert!((series[2].1 - 110.0).abs() < 1e-9);
+
+        // the 2nd is the first day from the 2nd, and worth 5000 there
+        let rebased = rebase(&series, day(2), 5000.0);
+        assert_eq!(rebased[1], (day(2), 5000.0));
+        assert!((rebased[2].1 - 5500.0).abs() < 1e-9);
+
+        let bad = BTreeMap::from([("x".to_string(), BTreeMap::from([("VTI".to_string(), 0.0)]))]);
+        assert!(check(&bad).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+// Last response per URL with its validators, for conditional requests. When
+// a provider answers 304 Not Modified the stored body is used as is; URLs
+// whose responses carry no ETag or Last-Modified are never stored.
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cassette::file_name;
+use crate::lock;
+use crate::profile;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+fn cache_dir() -> String {
+    format!("{}/cache", profile::data_dir())
+}
+
+fn path(url: &str) -> String {
+    format!("{}/{}", cache_dir(), file_name(url))
+}
+
+pub fn load(url: &str) -> Option<Entry> {
+    let buf = fs::read_to_string(path(url)).ok()?;
+    serde_json::from_str(&buf).ok()
+}
+
+pub fn store(url: &str, entry: &Entry) {
+    let res = fs::create_dir_all(cache_dir()).and_then(|_| {
+        lock::write_atomic(&path(url), serde_json::to_string(entry).unwrap().as_bytes())
+    });
+
+    if let Err(e) = res {
+        eprintln!("failed to cache {}: {}", url, e);
+    }
+}
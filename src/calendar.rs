@@ -0,0 +1,314 @@
+// Dates coming up for what's held: earnings and dividends from Yahoo, and
+// option and future expiries and grants vesting from the holdings. With
+// --ics they're written as an iCalendar file a phone's calendar can
+// subscribe to.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::future::join_all;
+use serde::Serialize;
+
+use crate::options;
+use crate::portfolio::{Account, Asset};
+use crate::yahoo::{self, Dates};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    Earnings,
+    ExDividend,
+    Dividend,
+    Expiry,
+    Vesting,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Earnings => "earnings",
+            Kind::ExDividend => "ex-dividend",
+            Kind::Dividend => "dividend",
+            Kind::Expiry => "expiry",
+            Kind::Vesting => "vesting",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub date: NaiveDate,
+    pub kind: Kind,
+    pub symbol: String,
+    // what's held that it's about, for the holdings' own dates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Event {
+    fn summary(&self) -> String {
+        match self.kind {
+            Kind::Earnings => format!("{} earnings", self.symbol),
+            Kind::ExDividend => format!("{} ex-dividend", self.symbol),
+            Kind::Dividend => format!("{} dividend paid", self.symbol),
+            Kind::Expiry => format!("{} expires", self.symbol),
+            Kind::Vesting => format!("{} vests", self.symbol),
+        }
+    }
+}
+
+// Stocks and funds Yahoo may have dates for; not contracts, coins or metal
+pub fn symbols(accounts: &[&Account]) -> Vec<String> {
+    let mut symbols = accounts
+        .iter()
+        .flat_map(|a| a.stocks.iter())
+        .filter(|s| s.asset == Asset::Security && options::parse(&s.symbol).is_none())
+        .map(|s| s.symbol.clone())
+        .collect::<Vec<_>>();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+// Each symbol's dates, warning about and leaving out any that fail
+pub async fn fetch(symbols: &[String]) -> HashMap<String, Dates> {
+    let results = join_all(symbols.iter().map(|s| yahoo::get_dates(s))).await;
+    symbols
+        .iter()
+        .zip(results)
+        .filter_map(|(s, res)| match res {
+            Ok(d) => Some((s.clone(), d)),
+            Err(e) => {
+                eprintln!("failed to fetch the dates for {}: {}", s, e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Everything from `today` on, soonest first
+pub fn build(
+    accounts: &[&Account],
+    dates: &HashMap<String, Dates>,
+    today: NaiveDate,
+) -> Vec<Event> {
+    let mut events = vec![];
+    for a in accounts.iter() {
+        for s in a.stocks.iter() {
+            let expiry = options::parse(&s.symbol)
+                .map(|c| c.expiry)
+                .or(s.expiry.filter(|_| s.asset == Asset::Future));
+            if let Some(date) = expiry {
+                events.push(Event {
+                    date,
+                    kind: Kind::Expiry,
+                    symbol: s.symbol.clone(),
+                    detail: Some(format!("{} contracts in {}", s.amount, a.name)),
+                });
+            }
+            for v in s.vesting.iter() {
+                events.push(Event {
+                    date: v.date,
+                    kind: Kind::Vesting,
+                    symbol: s.symbol.clone(),
+                    detail: Some(format!("{} shares in {}", v.num, a.name)),
+                });
+            }
+        }
+    }
+    for (symbol, d) in dates.iter() {
+        let found = [
+            (d.earnings, Kind::Earnings),
+            (d.ex_dividend, Kind::ExDividend),
+            (d.dividend, Kind::Dividend),
+        ];
+        for (date, kind) in found {
+            if let Some(date) = date {
+                events.push(Event {
+                    date,
+                    kind,
+                    symbol: symbol.clone(),
+                    detail: None,
+                });
+            }
+        }
+    }
+
+    events.retain(|e| e.date >= today);
+    events.sort_by(|a, b| (a.date, a.kind, &a.symbol).cmp(&(b.date, b.kind, &b.symbol)));
+    events
+}
+
+pub fn render(events: &[Event], out: &mut dyn Write) -> io::Result<()> {
+    if events.is_empty() {
+        return writeln!(out, "nothing coming up");
+    }
+    let w = events
+        .iter()
+        .map(|e| e.symbol.chars().count())
+        .fold(6, usize::max);
+    for e in events.iter() {
+        let line = format!(
+            "{}  {:<11}  {:<w$}  {}",
+            e.date.format("%Y-%m-%d"),
+            e.kind.name(),
+            e.symbol,
+            e.detail.as_deref().unwrap_or("")
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+// Text values escape backslashes, commas, semicolons and newlines
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Lines longer than 75 bytes continue on the next, after a space
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+// All-day events, each with a UID from what it is so a subscribed calendar
+// updates them in place rather than adding them again
+pub fn to_ics(events: &[Event], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//stocks//calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Stocks".to_string(),
+    ];
+    let stamp = now.format("%Y%m%dT%H%M%SZ");
+    for e in events.iter() {
+        let day = e.date.format("%Y%m%d");
+        let uid_symbol = e
+            .symbol
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-')
+            .collect::<String>();
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!(
+            "UID:{}-{}-{}@stocks",
+            e.kind.name(),
+            uid_symbol,
+            day
+        ));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", day));
+        lines.push(format!(
+            "DTEND;VALUE=DATE:{}",
+            (e.date + Duration::days(1)).format("%Y%m%d")
+        ));
+        lines.push(format!("SUMMARY:{}", escape(&e.summary())));
+        if let Some(d) = &e.detail {
+            lines.push(format!("DESCRIPTION:{}", escape(d)));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{Stock, Vest};
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, d).unwrap()
+    }
+
+    #[test]
+    fn holdings_and_provider_dates_become_calendar_events() {
+        let v = json!({"quoteSummary": {"result": [{"calendarEvents": {
+            "earnings": {"earningsDate": [{"raw": 1714680000}, {"raw": 1715025600}]},
+            "exDividendDate": {"raw": 1715299200},
+            "dividendDate": {"raw": 1715817600}
+        }}]}});
+        let aapl = yahoo::parse_dates(&v);
+        assert_eq!(aapl.earnings, Some(date(5, 2)));
+        assert_eq!(aapl.ex_dividend, Some(date(5, 10)));
+
+        let account = Account::test(
+            "Brokerage",
+            vec![
+                Stock {
+                    symbol: "AAPL".to_string(),
+                    amount: 10.0,
+                    ..Default::default()
+                },
+                Stock {
+                    symbol: "AAPL240621C00200000".to_string(),
+                    amount: 1.0,
+                    ..Default::default()
+                },
+                Stock {
+                    symbol: "GOOG".to_string(),
+                    vesting: vec![
+                        Vest {
+                            date: date(3, 1),
+                            num: 25.0,
+                        },
+                        Vest {
+                            date: date(6, 1),
+                            num: 25.0,
+                        },
+                    ],
+                    ..Default::default()
+                },
+            ],
+        );
+        assert_eq!(symbols(&[&account]), ["AAPL", "GOOG"]);
+
+        let dates = HashMap::from([("AAPL".to_string(), aapl)]);
+        let events = build(&[&account], &dates, date(4, 1));
+        let kinds = events.iter().map(|e| (e.date, e.kind)).collect::<Vec<_>>();
+        // the vest in March has passed
+        assert_eq!(
+            kinds,
+            [
+                (date(5, 2), Kind::Earnings),
+                (date(5, 10), Kind::ExDividend),
+                (date(5, 16), Kind::Dividend),
+                (date(6, 1), Kind::Vesting),
+                (date(6, 21), Kind::Expiry),
+            ]
+        );
+        assert_eq!(events[3].detail.as_deref(), Some("25 shares in Brokerage"));
+
+        let ics = to_ics(&events, Utc.with_ymd_and_hms(2024, 4, 1, 12, 0, 0).unwrap());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:earnings-AAPL-20240502@stocks\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240621\r\nDTEND;VALUE=DATE:20240622\r\n"));
+        assert!(ics.contains("SUMMARY:AAPL240621C00200000 expires\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 5);
+
+        assert_eq!(escape("a, b; c"), "a\\, b\\; c");
+        let long = fold(&"x".repeat(80));
+        assert_eq!(
+            long,
+            format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5))
+        );
+    }
+}
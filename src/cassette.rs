@@ -0,0 +1,259 @@
+// Record and replay raw provider responses.
+//
+// STOCKS_RECORD=<dir> saves every response body to a cassette file in <dir>;
+// STOCKS_REPLAY=<dir> serves responses from those files and never touches the
+// network, failing any request that has no cassette. Tests replay the
+// cassettes under tests/cassettes so parsers are checked against real payload
+// shapes offline.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cassette {
+    pub url: String,
+    pub status: u16,
+    pub body: String,
+}
+
+pub fn file_name(url: &str) -> String {
+    let name = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(200)
+        .collect::<String>();
+
+    format!("{}.json", name)
+}
+
+fn path(dir: &str, url: &str) -> PathBuf {
+    Path::new(dir).join(file_name(url))
+}
+
+pub fn load(path: &Path) -> Result<Cassette, String> {
+    let buf = fs::read_to_string(path)
+        .map_err(|e| format!("no cassette at {}: {}", path.display(), e))?;
+    serde_json::from_str(&buf).map_err(|e| format!("invalid cassette {}: {}", path.display(), e))
+}
+
+// Ok(None) when not replaying, so the caller goes to the network
+pub fn replay(url: &str) -> Result<Option<Cassette>, String> {
+    let dir = match env::var("STOCKS_REPLAY") {
+        Ok(d) => d,
+        Err(_) => return Ok(None),
+    };
+
+    load(&path(&dir, url)).map(Some)
+}
+
+pub fn record(url: &str, status: u16, body: &str) {
+    let dir = match env::var("STOCKS_RECORD") {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+
+    let c = Cassette {
+        url: url.to_string(),
+        status,
+        body: body.to_string(),
+    };
+
+    let res = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(path(&dir, url), serde_json::to_string_pretty(&c).unwrap()));
+
+    if let Err(e) = res {
+        eprintln!("failed to record cassette for {}: {}", url, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use crate::crypto::{self, Chain, Coins, Wallet};
+    use crate::finnhub::Finnhub;
+    use crate::metals::Spot;
+    use crate::provider::{QuoteError, QuoteProvider};
+    use crate::{nasdaq, yahoo};
+
+    const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes");
+
+    fn body(name: &str) -> serde_json::Value {
+        let c = load(&Path::new(DIR).join(name)).unwrap();
+        serde_json::from_str(&c.body).unwrap()
+    }
+
+    #[test]
+    fn nasdaq_stock_quote() {
+        let v = body("api.nasdaq.com_api_quote_AAPL_info_assetclass_stocks.json");
+        let q = nasdaq::parse_quote("AAPL", &v).unwrap();
+        assert_eq!(q.price.amount, 189.97);
+        assert_eq!(q.change.amount, -0.67);
+        assert_eq!(q.price.currency, "USD");
+
+        // "Closed at Nov 24, 2023 4:00 PM ET" is 21:00 UTC outside DST
+        let t = q.time.unwrap();
+        assert_eq!(t.to_rfc3339(), "2023-11-24T21:00:00+00:00");
+    }
+
+    #[test]
+    fn nasdaq_unchanged_etf_quote() {
+        let v = body("api.nasdaq.com_api_quote_VTI_info_assetclass_etf.json");
+        let q = nasdaq::parse_quote("VTI", &v).unwrap();
+        assert_eq!(q.price.amount, 1234.5);
+        assert_eq!(q.change.amount, 0.0);
+    }
+
+    #[test]
+    fn nasdaq_wrong_asset_class_is_not_found() {
+        let v = body("api.nasdaq.com_api_quote_VTI_info_assetclass_stocks.json");
+        assert!(matches!(
+            nasdaq::parse_quote("VTI", &v),
+            Err(QuoteError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn nasdaq_unknown_layout_is_schema_error() {
+        let v = body("api.nasdaq.com_api_quote_MSFT_info_assetclass_stocks.json");
+        assert!(matches!(
+            nasdaq::parse_quote("MSFT", &v),
+            Err(QuoteError::Schema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn nasdaq_history_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let from = chrono::NaiveDate::from_ymd_opt(2023, 11, 20).unwrap();
+        let to = chrono::NaiveDate::from_ymd_opt(2023, 11, 24).unwrap();
+        let bars = nasdaq::get_nasdaq_history("AAPL", "stocks", from, to)
+            .await
+            .unwrap();
+
+        // nasdaq sends the newest row first
+        assert_eq!(bars.len(), 3);
+        assert!(bars[0].date < bars[2].date);
+        assert_eq!(bars[2].close, 189.97);
+        assert_eq!(bars[2].volume, 24048344.0);
+    }
+
+    #[tokio::test]
+    async fn yahoo_quote_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let q = yahoo::get_yahoo_value("AAPL").await.unwrap();
+        assert_eq!(q.price.amount, 189.97);
+        assert_eq!(q.price.currency, "USD");
+        assert!((q.change.amount - -0.67).abs() < 1e-9);
+
+        assert!(matches!(
+            yahoo::get_yahoo_value("NOPE").await,
+            Err(QuoteError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn finnhub_quote_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let finnhub = Finnhub {
+            key: Some("test".to_string()),
+        };
+        let q = finnhub.quote("AAPL").await.unwrap();
+        assert_eq!(q.price.amount, 189.97);
+        assert_eq!(q.open.unwrap().amount, 190.33);
+        assert!((q.change.amount - -0.67).abs() < 1e-9);
+
+        assert!(matches!(
+            finnhub.quote("NOPE").await,
+            Err(QuoteError::NotFound)
+        ));
+        // without a key it fails before asking, so the next provider is tried
+        assert!(matches!(
+            Finnhub { key: None }.quote("AAPL").await,
+            Err(QuoteError::Network(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn yahoo_option_chain_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let put = crate::options::parse("AAPL231215P00190000").unwrap();
+        let iv = yahoo::get_implied_vol("AAPL231215P00190000", &put)
+            .await
+            .unwrap();
+        assert_eq!(iv, 0.1764);
+
+        let missing = crate::options::parse("AAPL231215P00100000").unwrap();
+        assert!(yahoo::get_implied_vol("AAPL231215P00100000", &missing)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn metal_spot_with_premium_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let spot = Spot {
+            premiums: BTreeMap::from([("gold".to_string(), 2.5)]),
+        };
+        let q = spot.quote("XAU").await.unwrap();
+        assert_eq!(q.price.amount, 2050.0);
+
+        assert!(matches!(
+            spot.quote("AAPL").await,
+            Err(QuoteError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn yahoo_search_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let matches = yahoo::search("BRKB").await.unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].symbol, "BRK-B");
+        assert_eq!(matches[0].name, "Berkshire Hathaway Inc. New");
+    }
+
+    #[tokio::test]
+    async fn openfigi_mapping_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let ids = ["US0378331005".to_string()];
+        let found = crate::lookup::map_identifiers(&Default::default(), &ids)
+            .await
+            .unwrap();
+        assert_eq!(found["US0378331005"], "AAPL");
+    }
+
+    #[tokio::test]
+    async fn wallet_balance_and_coin_quote_through_replay() {
+        env::set_var("STOCKS_REPLAY", DIR);
+
+        let wallet = Wallet {
+            chain: Chain::Bitcoin,
+            address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+        };
+        assert_eq!(crypto::balance(&wallet).await.unwrap(), 1.25);
+
+        let q = Coins.quote("BTC").await.unwrap();
+        assert_eq!(q.price.amount, 37720.28);
+        assert_eq!(q.open.unwrap().amount, 37296.32);
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use futures::future::join_all;
+use terminal_size::{terminal_size, Height, Width};
+
+use crate::benchmark::{blend, Benchmark};
+use crate::config::Closes;
+use crate::nasdaq::{get_series, Bar};
+use crate::portfolio::{holdings_path, parse_accounts, Trade};
+use crate::render::clr;
+use crate::{glyph, snapshot, time};
+
+const GREEN: &str = "\x1b[38;5;2m";
+const RED: &str = "\x1b[38;5;1m";
+const DIM: &str = "\x1b[38;5;8m";
+const BUY: &str = "\x1b[1;38;5;14m";
+const SELL: &str = "\x1b[1;38;5;13m";
+
+const PALETTE: [&str; 6] = [
+    "\x1b[38;5;4m",
+    "\x1b[38;5;3m",
+    "\x1b[38;5;5m",
+    "\x1b[38;5;6m",
+    "\x1b[38;5;2m",
+    "\x1b[38;5;1m",
+];
+
+pub struct ChartOpts {
+    pub range: String,
+    pub interval: String,
+    pub candles: bool,
+    pub volume: bool,
+    pub normalize: bool,
+    pub closes: Closes,
+}
+
+pub async fn run(symbols: &[String], opts: &ChartOpts, out: &mut dyn Write) {
+    if symbols.len() > 1 || opts.normalize {
+        if opts.candles {
+            eprintln!("--candles can only be used when charting a single symbol");
+            std::process::exit(1);
+        }
+        return compare(symbols, opts, out).await;
+    }
+
+    let symbol = &symbols[0];
+    let bars = fetch(symbol, opts).await;
+    written(single(symbol, &bars, opts, out));
+}
+
+// Writing to stdout only fails once it's closed, which ends the command
+fn written(res: io::Result<()>) {
+    if let Err(e) = res {
+        eprintln!("failed to write output: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn single(symbol: &str, bars: &[Bar], opts: &ChartOpts, out: &mut dyn Write) -> io::Result<()> {
+    let (cols, rows) = size();
+    let vol_rows = if opts.volume { 4 } else { 0 };
+    let height = rows.saturating_sub(vol_rows + 4).max(5);
+    let width = cols.saturating_sub(AXIS_WIDTH).max(10);
+
+    let bars = &bars[bars.len().saturating_sub(width)..];
+
+    let trades = my_trades(symbol)
+        .into_iter()
+        .filter(|t| t.date >= bars[0].date)
+        .collect::<Vec<_>>();
+
+    let lo = bars
+        .iter()
+        .map(|b| if opts.candles { b.low } else { b.close })
+        .chain(trades.iter().map(|t| t.price.amount))
+        .fold(f64::MAX, f64::min);
+    let hi = bars
+        .iter()
+        .map(|b| if opts.candles { b.high } else { b.close })
+        .chain(trades.iter().map(|t| t.price.amount))
+        .fold(f64::MIN, f64::max);
+
+    let mut grid = Grid::new(bars.len(), height, lo, hi);
+
+    if opts.candles {
+        for (x, b) in bars.iter().enumerate() {
+            let color = if b.close >= b.open { GREEN } else { RED };
+            grid.vline(x, b.low, b.high, glyph::pick('│', '|'), color);
+            let body = glyph::pick('┃', '#');
+            grid.vline(x, b.open.min(b.close), b.open.max(b.close), body, color);
+        }
+    } else {
+        let closes = bars.iter().map(|b| b.close).collect::<Vec<_>>();
+        let color = if closes.last() >= closes.first() {
+            GREEN
+        } else {
+            RED
+        };
+        grid.line(&closes, color);
+    }
+
+    for t in trades.iter() {
+        // resampled bars are dated by the start of their period
+        let x = bars.iter().rposition(|b| b.date <= t.date).unwrap_or(0);
+        let (c, color) = if t.num >= 0.0 {
+            (glyph::pick('▲', '^'), BUY)
+        } else {
+            (glyph::pick('▼', 'v'), SELL)
+        };
+        grid.set(x, grid.row(t.price.amount), c, color);
+    }
+
+    let first = bars.first().unwrap();
+    let last = bars.last().unwrap();
+    let change = last.close - first.open;
+
+    writeln!(
+        out,
+        "\x1b[1m{}\x1b[0m  ${:.2}  {}{:+.2} ({:+.2}%)\x1b[0m  {}",
+        symbol,
+        last.close,
+        clr(change),
+        change,
+        change * 100.0 / first.open,
+        opts.range
+    )?;
+
+    grid.print(out)?;
+
+    if opts.volume {
+        let volumes = bars.iter().map(|b| b.volume).collect::<Vec<_>>();
+        let colors = bars
+            .iter()
+            .map(|b| if b.close >= b.open { GREEN } else { RED })
+            .collect::<Vec<_>>();
+        print_volume(&volumes, &colors, vol_rows, out)?;
+    }
+
+    print_dates(first.date, last.date, bars.len(), out)?;
+
+    for t in trades.iter() {
+        let (c, color, side) = if t.num >= 0.0 {
+            (glyph::pick('▲', '^'), BUY, "bought")
+        } else {
+            (glyph::pick('▼', 'v'), SELL, "sold")
+        };
+        writeln!(
+            out,
+            "  {}{}\x1b[0m {}  {} {} @ {}",
+            color,
+            c,
+            t.date.format("%Y-%m-%d"),
+            side,
+            t.num.abs(),
+            t.price
+        )?;
+    }
+
+    Ok(())
+}
+
+fn my_trades(symbol: &str) -> Vec<Trade> {
+    let path = holdings_path();
+    if !Path::new(&path).exists() {
+        return vec![];
+    }
+
+    let mut trades = parse_accounts(&path)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|a| a.stocks)
+        .filter(|s| s.symbol == symbol)
+        .flat_map(|s| s.trades)
+        .collect::<Vec<_>>();
+    trades.sort_by_key(|t| t.date);
+
+    trades
+}
+
+async fn fetch(symbol: &str, opts: &ChartOpts) -> Vec<Bar> {
+    let to = time::today();
+    let from = match parse_range(&opts.range, to) {
+        Some(d) => d,
+        None => {
+            eprintln!(
+                "invalid range {}, expected e.g. 5d, 3m, 1y or ytd",
+                opts.range
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let bars = match get_series(symbol, from, to, opts.closes).await {
+        Some(b) => b,
+        None => {
+            eprintln!("no historical data for {}", symbol);
+            std::process::exit(1);
+        }
+    };
+
+    match resample(&bars, &opts.interval) {
+        Some(b) => b,
+        None => {
+            eprintln!("invalid interval {}, expected 1d, 1w or 1mo", opts.interval);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn compare(symbols: &[String], opts: &ChartOpts, out: &mut dyn Write) {
+    let histories = join_all(symbols.iter().map(|s| fetch(s, opts))).await;
+
+    let series = symbols
+        .iter()
+        .zip(histories)
+        .map(|(s, h)| (s.clone(), h.iter().map(|b| (b.date, b.close)).collect()))
+        .collect::<Vec<_>>();
+
+    written(overlay(&series, &opts.range, out));
+}
+
+pub async fn portfolio(benchmark: Option<&Benchmark>, opts: &ChartOpts, out: &mut dyn Write) {
+    let to = time::today();
+    let from = match parse_range(&opts.range, to) {
+        Some(d) => d,
+        None => {
+            eprintln!(
+                "invalid range {}, expected e.g. 5d, 3m, 1y or ytd",
+                opts.range
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let totals = snapshot::daily_totals(from);
+    if totals.len() < 2 {
+        eprintln!(
+            "not enough portfolio history in {} yet, run stocks on a few different days first",
+            snapshot::history_dir()
+        );
+        std::process::exit(1);
+    }
+
+    if let Some(benchmark) = benchmark {
+        let parts = join_all(benchmark.parts.iter().map(|(s, _)| fetch(s, opts))).await;
+        let closes = parts
+            .iter()
+            .map(|bars| bars.iter().map(|b| (b.date, b.close)).collect())
+            .collect::<Vec<_>>();
+        let series = vec![
+            ("portfolio".to_string(), totals),
+            (benchmark.name.clone(), blend(benchmark, &closes)),
+        ];
+        return written(overlay(&series, &opts.range, out));
+    }
+
+    written(history(&totals, opts, out));
+}
+
+fn history(totals: &[(NaiveDate, f64)], opts: &ChartOpts, out: &mut dyn Write) -> io::Result<()> {
+    let (cols, rows) = size();
+    let width = cols.saturating_sub(AXIS_WIDTH).max(10);
+    let height = rows.saturating_sub(4).max(5);
+    let totals = &totals[totals.len().saturating_sub(width)..];
+
+    let values = totals.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+    let lo = values.iter().cloned().fold(f64::MAX, f64::min);
+    let hi = values.iter().cloned().fold(f64::MIN, f64::max);
+
+    let first = values[0];
+    let last = *values.last().unwrap();
+    let change = last - first;
+
+    writeln!(
+        out,
+        "\x1b[1mportfolio\x1b[0m  ${:.2}  {}{:+.2} ({:+.2}%)\x1b[0m  {}",
+        last,
+        clr(change),
+        change,
+        change * 100.0 / first,
+        opts.range
+    )?;
+
+    let mut grid = Grid::new(values.len(), height, lo, hi);
+    grid.line(&values, if change >= 0.0 { GREEN } else { RED });
+    grid.print_with(|v| format!("{:.0}", v), out)?;
+    print_dates(totals[0].0, totals.last().unwrap().0, values.len(), out)
+}
+
+// plot several dated series as percent change from the start of the window
+fn overlay(
+    series: &[(String, Vec<(NaiveDate, f64)>)],
+    title: &str,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    // every series gets a value on every date any of them has, carrying the
+    // last value forward over gaps such as foreign holidays
+    let mut dates = series
+        .iter()
+        .flat_map(|(_, s)| s.iter().map(|(d, _)| *d))
+        .collect::<Vec<_>>();
+    dates.sort();
+    dates.dedup();
+
+    let (cols, rows) = size();
+    let width = cols.saturating_sub(AXIS_WIDTH).max(10);
+    let height = rows.saturating_sub(4 + series.len()).max(5);
+    let dates = &dates[dates.len().saturating_sub(width)..];
+
+    let percents = series
+        .iter()
+        .map(|(_, s)| {
+            let values = s.iter().cloned().collect::<BTreeMap<_, _>>();
+            let base = values
+                .range(dates[0]..)
+                .next()
+                .map(|(_, v)| *v)
+                .unwrap_or(1.0);
+
+            dates
+                .iter()
+                .map(|d| {
+                    let v = values
+                        .range(..=d)
+                        .next_back()
+                        .map(|(_, v)| *v)
+                        .unwrap_or(base);
+                    (v - base) * 100.0 / base
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let lo = percents.iter().flatten().cloned().fold(f64::MAX, f64::min);
+    let hi = percents.iter().flatten().cloned().fold(f64::MIN, f64::max);
+
+    let mut grid = Grid::new(dates.len(), height, lo, hi);
+    for (i, p) in percents.iter().enumerate() {
+        grid.line(p, PALETTE[i % PALETTE.len()]);
+    }
+
+    writeln!(out, "\x1b[1m{}\x1b[0m", title)?;
+    for (i, ((name, _), p)) in series.iter().zip(percents.iter()).enumerate() {
+        let last = p.last().cloned().unwrap_or_default();
+        writeln!(
+            out,
+            "  {}{}\x1b[0m {:<10} {}{:+.2}%\x1b[0m",
+            PALETTE[i % PALETTE.len()],
+            glyph::pick('•', '*'),
+            name,
+            clr(last),
+            last
+        )?;
+    }
+
+    grid.print_with(|v| format!("{:+.1}%", v), out)?;
+    print_dates(dates[0], *dates.last().unwrap(), dates.len(), out)
+}
+
+pub fn parse_range(range: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if range == "ytd" {
+        return NaiveDate::from_ymd_opt(today.year(), 1, 1);
+    }
+
+    let split = range.find(|c: char| !c.is_ascii_digit())?;
+    let n = range[..split].parse::<i64>().ok()?;
+
+    match &range[split..] {
+        "d" => Some(today - Duration::days(n)),
+        "w" => Some(today - Duration::weeks(n)),
+        "m" | "mo" => Some(today - Duration::days(n * 30)),
+        "y" => Some(today - Duration::days(n * 365)),
+        _ => None,
+    }
+}
+
+pub fn resample(bars: &[Bar], interval: &str) -> Option<Vec<Bar>> {
+    let key = |d: NaiveDate| -> (i32, u32) {
+        match interval {
+            "1w" => (d.iso_week().year(), d.iso_week().week()),
+            "1mo" => (d.year(), d.month()),
+            _ => (d.year(), d.ordinal()),
+        }
+    };
+
+    if !matches!(interval, "1d" | "1w" | "1mo") {
+        return None;
+    }
+
+    let mut out: Vec<Bar> = vec![];
+
+    for b in bars {
+        match out.last_mut() {
+            Some(last) if key(last.date) == key(b.date) => {
+                last.high = last.high.max(b.high);
+                last.low = last.low.min(b.low);
+                last.close = b.close;
+                last.volume += b.volume;
+            }
+            _ => out.push(b.clone()),
+        }
+    }
+
+    Some(out)
+}
+
+pub fn size() -> (usize, usize) {
+    match terminal_size() {
+        Some((Width(w), Height(h))) => (w as usize, h as usize),
+        None => (80, 24),
+    }
+}
+
+// width of the "  123.45 ┤ " price axis in front of every row
+pub const AXIS_WIDTH: usize = 13;
+
+pub struct Grid {
+    width: usize,
+    height: usize,
+    lo: f64,
+    hi: f64,
+    cells: Vec<Vec<(char, &'static str)>>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, lo: f64, hi: f64) -> Self {
+        let (lo, hi) = if (hi - lo).abs() < f64::EPSILON {
+            (lo - 1.0, hi + 1.0)
+        } else {
+            (lo, hi)
+        };
+
+        Grid {
+            width,
+            height,
+            lo,
+            hi,
+            cells: vec![vec![(' ', ""); width]; height],
+        }
+    }
+
+    pub fn row(&self, v: f64) -> usize {
+        let frac = (self.hi - v) / (self.hi - self.lo);
+        ((frac * (self.height - 1) as f64).round().max(0.0) as usize).min(self.height - 1)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, c: char, color: &'static str) {
+        if x < self.width && y < self.height {
+            self.cells[y][x] = (c, color);
+        }
+    }
+
+    pub fn vline(&mut self, x: usize, from: f64, to: f64, c: char, color: &'static str) {
+        let (top, bottom) = (self.row(to), self.row(from));
+        for y in top..=bottom {
+            self.set(x, y, c, color);
+        }
+    }
+
+    pub fn line(&mut self, values: &[f64], color: &'static str) {
+        for (x, v) in values.iter().enumerate() {
+            let y = self.row(*v);
+            let prev = if x == 0 { y } else { self.row(values[x - 1]) };
+
+            // connect to the previous point so steep moves stay readable
+            for fill in prev.min(y) + 1..prev.max(y) {
+                self.set(x, fill, glyph::pick('│', '|'), color);
+            }
+            self.set(x, y, glyph::pick('•', '*'), color);
+        }
+    }
+
+    pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.print_with(|v| format!("{:.2}", v), out)
+    }
+
+    pub fn print_with(&self, fmt: impl Fn(f64) -> String, out: &mut dyn Write) -> io::Result<()> {
+        for (y, row) in self.cells.iter().enumerate() {
+            let value = self.hi - (self.hi - self.lo) * y as f64 / (self.height - 1) as f64;
+
+            let label = if y % 3 == 0 || y == self.height - 1 {
+                format!("{:>10} {}", fmt(value), glyph::pick("┤", "+"))
+            } else {
+                format!("{:>10} {}", "", glyph::pick("│", "|"))
+            };
+
+            let line = row
+                .iter()
+                .map(|(c, color)| {
+                    if color.is_empty() {
+                        c.to_string()
+                    } else {
+                        format!("{}{}\x1b[0m", color, c)
+                    }
+                })
+                .collect::<String>();
+
+            writeln!(out, "{}{}\x1b[0m {}", DIM, label, line)?;
+        }
+        Ok(())
+    }
+}
+
+fn print_volume(
+    volumes: &[f64],
+    colors: &[&str],
+    rows: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    const ASCII_BLOCKS: [char; 9] = [' ', '_', '.', ',', '-', '~', '=', '*', '#'];
+    let blocks = glyph::pick(BLOCKS, ASCII_BLOCKS);
+
+    let max = volumes.iter().cloned().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Ok(());
+    }
+
+    for r in (0..rows).rev() {
+        let label = if r == rows - 1 {
+            format!("{:>10} {}", human(max), glyph::pick("┤", "+"))
+        } else {
+            format!("{:>10} {}", "", glyph::pick("│", "|"))
+        };
+
+        let line = volumes
+            .iter()
+            .zip(colors)
+            .map(|(v, color)| {
+                let eighths = (v / max * (rows * 8) as f64).round() as usize;
+                let c = blocks[eighths.saturating_sub(r * 8).min(8)];
+                format!("{}{}\x1b[0m", color, c)
+            })
+            .collect::<String>();
+
+        writeln!(out, "{}{}\x1b[0m {}", DIM, label, line)?;
+    }
+    Ok(())
+}
+
+pub fn print_dates(
+    first: NaiveDate,
+    last: NaiveDate,
+    width: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let left = first.format("%Y-%m-%d").to_string();
+    let right = last.format("%Y-%m-%d").to_string();
+    let gap = width.saturating_sub(left.len() + right.len()).max(1);
+
+    writeln!(
+        out,
+        "{}{:>w$}{}{}{}\x1b[0m",
+        DIM,
+        "",
+        left,
+        " ".repeat(gap),
+        right,
+        w = AXIS_WIDTH
+    )
+}
+
+fn human(v: f64) -> String {
+    if v >= 1e9 {
+        format!("{:.1}B", v / 1e9)
+    } else if v >= 1e6 {
+        format!("{:.1}M", v / 1e6)
+    } else if v >= 1e3 {
+        format!("{:.1}K", v / 1e3)
+    } else {
+        format!("{:.0}", v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Plain;
+
+    #[test]
+    fn plain_charts_have_no_escapes() {
+        let mut grid = Grid::new(3, 5, 1.0, 3.0);
+        grid.line(&[1.0, 3.0, 2.0], GREEN);
+
+        let mut text = Vec::new();
+        let mut out = Plain::new(&mut text);
+        grid.print(&mut out).unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        print_dates(day, day, 30, &mut out).unwrap();
+
+        let text = String::from_utf8(text).unwrap();
+        assert!(!text.contains('\x1b'));
+        assert!(text.ends_with("2024-01-02\n"));
+    }
+}
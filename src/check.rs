@@ -0,0 +1,56 @@
+use crate::config::Config;
+use crate::portfolio::parse_accounts;
+use crate::provider::{quote_accounts, QuoteError};
+
+// Validates holdings files the way a report would read them, then asks the
+// providers about every symbol. Problems go to stderr; the summary line is
+// left out with `quiet`. Returns whether every file passed.
+pub async fn run(files: &[String], conf: &Config, quiet: bool) -> bool {
+    let mut ok = true;
+
+    for path in files {
+        let accounts = match parse_accounts(path) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+
+        let failed = match quote_accounts(&accounts, conf).await {
+            Ok((_, failed)) => failed,
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+
+        let mut unknown = 0;
+        for (symbol, e) in failed.iter() {
+            match e {
+                QuoteError::NotFound => {
+                    eprintln!("{}: unknown symbol {}", path, symbol);
+                    unknown += 1;
+                }
+                // not the file's fault, so it doesn't fail the check
+                _ => eprintln!("{}: could not verify {}: {}", path, symbol, e),
+            }
+        }
+
+        if unknown > 0 {
+            ok = false;
+        } else if !quiet {
+            let positions = accounts.iter().map(|a| a.stocks.len()).sum::<usize>();
+            println!(
+                "{}: {} accounts, {} positions ok",
+                path,
+                accounts.len(),
+                positions
+            );
+        }
+    }
+
+    ok
+}
@@ -0,0 +1,208 @@
+// What each holding is, for `alloc --by category` and `--by class`. Funds
+// get their category from Yahoo's fund profile, like "Large Blend" or
+// "Intermediate Core Bond", and single stocks their sector. Categories
+// rarely change, so they're cached in classes.json and checked again every
+// few months; `classes` in config tags a symbol by hand instead.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use chrono::{Local, NaiveDate};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::lock;
+use crate::portfolio::{Account, Asset};
+use crate::profile;
+use crate::yahoo;
+
+const RECHECK_DAYS: i64 = 90;
+
+pub const UNCLASSIFIED: &str = "Unclassified";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cached {
+    // none when the provider had nothing for the symbol, so it isn't asked
+    // again every run
+    category: Option<String>,
+    checked: NaiveDate,
+}
+
+// The asset class a category falls under. Order matters: "Global Real
+// Estate" is real estate and "World Bond" a bond fund before either is
+// international.
+pub fn broad(category: &str) -> &'static str {
+    let c = category.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| c.contains(w));
+    if has(&["money market", "cash"]) {
+        "Cash"
+    } else if has(&["real estate", "reit"]) {
+        "Real estate"
+    } else if has(&["digital asset", "crypto"]) {
+        "Crypto"
+    } else if has(&["commodit", "precious metal", "natural resources"]) {
+        "Commodities"
+    } else if has(&[
+        "bond",
+        "treasury",
+        "government",
+        "muni",
+        "credit",
+        "bank loan",
+        "ultrashort",
+        "inflation-protected",
+        "high yield",
+        "securitized",
+        "fixed income",
+    ]) {
+        "Bonds"
+    } else if has(&["allocation", "target", "retirement income"]) {
+        "Balanced"
+    } else if has(&[
+        "foreign",
+        "world",
+        "global",
+        "international",
+        "emerging",
+        "europe",
+        "pacific",
+        "asia",
+        "china",
+        "japan",
+        "india",
+        "latin america",
+    ]) {
+        "International stocks"
+    } else {
+        "US stocks"
+    }
+}
+
+fn classes_path() -> String {
+    format!("{}/classes.json", profile::data_dir())
+}
+
+fn load_cache() -> BTreeMap<String, Cached> {
+    fs::read_to_string(classes_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &BTreeMap<String, Cached>) {
+    let res = fs::create_dir_all(profile::data_dir()).and_then(|_| {
+        lock::write_atomic(
+            &classes_path(),
+            serde_json::to_string_pretty(cache).unwrap().as_bytes(),
+        )
+    });
+    if let Err(e) = res {
+        eprintln!("failed to save {}: {}", classes_path(), e);
+    }
+}
+
+// The category of every symbol held, where one is known. Coins and metal
+// need no lookup; futures and options have no category and are left out.
+pub async fn load(accounts: &[&Account], conf: &Config) -> HashMap<String, String> {
+    let mut found = HashMap::new();
+    let mut lookup = vec![];
+    for s in accounts.iter().flat_map(|a| a.stocks.iter()) {
+        if let Some(c) = conf.classes.get(&s.symbol) {
+            found.insert(s.symbol.clone(), c.clone());
+            continue;
+        }
+        match s.asset {
+            Asset::Crypto => {
+                found.insert(s.symbol.clone(), "Digital Assets".to_string());
+            }
+            Asset::Metal => {
+                found.insert(s.symbol.clone(), "Precious Metals".to_string());
+            }
+            Asset::Security if s.expiry.is_none() => lookup.push(s.symbol.clone()),
+            _ => {}
+        }
+    }
+    lookup.sort();
+    lookup.dedup();
+
+    let today = Local::now().date_naive();
+    let mut cache = load_cache();
+    let stale = lookup
+        .iter()
+        .filter(|s| {
+            cache
+                .get(*s)
+                .is_none_or(|c| (today - c.checked).num_days() > RECHECK_DAYS)
+        })
+        .collect::<Vec<_>>();
+
+    if !stale.is_empty() {
+        let results = join_all(stale.iter().map(|s| yahoo::get_category(s))).await;
+        let mut changed = false;
+        for (symbol, res) in stale.into_iter().zip(results) {
+            match res {
+                Ok(category) => {
+                    cache.insert(
+                        symbol.clone(),
+                        Cached {
+                            category,
+                            checked: today,
+                        },
+                    );
+                    changed = true;
+                }
+                // an old category is still better than none
+                Err(e) => eprintln!("failed to look up the category of {}: {}", symbol, e),
+            }
+        }
+        if changed {
+            save_cache(&cache);
+        }
+    }
+
+    for symbol in lookup {
+        if let Some(c) = cache.get(&symbol).and_then(|c| c.category.clone()) {
+            found.insert(symbol, c);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn categories_fall_under_broad_classes() {
+        let fund = json!({"quoteSummary": {"result": [{
+            "quoteType": {"quoteType": "ETF"},
+            "fundProfile": {"categoryName": "Foreign Large Blend"}
+        }]}});
+        let stock = json!({"quoteSummary": {"result": [{
+            "quoteType": {"quoteType": "EQUITY"},
+            "assetProfile": {"sector": "Technology"}
+        }]}});
+        let unknown = json!({"quoteSummary": {"result": [{
+            "quoteType": {"quoteType": "ETF"},
+            "fundProfile": {"categoryName": null}
+        }]}});
+        assert_eq!(
+            yahoo::parse_category(&fund).as_deref(),
+            Some("Foreign Large Blend")
+        );
+        assert_eq!(yahoo::parse_category(&stock).as_deref(), Some("Technology"));
+        assert_eq!(yahoo::parse_category(&unknown), None);
+
+        assert_eq!(broad("Foreign Large Blend"), "International stocks");
+        assert_eq!(broad("Technology"), "US stocks");
+        assert_eq!(broad("Large Blend"), "US stocks");
+        assert_eq!(broad("Intermediate Core Bond"), "Bonds");
+        assert_eq!(broad("World Bond-USD Hedged"), "Bonds");
+        assert_eq!(broad("Global Real Estate"), "Real estate");
+        assert_eq!(broad("Real Estate"), "Real estate");
+        assert_eq!(broad("Target-Date 2050"), "Balanced");
+        assert_eq!(broad("Digital Assets"), "Crypto");
+    }
+}
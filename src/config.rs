@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::money::Rounding;
+use crate::profile;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// seconds between refreshes in watch mode
+    pub interval: Option<u64>,
+    /// quote sources in the order they are tried, e.g. ["nasdaq", "yahoo"];
+    /// "finnhub" also works given an API key
+    pub providers: Vec<String>,
+    /// query every provider at once and take the first valid quote
+    pub race: bool,
+    /// "config" tries providers as listed, "latency" fastest first
+    pub order: Order,
+    /// what today's change is measured against, "close" (previous) or "open"
+    pub change_basis: ChangeBasis,
+    /// how half cents round in totals, "half-up" or "half-even" (banker's)
+    pub rounding: Rounding,
+    /// decimals money is rounded to; the currency's minor unit if unset
+    pub money_decimals: Option<usize>,
+    /// currency the totals are in, converted at yahoo's rates; the one every
+    /// position is in, or USD, if unset
+    pub base_currency: Option<String>,
+    /// round share counts in the table to this many decimals; as held if unset
+    pub share_decimals: Option<usize>,
+    /// per provider API keys; prefer "${VAR}" or `stocks auth set` to plain text
+    pub api_keys: BTreeMap<String, String>,
+    /// seconds a fetched quote is reused by later runs, 60 if unset; 0 to always fetch
+    pub quote_ttl: Option<u64>,
+    /// minutes before a quote is flagged as stale, 30 if unset
+    pub stale_after: Option<i64>,
+    /// warn about options expiring within this many days, 7 if unset
+    pub expiry_warning_days: Option<i64>,
+    /// annual risk free rate for option greeks, 0.04 if unset
+    pub risk_free_rate: Option<f64>,
+    /// percent over spot for physical metal by name, e.g. { gold = 3, silver = -2 }
+    pub metal_premiums: BTreeMap<String, f64>,
+    /// broker action strings for `stocks import`, e.g. { "YOU BOUGHT" = "buy" }
+    pub import_actions: BTreeMap<String, String>,
+    /// yearly fund expense ratios in percent by symbol, e.g. { ARKK = 0.75 }
+    pub expense_ratios: BTreeMap<String, f64>,
+    /// percent of each symbol's dividends that are qualified, e.g. { VTI = 95, BND = 0 };
+    /// a dividend's own `qualified` amount wins, and anything else counts as ordinary
+    pub qualified_dividends: BTreeMap<String, f64>,
+    /// interest from these symbols that's free of tax, e.g. { VTEB = "federal",
+    /// VCAIX = "both", VGSH = "state" } for a national muni, an in-state muni and
+    /// a treasury fund
+    pub tax_exempt: BTreeMap<String, TaxExempt>,
+    /// fund category or sector by symbol for `alloc --by`, e.g. { FXAIX = "Large Blend" };
+    /// looked up from the provider if unset
+    pub classes: BTreeMap<String, String>,
+    /// yearly advisory fee in percent of assets by account name
+    pub advisory_fees: BTreeMap<String, f64>,
+    /// expense ratio `stocks fees` compares funds against, 0.03 if unset
+    pub low_cost_ratio: Option<f64>,
+    /// scheduled buys recorded into the holdings once their day has passed
+    pub recurring: Vec<Recurring>,
+    /// IANA zone used to show timestamps, e.g. "Europe/Berlin"; local if unset
+    pub timezone: Option<String>,
+    /// plain text file that gets a line with the day's total, change and top
+    /// mover after each close, relative to the data directory
+    pub journal: Option<String>,
+    /// Rhai script with custom metrics and alerts for `stocks metrics`,
+    /// relative to the config directory
+    pub script: Option<String>,
+    /// alerts by name and the condition that raises each, in the script's
+    /// language, e.g. { "VTI slide" = "VTI.net_pct < -2 && portfolio.net_pct < -1.5",
+    /// "idle cash" = "portfolio.cash > 5000" }
+    pub alerts: BTreeMap<String, String>,
+    /// hours before an alert that stays raised is told about again by `watch`
+    /// and the daemon; only once it clears and is raised again if unset
+    pub alert_repeat_hours: Option<i64>,
+    /// monthly consumer price index for `perf --real`, e.g. { "2024-01" = 308.4 };
+    /// fetched from FRED if unset
+    pub cpi: BTreeMap<String, f64>,
+    /// blends to compare against by name, e.g. { balanced = { VTI = 70, BND = 30 } }
+    pub benchmarks: BTreeMap<String, BTreeMap<String, f64>>,
+    /// TrueType font for the text in PNG charts; a usual system one if unset
+    pub font: Option<String>,
+    /// what `chart --portfolio` compares against without --benchmark, a
+    /// symbol or one of `benchmarks`
+    pub benchmark: Option<String>,
+    /// closes that charts and benchmarks use without --closes, "raw" as
+    /// traded or "adjusted" for splits and dividends
+    pub closes: Closes,
+    /// where the `stocks publish` site is hosted, put in the --qr code instead
+    /// of the summary
+    pub publish_url: Option<String>,
+    /// `[[highlight]]` rules emphasising table cells or rows by their value
+    pub highlight: Vec<Highlight>,
+    /// with --emoji, a day within this percent either way is flat, 0.1 if
+    /// unset
+    pub emoji_flat_pct: Option<f64>,
+    /// with --emoji, the move in percent that gets the summary a 🚀 or 📉, 1
+    /// if unset
+    pub emoji_big_pct: Option<f64>,
+}
+
+/// `[[highlight]]`, e.g. `column = "total_pct"`, `between = [-1, 1]`,
+/// `color = "yellow"`, or `column = "value"`, `above = 10000`, `bold = true`,
+/// `row = true`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Highlight {
+    /// a numeric --fields name, like net_pct or value
+    pub column: String,
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+    /// inclusive at both ends
+    pub between: Option<[f64; 2]>,
+    /// black, red, green, yellow, blue, magenta, cyan, white or grey
+    pub color: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+    /// style the whole row rather than only the column's cell
+    #[serde(default)]
+    pub row: bool,
+}
+
+/// `[[recurring]]`, e.g. $500 of VTI on the 1st of every month
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recurring {
+    pub account: String,
+    pub symbol: String,
+    /// dollars spent each time
+    pub amount: f64,
+    /// day of the month, the last day in shorter months
+    pub day: u32,
+    /// first date that counts; from the first run if unset
+    pub start: Option<toml::value::Datetime>,
+    /// ask before recording each buy
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeBasis {
+    #[default]
+    Close,
+    Open,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaxExempt {
+    Federal,
+    State,
+    Both,
+}
+
+impl TaxExempt {
+    pub fn federal(self) -> bool {
+        matches!(self, TaxExempt::Federal | TaxExempt::Both)
+    }
+
+    pub fn state(self) -> bool {
+        matches!(self, TaxExempt::State | TaxExempt::Both)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Closes {
+    #[default]
+    Raw,
+    Adjusted,
+}
+
+pub fn parse_closes(s: &str) -> Result<Closes, String> {
+    match s.to_lowercase().as_str() {
+        "raw" => Ok(Closes::Raw),
+        "adjusted" => Ok(Closes::Adjusted),
+        _ => Err(format!("closes should be raw or adjusted, not {}", s)),
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Config,
+    Latency,
+}
+
+pub fn config_path() -> String {
+    format!("{}/config.toml", profile::config_dir())
+}
+
+// Replaces each `${NAME}` in a string with the variable's value, so secrets
+// and paths can come from the environment; `$${` is a literal `${`.
+pub fn interpolate(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(r) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("${") {
+            let end = r
+                .find('}')
+                .ok_or_else(|| format!("unclosed ${{ in {}", s))?;
+            let name = &r[..end];
+            let value = lookup(name).ok_or_else(|| format!("{} is not set", name))?;
+            out.push_str(&value);
+            rest = &r[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn substitute(v: &mut toml::Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    match v {
+        toml::Value::String(s) => *s = interpolate(s, lookup)?,
+        toml::Value::Array(a) => {
+            for v in a.iter_mut() {
+                substitute(v, lookup)?;
+            }
+        }
+        toml::Value::Table(t) => {
+            for (_, v) in t.iter_mut() {
+                substitute(v, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub fn load() -> Result<Config, String> {
+    let path = config_path();
+    if !Path::new(&path).exists() {
+        return Ok(Config::default());
+    }
+
+    let buf = fs::read_to_string(&path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut raw: toml::Value =
+        toml::from_str(&buf).map_err(|e| format!("could not parse {}: {}", path, e))?;
+    substitute(&mut raw, &|name| env::var(name).ok()).map_err(|e| format!("{}: {}", path, e))?;
+    let conf: Config = raw
+        .try_into()
+        .map_err(|e| format!("could not parse {}: {}", path, e))?;
+
+    crate::provider::check_names(&conf.providers).map_err(|e| format!("{}: {}", path, e))?;
+    crate::benchmark::check(&conf.benchmarks).map_err(|e| format!("{}: {}", path, e))?;
+    crate::render::check_highlights(&conf.highlight).map_err(|e| format!("{}: {}", path, e))?;
+    if let Some(tz) = &conf.timezone {
+        crate::time::parse_zone(tz).map_err(|e| format!("{}: {}", path, e))?;
+    }
+
+    Ok(conf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "TZ_NAME" => Some("Europe/Berlin".to_string()),
+            "KEY" => Some("s3cret".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolates_variables() {
+        assert_eq!(interpolate("${TZ_NAME}", &lookup).unwrap(), "Europe/Berlin");
+        assert_eq!(
+            interpolate("key=${KEY}&x=$5", &lookup).unwrap(),
+            "key=s3cret&x=$5"
+        );
+        assert_eq!(interpolate("$${KEY}", &lookup).unwrap(), "${KEY}");
+    }
+
+    #[test]
+    fn missing_variable_is_an_error() {
+        assert_eq!(
+            interpolate("${NOPE}", &lookup).unwrap_err(),
+            "NOPE is not set"
+        );
+        assert!(interpolate("${KEY", &lookup).is_err());
+    }
+
+    #[test]
+    fn substitutes_through_the_config() {
+        let mut raw: toml::Value =
+            toml::from_str("timezone = \"${TZ_NAME}\"\nproviders = [\"nasdaq\"]").unwrap();
+        substitute(&mut raw, &lookup).unwrap();
+        let conf: Config = raw.try_into().unwrap();
+        assert_eq!(conf.timezone.as_deref(), Some("Europe/Berlin"));
+    }
+}
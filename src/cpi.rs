@@ -0,0 +1,115 @@
+// Consumer prices, to take inflation out of returns. The monthly index comes
+// from `cpi` in config when set, otherwise from FRED's CPIAUCSL series (US
+// city average, all items), which needs no key.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::config::Config;
+use crate::http::fetch_text;
+
+pub const FRED_CPI: &str = "https://fred.stlouisfed.org/graph/fredgraph.csv?id=CPIAUCSL";
+
+// The index by the first day of each month
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cpi(pub BTreeMap<NaiveDate, f64>);
+
+impl Cpi {
+    // The latest month published by `date`; the index lags by a few weeks,
+    // so today is usually last month's figure
+    pub fn at(&self, date: NaiveDate) -> Option<f64> {
+        self.0.range(..=date).next_back().map(|(_, v)| *v)
+    }
+
+    // How much prices rose from `from` to `to`, 1.03 for 3%
+    pub fn factor(&self, from: NaiveDate, to: NaiveDate) -> Option<f64> {
+        let then = self.at(from)?;
+        let now = self.at(to)?;
+        (then > 0.0).then(|| now / then)
+    }
+}
+
+// A return in percent with inflation over the same span taken out
+pub fn real_pct(nominal_pct: f64, factor: f64) -> f64 {
+    ((1.0 + nominal_pct / 100.0) / factor - 1.0) * 100.0
+}
+
+// `cpi = { "2024-01" = 308.4, "2024-02" = 310.3 }`
+pub fn from_config(months: &BTreeMap<String, f64>) -> Result<Cpi, String> {
+    months
+        .iter()
+        .map(|(m, v)| {
+            NaiveDate::parse_from_str(&format!("{}-01", m), "%Y-%m-%d")
+                .map(|d| (d, *v))
+                .map_err(|_| format!("cpi month {} should look like 2024-01", m))
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()
+        .map(Cpi)
+}
+
+// FRED's csv: a header, then `2024-01-01,309.685` a month, with "." where
+// there's no figure
+pub fn parse_fred(csv: &str) -> Cpi {
+    Cpi(csv
+        .lines()
+        .skip(1)
+        .filter_map(|l| {
+            let (date, value) = l.split_once(',')?;
+            let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+            let value = value.trim().parse::<f64>().ok()?;
+            Some((date.with_day(1)?, value))
+        })
+        .collect())
+}
+
+pub async fn load(conf: &Config) -> Result<Cpi, String> {
+    if !conf.cpi.is_empty() {
+        return from_config(&conf.cpi);
+    }
+    let csv = fetch_text(FRED_CPI).await.map_err(|e| {
+        format!(
+            "could not fetch CPI from FRED: {}, or set `cpi` in config",
+            e
+        )
+    })?;
+    let cpi = parse_fred(&csv);
+    if cpi.0.is_empty() {
+        return Err("FRED sent no CPI figures, set `cpi` in config instead".to_string());
+    }
+    Ok(cpi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn deflates_by_the_months_in_between() {
+        let cpi = parse_fred(
+            "observation_date,CPIAUCSL\n2023-01-01,300.0\n2023-07-01,.\n2024-01-01,309.0\n",
+        );
+        assert_eq!(cpi.0.len(), 2);
+        // mid month takes that month's figure, and past the end the last one
+        assert_eq!(cpi.at(day(2023, 1, 20)), Some(300.0));
+        assert_eq!(cpi.at(day(2024, 10, 14)), Some(309.0));
+        assert_eq!(cpi.at(day(2022, 12, 31)), None);
+
+        let f = cpi.factor(day(2023, 1, 15), day(2024, 3, 1)).unwrap();
+        assert!((f - 1.03).abs() < 1e-9);
+        // 10% nominal with 3% inflation
+        assert!((real_pct(10.0, f) - 6.796116504854).abs() < 1e-9);
+
+        let months = BTreeMap::from([("2024-01".to_string(), 309.0)]);
+        assert_eq!(
+            from_config(&months).unwrap().at(day(2024, 1, 9)),
+            Some(309.0)
+        );
+        let bad = BTreeMap::from([("Jan 24".to_string(), 309.0)]);
+        assert!(from_config(&bad).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use futures::future::{BoxFuture, FutureExt};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::http::fetch_json;
+use crate::portfolio::Account;
+use crate::provider::{QuoteProvider, QuoteResult};
+use crate::yahoo::get_yahoo_value;
+
+const SATS_PER_BTC: f64 = 1e8;
+const WEI_PER_ETH: f64 = 1e18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Chain {
+    Bitcoin,
+    Ethereum,
+}
+
+impl Chain {
+    pub fn parse(name: &str) -> Option<Chain> {
+        match name {
+            "bitcoin" | "btc" => Some(Chain::Bitcoin),
+            "ethereum" | "eth" => Some(Chain::Ethereum),
+            _ => None,
+        }
+    }
+}
+
+// A public address whose balance is fetched rather than written down
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Wallet {
+    pub chain: Chain,
+    pub address: String,
+}
+
+// Confirmed and pending, from blockstream's esplora
+pub fn parse_bitcoin(v: &Value) -> Result<f64, String> {
+    let stats = |key: &str| -> Result<f64, String> {
+        let s = &v[key];
+        match (s["funded_txo_sum"].as_f64(), s["spent_txo_sum"].as_f64()) {
+            (Some(funded), Some(spent)) => Ok(funded - spent),
+            _ => Err(format!("bitcoin address response has no {}", key)),
+        }
+    };
+    Ok((stats("chain_stats")? + stats("mempool_stats").unwrap_or(0.0)) / SATS_PER_BTC)
+}
+
+// blockscout sends the balance in wei as a string, too big for an integer
+pub fn parse_ethereum(v: &Value) -> Result<f64, String> {
+    v["coin_balance"]
+        .as_str()
+        .and_then(|b| b.parse::<f64>().ok())
+        .map(|wei| wei / WEI_PER_ETH)
+        .ok_or_else(|| "ethereum address response has no coin_balance".to_string())
+}
+
+pub async fn balance(wallet: &Wallet) -> Result<f64, String> {
+    match wallet.chain {
+        Chain::Bitcoin => {
+            let url = format!("https://blockstream.info/api/address/{}", wallet.address);
+            parse_bitcoin(&fetch_json(&url).await?)
+        }
+        Chain::Ethereum => {
+            let url = format!(
+                "https://eth.blockscout.com/api/v2/addresses/{}",
+                wallet.address
+            );
+            parse_ethereum(&fetch_json(&url).await?)
+        }
+    }
+}
+
+// Replaces the `num` of every position with a wallet by what the chain says
+// it holds. One that can't be fetched keeps whatever `num` was given.
+pub async fn resolve_wallets(accounts: &mut [Account]) {
+    for stock in accounts.iter_mut().flat_map(|a| a.stocks.iter_mut()) {
+        let wallet = match &stock.wallet {
+            Some(w) => w,
+            None => continue,
+        };
+        match balance(wallet).await {
+            Ok(b) => stock.amount = b,
+            Err(e) => eprintln!(
+                "failed to fetch the {} balance of {}: {}",
+                stock.symbol, wallet.address, e
+            ),
+        }
+    }
+}
+
+// Coins are quoted in dollars on yahoo as e.g. BTC-USD
+pub struct Coins;
+
+impl QuoteProvider for Coins {
+    fn name(&self) -> &'static str {
+        "yahoo"
+    }
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult> {
+        async move { get_yahoo_value(&format!("{}-USD", symbol)).await }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bitcoin_balance_counts_pending() {
+        let v = json!({
+            "address": "bc1q",
+            "chain_stats": { "funded_txo_sum": 150_000_000, "spent_txo_sum": 50_000_000 },
+            "mempool_stats": { "funded_txo_sum": 0, "spent_txo_sum": 10_000_000 }
+        });
+        assert!((parse_bitcoin(&v).unwrap() - 0.9).abs() < 1e-12);
+        assert!(parse_bitcoin(&json!({})).is_err());
+    }
+
+    #[test]
+    fn ethereum_balance_is_in_wei() {
+        let v = json!({ "coin_balance": "2500000000000000000" });
+        assert_eq!(parse_ethereum(&v).unwrap(), 2.5);
+        assert!(parse_ethereum(&json!({ "coin_balance": null })).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+// Background mode: `stocks daemon run` refreshes quotes on a timer with no
+// screen, recording a snapshot each time and printing any alert that
+// `alerts` or the `script` raises, and `stocks daemon install` sets it up to
+// start at login as a systemd user unit on Linux or a launchd agent on macOS.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::config::{self, Config};
+use crate::portfolio::{holdings_path, load_accounts, shown};
+use crate::provider::quote_accounts;
+use crate::watch::shutdown_signal;
+use crate::{alerts, crypto, journal, lock, profile, recurring, report, script, snapshot, time};
+
+// quotes barely move faster than this, and providers limit how often
+// they're asked
+const DEFAULT_INTERVAL: u64 = 300;
+
+// One refresh: new quotes, a snapshot, and the alerts raised
+async fn refresh(conf: &Config) -> Result<Vec<alerts::Alert>, String> {
+    if let Err(e) = recurring::run(conf, &holdings_path(), time::today()).await {
+        eprintln!("failed to record recurring buys: {}", e);
+    }
+    let mut accounts = load_accounts(&holdings_path(), false)?;
+    crypto::resolve_wallets(&mut accounts).await;
+    let (stock_info, failed) = quote_accounts(&accounts, conf).await?;
+    for (symbol, e) in failed.iter() {
+        eprintln!("failed to fetch {}: {}", symbol, e);
+    }
+    if let Err(e) = snapshot::record(&snapshot::take(
+        &accounts,
+        &stock_info,
+        conf.base_currency.as_deref(),
+    )) {
+        eprintln!("failed to record snapshot: {}", e);
+    }
+    if let Err(e) = journal::record(&accounts, &stock_info, conf) {
+        eprintln!("failed to write the journal: {}", e);
+    }
+
+    if conf.script.is_none() && conf.alerts.is_empty() {
+        return Ok(vec![]);
+    }
+    let opts = report::Options::new(conf, false);
+    let r = report::build(&shown(&accounts, false), &stock_info, &opts);
+    script::alerts(conf, &r)
+}
+
+// Runs until stopped. An alert is printed when it's first raised, and after
+// that only as `alerts` decides, across restarts too.
+pub async fn run(interval: Option<u64>) {
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+
+    let live = |c: Config| Config {
+        quote_ttl: Some(0),
+        ..c
+    };
+    let mut conf = match config::load() {
+        Ok(c) => live(c),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    loop {
+        let every = interval.or(conf.interval).unwrap_or(DEFAULT_INTERVAL);
+
+        match refresh(&conf).await {
+            Ok(raised) => match alerts::fresh(&raised, &conf, Utc::now()) {
+                Ok(fresh) => {
+                    for a in fresh.iter() {
+                        println!("{} alert: {}", time::now_stamp("%Y-%m-%d %H:%M:%S"), a);
+                    }
+                }
+                Err(e) => eprintln!("{} {}", time::now_stamp("%Y-%m-%d %H:%M:%S"), e),
+            },
+            // a bad edit or the network being down shouldn't stop it for good
+            Err(e) => eprintln!("{} {}", time::now_stamp("%Y-%m-%d %H:%M:%S"), e),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(every)) => {}
+            _ = &mut stop => break,
+        }
+
+        // picks up edits as it goes, keeping the last good config through a
+        // half saved one
+        match config::load() {
+            Ok(c) => conf = live(c),
+            Err(e) => eprintln!("{} {}", time::now_stamp("%Y-%m-%d %H:%M:%S"), e),
+        }
+    }
+}
+
+// `stocks`, or `stocks-club` for a profile, so each can run its own
+fn service_name() -> String {
+    match profile::name() {
+        Some(p) => format!("stocks-{}", p),
+        None => "stocks".to_string(),
+    }
+}
+
+fn label() -> String {
+    format!("com.github.devinvs.{}", service_name())
+}
+
+// The command line the service runs
+fn command(exe: &str, interval: Option<u64>) -> Vec<String> {
+    let mut args = vec![exe.to_string()];
+    if let Some(p) = profile::name() {
+        args.extend(["--profile".to_string(), p.to_string()]);
+    }
+    args.extend(["daemon".to_string(), "run".to_string()]);
+    if let Some(i) = interval {
+        args.extend(["--interval".to_string(), i.to_string()]);
+    }
+    args
+}
+
+pub fn systemd_unit(args: &[String]) -> String {
+    let quoted = args
+        .iter()
+        .map(|a| {
+            if a.contains([' ', '"', '\\']) {
+                format!("\"{}\"", a.replace('\\', "\\\\").replace('"', "\\\""))
+            } else {
+                a.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "[Unit]\n\
+         Description=stocks portfolio refresh and alerts\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         RestartSec=60\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        quoted.join(" ")
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn launchd_plist(label: &str, args: &[String], log: &str) -> String {
+    let args = args
+        .iter()
+        .map(|a| format!("        <string>{}</string>\n", xml_escape(a)))
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         {}\
+         \x20   </array>\n\
+         \x20   <key>RunAtLoad</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>KeepAlive</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>StandardOutPath</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>StandardErrorPath</key>\n\
+         \x20   <string>{}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        xml_escape(label),
+        args,
+        xml_escape(log),
+        xml_escape(log)
+    )
+}
+
+fn home() -> String {
+    std::env::var("HOME").unwrap()
+}
+
+fn unit_path() -> String {
+    if cfg!(target_os = "macos") {
+        format!("{}/Library/LaunchAgents/{}.plist", home(), label())
+    } else {
+        format!("{}/.config/systemd/user/{}.service", home(), service_name())
+    }
+}
+
+// Runs a service manager command, failing with what it printed
+fn manage(program: &str, args: &[&str]) -> Result<(), String> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("could not run {}: {}", program, e))?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ))
+    }
+}
+
+// Writes the unit or plist for this binary and starts it, returning where
+// it was written
+pub fn install(interval: Option<u64>) -> Result<String, String> {
+    if !cfg!(any(target_os = "linux", target_os = "macos")) {
+        return Err("daemon install needs systemd on Linux or launchd on macOS".to_string());
+    }
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("could not find this binary: {}", e))?
+        .to_string_lossy()
+        .to_string();
+    let args = command(&exe, interval);
+    let path = unit_path();
+
+    let contents = if cfg!(target_os = "macos") {
+        // launchd has no journal, so output goes to a log beside the data
+        let log = format!("{}/daemon.log", profile::data_dir());
+        fs::create_dir_all(profile::data_dir())
+            .map_err(|e| format!("could not create {}: {}", profile::data_dir(), e))?;
+        launchd_plist(&label(), &args, &log)
+    } else {
+        systemd_unit(&args)
+    };
+    if let Some(dir) = Path::new(&path).parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {:?}: {}", dir, e))?;
+    }
+    lock::write_atomic(&path, contents.as_bytes())
+        .map_err(|e| format!("could not write {}: {}", path, e))?;
+
+    if cfg!(target_os = "macos") {
+        // loading twice fails, so a reinstall unloads the old one first
+        let _ = manage("launchctl", &["unload", &path]);
+        manage("launchctl", &["load", "-w", &path])?;
+    } else {
+        let unit = format!("{}.service", service_name());
+        manage("systemctl", &["--user", "daemon-reload"])?;
+        manage("systemctl", &["--user", "enable", "--now", &unit])?;
+        // already running from an earlier install, it needs the new arguments
+        manage("systemctl", &["--user", "restart", &unit])?;
+    }
+    Ok(path)
+}
+
+// Stops the service and removes what install wrote
+pub fn uninstall() -> Result<String, String> {
+    let path = unit_path();
+    if !Path::new(&path).exists() {
+        return Err(format!("not installed, there's no {}", path));
+    }
+    if cfg!(target_os = "macos") {
+        let _ = manage("launchctl", &["unload", "-w", &path]);
+    } else {
+        let unit = format!("{}.service", service_name());
+        let _ = manage("systemctl", &["--user", "disable", "--now", &unit]);
+    }
+    fs::remove_file(&path).map_err(|e| format!("could not remove {}: {}", path, e))?;
+    if !cfg!(target_os = "macos") {
+        let _ = manage("systemctl", &["--user", "daemon-reload"]);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_files_run_the_daemon_with_its_arguments() {
+        let args = command("/opt/my tools/stocks", Some(120));
+        assert_eq!(
+            args[1..],
+            ["daemon", "run", "--interval", "120"].map(String::from)
+        );
+
+        let unit = systemd_unit(&args);
+        assert!(unit.contains("ExecStart=\"/opt/my tools/stocks\" daemon run --interval 120\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+
+        let plist = launchd_plist("com.github.devinvs.stocks", &args, "/tmp/a&b.log");
+        assert!(plist.contains("<string>/opt/my tools/stocks</string>"));
+        assert!(plist.contains("<string>--interval</string>"));
+        assert!(plist.contains("<string>/tmp/a&amp;b.log</string>"));
+        assert!(plist.contains("<key>RunAtLoad</key>"));
+    }
+}
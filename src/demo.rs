@@ -0,0 +1,42 @@
+use crate::portfolio::{parse_holdings_str, Holdings};
+
+// A made up portfolio for `stocks demo`, quoted live like real holdings
+pub const HOLDINGS: &str = r#"
+[Brokerage]
+cash = 2500
+AAPL = { num = 25, price = 142.10 }
+MSFT = { num = 12, price = 281.35 }
+NVDA = { num = 8, price = 410.00, alias = "Nvidia" }
+KO = { num = 40, price = 58.20, alias = "Coca-Cola" }
+
+[Brokerage.JNJ]
+num = 15
+price = 161.40
+dividends = [{ date = 2023-09-05, amount = 17.85 }]
+
+[Retirement]
+VTI = { num = 120.5, price = 198.75, alias = "Total Market" }
+VXUS = { num = 85, price = 55.10, alias = "International" }
+BND = { num = 60, price = 72.30, alias = "Bonds" }
+
+[assets.car]
+value = 18000
+appreciation = -12
+"#;
+
+pub fn holdings() -> Holdings {
+    parse_holdings_str(HOLDINGS, "the demo holdings").expect("demo holdings parse")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_holdings_parse() {
+        let h = holdings();
+        assert_eq!(h.accounts.len(), 2);
+        assert_eq!(h.accounts[0].stocks.len(), 5);
+        assert_eq!(h.other.len(), 1);
+    }
+}
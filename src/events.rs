@@ -0,0 +1,196 @@
+// Every change made to the holdings through the CLI, one JSON line each,
+// appended and never rewritten, so `stocks log` can show who changed what
+// and when.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::history_dir;
+use crate::time;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub time: DateTime<Utc>,
+    pub user: String,
+    // what was done, e.g. "add", "remove", "close" or "import buy"
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Event {
+    // happening now, by whoever is logged in
+    pub fn new(action: &str) -> Self {
+        Event {
+            time: Utc::now(),
+            user: user(),
+            action: action.to_string(),
+            account: None,
+            symbol: None,
+            num: None,
+            price: None,
+            detail: None,
+        }
+    }
+}
+
+fn user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn events_path() -> String {
+    format!("{}/events.jsonl", history_dir())
+}
+
+fn append(events: &[Event]) -> io::Result<()> {
+    fs::create_dir_all(history_dir())?;
+    let mut buf = String::new();
+    for e in events.iter() {
+        buf.push_str(&serde_json::to_string(e)?);
+        buf.push('\n');
+    }
+    // one write, so lines from two processes never interleave
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(events_path())?
+        .write_all(buf.as_bytes())
+}
+
+// The change is already made by the time it's recorded, so failing to
+// record it is only worth a warning
+pub fn record(events: &[Event]) {
+    if events.is_empty() {
+        return;
+    }
+    if let Err(e) = append(events) {
+        eprintln!("failed to record the change in {}: {}", events_path(), e);
+    }
+}
+
+pub fn load() -> Vec<Event> {
+    let f = match File::open(events_path()) {
+        Ok(f) => f,
+        Err(_) => return vec![],
+    };
+
+    BufReader::new(f)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct Query {
+    pub since: Option<NaiveDate>,
+    pub account: Option<String>,
+    pub symbol: Option<String>,
+    // only the last this many that match
+    pub limit: Option<usize>,
+}
+
+pub fn query(events: Vec<Event>, q: &Query) -> Vec<Event> {
+    let same = |want: &Option<String>, got: &Option<String>| {
+        want.as_ref()
+            .is_none_or(|w| got.as_ref().is_some_and(|g| g.eq_ignore_ascii_case(w)))
+    };
+    let mut found = events
+        .into_iter()
+        .filter(|e| q.since.is_none_or(|d| time::date(&e.time) >= d))
+        .filter(|e| same(&q.account, &e.account) && same(&q.symbol, &e.symbol))
+        .collect::<Vec<_>>();
+    if let Some(n) = q.limit {
+        found.drain(..found.len().saturating_sub(n));
+    }
+    found
+}
+
+pub fn render(events: &[Event], out: &mut dyn Write) -> io::Result<()> {
+    if events.is_empty() {
+        return writeln!(out, "no changes recorded");
+    }
+
+    for e in events.iter() {
+        let what = match (&e.account, &e.symbol) {
+            (Some(a), Some(s)) => format!("{}.{}", a, s),
+            (Some(a), None) => a.clone(),
+            (None, Some(s)) => s.clone(),
+            (None, None) => String::new(),
+        };
+        let mut line = format!(
+            "{}  {:<10}  {:<16}  {}",
+            time::format(&e.time, "%Y-%m-%d %H:%M"),
+            e.user,
+            e.action,
+            what
+        );
+        match (e.num, e.price) {
+            (Some(n), Some(p)) => line.push_str(&format!("  {} at {}", n, p)),
+            (Some(n), None) => line.push_str(&format!("  {}", n)),
+            _ => {}
+        }
+        if let Some(d) = &e.detail {
+            line.push_str(&format!("  {}", d));
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(day: u32, action: &str, account: &str, symbol: Option<&str>) -> Event {
+        Event {
+            time: Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap(),
+            account: Some(account.to_string()),
+            symbol: symbol.map(String::from),
+            ..Event::new(action)
+        }
+    }
+
+    #[test]
+    fn queries_filter_by_date_account_and_symbol() {
+        let events = vec![
+            event(1, "add", "Brokerage", Some("AAPL")),
+            event(2, "import buy", "IRA", Some("VTI")),
+            event(3, "remove", "Brokerage", Some("aapl")),
+            event(4, "close", "Brokerage", None),
+        ];
+
+        let q = Query {
+            symbol: Some("AAPL".to_string()),
+            ..Default::default()
+        };
+        let found = query(events.clone(), &q);
+        assert_eq!(found, [events[0].clone(), events[2].clone()]);
+
+        let q = Query {
+            since: NaiveDate::from_ymd_opt(2024, 3, 2),
+            account: Some("brokerage".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(query(events.clone(), &q), [events[3].clone()]);
+
+        // a line written and read back is the same event
+        let line = serde_json::to_string(&events[1]).unwrap();
+        assert_eq!(serde_json::from_str::<Event>(&line).unwrap(), events[1]);
+    }
+}
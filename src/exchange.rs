@@ -0,0 +1,78 @@
+// Listings outside the US go by yahoo's exchange suffix, e.g. SHOP.TO in
+// Toronto or VOD.L in London. Nasdaq only knows US markets, so these are
+// always quoted from yahoo, and the suffix says which currency the listing
+// trades in when nothing else does.
+
+// suffix and the currency traded in
+const SUFFIXES: [(&str, &str); 20] = [
+    (".TO", "CAD"),
+    (".V", "CAD"),
+    (".NE", "CAD"),
+    (".CN", "CAD"),
+    (".L", "GBP"),
+    (".DE", "EUR"),
+    (".F", "EUR"),
+    (".PA", "EUR"),
+    (".AS", "EUR"),
+    (".BR", "EUR"),
+    (".MI", "EUR"),
+    (".MC", "EUR"),
+    (".HE", "EUR"),
+    (".SW", "CHF"),
+    (".ST", "SEK"),
+    (".CO", "DKK"),
+    (".OL", "NOK"),
+    (".T", "JPY"),
+    (".HK", "HKD"),
+    (".AX", "AUD"),
+];
+
+fn find(symbol: &str) -> Option<&'static (&'static str, &'static str)> {
+    let symbol = symbol.to_uppercase();
+    // share classes like BRK.B have a dot too, so only known suffixes count
+    SUFFIXES
+        .iter()
+        .find(|(suffix, _)| symbol.len() > suffix.len() && symbol.ends_with(suffix))
+}
+
+// Whether `symbol` is listed outside the US
+pub fn foreign(symbol: &str) -> bool {
+    find(symbol).is_some()
+}
+
+// The currency a suffixed listing trades in
+pub fn currency(symbol: &str) -> Option<&'static str> {
+    find(symbol).map(|(_, currency)| *currency)
+}
+
+// Some exchanges quote in the currency's minor unit, London in pence as
+// "GBp" and Johannesburg in cents as "ZAc". The major currency and what to
+// divide prices by to get there.
+pub fn major_unit(currency: &str) -> (String, f64) {
+    match currency {
+        "GBp" | "GBX" => ("GBP".to_string(), 100.0),
+        "ZAc" | "ZAC" => ("ZAR".to_string(), 100.0),
+        "ILA" => ("ILS".to_string(), 100.0),
+        c => (c.to_uppercase(), 1.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffixes_give_the_listing_currency() {
+        assert_eq!(currency("SHOP.TO"), Some("CAD"));
+        assert_eq!(currency("vod.l"), Some("GBP"));
+        assert_eq!(currency("SAP.DE"), Some("EUR"));
+        assert_eq!(currency("7203.T"), Some("JPY"));
+        // US share classes and plain tickers stay with the configured providers
+        assert!(!foreign("BRK.B"));
+        assert!(!foreign("AAPL"));
+        assert!(!foreign(".L"));
+
+        assert_eq!(major_unit("GBp"), ("GBP".to_string(), 100.0));
+        assert_eq!(major_unit("cad"), ("CAD".to_string(), 1.0));
+    }
+}
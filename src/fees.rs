@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::money::Money;
+use crate::portfolio::Account;
+use crate::provider::Quote;
+use crate::report::{self, sum};
+
+const LOW_COST_RATIO: f64 = 0.03;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Projection {
+    pub account: String,
+    pub symbol: String,
+    pub value: Money,
+    // yearly fees in percent, the fund's and the advisor's together
+    pub fee: f64,
+    // what the alternative would charge instead
+    pub alternative_fee: f64,
+    // value after the projected years with each
+    pub with_fees: Money,
+    pub alternative: Money,
+    // the difference, what the fees cost over the period
+    pub drag: Money,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeReport {
+    pub years: u32,
+    pub growth: f64,
+    pub positions: Vec<Projection>,
+    pub value: Money,
+    pub with_fees: Money,
+    pub alternative: Money,
+    pub drag: Money,
+}
+
+// Compounded yearly, with fees taken out of the return
+pub fn grow(value: f64, growth: f64, fee: f64, years: u32) -> f64 {
+    value * (1.0 + (growth - fee) / 100.0).powi(years as i32)
+}
+
+// Positions without an expense ratio, like single stocks, cost nothing to hold
+// and only carry the advisory fee
+pub fn project(
+    accounts: &[&Account],
+    stock_info: &HashMap<String, Quote>,
+    conf: &Config,
+    years: u32,
+    growth: f64,
+) -> FeeReport {
+    let report = report::build(accounts, stock_info, &report::Options::default());
+    let low_cost = conf.low_cost_ratio.unwrap_or(LOW_COST_RATIO);
+
+    let positions = report
+        .accounts
+        .iter()
+        .flat_map(|a| {
+            let advisory = conf.advisory_fees.get(&a.name).copied().unwrap_or(0.0);
+            a.rows.iter().filter(|r| !r.failed).map(move |r| {
+                let ratio = conf.expense_ratios.get(&r.symbol).copied();
+                let fee = ratio.unwrap_or(0.0) + advisory;
+                let alternative_fee = ratio.map(|r| r.min(low_cost)).unwrap_or(0.0);
+
+                let with_fees = (r.value.clone() * grow(1.0, growth, fee, years)).round();
+                let alternative =
+                    (r.value.clone() * grow(1.0, growth, alternative_fee, years)).round();
+                Projection {
+                    account: a.name.clone(),
+                    symbol: r.symbol.clone(),
+                    value: r.value.clone(),
+                    fee,
+                    alternative_fee,
+                    drag: (alternative.clone() - with_fees.clone()).round(),
+                    with_fees,
+                    alternative,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    FeeReport {
+        years,
+        growth,
+        value: sum(positions.iter().map(|p| p.value.clone())),
+        with_fees: sum(positions.iter().map(|p| p.with_fees.clone())),
+        alternative: sum(positions.iter().map(|p| p.alternative.clone())),
+        drag: sum(positions.iter().map(|p| p.drag.clone())),
+        positions,
+    }
+}
+
+pub fn render(r: &FeeReport, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "fees over {} years at {:.1}% growth a year",
+        r.years, r.growth
+    )?;
+    writeln!(
+        out,
+        "\x1b[1m\t{:<16}  {:<8}  {:>12}  {:>6}  {:>6}  {:>14}\x1b[0m",
+        "Account", "Symbol", "Value", "Fee", "Alt", "Cost"
+    )?;
+
+    for p in r.positions.iter().filter(|p| p.fee > 0.0) {
+        writeln!(
+            out,
+            "\t{:<16}  {:<8}  {:>12}  {:>5.2}%  {:>5.2}%  {:>14}",
+            p.account, p.symbol, p.value, p.fee, p.alternative_fee, p.drag
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "\t{:<16}  {:>14}", "With fees", r.with_fees)?;
+    writeln!(out, "\t{:<16}  {:>14}", "Low cost", r.alternative)?;
+    writeln!(out, "\x1b[1m\t{:<16}  {:>14}\x1b[0m", "Fee drag", r.drag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Stock;
+
+    fn stock(symbol: &str, amount: f64) -> Stock {
+        Stock {
+            symbol: symbol.to_string(),
+            amount,
+            cost_basis: Money::usd(100.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fund_and_advisory_fees_compound_against_a_low_cost_fund() {
+        let account = Account::test("Advised", vec![stock("ARKK", 100.0), stock("AAPL", 100.0)]);
+        let quotes = ["ARKK", "AAPL"]
+            .map(|s| {
+                (
+                    s.to_string(),
+                    Quote {
+                        price: Money::usd(100.0),
+                        ..Default::default()
+                    },
+                )
+            })
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let conf = Config {
+            expense_ratios: [("ARKK".to_string(), 0.75)].into(),
+            advisory_fees: [("Advised".to_string(), 1.0)].into(),
+            ..Default::default()
+        };
+
+        let r = project(&[&account], &quotes, &conf, 10, 6.0);
+        let arkk = &r.positions[0];
+        assert_eq!(arkk.fee, 1.75);
+        assert_eq!(arkk.alternative_fee, 0.03);
+        assert!((arkk.with_fees.amount - 10000.0 * 1.0425f64.powi(10)).abs() < 0.005);
+        assert!((arkk.alternative.amount - 10000.0 * 1.0597f64.powi(10)).abs() < 0.005);
+
+        // a single stock only pays the advisor
+        let aapl = &r.positions[1];
+        assert_eq!(aapl.alternative_fee, 0.0);
+        assert!((aapl.drag.amount - 10000.0 * (1.06f64.powi(10) - 1.05f64.powi(10))).abs() < 0.01);
+
+        assert_eq!(r.drag, (arkk.drag.clone() + aapl.drag.clone()).round());
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::DateTime;
+use futures::future::{BoxFuture, FutureExt};
+use serde_json::Value;
+
+use crate::http::fetch_json_with;
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::provider::{Quote, QuoteError, QuoteProvider, QuoteResult, Status};
+
+// Needs a free API key from finnhub.io, as `api_keys.finnhub`, the
+// FINNHUB_API_KEY environment variable or `stocks auth set finnhub`
+pub struct Finnhub {
+    pub key: Option<String>,
+}
+
+impl QuoteProvider for Finnhub {
+    fn name(&self) -> &'static str {
+        "finnhub"
+    }
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult> {
+        async move {
+            let key = self.key.as_deref().ok_or_else(|| {
+                QuoteError::Network("no finnhub API key, see `stocks auth set`".to_string())
+            })?;
+            let url = format!("https://finnhub.io/api/v1/quote?symbol={}", symbol);
+
+            let v = fetch_json_with(&url, &[("X-Finnhub-Token", key)])
+                .await
+                .map_err(QuoteError::Network)?;
+            parse_quote(symbol, &v)
+        }
+        .boxed()
+    }
+}
+
+// Unknown symbols aren't an error to finnhub, they come back as all zeros.
+// Prices are in the listing's currency, which the quote doesn't name.
+pub fn parse_quote(symbol: &str, v: &Value) -> QuoteResult {
+    if let Some(e) = v["error"].as_str() {
+        return Err(QuoteError::Network(format!("finnhub: {}", e)));
+    }
+
+    let price = v["c"].as_f64().ok_or_else(|| {
+        QuoteError::Schema(format!("finnhub response for {} has no price", symbol))
+    })?;
+    if price == 0.0 && v["t"].as_i64().unwrap_or(0) == 0 {
+        return Err(QuoteError::NotFound);
+    }
+
+    let prev = v["pc"].as_f64().ok_or_else(|| {
+        QuoteError::Schema(format!(
+            "finnhub response for {} has no previous close",
+            symbol
+        ))
+    })?;
+
+    Ok(Quote {
+        price: Money::new(price, DEFAULT_CURRENCY),
+        change: Money::new(price - prev, DEFAULT_CURRENCY),
+        open: v["o"]
+            .as_f64()
+            .filter(|o| *o != 0.0)
+            .map(|o| Money::new(o, DEFAULT_CURRENCY)),
+        time: v["t"].as_i64().and_then(|t| DateTime::from_timestamp(t, 0)),
+        status: Status::Trading,
+    })
+}
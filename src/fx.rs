@@ -0,0 +1,103 @@
+// Listings abroad are in their own currency, which each row keeps, while
+// the totals across them are in one base currency: `base_currency` in config,
+// or else the one every position and cash balance is in, or dollars. Rates
+// are yahoo's currency pairs, like CADUSD=X for what one Canadian dollar
+// buys in US dollars, quoted along with the positions and kept among them.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::portfolio::Account;
+use crate::provider::Quote;
+
+// The symbol yahoo quotes `to` per unit of `from` under
+pub fn pair(from: &str, to: &str) -> String {
+    format!("{}{}=X", from, to)
+}
+
+// Whether a quote is one of these rates rather than something held
+pub fn is_pair(symbol: &str) -> bool {
+    symbol.len() == 8 && symbol.ends_with("=X")
+}
+
+// Every currency the accounts hold positions or cash in
+pub fn currencies<'a>(accounts: impl Iterator<Item = &'a Account>) -> BTreeSet<String> {
+    let mut held = BTreeSet::new();
+    for a in accounts {
+        held.extend(a.stocks.iter().map(|s| s.cost_basis.currency.clone()));
+        held.extend(a.cash.iter().map(|c| c.currency.clone()));
+    }
+    held
+}
+
+pub fn base<'a>(configured: Option<&str>, accounts: impl Iterator<Item = &'a Account>) -> String {
+    if let Some(c) = configured {
+        return c.to_uppercase();
+    }
+    let held = currencies(accounts);
+    match held.iter().next() {
+        Some(c) if held.len() == 1 => c.clone(),
+        _ => DEFAULT_CURRENCY.to_string(),
+    }
+}
+
+// `m` in `to` at the rate among the quotes, either way round; with no rate
+// it's NaN, as any other sum across currencies is, and shows as n/a
+pub fn convert(m: &Money, to: &str, quotes: &HashMap<String, Quote>) -> Money {
+    if m.currency == to {
+        return m.clone();
+    }
+    let rate = |from: &str, to: &str| {
+        quotes
+            .get(&pair(from, to))
+            .map(|q| &q.price)
+            .filter(|p| p.currency == to && p.amount != 0.0)
+            .map(|p| p.amount)
+    };
+    match (rate(&m.currency, to), rate(to, &m.currency)) {
+        (Some(r), _) => Money::new(m.amount * r, to),
+        (None, Some(r)) => Money::new(m.amount / r, to),
+        (None, None) => Money::new(f64::NAN, to),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Stock;
+
+    #[test]
+    fn totals_go_to_one_currency() {
+        let stock = |symbol: &str, currency: &str| Stock {
+            symbol: symbol.to_string(),
+            cost_basis: Money::new(10.0, currency),
+            ..Default::default()
+        };
+        let account = |stocks| Account::test("Brokerage", stocks);
+        let mixed = account(vec![stock("AAPL", "USD"), stock("SHOP.TO", "CAD")]);
+        let canadian = account(vec![stock("SHOP.TO", "CAD")]);
+
+        assert_eq!(base(None, [&mixed].into_iter()), "USD");
+        assert_eq!(base(None, [&canadian].into_iter()), "CAD");
+        assert_eq!(base(Some("eur"), [&canadian].into_iter()), "EUR");
+
+        let quotes = HashMap::from([(
+            pair("CAD", "USD"),
+            Quote {
+                price: Money::usd(0.75),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(
+            convert(&Money::new(100.0, "CAD"), "USD", &quotes),
+            Money::usd(75.0)
+        );
+        assert_eq!(
+            convert(&Money::usd(75.0), "CAD", &quotes),
+            Money::new(100.0, "CAD")
+        );
+        assert!(convert(&Money::new(100.0, "GBP"), "USD", &quotes).is_nan());
+        assert!(is_pair(&pair("CAD", "USD")));
+        assert!(!is_pair("ES=F"));
+    }
+}
@@ -0,0 +1,79 @@
+// Charts, sparklines and bars draw with block and box characters that not
+// every terminal has: the Linux console lacks most of them, and a locale that
+// isn't UTF-8 can't show them at all. With --ascii, or when the terminal looks
+// like one of those, they're drawn from plain ASCII instead, one column each.
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once at startup, like the money rounding
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+pub fn set_ascii(on: bool) {
+    ASCII.store(on, Ordering::Relaxed);
+}
+
+pub fn ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+// The unicode glyph, or its stand in when drawing in ASCII
+pub fn pick<T>(unicode: T, ascii: T) -> T {
+    if self::ascii() {
+        ascii
+    } else {
+        unicode
+    }
+}
+
+// Whether the environment can't show unicode: a locale set to something other
+// than UTF-8 (the first of LC_ALL, LC_CTYPE and LANG that's set wins, as in
+// libc), or a terminal known to lack the glyphs
+pub fn unsupported(var: &dyn Fn(&str) -> Option<String>) -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|v| var(v).filter(|l| !l.is_empty()));
+    let utf8 = |l: &str| {
+        let l = l.to_lowercase();
+        l.contains("utf-8") || l.contains("utf8")
+    };
+    if locale.is_some_and(|l| !utf8(&l)) {
+        return true;
+    }
+    matches!(
+        var("TERM").as_deref(),
+        Some("dumb" | "linux" | "vt100" | "vt220")
+    )
+}
+
+pub fn detect() -> bool {
+    unsupported(&|v| env::var(v).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn plain_locales_and_consoles_lack_unicode() {
+        let check = |vars: &[(&str, &str)]| {
+            let vars = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>();
+            unsupported(&|v| vars.get(v).cloned())
+        };
+        assert!(!check(&[
+            ("LANG", "en_US.UTF-8"),
+            ("TERM", "xterm-256color")
+        ]));
+        assert!(!check(&[("LC_ALL", "C.utf8")]));
+        assert!(check(&[("LANG", "C")]));
+        // LC_ALL overrides LANG
+        assert!(check(&[("LC_ALL", "POSIX"), ("LANG", "en_US.UTF-8")]));
+        assert!(check(&[("LANG", "en_US.UTF-8"), ("TERM", "linux")]));
+        // nothing set says nothing either way
+        assert!(!check(&[]));
+    }
+}
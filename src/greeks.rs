@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use futures::future::join_all;
+
+use crate::config::Config;
+use crate::options::{self, greeks, Greeks};
+use crate::portfolio::Account;
+use crate::provider::Quote;
+use crate::render::clr;
+use crate::time;
+use crate::yahoo::get_implied_vol;
+
+const RATE: f64 = 0.04;
+
+// Exposure per underlying in units: shares or futures contracts times their
+// size, plus each option's delta times its contract size. Options without greeks are left out.
+pub fn exposure(accounts: &[&Account], greeks: &BTreeMap<String, Greeks>) -> BTreeMap<String, f64> {
+    let mut out = BTreeMap::new();
+
+    for stock in accounts.iter().flat_map(|a| a.stocks.iter()) {
+        match options::parse(&stock.symbol) {
+            Some(c) => {
+                if let Some(g) = greeks.get(&stock.symbol) {
+                    *out.entry(c.underlying).or_insert(0.0) +=
+                        g.delta * stock.amount * stock.multiplier;
+                }
+            }
+            None => {
+                *out.entry(stock.symbol.clone()).or_insert(0.0) += stock.amount * stock.multiplier
+            }
+        }
+    }
+
+    out
+}
+
+pub async fn run(
+    accounts: &[&Account],
+    stock_info: &HashMap<String, Quote>,
+    conf: &Config,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let today = time::today();
+    let rate = conf.risk_free_rate.unwrap_or(RATE);
+
+    let mut contracts = accounts
+        .iter()
+        .flat_map(|a| a.stocks.iter())
+        .filter_map(|s| Some((s.symbol.clone(), options::parse(&s.symbol)?)))
+        .collect::<Vec<_>>();
+    contracts.sort_by(|a, b| a.0.cmp(&b.0));
+    contracts.dedup_by(|a, b| a.0 == b.0);
+
+    let ivs = join_all(contracts.iter().map(|(s, c)| get_implied_vol(s, c))).await;
+
+    let mut computed = BTreeMap::new();
+    for ((symbol, c), iv) in contracts.iter().zip(ivs) {
+        if c.expiry < today {
+            continue;
+        }
+        let spot = stock_info
+            .get(&c.underlying)
+            .map(|q| q.price.amount)
+            .unwrap_or_default();
+        // expiring today still has the rest of the session left
+        let years = ((c.expiry - today).num_days() as f64).max(0.5) / 365.0;
+
+        match iv {
+            Ok(iv) if spot > 0.0 => {
+                computed.insert(
+                    symbol.clone(),
+                    greeks(c.kind, spot, c.strike, years, iv, rate),
+                );
+            }
+            Ok(_) => eprintln!("no price for {}, skipping {}", c.underlying, symbol),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if contracts.is_empty() {
+        writeln!(out, "no option positions")?;
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "\x1b[1m\t{:<21}  {:>8}  {:>7}  {:>10}  {:>7}\x1b[0m",
+        "Contract", "Qty", "Delta", "Theta/day", "IV"
+    )?;
+
+    for account in accounts.iter() {
+        for stock in account.stocks.iter() {
+            let g = match computed.get(&stock.symbol) {
+                Some(g) => g,
+                None => continue,
+            };
+            // theta per share, so a position's daily decay needs the contract size
+            let theta = g.theta * stock.amount * stock.multiplier;
+            writeln!(
+                out,
+                "\t{:<21}  {:>8}  {:>7.3}  {}{:>10.2}\x1b[0m  {:>6.1}%",
+                stock.symbol,
+                stock.amount,
+                g.delta,
+                clr(theta),
+                theta,
+                g.iv * 100.0
+            )?;
+        }
+    }
+
+    writeln!(
+        out,
+        "\n\x1b[1m\t{:<21}  {:>10}  {:>12}\x1b[0m",
+        "Underlying", "Delta sh", "Exposure"
+    )?;
+    for (underlying, shares) in exposure(accounts, &computed) {
+        let price = stock_info
+            .get(&underlying)
+            .map(|q| q.price.clone())
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "\t{:<21}  {:>10.1}  {:>12}",
+            underlying,
+            shares,
+            price * shares
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::portfolio::Stock;
+
+    fn stock(symbol: &str, amount: f64) -> Stock {
+        Stock {
+            symbol: symbol.to_string(),
+            amount,
+            cost_basis: Money::usd(1.0),
+            multiplier: if options::parse(symbol).is_some() {
+                options::MULTIPLIER
+            } else {
+                1.0
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exposure_adds_option_deltas_to_shares() {
+        let account = Account::test(
+            "a",
+            vec![
+                stock("AAPL", 100.0),
+                stock("AAPL231215C00190000", -1.0),
+                stock("VTI", 5.0),
+            ],
+        );
+        let greeks = BTreeMap::from([(
+            "AAPL231215C00190000".to_string(),
+            Greeks {
+                delta: 0.4,
+                theta: -0.1,
+                iv: 0.2,
+            },
+        )]);
+
+        let e = exposure(&[&account], &greeks);
+        assert_eq!(e["AAPL"], 60.0);
+        assert_eq!(e["VTI"], 5.0);
+    }
+}
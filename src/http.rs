@@ -0,0 +1,268 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use crate::debug;
+use crate::{cache, cassette};
+
+// A provider that stops answering fails the request rather than holding up
+// the rest, so the next provider gets its turn
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(15);
+// downloads are bigger, so have longer to finish
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+// One client for every request, so connections are pooled and reused
+// across symbols rather than set up per quote
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(TIMEOUT)
+            .build()
+            .expect("could not set up the http client")
+    })
+}
+
+static FROM_NETWORK: AtomicUsize = AtomicUsize::new(0);
+static FROM_CACHE: AtomicUsize = AtomicUsize::new(0);
+
+// Responses so far that came over the network and that were answered 304
+// from the cache, replayed cassettes counting as the network
+pub fn counts() -> (usize, usize) {
+    (
+        FROM_NETWORK.load(Ordering::Relaxed),
+        FROM_CACHE.load(Ordering::Relaxed),
+    )
+}
+
+pub async fn fetch_text(url: &str) -> Result<String, String> {
+    fetch_text_with(url, &[]).await
+}
+
+// Extra headers are for credentials, which are kept out of the url so they
+// never end up in a cassette or cache file name
+pub async fn fetch_text_with(url: &str, headers: &[(&str, &str)]) -> Result<String, String> {
+    let (status, body) = match cassette::replay(url)? {
+        Some(c) => {
+            FROM_NETWORK.fetch_add(1, Ordering::Relaxed);
+            (c.status, c.body)
+        }
+        None => {
+            let (status, body) = fetch_remote(url, headers).await?;
+            // recorded either way, so a replay fails the same way
+            cassette::record(url, status, &body);
+            (status, body)
+        }
+    };
+    checked(url, status)?;
+    Ok(body)
+}
+
+// Error pages are an error here, rather than a body that fails to parse
+// further on. 304 is a cache hit, answered with the cached body.
+fn checked(url: &str, status: u16) -> Result<(), String> {
+    match StatusCode::from_u16(status) {
+        Ok(s) if s.is_success() || s == StatusCode::NOT_MODIFIED => Ok(()),
+        Ok(s) => Err(format!("{} returned {}", url, s)),
+        Err(_) => Err(format!("{} returned {}", url, status)),
+    }
+}
+
+// Whether a fetch failed because there's nothing at the url, which to a
+// provider usually means an unknown symbol
+pub fn not_found(e: &str) -> bool {
+    e.ends_with(&format!("returned {}", StatusCode::NOT_FOUND))
+}
+
+async fn fetch_remote(url: &str, headers: &[(&str, &str)]) -> Result<(u16, String), String> {
+    // Accept-Encoding is left to reqwest, which only offers the encodings its
+    // gzip, deflate and brotli features can decode
+    let client = client();
+    let cached = cache::load(url);
+
+    let mut req = client.get(url)
+            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/85.0.4183.121 Safari/537.36")
+            .header("Accept", "*/*")
+            .header("Connection", "keep-alive");
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+
+    if let Some(c) = &cached {
+        if let Some(etag) = &c.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &c.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+
+    let res = req.send().await.map_err(|e| e.to_string())?;
+    let status = res.status();
+
+    if status == StatusCode::NOT_MODIFIED {
+        // only asked for with a cached copy, but a proxy or a cache file
+        // gone since may still leave nothing to answer with
+        let c = cached.ok_or_else(|| format!("{} returned 304 with nothing cached", url))?;
+        debug!("{} not modified, using the cached body", url);
+        FROM_CACHE.fetch_add(1, Ordering::Relaxed);
+        return Ok((status.as_u16(), c.body));
+    }
+
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+            .map(String::from)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+
+    let body = res.text().await.map_err(|e| e.to_string())?;
+    FROM_NETWORK.fetch_add(1, Ordering::Relaxed);
+
+    if status.is_success() && (etag.is_some() || last_modified.is_some()) {
+        cache::store(
+            url,
+            &cache::Entry {
+                etag,
+                last_modified,
+                body: body.clone(),
+            },
+        );
+    }
+
+    Ok((status.as_u16(), body))
+}
+
+pub async fn fetch_json(url: &str) -> Result<Value, String> {
+    fetch_json_with(url, &[]).await
+}
+
+pub async fn fetch_json_with(url: &str, headers: &[(&str, &str)]) -> Result<Value, String> {
+    let res = fetch_text_with(url, headers).await?;
+    serde_json::from_str(&res).map_err(|e| format!("invalid json: {}", e))
+}
+
+pub async fn get_json(url: &str) -> Option<Value> {
+    fetch_json(url).await.ok()
+}
+
+// POSTs `body` as JSON. Cassettes are keyed on the body as well as the URL,
+// since lookups all go to the same address.
+pub async fn post_json(
+    url: &str,
+    body: &Value,
+    headers: &[(&str, String)],
+) -> Result<Value, String> {
+    let body = body.to_string();
+    let key = format!("{}?{}", url, body);
+
+    let (status, text) = match cassette::replay(&key)? {
+        Some(c) => (c.status, c.body),
+        None => {
+            let mut req = client()
+                .post(url)
+                .header("Content-Type", "application/json")
+                .header("User-Agent", concat!("stocks/", env!("CARGO_PKG_VERSION")));
+            for (name, value) in headers {
+                req = req.header(*name, value);
+            }
+
+            let res = req.body(body).send().await.map_err(|e| e.to_string())?;
+            let status = res.status().as_u16();
+            let text = res.text().await.map_err(|e| e.to_string())?;
+            cassette::record(&key, status, &text);
+            (status, text)
+        }
+    };
+    checked(url, status)?;
+
+    serde_json::from_str(&text).map_err(|e| format!("invalid json: {}", e))
+}
+
+// Raw response body for downloads; never recorded, cassettes only hold text
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let res = client()
+        .get(url)
+        .header("User-Agent", concat!("stocks/", env!("CARGO_PKG_VERSION")))
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !res.status().is_success() {
+        return Err(format!("{} returned {}", url, res.status()));
+    }
+
+    let body = res.bytes().await.map_err(|e| e.to_string())?;
+    Ok(body.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Serves one file with the given Content-Encoding, once
+    async fn serve(encoding: &'static str, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = sock.read(&mut buf).await;
+
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                encoding,
+                body.len()
+            );
+            sock.write_all(head.as_bytes()).await.unwrap();
+            sock.write_all(&body).await.unwrap();
+        });
+
+        format!("http://{}/{}", addr, encoding)
+    }
+
+    #[tokio::test]
+    async fn decodes_compressed_responses() {
+        let plain = include_str!("../tests/fixtures/aapl_info.json");
+        let fixtures: [(&str, &[u8]); 3] = [
+            (
+                "gzip",
+                include_bytes!("../tests/fixtures/aapl_info.json.gz"),
+            ),
+            (
+                "deflate",
+                include_bytes!("../tests/fixtures/aapl_info.json.zz"),
+            ),
+            ("br", include_bytes!("../tests/fixtures/aapl_info.json.br")),
+        ];
+
+        for (encoding, body) in fixtures {
+            let url = serve(encoding, body.to_vec()).await;
+            // straight to the network, other tests may have STOCKS_REPLAY set
+            let (_, text) = fetch_remote(&url, &[]).await.unwrap();
+            assert_eq!(text, plain, "{} body", encoding);
+        }
+    }
+
+    #[test]
+    fn error_statuses_are_errors() {
+        assert!(checked("https://x", 200).is_ok());
+        assert!(checked("https://x", 304).is_ok());
+        let e = checked("https://x", 404).unwrap_err();
+        assert_eq!(e, "https://x returned 404 Not Found");
+        assert!(not_found(&e));
+        assert!(!not_found(&checked("https://x", 503).unwrap_err()));
+    }
+}
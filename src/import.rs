@@ -0,0 +1,910 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use chrono::NaiveDate;
+use toml_edit::{Array, Document, InlineTable, Item, Table, Value};
+
+use crate::config::Config;
+use crate::events::{self, Event};
+use crate::lots;
+use crate::money::Money;
+use crate::portfolio::{self, edit_holdings, Trade};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Buy,
+    Sell,
+    Dividend,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub date: NaiveDate,
+    pub symbol: String,
+    pub action: Action,
+    // shares bought or sold, always positive
+    pub num: f64,
+    pub price: f64,
+    // cash paid out, for dividends
+    pub amount: f64,
+}
+
+// A row as the broker wrote it, before its action is looked up
+#[derive(Debug, Default, Clone)]
+pub struct Raw {
+    pub date: Option<NaiveDate>,
+    pub action: String,
+    pub symbol: String,
+    pub num: Option<f64>,
+    pub price: Option<f64>,
+    pub amount: Option<f64>,
+}
+
+impl Transaction {
+    // what a ledger entry for this transaction looks like, see `ledger_key`
+    fn key(&self) -> String {
+        match self.action {
+            Action::Buy => ledger_key(self.date, &self.symbol, "trade", self.num, self.price),
+            Action::Sell => ledger_key(self.date, &self.symbol, "trade", -self.num, self.price),
+            Action::Dividend => ledger_key(self.date, &self.symbol, "dividend", self.amount, 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub buys: usize,
+    pub sells: usize,
+    pub dividends: usize,
+    // written to the ledger, in order
+    pub applied: Vec<Transaction>,
+    // already in the ledger from an earlier import
+    pub duplicates: Vec<Transaction>,
+}
+
+// Broker action strings are matched case-insensitively against this table;
+// `import_actions` in config.toml adds to it or overrides it, with "ignore"
+// for actions that should be skipped quietly
+const ACTIONS: [(&str, Option<Action>); 31] = [
+    ("BUY", Some(Action::Buy)),
+    ("BOUGHT", Some(Action::Buy)),
+    ("YOU BOUGHT", Some(Action::Buy)),
+    ("BUYX", Some(Action::Buy)),
+    ("REINVEST", Some(Action::Buy)),
+    ("REINVDIV", Some(Action::Buy)),
+    ("REINVESTMENT", Some(Action::Buy)),
+    ("BUYSTOCK", Some(Action::Buy)),
+    ("BUYMF", Some(Action::Buy)),
+    ("BUYOTHER", Some(Action::Buy)),
+    ("BUYDEBT", Some(Action::Buy)),
+    ("BUYOPT", Some(Action::Buy)),
+    ("SELL", Some(Action::Sell)),
+    ("SOLD", Some(Action::Sell)),
+    ("YOU SOLD", Some(Action::Sell)),
+    ("SELLX", Some(Action::Sell)),
+    ("SELLSTOCK", Some(Action::Sell)),
+    ("SELLMF", Some(Action::Sell)),
+    ("SELLOTHER", Some(Action::Sell)),
+    ("SELLDEBT", Some(Action::Sell)),
+    ("SELLOPT", Some(Action::Sell)),
+    ("DIV", Some(Action::Dividend)),
+    ("DIVX", Some(Action::Dividend)),
+    ("DIVIDEND", Some(Action::Dividend)),
+    ("CASH DIVIDEND", Some(Action::Dividend)),
+    ("DIVIDEND RECEIVED", Some(Action::Dividend)),
+    ("QUALIFIED DIVIDEND", Some(Action::Dividend)),
+    ("INCOME", Some(Action::Dividend)),
+    ("INTEREST", None),
+    ("TRANSFER", None),
+    ("FEE", None),
+];
+
+pub fn action_table(conf: &Config) -> Result<HashMap<String, Option<Action>>, String> {
+    let mut table = ACTIONS
+        .iter()
+        .map(|(k, a)| (k.to_string(), *a))
+        .collect::<HashMap<_, _>>();
+
+    for (k, v) in conf.import_actions.iter() {
+        let action = match v.to_lowercase().as_str() {
+            "buy" => Some(Action::Buy),
+            "sell" => Some(Action::Sell),
+            "dividend" => Some(Action::Dividend),
+            "ignore" => None,
+            _ => {
+                return Err(format!(
+                    "import_actions.{} should be buy, sell, dividend or ignore, not {}",
+                    k, v
+                ))
+            }
+        };
+        table.insert(k.trim().to_uppercase(), action);
+    }
+
+    Ok(table)
+}
+
+// Rows whose action is ignored come back as None; unknown actions are an error
+// carrying the action so they can be counted
+pub fn resolve(
+    raw: &Raw,
+    table: &HashMap<String, Option<Action>>,
+) -> Result<Option<Transaction>, String> {
+    let key = raw.action.trim().to_uppercase();
+    let action = match table.get(&key) {
+        Some(Some(a)) => *a,
+        Some(None) => return Ok(None),
+        None => return Err(key),
+    };
+    let date = raw.date.ok_or_else(|| key.clone())?;
+    if raw.symbol.is_empty() {
+        return Err(key);
+    }
+
+    let num = raw.num.map(f64::abs).unwrap_or_default();
+    let amount = raw.amount.map(f64::abs).unwrap_or_default();
+    // some exports only give the total
+    let price = match raw.price {
+        Some(p) => p.abs(),
+        None if num > 0.0 => amount / num,
+        None => 0.0,
+    };
+
+    if action != Action::Dividend && num == 0.0 {
+        return Err(key);
+    }
+
+    Ok(Some(Transaction {
+        date,
+        symbol: raw.symbol.trim().to_uppercase(),
+        action,
+        num,
+        price,
+        amount,
+    }))
+}
+
+// The transactions that could be read, and how many rows of each unknown or
+// incomplete action were left out
+pub fn resolve_all(
+    rows: &[Raw],
+    table: &HashMap<String, Option<Action>>,
+) -> (Vec<Transaction>, BTreeMap<String, usize>) {
+    let mut txns = vec![];
+    let mut skipped = BTreeMap::new();
+
+    for raw in rows.iter() {
+        match resolve(raw, table) {
+            Ok(Some(t)) => txns.push(t),
+            Ok(None) => {}
+            Err(action) => *skipped.entry(action).or_insert(0) += 1,
+        }
+    }
+
+    (txns, skipped)
+}
+
+pub fn read(path: &str, kind: Option<&str>) -> Result<Vec<Raw>, String> {
+    let buf = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+
+    let ext = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+    match kind.unwrap_or(&ext) {
+        "csv" => parse_csv(&buf),
+        "qif" => Ok(parse_qif(&buf)),
+        "ofx" | "qfx" => Ok(parse_ofx(&buf)),
+        k => Err(format!(
+            "can't tell what kind of export {} is ({}), pass --kind csv, qif or ofx",
+            path, k
+        )),
+    }
+}
+
+// Header names that brokers use for each column, lowercased
+const DATE_COLUMNS: [&str; 6] = [
+    "date",
+    "trade date",
+    "run date",
+    "transaction date",
+    "settlement date",
+    "activity date",
+];
+const ACTION_COLUMNS: [&str; 5] = [
+    "action",
+    "type",
+    "activity",
+    "transaction type",
+    "transaction",
+];
+const SYMBOL_COLUMNS: [&str; 3] = ["symbol", "ticker", "security"];
+const NUM_COLUMNS: [&str; 4] = ["quantity", "qty", "shares", "units"];
+const PRICE_COLUMNS: [&str; 3] = ["price", "unit price", "price ($)"];
+const AMOUNT_COLUMNS: [&str; 4] = ["amount", "net amount", "total", "amount ($)"];
+
+// Exports saved from Excel and some brokers start with a byte order mark,
+// which would otherwise stick to the first header or line
+const BOM: char = '\u{feff}';
+
+pub fn parse_csv(buf: &str) -> Result<Vec<Raw>, String> {
+    let buf = buf.trim_start_matches(BOM);
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(buf.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("could not read csv header: {}", e))?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect::<Vec<_>>();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let date = column(&DATE_COLUMNS).ok_or("csv has no date column")?;
+    let action = column(&ACTION_COLUMNS).ok_or("csv has no action column")?;
+    let symbol = column(&SYMBOL_COLUMNS).ok_or("csv has no symbol column")?;
+    let num = column(&NUM_COLUMNS);
+    let price = column(&PRICE_COLUMNS);
+    let amount = column(&AMOUNT_COLUMNS);
+
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("could not read csv: {}", e))?;
+        let get = |i: Option<usize>| i.and_then(|i| record.get(i)).unwrap_or_default();
+
+        rows.push(Raw {
+            date: parse_date(get(Some(date))),
+            action: get(Some(action)).to_string(),
+            symbol: get(Some(symbol)).to_string(),
+            num: parse_number(get(num)),
+            price: parse_number(get(price)),
+            amount: parse_number(get(amount)),
+        });
+    }
+
+    Ok(rows)
+}
+
+// Investment QIF: one field per line keyed by its first character, "^" ends
+// a record
+pub fn parse_qif(buf: &str) -> Vec<Raw> {
+    let mut rows = vec![];
+    let mut raw = Raw::default();
+
+    for line in buf.trim_start_matches(BOM).lines().map(str::trim) {
+        let code = match line.chars().next() {
+            Some('!') | None => continue,
+            Some(c) => c,
+        };
+        let value = &line[code.len_utf8()..];
+        match code {
+            'D' => raw.date = parse_date(value),
+            'N' => raw.action = value.to_string(),
+            'Y' => raw.symbol = value.to_string(),
+            'I' => raw.price = parse_number(value),
+            'Q' => raw.num = parse_number(value),
+            'T' | 'U' => raw.amount = parse_number(value),
+            '^' => rows.push(std::mem::take(&mut raw)),
+            _ => {}
+        }
+    }
+
+    rows
+}
+
+const OFX_TRANSACTIONS: [&str; 12] = [
+    "BUYSTOCK",
+    "BUYMF",
+    "BUYOTHER",
+    "BUYDEBT",
+    "BUYOPT",
+    "SELLSTOCK",
+    "SELLMF",
+    "SELLOTHER",
+    "SELLDEBT",
+    "SELLOPT",
+    "INCOME",
+    "REINVEST",
+];
+
+// Both the SGML and XML flavours of OFX: leaf values may or may not be
+// closed, but the aggregates always are. The transaction's own tag is its
+// action, and securities are named by CUSIP until the SECLIST maps them
+// to tickers.
+pub fn parse_ofx(buf: &str) -> Vec<Raw> {
+    let mut rows = vec![];
+    let mut current: Option<(String, Raw)> = None;
+    let mut in_secinfo = false;
+    let mut sec = (String::new(), String::new());
+    let mut tickers = HashMap::new();
+
+    for part in buf.split('<').skip(1) {
+        let (tag, value) = part.split_once('>').unwrap_or((part, ""));
+        let value = value.trim();
+
+        if let Some(tag) = tag.strip_prefix('/') {
+            if tag == "SECINFO" {
+                in_secinfo = false;
+                tickers.insert(std::mem::take(&mut sec.0), std::mem::take(&mut sec.1));
+            } else if current.as_ref().is_some_and(|(t, _)| t == tag) {
+                rows.push(current.take().unwrap().1);
+            }
+            continue;
+        }
+
+        if OFX_TRANSACTIONS.contains(&tag) {
+            current = Some((
+                tag.to_string(),
+                Raw {
+                    action: tag.to_string(),
+                    ..Default::default()
+                },
+            ));
+            continue;
+        }
+        if tag == "SECINFO" {
+            in_secinfo = true;
+            continue;
+        }
+
+        if in_secinfo {
+            match tag {
+                "UNIQUEID" => sec.0 = value.to_string(),
+                "TICKER" => sec.1 = value.to_string(),
+                _ => {}
+            }
+        } else if let Some((_, raw)) = current.as_mut() {
+            match tag {
+                "DTTRADE" => {
+                    raw.date = value
+                        .get(..8)
+                        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+                }
+                "UNIQUEID" => raw.symbol = value.to_string(),
+                "UNITS" => raw.num = parse_number(value),
+                "UNITPRICE" => raw.price = parse_number(value),
+                "TOTAL" => raw.amount = parse_number(value),
+                _ => {}
+            }
+        }
+    }
+
+    for raw in rows.iter_mut() {
+        if let Some(t) = tickers.get(&raw.symbol).filter(|t| !t.is_empty()) {
+            raw.symbol = t.clone();
+        }
+    }
+
+    rows
+}
+
+// "2023-11-24", "11/24/2023", "11/24/23" and QIF's "11/24'23", with any
+// time after the date dropped
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    let s = s.split(['T', ' ']).find(|p| !p.is_empty())?;
+    let s = s.replace('\'', "/");
+
+    // %Y would take "23" as the year 23
+    let format = match s.rsplit('/').next() {
+        _ if s.contains('-') => "%Y-%m-%d",
+        Some(year) if year.len() == 2 => "%m/%d/%y",
+        _ => "%m/%d/%Y",
+    };
+    NaiveDate::parse_from_str(&s, format).ok()
+}
+
+// "$1,234.50" and accounting style "(12.00)" for negatives
+pub fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let (s, sign) = match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => (inner, -1.0),
+        None => (s, 1.0),
+    };
+    let n = s.replace(['$', ','], "").parse::<f64>().ok()?;
+    Some(n * sign)
+}
+
+// `source` is what the event log says made the change, e.g. "import"
+pub fn apply(
+    path: &str,
+    account: &str,
+    txns: &[Transaction],
+    source: &str,
+) -> Result<Summary, String> {
+    let mut summary = Summary::default();
+    edit_holdings(path, |doc| {
+        summary = apply_to(doc, account, txns)?;
+        Ok(())
+    })?;
+
+    let logged = summary
+        .applied
+        .iter()
+        .map(|t| {
+            let kind = match t.action {
+                Action::Buy => "buy",
+                Action::Sell => "sell",
+                Action::Dividend => "dividend",
+            };
+            let (num, price) = match t.action {
+                Action::Dividend => (None, Some(t.amount)),
+                _ => (Some(t.num), Some(t.price)),
+            };
+            Event {
+                account: Some(account.to_string()),
+                symbol: Some(t.symbol.clone()),
+                num,
+                price,
+                detail: Some(format!("on {}", t.date.format("%Y-%m-%d"))),
+                ..Event::new(&format!("{} {}", source, kind))
+            }
+        })
+        .collect::<Vec<_>>();
+    events::record(&logged);
+
+    Ok(summary)
+}
+
+// What `apply` would do, without writing anything
+pub fn preview(path: &str, account: &str, txns: &[Transaction]) -> Result<Summary, String> {
+    let buf = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut doc = buf
+        .parse::<Document>()
+        .map_err(|e| format!("could not parse {}: {}", path, e))?;
+    apply_to(&mut doc, account, txns)
+}
+
+// Day, symbol, kind and amounts, which is as close to an id as broker files
+// get. Rounded so a value written to the holdings file and read back matches.
+fn ledger_key(date: NaiveDate, symbol: &str, kind: &str, a: f64, b: f64) -> String {
+    format!("{}|{}|{}|{:.6}|{:.6}", date, symbol, kind, a, b)
+}
+
+// How many times each entry already appears in the account's ledger
+fn ledger_keys(doc: &Document, account: &str) -> HashMap<String, usize> {
+    let mut keys = HashMap::new();
+    let t = match doc.to_string().parse::<toml::Table>() {
+        Ok(t) => t,
+        Err(_) => return keys,
+    };
+    let positions = match t.get(account).and_then(|a| a.as_table()) {
+        Some(p) => p,
+        None => return keys,
+    };
+
+    let date = |v: &toml::Value| {
+        let s = match v.get("date")? {
+            toml::Value::Datetime(d) => d.to_string(),
+            toml::Value::String(s) => s.clone(),
+            _ => return None,
+        };
+        NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok()
+    };
+    let field = |v: &toml::Value, k: &str| portfolio::number(v.get(k)).unwrap_or_default();
+    let entries = |p: &toml::Value, k: &str| p.get(k).and_then(|a| a.as_array()).cloned();
+
+    for (symbol, p) in positions.iter() {
+        for trade in entries(p, "trades").unwrap_or_default().iter() {
+            if let Some(d) = date(trade) {
+                let k = ledger_key(
+                    d,
+                    symbol,
+                    "trade",
+                    field(trade, "num"),
+                    field(trade, "price"),
+                );
+                *keys.entry(k).or_insert(0) += 1;
+            }
+        }
+        for div in entries(p, "dividends").unwrap_or_default().iter() {
+            if let Some(d) = date(div) {
+                let k = ledger_key(d, symbol, "dividend", field(div, "amount"), 0.0);
+                *keys.entry(k).or_insert(0) += 1;
+            }
+        }
+    }
+
+    keys
+}
+
+// Each position's trades as written, for the ones that leave their shares or
+// cost to the open lots
+fn ledger_trades(doc: &Document, account: &str) -> HashMap<String, Vec<Trade>> {
+    let t = match doc.to_string().parse::<toml::Table>() {
+        Ok(t) => t,
+        Err(_) => return HashMap::new(),
+    };
+    let positions = match t.get(account).and_then(|a| a.as_table()) {
+        Some(p) => p,
+        None => return HashMap::new(),
+    };
+
+    positions
+        .iter()
+        .map(|(symbol, p)| {
+            let trades = p
+                .get("trades")
+                .and_then(|a| a.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|t| portfolio::parse_trade(symbol, "USD", t).ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            (symbol.clone(), trades)
+        })
+        .collect()
+}
+
+// Appends each transaction to its position's `trades` or `dividends` and
+// keeps `num` and the average cost `price` in step, creating the account and
+// position if they don't exist yet. A position written only as trades takes
+// its shares and cost from the open lots, so it just gets the trade.
+//
+// Transactions already in the ledger are skipped, so importing the same
+// export twice changes nothing. Each existing entry only cancels one
+// transaction, which keeps two identical fills on the same day in one file.
+pub fn apply_to(
+    doc: &mut Document,
+    account: &str,
+    txns: &[Transaction],
+) -> Result<Summary, String> {
+    let mut summary = Summary::default();
+    let mut existing = ledger_keys(doc, account);
+    let mut trades = ledger_trades(doc, account);
+
+    if doc.get(account).is_none() {
+        doc.insert(account, Item::Table(Table::new()));
+    }
+    let acct = doc
+        .get_mut(account)
+        .and_then(|a| a.as_table_like_mut())
+        .ok_or_else(|| format!("{} is not an account", account))?;
+
+    for t in txns.iter() {
+        if let Some(n) = existing.get_mut(&t.key()).filter(|n| **n > 0) {
+            *n -= 1;
+            summary.duplicates.push(t.clone());
+            continue;
+        }
+
+        if acct.get(&t.symbol).is_none() {
+            let mut position = InlineTable::new();
+            position.insert("num", 0.0.into());
+            position.insert("price", 0.0.into());
+            acct.insert(&t.symbol, toml_edit::value(position));
+        }
+        let position = acct
+            .get_mut(&t.symbol)
+            .and_then(|p| p.as_table_like_mut())
+            .ok_or_else(|| format!("{}.{} is not a position", account, t.symbol))?;
+
+        let date = t
+            .date
+            .format("%Y-%m-%d")
+            .to_string()
+            .parse::<toml_edit::Datetime>()
+            .unwrap();
+        // whichever of num and price isn't written comes from the open lots,
+        // and stays unwritten
+        let (has_num, has_price) = (position.contains_key("num"), position.contains_key("price"));
+        let ledger = trades.entry(t.symbol.clone()).or_default();
+        let open = lots::match_lots(ledger).0;
+        let num = match has_num {
+            true => float(position.get("num")),
+            false => open.iter().map(|l| l.num).sum(),
+        };
+        let price = match has_price {
+            true => float(position.get("price")),
+            false => lots::average_cost(&open),
+        };
+        let mut trade = |num: f64| {
+            ledger.push(Trade {
+                date: t.date,
+                num,
+                price: Money::usd(t.price),
+                fees: Money::usd(0.0),
+            })
+        };
+        summary.applied.push(t.clone());
+
+        match t.action {
+            Action::Buy => {
+                let total = num + t.num;
+                let cost = if total != 0.0 {
+                    (num * price + t.num * t.price) / total
+                } else {
+                    price
+                };
+                if has_num {
+                    position.insert("num", toml_edit::value(round(total)));
+                }
+                if has_price {
+                    position.insert("price", toml_edit::value(round(cost)));
+                }
+                trade(t.num);
+                push(
+                    position,
+                    "trades",
+                    date,
+                    &[("num", t.num), ("price", t.price)],
+                );
+                summary.buys += 1;
+            }
+            Action::Sell => {
+                if has_num {
+                    position.insert("num", toml_edit::value(round(num - t.num)));
+                }
+                trade(-t.num);
+                push(
+                    position,
+                    "trades",
+                    date,
+                    &[("num", -t.num), ("price", t.price)],
+                );
+                summary.sells += 1;
+            }
+            Action::Dividend => {
+                push(position, "dividends", date, &[("amount", t.amount)]);
+                summary.dividends += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn float(item: Option<&Item>) -> f64 {
+    match item.and_then(|i| i.as_value()) {
+        Some(Value::Float(f)) => *f.value(),
+        Some(Value::Integer(i)) => *i.value() as f64,
+        _ => 0.0,
+    }
+}
+
+// keeps float noise like 0.30000000000000004 out of the file
+fn round(n: f64) -> f64 {
+    (n * 1e8).round() / 1e8
+}
+
+// Adds `{ date = ..., <fields> }` to an array, whether it was written inline
+// or as `[[account.symbol.trades]]` tables
+fn push(
+    position: &mut dyn toml_edit::TableLike,
+    key: &str,
+    date: toml_edit::Datetime,
+    fields: &[(&str, f64)],
+) {
+    if let Some(tables) = position
+        .get_mut(key)
+        .and_then(|i| i.as_array_of_tables_mut())
+    {
+        let mut t = Table::new();
+        t.insert("date", toml_edit::value(date));
+        for (k, v) in fields {
+            t.insert(k, toml_edit::value(*v));
+        }
+        tables.push(t);
+        return;
+    }
+
+    if position.get(key).and_then(|i| i.as_array()).is_none() {
+        position.insert(key, toml_edit::value(Array::new()));
+    }
+    let mut t = InlineTable::new();
+    t.insert("date", date.into());
+    for (k, v) in fields {
+        t.insert(*k, (*v).into());
+    }
+    if let Some(array) = position.get_mut(key).and_then(|i| i.as_array_mut()) {
+        array.push(t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> HashMap<String, Option<Action>> {
+        action_table(&Config::default()).unwrap()
+    }
+
+    fn resolved(rows: Vec<Raw>) -> Vec<Transaction> {
+        resolve_all(&rows, &table()).0
+    }
+
+    #[test]
+    fn csv_columns_are_found_by_name() {
+        let csv = "Run Date,Action,Symbol,Quantity,Price ($),Amount ($)\n\
+                   11/20/2023,YOU BOUGHT,AAPL,10,\"$1,190.00\",(11900.00)\n\
+                   11/21/2023,DIVIDEND RECEIVED,AAPL,,,2.40\n\
+                   11/22/2023,Transfer,,,,500\n";
+        let txns = resolved(parse_csv(csv).unwrap());
+
+        assert_eq!(txns.len(), 2);
+        assert_eq!(txns[0].action, Action::Buy);
+        assert_eq!(txns[0].date, NaiveDate::from_ymd_opt(2023, 11, 20).unwrap());
+        assert_eq!(txns[0].price, 1190.0);
+        assert_eq!(txns[1].action, Action::Dividend);
+        assert_eq!(txns[1].amount, 2.4);
+    }
+
+    #[test]
+    fn configured_actions_override_the_defaults() {
+        let mut conf = Config::default();
+        conf.import_actions
+            .insert("Reinvestment".to_string(), "ignore".to_string());
+        conf.import_actions
+            .insert("Bought To Open".to_string(), "buy".to_string());
+        let table = action_table(&conf).unwrap();
+
+        let raw = |action: &str| Raw {
+            date: NaiveDate::from_ymd_opt(2023, 1, 1),
+            action: action.to_string(),
+            symbol: "VTI".to_string(),
+            num: Some(1.0),
+            price: Some(200.0),
+            amount: None,
+        };
+        assert_eq!(resolve(&raw("REINVESTMENT"), &table), Ok(None));
+        assert!(resolve(&raw("bought to open"), &table).unwrap().is_some());
+        assert_eq!(resolve(&raw("Journal"), &table), Err("JOURNAL".to_string()));
+
+        conf.import_actions
+            .insert("x".to_string(), "maybe".to_string());
+        assert!(action_table(&conf).is_err());
+    }
+
+    #[test]
+    fn qif_records() {
+        let qif = "!Type:Invst\nD11/20'23\nNBuy\nYVTI\nI220.50\nQ2\nT441.00\n^\n\
+                   D11/24'23\nNSell\nYVTI\nI225\nQ1\nT225\n^\n";
+        let txns = resolved(parse_qif(qif));
+
+        assert_eq!(txns.len(), 2);
+        assert_eq!(txns[0].symbol, "VTI");
+        assert_eq!(txns[0].num, 2.0);
+        assert_eq!(txns[1].action, Action::Sell);
+        assert_eq!(txns[1].date, NaiveDate::from_ymd_opt(2023, 11, 24).unwrap());
+    }
+
+    #[test]
+    fn byte_order_marks_are_skipped() {
+        let qif = "\u{feff}!Type:Invst\nD11/20'23\nNBuy\nYVTI\nI220.50\nQ2\n^\n\u{e9}junk\n";
+        let txns = resolved(parse_qif(qif));
+        assert_eq!(txns.len(), 1);
+        assert_eq!(txns[0].symbol, "VTI");
+
+        let csv = "\u{feff}Date,Action,Symbol,Quantity,Price\n11/20/2023,BUY,AAPL,10,190\n";
+        let txns = resolved(parse_csv(csv).unwrap());
+        assert_eq!(txns.len(), 1);
+        assert_eq!(txns[0].date, NaiveDate::from_ymd_opt(2023, 11, 20).unwrap());
+    }
+
+    #[test]
+    fn ofx_maps_cusips_to_tickers() {
+        let ofx = "<OFX><INVTRANLIST>\
+                   <BUYSTOCK><INVBUY><INVTRAN><FITID>1<DTTRADE>20231120120000</INVTRAN>\
+                   <SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>\
+                   <UNITS>5<UNITPRICE>190.25<TOTAL>-951.25</INVBUY><BUYTYPE>BUY</BUYSTOCK>\
+                   <INCOME><INVTRAN><DTTRADE>20231124</INVTRAN>\
+                   <SECID><UNIQUEID>037833100</SECID><INCOMETYPE>DIV<TOTAL>1.20</INCOME>\
+                   </INVTRANLIST><SECLIST><STOCKINFO><SECINFO>\
+                   <SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>\
+                   <NAME>Apple Inc<TICKER>AAPL</SECINFO></STOCKINFO></SECLIST></OFX>";
+        let txns = resolved(parse_ofx(ofx));
+
+        assert_eq!(txns.len(), 2);
+        assert_eq!(txns[0].symbol, "AAPL");
+        assert_eq!(txns[0].num, 5.0);
+        assert_eq!(txns[0].price, 190.25);
+        assert_eq!(txns[1].action, Action::Dividend);
+        assert_eq!(txns[1].amount, 1.2);
+    }
+
+    #[test]
+    fn applying_updates_shares_cost_and_ledger() {
+        let mut doc = "[Brokerage]\nAAPL = { num = 10, price = 150 }\n"
+            .parse::<Document>()
+            .unwrap();
+        let day = NaiveDate::from_ymd_opt(2023, 11, 20).unwrap();
+        let txn = |symbol: &str, action, num, price, amount| Transaction {
+            date: day,
+            symbol: symbol.to_string(),
+            action,
+            num,
+            price,
+            amount,
+        };
+
+        let summary = apply_to(
+            &mut doc,
+            "Brokerage",
+            &[
+                txn("AAPL", Action::Buy, 10.0, 190.0, 0.0),
+                txn("AAPL", Action::Sell, 5.0, 195.0, 0.0),
+                txn("AAPL", Action::Dividend, 0.0, 0.0, 3.6),
+                txn("VTI", Action::Buy, 2.0, 220.0, 0.0),
+            ],
+        )
+        .unwrap();
+        assert_eq!((summary.buys, summary.sells, summary.dividends), (2, 1, 1));
+
+        let t = doc.to_string().parse::<toml::Table>().unwrap();
+        let aapl = &t["Brokerage"]["AAPL"];
+        assert_eq!(aapl["num"].as_float(), Some(15.0));
+        // average cost of both buys, untouched by the sale
+        assert_eq!(aapl["price"].as_float(), Some(170.0));
+        assert_eq!(aapl["trades"].as_array().unwrap().len(), 2);
+        assert_eq!(aapl["trades"][1]["num"].as_float(), Some(-5.0));
+        assert_eq!(aapl["dividends"][0]["amount"].as_float(), Some(3.6));
+        assert_eq!(t["Brokerage"]["VTI"]["num"].as_float(), Some(2.0));
+    }
+
+    #[test]
+    fn positions_of_only_trades_just_get_the_trade() {
+        let mut doc = "[Brokerage]\n\
+                       AAPL = { trades = [{ date = 2023-01-03, num = 10, price = 125 }] }\n"
+            .parse::<Document>()
+            .unwrap();
+        let buy = Transaction {
+            date: NaiveDate::from_ymd_opt(2023, 11, 20).unwrap(),
+            symbol: "AAPL".to_string(),
+            action: Action::Buy,
+            num: 1.0,
+            price: 190.0,
+            amount: 0.0,
+        };
+        let sell = Transaction {
+            action: Action::Sell,
+            num: 2.0,
+            ..buy.clone()
+        };
+        apply_to(&mut doc, "Brokerage", &[buy, sell]).unwrap();
+
+        let t = doc.to_string().parse::<toml::Table>().unwrap();
+        let aapl = &t["Brokerage"]["AAPL"];
+        assert!(aapl.get("num").is_none());
+        assert!(aapl.get("price").is_none());
+        assert_eq!(aapl["trades"].as_array().unwrap().len(), 3);
+
+        let holdings = portfolio::parse_holdings_str(&doc.to_string(), "test").unwrap();
+        assert_eq!(holdings.accounts[0].stocks[0].amount, 9.0);
+    }
+
+    #[test]
+    fn reimporting_skips_what_is_already_there() {
+        let mut doc = "[Brokerage]\n".parse::<Document>().unwrap();
+        let fill = Transaction {
+            date: NaiveDate::from_ymd_opt(2023, 11, 20).unwrap(),
+            symbol: "VTI".to_string(),
+            action: Action::Buy,
+            num: 1.0,
+            price: 220.1,
+            amount: 0.0,
+        };
+        let div = Transaction {
+            action: Action::Dividend,
+            num: 0.0,
+            price: 0.0,
+            amount: 0.85,
+            ..fill.clone()
+        };
+        // two identical fills are both kept the first time
+        let txns = [fill.clone(), fill.clone(), div];
+
+        let first = apply_to(&mut doc, "Brokerage", &txns).unwrap();
+        assert_eq!(first.buys, 2);
+        assert!(first.duplicates.is_empty());
+
+        let written = doc.to_string();
+        let mut doc = written.parse::<Document>().unwrap();
+        let again = apply_to(&mut doc, "Brokerage", &txns).unwrap();
+        assert_eq!((again.buys, again.dividends), (0, 0));
+        assert_eq!(again.duplicates.len(), 3);
+        assert_eq!(doc.to_string(), written);
+
+        // a third fill in a later export is still new
+        let more = apply_to(&mut doc, "Brokerage", &[fill.clone(), fill.clone(), fill]).unwrap();
+        assert_eq!((more.buys, more.duplicates.len()), (1, 2));
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::config::{Config, TaxExempt};
+use crate::money::Money;
+use crate::portfolio::{Account, Dividend};
+use crate::provider::Quote;
+use crate::report::{self, sum};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountIncome {
+    pub name: String,
+    // paid on positions
+    pub dividends: Money,
+    // the dividends split for tax, qualified ones taxed at the lower rate
+    pub qualified: Money,
+    pub ordinary: Money,
+    // withheld by foreign payers, what the foreign tax credit is claimed on
+    pub foreign_tax: Money,
+    // paid by funds set in `tax_exempt`, like munis, that's free of federal
+    // or of state tax
+    pub federal_exempt: Money,
+    pub state_exempt: Money,
+    // everything else by kind, like "lending" or "interest"; coin rewards
+    // come in under "staking", valued at today's price
+    pub other: BTreeMap<String, Money>,
+    pub staking: Vec<Staking>,
+    pub total: Money,
+    // the account's current value, cash included
+    pub value: Money,
+    // total over the period scaled to a year, as a percentage of value
+    pub yield_pct: f64,
+}
+
+// Rewards paid on one coin over the period
+#[derive(Debug, Clone, Serialize)]
+pub struct Staking {
+    pub symbol: String,
+    pub coins: f64,
+    pub value: Money,
+    // coins received scaled to a year, as a percentage of the balance
+    pub apy: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub accounts: Vec<AccountIncome>,
+    pub qualified: Money,
+    pub ordinary: Money,
+    pub foreign_tax: Money,
+    pub federal_exempt: Money,
+    pub state_exempt: Money,
+    pub total: Money,
+    pub yield_pct: f64,
+}
+
+// The qualified part of a dividend: what was recorded on it, otherwise the
+// symbol's share from `qualified_dividends`, otherwise none
+fn qualified(d: &Dividend, symbol: &str, conf: &Config) -> Money {
+    if let Some(q) = &d.qualified {
+        return q.clone();
+    }
+    let pct = conf.qualified_dividends.get(symbol).copied().unwrap_or(0.0);
+    d.amount.clone() * (pct.clamp(0.0, 100.0) / 100.0)
+}
+
+// All of calendar year `y`, as tax is reported
+pub fn year(y: i32) -> (NaiveDate, NaiveDate) {
+    (
+        NaiveDate::from_ymd_opt(y, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(y, 12, 31).unwrap(),
+    )
+}
+
+fn annualised(total: &Money, value: &Money, days: i64) -> f64 {
+    if days <= 0 {
+        return 0.0;
+    }
+    total.pct_of(value) * 365.0 / days as f64
+}
+
+// Income paid from `since` up to and including `until`
+pub fn summary(
+    accounts: &[&Account],
+    stock_info: &HashMap<String, Quote>,
+    conf: &Config,
+    since: NaiveDate,
+    until: NaiveDate,
+) -> Summary {
+    let report = report::build(accounts, stock_info, &report::Options::default());
+    let in_period = |d: &NaiveDate| *d >= since && *d <= until;
+    let days = (until - since).num_days() + 1;
+
+    let accounts = accounts
+        .iter()
+        .zip(report.accounts.iter())
+        .map(|(a, r)| {
+            let paid = a
+                .stocks
+                .iter()
+                .flat_map(|s| s.dividends.iter().map(move |d| (s, d)))
+                .filter(|(_, d)| in_period(&d.date))
+                .collect::<Vec<_>>();
+            let dividends = sum(paid.iter().map(|(_, d)| d.amount.clone()));
+            let qualified = sum(paid.iter().map(|(s, d)| qualified(d, &s.symbol, conf)));
+            let ordinary = dividends.clone() - qualified.clone();
+            let foreign_tax = sum(paid.iter().filter_map(|(_, d)| d.withheld.clone()));
+            let exempt = |free: fn(TaxExempt) -> bool| {
+                sum(paid
+                    .iter()
+                    .filter(|(s, _)| conf.tax_exempt.get(&s.symbol).is_some_and(|e| free(*e)))
+                    .map(|(_, d)| d.amount.clone()))
+            };
+            let federal_exempt = exempt(TaxExempt::federal);
+            let state_exempt = exempt(TaxExempt::state);
+
+            let mut other: BTreeMap<String, Money> = BTreeMap::new();
+            for i in a.income.iter().filter(|i| in_period(&i.date)) {
+                *other
+                    .entry(i.kind.clone())
+                    .or_insert_with(|| Money::zero(&i.amount.currency)) += i.amount.clone();
+            }
+
+            let staking = a
+                .stocks
+                .iter()
+                .filter_map(|s| {
+                    let coins: f64 = s
+                        .rewards
+                        .iter()
+                        .filter(|r| in_period(&r.date))
+                        .map(|r| r.amount)
+                        .sum();
+                    if coins == 0.0 {
+                        return None;
+                    }
+                    let price = stock_info
+                        .get(&s.symbol)
+                        .map(|q| q.price.clone())
+                        .unwrap_or_default();
+                    let apy = if s.amount > 0.0 && days > 0 {
+                        coins / s.amount * 100.0 * 365.0 / days as f64
+                    } else {
+                        0.0
+                    };
+                    Some(Staking {
+                        symbol: s.symbol.clone(),
+                        value: price * coins,
+                        coins,
+                        apy,
+                    })
+                })
+                .collect::<Vec<_>>();
+            for st in staking.iter() {
+                *other
+                    .entry("staking".to_string())
+                    .or_insert_with(|| Money::zero(&st.value.currency)) += st.value.clone();
+            }
+
+            let total = sum(std::iter::once(dividends.clone()).chain(other.values().cloned()));
+            let value = match a.cash.clone() {
+                Some(cash) => r.value.clone() + cash,
+                None => r.value.clone(),
+            };
+
+            AccountIncome {
+                name: a.name.clone(),
+                yield_pct: annualised(&total, &value, days),
+                dividends,
+                qualified,
+                ordinary,
+                foreign_tax,
+                federal_exempt,
+                state_exempt,
+                other,
+                staking,
+                total,
+                value,
+            }
+        })
+        .filter(|a| a.total.amount != 0.0)
+        .collect::<Vec<_>>();
+
+    let total = sum(accounts.iter().map(|a| a.total.clone()));
+    let value = sum(accounts.iter().map(|a| a.value.clone()));
+
+    Summary {
+        since,
+        until,
+        qualified: sum(accounts.iter().map(|a| a.qualified.clone())),
+        ordinary: sum(accounts.iter().map(|a| a.ordinary.clone())),
+        foreign_tax: sum(accounts.iter().map(|a| a.foreign_tax.clone())),
+        federal_exempt: sum(accounts.iter().map(|a| a.federal_exempt.clone())),
+        state_exempt: sum(accounts.iter().map(|a| a.state_exempt.clone())),
+        yield_pct: annualised(&total, &value, days),
+        accounts,
+        total,
+    }
+}
+
+// What's free of tax, in one line when it's free of both
+fn exempt(
+    out: &mut dyn Write,
+    indent: &str,
+    w: usize,
+    federal: &Money,
+    state: &Money,
+) -> io::Result<()> {
+    let mut line = |amount: &Money, of: &str| {
+        writeln!(
+            out,
+            "{}{:<w$}  {:>12}  {}",
+            indent, "tax exempt", amount, of
+        )
+    };
+    if federal.amount != 0.0 && federal.amount == state.amount {
+        return line(federal, "federal and state");
+    }
+    if federal.amount != 0.0 {
+        line(federal, "federal")?;
+    }
+    if state.amount != 0.0 {
+        line(state, "state")?;
+    }
+    Ok(())
+}
+
+pub fn render(s: &Summary, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "income from {} to {}",
+        s.since.format("%Y-%m-%d"),
+        s.until.format("%Y-%m-%d")
+    )?;
+    if s.accounts.is_empty() {
+        return writeln!(out, "no dividends, rewards or other income recorded");
+    }
+
+    for a in s.accounts.iter() {
+        writeln!(out, "\x1b[1m{}\x1b[0m", a.name)?;
+        if a.dividends.amount != 0.0 {
+            writeln!(out, "\t{:<12}  {:>12}", "dividends", a.dividends)?;
+            if a.qualified.amount != 0.0 {
+                writeln!(out, "\t  {:<10}  {:>12}", "qualified", a.qualified)?;
+                writeln!(out, "\t  {:<10}  {:>12}", "ordinary", a.ordinary)?;
+            }
+            if a.foreign_tax.amount != 0.0 {
+                writeln!(out, "\t  {:<10}  {:>12}", "withheld", a.foreign_tax)?;
+            }
+            exempt(out, "\t  ", 10, &a.federal_exempt, &a.state_exempt)?;
+        }
+        for (kind, amount) in a.other.iter() {
+            writeln!(out, "\t{:<12}  {:>12}", kind, amount)?;
+        }
+        for st in a.staking.iter() {
+            writeln!(
+                out,
+                "\t  {:<10}  {:>12}  {:>6.2}% APY  {:.6} {}",
+                st.symbol, st.value, st.apy, st.coins, st.symbol
+            )?;
+        }
+        writeln!(
+            out,
+            "\t{:<12}  {:>12}  {:>6.2}% a year",
+            "total", a.total, a.yield_pct
+        )?;
+    }
+
+    writeln!(out)?;
+    if s.qualified.amount != 0.0 {
+        writeln!(out, "\t{:<12}  {:>12}", "qualified", s.qualified)?;
+        writeln!(out, "\t{:<12}  {:>12}", "ordinary", s.ordinary)?;
+    }
+    if s.foreign_tax.amount != 0.0 {
+        writeln!(out, "\t{:<12}  {:>12}", "foreign tax", s.foreign_tax)?;
+    }
+    exempt(out, "\t", 12, &s.federal_exempt, &s.state_exempt)?;
+    writeln!(
+        out,
+        "\x1b[1m\t{:<12}  {:>12}  {:>6.2}% a year\x1b[0m",
+        "Total", s.total, s.yield_pct
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{Asset, Income, Reward, Stock};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn lending_and_interest_count_towards_yield() {
+        let account = Account {
+            cash: Some(Money::usd(1000.0)),
+            income: vec![
+                Income {
+                    date: date(2023, 6, 30),
+                    amount: Money::usd(5.0),
+                    kind: "lending".to_string(),
+                },
+                Income {
+                    date: date(2023, 9, 30),
+                    amount: Money::usd(25.0),
+                    kind: "interest".to_string(),
+                },
+                Income {
+                    date: date(2023, 12, 31),
+                    amount: Money::usd(10.0),
+                    kind: "interest".to_string(),
+                },
+            ],
+            ..Account::test(
+                "Brokerage",
+                vec![Stock {
+                    symbol: "AAPL".to_string(),
+                    amount: 10.0,
+                    cost_basis: Money::usd(150.0),
+                    dividends: vec![
+                        Dividend {
+                            date: date(2023, 11, 16),
+                            amount: Money::usd(20.0),
+                            qualified: None,
+                            withheld: None,
+                        },
+                        // before the period
+                        Dividend {
+                            date: date(2022, 11, 10),
+                            amount: Money::usd(20.0),
+                            qualified: None,
+                            withheld: None,
+                        },
+                    ],
+                    ..Default::default()
+                }],
+            )
+        };
+        let quotes = HashMap::from([(
+            "AAPL".to_string(),
+            Quote {
+                price: Money::usd(200.0),
+                ..Default::default()
+            },
+        )]);
+
+        let s = summary(
+            &[&account],
+            &quotes,
+            &Config::default(),
+            date(2023, 1, 1),
+            date(2023, 12, 31),
+        );
+        let a = &s.accounts[0];
+        assert_eq!(a.dividends, Money::usd(20.0));
+        assert_eq!(a.other["lending"], Money::usd(5.0));
+        assert_eq!(a.other["interest"], Money::usd(35.0));
+        assert_eq!(a.total, Money::usd(60.0));
+        // 60 on 2000 of shares and 1000 cash over a full year
+        assert_eq!(a.value, Money::usd(3000.0));
+        assert!((s.yield_pct - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn staking_rewards_are_valued_at_the_current_price() {
+        let account = Account::test(
+            "Coins",
+            vec![Stock {
+                symbol: "ETH".to_string(),
+                amount: 10.0,
+                cost_basis: Money::usd(1500.0),
+                asset: Asset::Crypto,
+                rewards: vec![
+                    Reward {
+                        date: date(2023, 3, 1),
+                        amount: 0.2,
+                    },
+                    Reward {
+                        date: date(2023, 9, 1),
+                        amount: 0.2,
+                    },
+                ],
+                ..Default::default()
+            }],
+        );
+        let quotes = HashMap::from([(
+            "ETH".to_string(),
+            Quote {
+                price: Money::usd(2000.0),
+                ..Default::default()
+            },
+        )]);
+
+        let s = summary(
+            &[&account],
+            &quotes,
+            &Config::default(),
+            date(2023, 1, 1),
+            date(2023, 12, 31),
+        );
+        let a = &s.accounts[0];
+        assert_eq!(a.other["staking"], Money::usd(800.0));
+        assert_eq!(a.total, Money::usd(800.0));
+        assert_eq!(a.staking[0].symbol, "ETH");
+        assert!((a.staking[0].coins - 0.4).abs() < 1e-9);
+        // 0.4 coins on a balance of 10 over a full year
+        assert!((a.staking[0].apy - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dividends_split_into_qualified_and_ordinary() {
+        let dividend = |amount: f64, qualified: Option<f64>| Dividend {
+            date: date(2023, 6, 1),
+            amount: Money::usd(amount),
+            qualified: qualified.map(Money::usd),
+            withheld: None,
+        };
+        let account = Account::test(
+            "Brokerage",
+            vec![
+                Stock {
+                    symbol: "VTI".to_string(),
+                    amount: 10.0,
+                    dividends: vec![dividend(100.0, None), dividend(50.0, Some(40.0))],
+                    ..Default::default()
+                },
+                Stock {
+                    symbol: "BND".to_string(),
+                    amount: 10.0,
+                    dividends: vec![dividend(30.0, None)],
+                    ..Default::default()
+                },
+            ],
+        );
+        let conf = Config {
+            qualified_dividends: BTreeMap::from([("VTI".to_string(), 90.0)]),
+            ..Default::default()
+        };
+
+        let s = summary(
+            &[&account],
+            &HashMap::new(),
+            &conf,
+            date(2023, 1, 1),
+            date(2023, 12, 31),
+        );
+        // 90 of the first VTI dividend, the 40 recorded on the second, and
+        // none of BND's
+        let a = &s.accounts[0];
+        assert_eq!(a.dividends, Money::usd(180.0));
+        assert_eq!(a.qualified, Money::usd(130.0));
+        assert_eq!(a.ordinary, Money::usd(50.0));
+        assert_eq!(s.qualified, Money::usd(130.0));
+    }
+
+    #[test]
+    fn foreign_tax_withheld_is_totalled_for_the_year() {
+        let dividend = |y: i32, amount: f64, withheld: Option<f64>| Dividend {
+            date: date(y, 3, 15),
+            amount: Money::usd(amount),
+            qualified: None,
+            withheld: withheld.map(Money::usd),
+        };
+        let account = Account::test(
+            "Brokerage",
+            vec![Stock {
+                symbol: "NVS".to_string(),
+                amount: 10.0,
+                dividends: vec![
+                    dividend(2023, 35.0, Some(12.25)),
+                    // the year before doesn't count
+                    dividend(2022, 30.0, Some(10.5)),
+                    dividend(2023, 20.0, None),
+                ],
+                ..Default::default()
+            }],
+        );
+
+        let (since, until) = year(2023);
+        let s = summary(
+            &[&account],
+            &HashMap::new(),
+            &Config::default(),
+            since,
+            until,
+        );
+        assert_eq!(s.accounts[0].dividends, Money::usd(55.0));
+        assert_eq!(s.accounts[0].foreign_tax, Money::usd(12.25));
+        assert_eq!(s.foreign_tax, Money::usd(12.25));
+    }
+
+    #[test]
+    fn muni_interest_is_exempt_where_configured() {
+        let dividend = |amount: f64| Dividend {
+            date: date(2023, 6, 1),
+            amount: Money::usd(amount),
+            qualified: None,
+            withheld: None,
+        };
+        let stock = |symbol: &str, amount: f64| Stock {
+            symbol: symbol.to_string(),
+            amount: 10.0,
+            dividends: vec![dividend(amount)],
+            ..Default::default()
+        };
+        let account = Account::test(
+            "Brokerage",
+            vec![
+                stock("VTEB", 100.0),
+                stock("VCAIX", 50.0),
+                stock("VGSH", 20.0),
+                stock("VTI", 10.0),
+            ],
+        );
+        let conf = Config {
+            tax_exempt: BTreeMap::from([
+                ("VTEB".to_string(), TaxExempt::Federal),
+                ("VCAIX".to_string(), TaxExempt::Both),
+                ("VGSH".to_string(), TaxExempt::State),
+            ]),
+            ..Default::default()
+        };
+
+        let (since, until) = year(2023);
+        let s = summary(&[&account], &HashMap::new(), &conf, since, until);
+        assert_eq!(s.accounts[0].federal_exempt, Money::usd(150.0));
+        assert_eq!(s.accounts[0].state_exempt, Money::usd(70.0));
+        assert_eq!(s.accounts[0].dividends, Money::usd(180.0));
+
+        let parsed: Config = toml::from_str("tax_exempt = { VTEB = \"federal\" }").unwrap();
+        assert_eq!(parsed.tax_exempt["VTEB"], TaxExempt::Federal);
+        assert!(toml::from_str::<Config>("tax_exempt = { VTEB = \"city\" }").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use toml_edit::{value, Document, InlineTable, Item, Table};
+
+use crate::config::Config;
+use crate::events::{self, Event};
+use crate::lock;
+use crate::lookup::check_symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub symbol: String,
+    pub num: f64,
+    pub price: f64,
+}
+
+// The holdings file for one account, written the way the examples are:
+// `AAPL = { num = 10, price = 150 }` under `[Brokerage]`
+pub fn document(account: &str, entries: &[Entry]) -> Document {
+    let mut table = Table::new();
+    for e in entries.iter() {
+        let mut position = InlineTable::new();
+        position.insert("num", e.num.into());
+        position.insert("price", e.price.into());
+        table.insert(&e.symbol, value(position));
+    }
+
+    let mut doc = Document::new();
+    doc.insert(account, Item::Table(table));
+    doc
+}
+
+fn ask(input: &mut dyn BufRead, prompt: &str) -> Result<String, String> {
+    eprint!("{}: ", prompt);
+    let _ = io::stderr().flush();
+
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) => Err("setup cancelled".to_string()),
+        Ok(_) => Ok(line.trim().to_string()),
+        Err(e) => Err(format!("could not read input: {}", e)),
+    }
+}
+
+fn ask_number(input: &mut dyn BufRead, prompt: &str) -> Result<f64, String> {
+    loop {
+        let answer = ask(input, prompt)?;
+        match answer.replace(['$', ','], "").parse::<f64>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => return Ok(n),
+            _ => eprintln!("{} is not a number", answer),
+        }
+    }
+}
+
+// Asks for an account and its positions, checking each symbol has a quote,
+// then writes the holdings to `path`
+pub async fn run(conf: &Config, path: &str) -> Result<(), String> {
+    if Path::new(path).exists() {
+        return Err(format!("{} already exists", path));
+    }
+
+    let stdin = io::stdin();
+    let input = &mut stdin.lock();
+    eprintln!("No holdings yet, let's set up {}", path);
+
+    let account = match ask(input, "Account name [Brokerage]")? {
+        a if a.is_empty() => "Brokerage".to_string(),
+        a => a,
+    };
+
+    let mut entries: Vec<Entry> = vec![];
+    loop {
+        let symbol = ask(input, "Symbol (empty to finish)")?.to_uppercase();
+        if symbol.is_empty() {
+            break;
+        }
+        if entries.iter().any(|e| e.symbol == symbol) {
+            eprintln!("{} is already in {}", symbol, account);
+            continue;
+        }
+
+        match check_symbol(conf, &symbol).await {
+            Ok(q) => eprintln!("{} is at {}", symbol, q.price),
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        }
+
+        let num = ask_number(input, "Shares")?;
+        let price = ask_number(input, "Average price paid per share")?;
+        entries.push(Entry { symbol, num, price });
+    }
+
+    if entries.is_empty() {
+        return Err("no positions given, nothing written".to_string());
+    }
+
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    }
+    lock::write_atomic(path, document(&account, &entries).to_string().as_bytes())
+        .map_err(|e| format!("could not write {}: {}", path, e))?;
+    events::record(&[Event {
+        account: Some(account.clone()),
+        detail: Some(format!("{} positions", entries.len())),
+        ..Event::new("init")
+    }]);
+
+    eprintln!("wrote {} positions to {}", entries.len(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn written_holdings_read_back() {
+        let doc = document(
+            "Roth IRA",
+            &[
+                Entry {
+                    symbol: "AAPL".to_string(),
+                    num: 10.0,
+                    price: 150.0,
+                },
+                Entry {
+                    symbol: "VTI".to_string(),
+                    num: 3.5,
+                    price: 200.25,
+                },
+            ],
+        );
+        let s = doc.to_string();
+        assert!(s.contains("[\"Roth IRA\"]"));
+
+        let t = s.parse::<toml::Table>().unwrap();
+        assert_eq!(t["Roth IRA"]["AAPL"]["num"].as_float(), Some(10.0));
+        assert_eq!(t["Roth IRA"]["VTI"]["price"].as_float(), Some(200.25));
+    }
+
+    #[test]
+    fn numbers_are_asked_again_until_valid() {
+        let mut input = Cursor::new("ten\n-3\n$1,250.50\n");
+        assert_eq!(ask_number(&mut input, "Shares"), Ok(1250.5));
+        assert!(ask_number(&mut input, "Shares").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// A line a day in plain text, for reading back without any tools next to the
+// snapshots kept for charts:
+//
+//     2024-03-01  total $52340.12  day +$412.80 (+0.79%)  top NVDA +4.02%
+//
+// Written by whatever run first records a snapshot after the US close on a
+// weekday, into the file named by `journal` in config, and only once a day.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::America::New_York;
+
+use crate::config::Config;
+use crate::money::Money;
+use crate::portfolio::Account;
+use crate::profile;
+use crate::provider::Quote;
+use crate::report::day_change;
+
+// `journal` as given in config, relative paths from the data directory
+pub fn journal_path(journal: &str) -> String {
+    if Path::new(journal).is_absolute() {
+        journal.to_string()
+    } else {
+        format!("{}/{}", profile::data_dir(), journal)
+    }
+}
+
+// The trading day that has closed by `now`, if it's after the close on one
+pub fn closed_day(now: DateTime<Utc>) -> Option<NaiveDate> {
+    let local = now.with_timezone(&New_York);
+    let weekend = matches!(local.weekday(), Weekday::Sat | Weekday::Sun);
+    let close = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+    (!weekend && local.time() >= close).then(|| local.date_naive())
+}
+
+// The day's line, counting the accounts the total counts
+pub fn line(
+    date: NaiveDate,
+    accounts: &[Account],
+    stock_info: &HashMap<String, Quote>,
+    conf: &Config,
+) -> String {
+    let mut total = 0.0;
+    let mut change = 0.0;
+    let mut top: Option<(&str, f64)> = None;
+    for s in accounts
+        .iter()
+        .filter(|a| a.closed.is_none() && !a.hidden)
+        .flat_map(|a| a.stocks.iter())
+    {
+        let Some(q) = stock_info.get(&s.symbol) else {
+            continue;
+        };
+        let (diff, pct) = day_change(q, conf.change_basis);
+        total += q.price.amount * s.amount * s.multiplier;
+        change += diff.amount * s.amount * s.multiplier;
+        if pct.is_finite() && top.is_none_or(|(_, p)| pct.abs() > p.abs()) {
+            top = Some((&s.symbol, pct));
+        }
+    }
+
+    let before = total - change;
+    let pct = if before != 0.0 {
+        change * 100.0 / before
+    } else {
+        0.0
+    };
+    let sign = if change < 0.0 { "-" } else { "+" };
+    let mut line = format!(
+        "{}  total {}  day {}{} ({:+.2}%)",
+        date.format("%Y-%m-%d"),
+        Money::usd(total).round(),
+        sign,
+        Money::usd(change.abs()).round(),
+        pct
+    );
+    if let Some((symbol, p)) = top {
+        line.push_str(&format!("  top {} {:+.2}%", symbol, p));
+    }
+    line
+}
+
+fn written(path: &str, date: NaiveDate) -> bool {
+    let day = date.format("%Y-%m-%d").to_string();
+    fs::read_to_string(path)
+        .map(|s| s.lines().any(|l| l.starts_with(&day)))
+        .unwrap_or(false)
+}
+
+// Adds today's line when `journal` is set, the market has closed and it isn't
+// there yet
+pub fn record(
+    accounts: &[Account],
+    stock_info: &HashMap<String, Quote>,
+    conf: &Config,
+) -> std::io::Result<()> {
+    let (Some(journal), Some(date)) = (conf.journal.as_deref(), closed_day(Utc::now())) else {
+        return Ok(());
+    };
+    // leaving out a failed quote would look like a crash, a later run can
+    // write the day instead
+    let quoted = accounts
+        .iter()
+        .flat_map(|a| a.stocks.iter())
+        .all(|s| stock_info.contains_key(&s.symbol));
+    let path = journal_path(journal);
+    if !quoted || written(&path, date) {
+        return Ok(());
+    }
+    if let Some(dir) = Path::new(&path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(f, "{}", line(date, accounts, stock_info, conf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Stock;
+    use chrono::TimeZone;
+
+    #[test]
+    fn a_line_a_trading_day_after_the_close() {
+        // 20:30 UTC is 15:30 in New York before the clocks change
+        let friday = Utc.with_ymd_and_hms(2024, 3, 1, 20, 30, 0).unwrap();
+        assert_eq!(closed_day(friday), None);
+        let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(closed_day(friday + chrono::Duration::hours(1)), Some(day));
+        assert_eq!(closed_day(friday + chrono::Duration::days(1)), None);
+
+        let stock = |symbol: &str, amount: f64| Stock {
+            symbol: symbol.to_string(),
+            amount,
+            ..Default::default()
+        };
+        let account = Account::test("Brokerage", vec![stock("VTI", 10.0), stock("NVDA", 2.0)]);
+        let quote = |price: f64, change: f64| Quote {
+            price: Money::usd(price),
+            change: Money::usd(change),
+            ..Default::default()
+        };
+        let quotes = HashMap::from([
+            ("VTI".to_string(), quote(250.0, -2.5)),
+            ("NVDA".to_string(), quote(800.0, 32.0)),
+        ]);
+        assert_eq!(
+            line(day, &[account], &quotes, &Config::default()),
+            "2024-03-01  total $4100.00  day +$39.00 (+0.96%)  top NVDA +4.17%"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+pub mod alerts;
+pub mod alloc;
+pub mod attribution;
+pub mod auth;
+pub mod benchmark;
+pub mod cache;
+pub mod calendar;
+pub mod cassette;
+pub mod chart;
+pub mod check;
+pub mod classify;
+pub mod config;
+pub mod cpi;
+pub mod crypto;
+pub mod daemon;
+pub mod demo;
+pub mod events;
+pub mod exchange;
+pub mod fees;
+pub mod finnhub;
+pub mod fx;
+pub mod glyph;
+pub mod greeks;
+pub mod http;
+pub mod import;
+pub mod income;
+pub mod init;
+pub mod journal;
+pub mod lock;
+pub mod log;
+pub mod lookup;
+pub mod lots;
+pub mod metals;
+pub mod money;
+pub mod nasdaq;
+pub mod networth;
+pub mod options;
+pub mod perf;
+pub mod plot;
+pub mod plugin;
+pub mod portfolio;
+pub mod profile;
+pub mod provider;
+pub mod publish;
+pub mod qr;
+pub mod quote;
+pub mod reconcile;
+pub mod recurring;
+pub mod render;
+pub mod report;
+pub mod script;
+pub mod since;
+pub mod snapshot;
+pub mod ta;
+pub mod time;
+pub mod update;
+pub mod upstream;
+pub mod watch;
+pub mod yahoo;
@@ -0,0 +1,111 @@
+// Keeps processes writing the same file from stepping on each other. A writer
+// holds `<path>.lock` while it reads, changes and replaces the file, and
+// replaces it by renaming a finished copy over it, so a reader like `watch`
+// sees either the old file or the new one and never half of either.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long to wait on another writer before giving up
+const WAIT: Duration = Duration::from_secs(10);
+// no edit takes this long, so an older lock was left by a process that died
+const STALE: Duration = Duration::from_secs(60);
+
+// Held until dropped, which removes the lock file
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub fn lock(path: &str) -> Result<Lock, String> {
+    lock_within(path, WAIT)
+}
+
+fn lock_within(path: &str, wait: Duration) -> Result<Lock, String> {
+    let lock = format!("{}.lock", path);
+    let start = Instant::now();
+
+    loop {
+        // creating the file only if it isn't there is the one atomic step
+        match OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(mut f) => {
+                let _ = writeln!(f, "{}", std::process::id());
+                return Ok(Lock { path: lock.into() });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                if is_stale(&lock) {
+                    let _ = fs::remove_file(&lock);
+                    continue;
+                }
+                if start.elapsed() >= wait {
+                    return Err(format!(
+                        "{} is being changed by another stocks process, remove {} if none is running",
+                        path, lock
+                    ));
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(format!("could not lock {}: {}", path, e)),
+        }
+    }
+}
+
+fn is_stale(lock: &str) -> bool {
+    fs::metadata(lock)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > STALE)
+}
+
+// Writes next to `path` and renames over it, so a crash never leaves a
+// half-written file behind. The copy is named after the process so two
+// writers never share one.
+pub fn write_atomic(path: &str, contents: &[u8]) -> io::Result<()> {
+    let tmp = format!("{}.{}.tmp", path, std::process::id());
+    let write = || -> io::Result<()> {
+        let mut out = File::create(&tmp)?;
+        out.write_all(contents)?;
+        out.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_held_lock_keeps_other_writers_out() {
+        let dir = std::env::temp_dir().join(format!("stocks-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stocks.toml").to_string_lossy().to_string();
+
+        let held = lock_within(&path, Duration::ZERO).unwrap();
+        assert!(lock_within(&path, Duration::ZERO).is_err());
+        drop(held);
+        let again = lock_within(&path, Duration::ZERO).unwrap();
+
+        write_atomic(&path, b"[Brokerage]\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[Brokerage]\n");
+        drop(again);
+
+        // only the file itself is left, no lock or half-written copy
+        let left = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(left, 1);
+    }
+}
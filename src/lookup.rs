@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde_json::{json, Value};
+
+use crate::auth;
+use crate::config::Config;
+use crate::exchange;
+use crate::http::post_json;
+use crate::lock;
+use crate::portfolio::Holdings;
+use crate::profile;
+use crate::provider::{from_names, update_stock_info, Quote};
+use crate::yahoo::{search, Match};
+
+// Suggests at most this many other tickers
+const SUGGESTIONS: usize = 3;
+
+const OPENFIGI_URL: &str = "https://api.openfigi.com/v3/mapping";
+// identifiers per request OpenFIGI allows without an API key
+const OPENFIGI_BATCH: usize = 10;
+
+// The quote for `symbol` from the configured providers, or an error naming
+// tickers that look like what was meant, e.g. BRK-B for BRKB
+pub async fn check_symbol(conf: &Config, symbol: &str) -> Result<Quote, String> {
+    let providers = if exchange::foreign(symbol) {
+        from_names(&["yahoo".to_string()], conf)?
+    } else {
+        from_names(&conf.providers, conf)?
+    };
+    let (quotes, failed) = update_stock_info(vec![symbol.to_string()], providers, conf).await;
+
+    let reason = match failed.first() {
+        Some((_, e)) => e.to_string(),
+        None => match quotes.get(symbol) {
+            Some(q) => return Ok(q.clone()),
+            None => "not found".to_string(),
+        },
+    };
+
+    let matches = search(symbol).await.unwrap_or_default();
+    Err(not_found(symbol, &reason, &matches))
+}
+
+pub fn not_found(symbol: &str, reason: &str, matches: &[Match]) -> String {
+    let names = matches
+        .iter()
+        .filter(|m| !m.symbol.eq_ignore_ascii_case(symbol))
+        .take(SUGGESTIONS)
+        .map(|m| match m.name.as_str() {
+            "" => m.symbol.clone(),
+            name => format!("{} ({})", m.symbol, name),
+        })
+        .collect::<Vec<_>>();
+
+    match names.as_slice() {
+        [] => format!("no quote for {}: {}", symbol, reason),
+        [one] => format!("no quote for {}, did you mean {}?", symbol, one),
+        [rest @ .., last] => format!(
+            "no quote for {}, did you mean {} or {}?",
+            symbol,
+            rest.join(", "),
+            last
+        ),
+    }
+}
+
+// ISINs like US0378331005: a country, nine characters and a check digit
+// over the letters spelled out as numbers
+pub fn is_isin(id: &str) -> bool {
+    let b = id.as_bytes();
+    if b.len() != 12
+        || !b[..2].iter().all(u8::is_ascii_uppercase)
+        || !b[2..11]
+            .iter()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        || !b[11].is_ascii_digit()
+    {
+        return false;
+    }
+
+    let digits = id
+        .chars()
+        .flat_map(|c| {
+            c.to_digit(36)
+                .unwrap()
+                .to_string()
+                .chars()
+                .collect::<Vec<_>>()
+        })
+        .map(|c| c.to_digit(10).unwrap())
+        .collect::<Vec<_>>();
+    luhn(&digits)
+}
+
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => *d,
+            (_, d) if d > 9 => d - 9,
+            (_, d) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+// CUSIPs like 037833100, eight characters and a check digit
+pub fn is_cusip(id: &str) -> bool {
+    let b = id.as_bytes();
+    if b.len() != 9
+        || !b[..8].iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        || !b[8].is_ascii_digit()
+        // plain tickers and numbers aren't worth a lookup
+        || !b[..3].iter().any(u8::is_ascii_digit)
+    {
+        return false;
+    }
+
+    let sum: u32 = id[..8]
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let v = c.to_digit(36).unwrap();
+            let v = if i % 2 == 1 { v * 2 } else { v };
+            v / 10 + v % 10
+        })
+        .sum();
+    (10 - sum % 10) % 10 == (b[8] - b'0') as u32
+}
+
+fn id_type(id: &str) -> Option<&'static str> {
+    if is_isin(id) {
+        Some("ID_ISIN")
+    } else if is_cusip(id) {
+        Some("ID_CUSIP")
+    } else {
+        None
+    }
+}
+
+fn identifiers_path() -> String {
+    format!("{}/identifiers.json", profile::data_dir())
+}
+
+// Tickers found for each identifier so far; they don't change, so this is
+// kept for good
+fn load_identifiers() -> BTreeMap<String, String> {
+    fs::read_to_string(identifiers_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_identifiers(ids: &BTreeMap<String, String>) {
+    let res = fs::create_dir_all(profile::data_dir()).and_then(|_| {
+        lock::write_atomic(
+            &identifiers_path(),
+            serde_json::to_string_pretty(ids).unwrap().as_bytes(),
+        )
+    });
+    if let Err(e) = res {
+        eprintln!("failed to save {}: {}", identifiers_path(), e);
+    }
+}
+
+// One result per job sent; the US listing when there are several
+pub fn parse_mapping(v: &Value) -> Vec<Option<String>> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .map(|job| {
+            let data = job["data"].as_array()?;
+            let pick = data
+                .iter()
+                .find(|d| d["exchCode"].as_str() == Some("US"))
+                .or(data.first())?;
+            pick["ticker"].as_str().map(String::from)
+        })
+        .collect()
+}
+
+pub async fn map_identifiers(
+    conf: &Config,
+    ids: &[String],
+) -> Result<BTreeMap<String, String>, String> {
+    let headers = auth::api_key(conf, "openfigi")
+        .map(|k| vec![("X-OPENFIGI-APIKEY", k)])
+        .unwrap_or_default();
+
+    let mut found = BTreeMap::new();
+    for chunk in ids.chunks(OPENFIGI_BATCH) {
+        let jobs = chunk
+            .iter()
+            .map(|id| json!({ "idType": id_type(id), "idValue": id }))
+            .collect::<Vec<_>>();
+        let v = post_json(OPENFIGI_URL, &Value::Array(jobs), &headers).await?;
+
+        for (id, ticker) in chunk.iter().zip(parse_mapping(&v)) {
+            if let Some(t) = ticker {
+                found.insert(id.clone(), t);
+            }
+        }
+    }
+    Ok(found)
+}
+
+// Swaps ISIN and CUSIP symbols in the holdings for their tickers so they can
+// be quoted; the identifier is kept as the alias when there isn't one
+pub async fn resolve_identifiers(conf: &Config, holdings: &mut Holdings) {
+    let stocks = || holdings.accounts.iter().flat_map(|a| a.stocks.iter());
+    if !stocks().any(|s| id_type(&s.symbol).is_some()) {
+        return;
+    }
+
+    let mut known = load_identifiers();
+    let mut missing = stocks()
+        .map(|s| s.symbol.clone())
+        .filter(|s| id_type(s).is_some() && !known.contains_key(s))
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+
+    if !missing.is_empty() {
+        match map_identifiers(conf, &missing).await {
+            Ok(found) if !found.is_empty() => {
+                known.extend(found);
+                save_identifiers(&known);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("failed to look up identifiers: {}", e),
+        }
+    }
+
+    for stock in holdings
+        .accounts
+        .iter_mut()
+        .flat_map(|a| a.stocks.iter_mut())
+    {
+        if id_type(&stock.symbol).is_none() {
+            continue;
+        }
+        match known.get(&stock.symbol) {
+            Some(ticker) => {
+                if stock.alias.is_none() {
+                    stock.alias = Some(stock.symbol.clone());
+                }
+                stock.symbol = ticker.clone();
+            }
+            None => eprintln!("no ticker found for {}", stock.symbol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(symbol: &str, name: &str) -> Match {
+        Match {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn suggestions_read_as_a_question() {
+        assert_eq!(
+            not_found("BRKB", "not found", &[m("BRK-B", "Berkshire Hathaway")]),
+            "no quote for BRKB, did you mean BRK-B (Berkshire Hathaway)?"
+        );
+        assert_eq!(
+            not_found(
+                "X",
+                "not found",
+                &[m("X", ""), m("XA", ""), m("XB", ""), m("XC", "")]
+            ),
+            "no quote for X, did you mean XA, XB or XC?"
+        );
+        assert_eq!(
+            not_found("NOPE", "not found", &[]),
+            "no quote for NOPE: not found"
+        );
+    }
+
+    #[test]
+    fn isin_and_cusip_check_digits() {
+        assert!(is_isin("US0378331005"));
+        assert!(is_isin("DE0007164600"));
+        assert!(!is_isin("US0378331006"));
+        assert!(!is_isin("AAPL"));
+
+        assert!(is_cusip("037833100"));
+        assert!(is_cusip("92826C839"));
+        assert!(!is_cusip("037833101"));
+        // a long ticker isn't a CUSIP
+        assert!(!is_cusip("GOOGLEXX1"));
+    }
+
+    #[test]
+    fn mapping_prefers_the_us_listing() {
+        let v = serde_json::json!([
+            { "data": [
+                { "ticker": "APC", "exchCode": "GY" },
+                { "ticker": "AAPL", "exchCode": "US" },
+            ] },
+            { "data": [{ "ticker": "SAP", "exchCode": "GY" }] },
+            { "warning": "No identifier found." },
+        ]);
+        assert_eq!(
+            parse_mapping(&v),
+            [Some("AAPL".to_string()), Some("SAP".to_string()), None]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::money::Money;
+use crate::portfolio::{Account, Trade};
+use crate::provider::Quote;
+use crate::render::csv_field;
+use crate::report::sum;
+
+// fractional shares leave float dust once a lot is worked through
+const DUST: f64 = 1e-9;
+
+// Shares bought (or shorted) in one trade that haven't been closed yet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lot {
+    pub date: NaiveDate,
+    // negative for a short
+    pub num: f64,
+    // per share, commission included
+    pub cost: Money,
+}
+
+// Part of a lot closed by a later trade
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Realized {
+    pub opened: NaiveDate,
+    pub closed: NaiveDate,
+    pub num: f64,
+    pub cost: Money,
+    // per share, commission taken off
+    pub proceeds: Money,
+    pub gain: Money,
+}
+
+// Matches trades first in, first out: each closes the oldest lots on the
+// other side, and whatever is left opens a new lot
+pub fn match_lots(trades: &[Trade]) -> (Vec<Lot>, Vec<Realized>) {
+    let mut trades = trades.iter().collect::<Vec<_>>();
+    trades.sort_by_key(|t| t.date);
+
+    let mut open: Vec<Lot> = vec![];
+    let mut realized = vec![];
+
+    for t in trades {
+        if t.num == 0.0 {
+            continue;
+        }
+        let sign = t.num.signum();
+        let mut left = t.num.abs();
+        // a buy costs its commission on top, a sale gets it taken off
+        let price = t.price.clone() + t.fees.clone() * (sign / left);
+
+        while left > DUST {
+            let lot = match open.first_mut() {
+                Some(lot) if lot.num.signum() != sign => lot,
+                _ => break,
+            };
+            let num = left.min(lot.num.abs());
+            let (cost, proceeds) = if lot.num > 0.0 {
+                (lot.cost.clone(), price.clone())
+            } else {
+                (price.clone(), lot.cost.clone())
+            };
+
+            realized.push(Realized {
+                opened: lot.date,
+                closed: t.date,
+                num,
+                gain: (proceeds.clone() - cost.clone()) * num,
+                cost,
+                proceeds,
+            });
+
+            lot.num -= num * lot.num.signum();
+            left -= num;
+            if lot.num.abs() < DUST {
+                open.remove(0);
+            }
+        }
+
+        if left > DUST {
+            open.push(Lot {
+                date: t.date,
+                num: left * sign,
+                cost: price,
+            });
+        }
+    }
+
+    (open, realized)
+}
+
+pub fn average_cost(lots: &[Lot]) -> f64 {
+    let num: f64 = lots.iter().map(|l| l.num).sum();
+    if num == 0.0 {
+        return 0.0;
+    }
+    lots.iter().map(|l| l.num * l.cost.amount).sum::<f64>() / num
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Position {
+    pub account: String,
+    pub symbol: String,
+    pub price: Money,
+    pub multiplier: f64,
+    pub lots: Vec<Lot>,
+    pub realized: Vec<Realized>,
+    pub unrealized_gain: Money,
+    pub realized_gain: Money,
+}
+
+// Every position with trades recorded, or just those of `symbol`
+pub fn positions(
+    accounts: &[&Account],
+    stock_info: &HashMap<String, Quote>,
+    symbol: Option<&str>,
+) -> Vec<Position> {
+    accounts
+        .iter()
+        .flat_map(|a| a.stocks.iter().map(move |s| (a, s)))
+        .filter(|(_, s)| !s.trades.is_empty())
+        .filter(|(_, s)| symbol.is_none_or(|sym| s.symbol.eq_ignore_ascii_case(sym)))
+        .map(|(a, s)| {
+            let (lots, realized) = match_lots(&s.trades);
+            let price = stock_info
+                .get(&s.symbol)
+                .map(|q| q.price.clone())
+                .unwrap_or_default();
+
+            Position {
+                account: a.name.clone(),
+                symbol: s.symbol.clone(),
+                unrealized_gain: sum(lots
+                    .iter()
+                    .map(|l| (price.clone() - l.cost.clone()) * (l.num * s.multiplier))),
+                realized_gain: sum(realized.iter().map(|r| r.gain.clone() * s.multiplier)),
+                price,
+                multiplier: s.multiplier,
+                lots,
+                realized,
+            }
+        })
+        .collect()
+}
+
+pub fn render(positions: &[Position], out: &mut dyn Write) -> io::Result<()> {
+    if positions.is_empty() {
+        return writeln!(out, "no trades recorded, add them as `trades = [...]`");
+    }
+
+    for p in positions.iter() {
+        writeln!(
+            out,
+            "\x1b[1m{} {}\x1b[0m at {}",
+            p.account, p.symbol, p.price
+        )?;
+        for l in p.lots.iter() {
+            writeln!(
+                out,
+                "\t{}  open  {:>10.4}  at {:>10}  unrealized {:>12}",
+                l.date.format("%Y-%m-%d"),
+                l.num,
+                l.cost,
+                (p.price.clone() - l.cost.clone()) * (l.num * p.multiplier)
+            )?;
+        }
+        for r in p.realized.iter() {
+            writeln!(
+                out,
+                "\t{}  sold  {:>10.4}  at {:>10}  realized   {:>12}  bought {}",
+                r.closed.format("%Y-%m-%d"),
+                r.num,
+                r.proceeds,
+                r.gain.clone() * p.multiplier,
+                r.opened.format("%Y-%m-%d")
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    let unrealized = sum(positions.iter().map(|p| p.unrealized_gain.clone()));
+    let realized = sum(positions.iter().map(|p| p.realized_gain.clone()));
+    writeln!(out, "\t{:<10}  {:>12}", "Unrealized", unrealized)?;
+    writeln!(out, "\t{:<10}  {:>12}", "Realized", realized)?;
+    writeln!(
+        out,
+        "\x1b[1m\t{:<10}  {:>12}\x1b[0m",
+        "Total",
+        unrealized + realized
+    )
+}
+
+// Held for longer than a year counts as a long term gain
+fn term(r: &Realized) -> &'static str {
+    match r.opened.checked_add_months(chrono::Months::new(12)) {
+        Some(year) if r.closed > year => "Long",
+        _ => "Short",
+    }
+}
+
+// One row per disposal in the columns tax software and Form 8949 imports
+// expect, totals for the lot rather than per share
+pub fn render_csv(positions: &[Position], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "Description,Symbol,Quantity,Date Acquired,Date Sold,Proceeds,Cost Basis,Gain or Loss,Term,Currency,Account"
+    )?;
+    for p in positions.iter() {
+        for r in p.realized.iter() {
+            let units = r.num * p.multiplier;
+            writeln!(
+                out,
+                "{},{},{},{},{},{:.2},{:.2},{:.2},{},{},{}",
+                csv_field(&format!("{} {}", units, p.symbol)),
+                csv_field(&p.symbol),
+                units,
+                r.opened.format("%Y-%m-%d"),
+                r.closed.format("%Y-%m-%d"),
+                r.proceeds.amount * units,
+                r.cost.amount * units,
+                r.gain.amount * p.multiplier,
+                term(r),
+                r.gain.currency,
+                csv_field(&p.account)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(d: u32, num: f64, price: f64, fees: f64) -> Trade {
+        Trade {
+            date: NaiveDate::from_ymd_opt(2023, 1, d).unwrap(),
+            num,
+            price: Money::usd(price),
+            fees: Money::usd(fees),
+        }
+    }
+
+    #[test]
+    fn sells_close_the_oldest_lots_first() {
+        let trades = [
+            trade(10, -15.0, 130.0, 0.0),
+            trade(1, 10.0, 100.0, 10.0),
+            trade(5, 10.0, 120.0, 0.0),
+        ];
+        let (open, realized) = match_lots(&trades);
+
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].num, 5.0);
+        assert_eq!(open[0].cost, Money::usd(120.0));
+
+        // the commission is part of the first lot's cost
+        assert_eq!(realized[0].num, 10.0);
+        assert_eq!(realized[0].cost, Money::usd(101.0));
+        assert_eq!(realized[0].gain, Money::usd(290.0));
+        assert_eq!(realized[1].num, 5.0);
+        assert_eq!(realized[1].gain, Money::usd(50.0));
+        assert_eq!(average_cost(&open), 120.0);
+    }
+
+    #[test]
+    fn selling_past_zero_opens_a_short() {
+        let (open, realized) = match_lots(&[
+            trade(1, 5.0, 10.0, 0.0),
+            trade(2, -8.0, 12.0, 0.0),
+            trade(3, 3.0, 11.0, 0.0),
+        ]);
+
+        assert!(open.is_empty());
+        assert_eq!(realized[0].gain, Money::usd(10.0));
+        // shorted 3 at 12, covered at 11
+        assert_eq!(realized[1].num, 3.0);
+        assert_eq!(realized[1].gain, Money::usd(3.0));
+    }
+
+    #[test]
+    fn disposals_export_with_totals_and_term() {
+        let mut bought = trade(1, 2.0, 100.0, 0.0);
+        bought.date = NaiveDate::from_ymd_opt(2021, 6, 1).unwrap();
+        let (lots, realized) = match_lots(&[
+            bought,
+            trade(2, 0.5, 200.0, 0.0),
+            trade(10, -2.5, 300.0, 0.0),
+        ]);
+        let p = Position {
+            account: "Coins, Cold".to_string(),
+            symbol: "BTC".to_string(),
+            price: Money::usd(300.0),
+            multiplier: 1.0,
+            lots,
+            realized,
+            unrealized_gain: Money::usd(0.0),
+            realized_gain: Money::usd(450.0),
+        };
+
+        let mut out = vec![];
+        render_csv(&[p], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows = out.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "2 BTC,BTC,2,2021-06-01,2023-01-10,600.00,200.00,400.00,Long,USD,\"Coins, Cold\"",
+                "0.5 BTC,BTC,0.5,2023-01-02,2023-01-10,150.00,100.00,50.00,Short,USD,\"Coins, Cold\"",
+            ]
+        );
+    }
+}
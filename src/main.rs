@@ -1,165 +1,1383 @@
 use std::collections::HashMap;
-use std::env;
-use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
 
-use reqwest::Client;
-use serde::Deserialize;
-use serde_json::Value;
+use chrono::{Duration, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
 
-use futures::future::join_all;
-use std::io::Read;
+use stocks::portfolio::{self, holdings_path, load_accounts, load_holdings, shown, Holdings};
+use stocks::profile;
+use stocks::provider::{self, quote_accounts, Quote, Status};
+use stocks::{
+    alerts, alloc, attribution, auth, benchmark, calendar, chart, check, classify, config, cpi,
+    crypto, daemon, demo, events, fees, glyph, greeks, http, import, income, init, journal, lock,
+    log, lookup, lots, money, networth, perf, plot, plugin, publish, qr, quote, reconcile,
+    recurring, render, report, script, since, snapshot, ta, time, update, upstream, watch,
+};
 
-use toml::Table;
-
-#[derive(Debug, Deserialize)]
-struct Account {
-    name: String,
-    stocks: Vec<Stock>,
+#[derive(Parser)]
+#[command(about = "Track stock portfolios from the terminal")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Include accounts marked `hidden = true`
+    #[arg(long, global = true)]
+    all: bool,
+    /// Show tickers next to position aliases
+    #[arg(long, global = true)]
+    tickers: bool,
+    /// Exit with an error instead of showing partial data when a quote fails
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Log provider details to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Output format: table, json, csv, tsv, markdown, bar or template
+    #[arg(long, global = true, default_value = "table")]
+    format: String,
+    /// Leave out colours and other escape codes, the default when not on a terminal
+    #[arg(long, global = true)]
+    plain: bool,
+    /// List the positions under each strategy instead of just its total
+    #[arg(long, global = true)]
+    legs: bool,
+    /// Use a separate named set of holdings, config and history
+    #[arg(long, global = true, value_parser = profile::parse_name)]
+    profile: Option<String>,
+    /// Line printed per position with --format template, e.g. '{symbol} {price}'
+    #[arg(long, global = true)]
+    template: Option<String>,
+    /// Add a net worth line counting `[assets]` and `[liabilities]` alongside the accounts
+    #[arg(long, global = true)]
+    net_worth: bool,
+    /// Print where quotes came from and how long fetching them took under the table
+    #[arg(long, global = true)]
+    footer: bool,
+    /// Quote sources to try instead of `providers` in config, e.g. yahoo or finnhub,yahoo
+    #[arg(long, global = true, value_delimiter = ',')]
+    provider: Vec<String>,
+    /// Print only the total value and today's change on one line, for status bars
+    #[arg(long, global = true)]
+    summary: bool,
+    /// Print a QR code of the summary as JSON, or of `publish_url` if set,
+    /// to scan with a phone
+    #[arg(long, global = true, conflicts_with = "summary")]
+    qr: bool,
+    /// Lead each position with 🟢, 🔴 or ⚪ by today's move, and the summary
+    /// with 🚀 or 📉 on a big day
+    #[arg(long, global = true)]
+    emoji: bool,
+    /// One labelled sentence per value with no table or colours, for screen
+    /// readers
+    #[arg(long, global = true, conflicts_with_all = ["summary", "qr", "emoji"])]
+    accessible: bool,
+    /// Draw charts, sparklines and bars in plain ASCII, the default on
+    /// terminals that can't show unicode
+    #[arg(long, global = true)]
+    ascii: bool,
 }
 
-#[derive(Debug, Deserialize)]
-struct Stock {
-    symbol: String,
-    amount: f64,
-    cost_basis: f64,
+#[derive(Subcommand)]
+enum Command {
+    /// Technical summary (RSI, MACD, Bollinger) for a symbol
+    Ta { symbol: String },
+    /// Price and day's change for one symbol, with the home listing and
+    /// implied FX for ADRs held with `home = "..."`
+    Quote { symbol: String },
+    /// Delta, theta and implied volatility of option positions, and the
+    /// delta adjusted exposure per underlying
+    Greeks,
+    /// Terminal price chart for one or more symbols
+    Chart {
+        #[arg(required_unless_present = "portfolio")]
+        symbols: Vec<String>,
+        /// Chart recorded portfolio value instead of a symbol
+        #[arg(long, conflicts_with_all = ["symbols", "candles"])]
+        portfolio: bool,
+        /// Symbol or blend from `benchmarks` to compare the portfolio against,
+        /// `benchmark` in config if unset
+        #[arg(long, requires = "portfolio")]
+        benchmark: Option<String>,
+        /// How far back to chart, e.g. 5d, 3m, 1y, ytd
+        #[arg(long, default_value = "3m")]
+        range: String,
+        /// Bar size: 1d, 1w or 1mo
+        #[arg(long, default_value = "1d")]
+        interval: String,
+        /// Draw OHLC candlesticks instead of a close line
+        #[arg(long)]
+        candles: bool,
+        /// Show volume bars below the chart (always on with --candles)
+        #[arg(long)]
+        volume: bool,
+        /// Plot percent change from the start of the range, implied with several symbols
+        #[arg(long)]
+        normalize: bool,
+        /// "raw" closes as traded or "adjusted" for splits and dividends,
+        /// `closes` in config if unset
+        #[arg(long, value_parser = config::parse_closes)]
+        closes: Option<config::Closes>,
+    },
+    /// Show the portfolio table, the same as running with no command
+    Show {
+        /// Holdings file to read instead of the usual one, "-" for TOML or JSON on stdin
+        #[arg(long)]
+        file: Option<String>,
+    },
+    /// Fetch quotes and add a snapshot to the history, printing one line;
+    /// for running from cron
+    Snapshot {
+        /// Print nothing, only errors
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Keep refreshing the portfolio in place until interrupted
+    Watch {
+        /// Seconds between refreshes, defaults to `interval` in config.toml or 60
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Download the latest release binary and replace this one
+    SelfUpdate,
+    /// Validate holdings files and their symbols, e.g. from a pre-commit hook
+    Check {
+        /// Holdings files to check, defaults to the usual one
+        files: Vec<String>,
+        /// Only print problems
+        #[arg(short, long)]
+        quiet: bool,
+        /// Exit with status 1 when a problem is found
+        #[arg(long)]
+        exit_code: bool,
+    },
+    /// Refresh in the background, recording snapshots and printing script
+    /// alerts, or set that up to start at login
+    Daemon {
+        #[command(subcommand)]
+        action: Daemon,
+    },
+    /// Manage provider API keys in the OS keyring
+    Auth {
+        #[command(subcommand)]
+        action: Auth,
+    },
+    /// Look up known provider breakages published in the repository
+    CheckUpstream,
+    /// Archive an account, keeping its history but leaving it out of reports
+    CloseAccount { name: String },
+    /// Add shares to a position, creating the account or position if it's new.
+    /// The symbol is checked for a quote first, with close matches suggested
+    /// when it has none
+    Add {
+        account: String,
+        symbol: String,
+        /// Shares bought
+        num: f64,
+        /// Price paid per share
+        price: f64,
+    },
+    /// Take a position out of an account
+    Remove { account: String, symbol: String },
+    /// List the accounts in the holdings file
+    Accounts,
+    /// List the id of every account and position, which the JSON report and
+    /// --account also go by
+    Ids {
+        /// Write a generated id into those without one, so it survives
+        /// renaming or moving them
+        #[arg(long)]
+        pin: bool,
+    },
+    /// Set up a holdings file by answering a few questions
+    Init,
+    /// How the recorded portfolio value has moved over the last day, week,
+    /// month and year, from the snapshot each run records
+    #[command(alias = "history", alias = "performance")]
+    Perf {
+        /// Also list each symbol
+        #[arg(long)]
+        symbols: bool,
+        /// Also show each change less inflation, by the consumer price index
+        #[arg(long)]
+        real: bool,
+        /// Draw the recorded value as a line chart to this .svg or .png file
+        /// instead
+        #[arg(long)]
+        output: Option<String>,
+        /// How far back to draw, e.g. 3m, 1y or ytd; all the history if unset
+        #[arg(long, requires = "output")]
+        range: Option<String>,
+        /// Symbol or blend from `benchmarks` to draw alongside, `benchmark` in
+        /// config if unset
+        #[arg(long, requires = "output")]
+        benchmark: Option<String>,
+        /// "raw" or "adjusted" closes for the benchmark, `closes` in config if
+        /// unset
+        #[arg(long, requires = "output", value_parser = config::parse_closes)]
+        closes: Option<config::Closes>,
+        #[command(flatten)]
+        image: ImageArgs,
+    },
+    /// Upcoming earnings, dividends, expiries and vesting for what's held
+    Calendar {
+        /// Write them as an iCalendar file to subscribe to instead, "-" for stdout
+        #[arg(long)]
+        ics: Option<String>,
+    },
+    /// Each position's and the total change since a day in the history, split
+    /// into what was put in by trading and what the market did
+    Since {
+        /// YYYY-MM-DD, compared from the last snapshot on or before it
+        #[arg(long)]
+        date: NaiveDate,
+    },
+    /// Show a made up sample portfolio at live prices, to try things out
+    Demo,
+    /// Add buys, sells and dividends from a broker CSV, QIF or OFX export to
+    /// the holdings file
+    Import {
+        /// Broker export to read
+        file: String,
+        /// Account the transactions belong to, by name or id, created if missing
+        #[arg(long)]
+        account: String,
+        /// csv, qif or ofx; guessed from the file extension if unset
+        #[arg(long)]
+        kind: Option<String>,
+        /// List what would be imported without changing the holdings file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compare share counts and cost basis against a broker's positions CSV,
+    /// exiting with status 1 on any mismatch
+    Reconcile {
+        /// Positions export from the broker
+        file: String,
+        /// Only compare this account, by name or id, by default every open one together
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// Securities, cash, other assets and liabilities as one net worth
+    /// statement, recorded to the history each run
+    Networth {
+        /// Show the recorded net worth by day instead
+        #[arg(long)]
+        history: bool,
+    },
+    /// Dividends, lending fees and interest paid into each account, with the
+    /// yield they make on its current value
+    Income {
+        /// Start of the period as YYYY-MM-DD, by default a year ago
+        #[arg(long, conflicts_with = "year")]
+        since: Option<NaiveDate>,
+        /// One calendar year instead, e.g. for the foreign tax paid in it
+        #[arg(long)]
+        year: Option<i32>,
+    },
+    /// What fund expense ratios and advisory fees cost over the years against
+    /// a low cost index fund, set with `expense_ratios` and `advisory_fees`
+    Fees {
+        /// How many years to project
+        #[arg(long, default_value_t = 30)]
+        years: u32,
+        /// Yearly return before fees, in percent
+        #[arg(long, default_value_t = 6.0)]
+        growth: f64,
+    },
+    /// Open lots from each position's `trades` with their unrealized gains,
+    /// and what sales have realized, matched first in, first out. With
+    /// --format csv, one row per sale for tax software
+    Lots {
+        /// Only this symbol
+        symbol: Option<String>,
+    },
+    /// Each position's and the cash's share of its account and of the
+    /// portfolio, largest first
+    #[command(alias = "allocation")]
+    Alloc {
+        /// Group by "symbol", by fund "category" or sector, or by asset "class"
+        #[arg(long, default_value = "symbol")]
+        by: String,
+        /// Draw the portfolio's allocation as a pie chart to this .svg or .png
+        /// file instead
+        #[arg(long)]
+        output: Option<String>,
+        #[command(flatten)]
+        image: ImageArgs,
+    },
+    /// Write the portfolio as a static page with charts and JSON, to copy
+    /// onto any static host and view from anywhere
+    Publish {
+        /// Directory to write into, created if missing
+        #[arg(long)]
+        dir: String,
+        /// Leave out amounts and share counts, publishing only percentages
+        #[arg(long)]
+        private: bool,
+    },
+    /// What drove the return over a span: each symbol's, account's or
+    /// sector's weight times its return, from the recorded snapshots
+    Attribution {
+        /// How far back, e.g. 1m, 3m, 1y or ytd
+        #[arg(long, default_value = "3m")]
+        range: String,
+        /// Group by "symbol", "account", fund "category" or sector, or asset "class"
+        #[arg(long, default_value = "symbol")]
+        by: String,
+    },
+    /// Every change made to the holdings through stocks, oldest first
+    Log {
+        /// Only changes from this day on, as YYYY-MM-DD
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only changes to this account
+        #[arg(long)]
+        account: Option<String>,
+        /// Only changes to this symbol
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Only the last this many
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Run the `script` from config for custom metrics and alerts, and check
+    /// the `alerts` conditions
+    Metrics {
+        /// Exit with status 1 when the script raises an alert
+        #[arg(long)]
+        exit_code: bool,
+    },
+    /// List the extensions found on PATH, each run as `stocks <name>`
+    Plugins,
+    /// Anything else runs `stocks-<name>` from PATH with the report as JSON
+    /// on stdin
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
-#[tokio::main]
-async fn main() {
-    let path = format!("{}/.local/share/stocks.toml", env::var("HOME").unwrap());
-    let accounts = parse_accounts(&path);
-
-    let mut stock_info = HashMap::new();
+// How an image drawn with --output looks
+#[derive(Args)]
+struct ImageArgs {
+    /// Image size in pixels
+    #[arg(long, default_value = "900x560", value_parser = plot::parse_size, requires = "output")]
+    size: (u32, u32),
+    /// "light" or "dark"
+    #[arg(long, default_value = "light", value_parser = plot::parse_theme, requires = "output")]
+    theme: plot::Theme,
+}
 
-    for acct in accounts.iter() {
-        for stock in acct.stocks.iter() {
-            stock_info.insert(stock.symbol.clone(), (0.0, 0.0));
+impl ImageArgs {
+    fn style(&self) -> plot::Style {
+        plot::Style {
+            width: self.size.0,
+            height: self.size.1,
+            theme: self.theme,
         }
     }
-
-    let stock_info = update_stock_info(stock_info).await;
-
-    print(&accounts, &stock_info);
 }
 
-fn parse_accounts(path: &str) -> Vec<Account> {
-    let mut f = File::open(path).unwrap();
-    let mut buf = String::new();
+#[derive(Subcommand)]
+enum Daemon {
+    /// Refresh until stopped, without a screen
+    Run {
+        /// Seconds between refreshes, defaults to `interval` in config.toml or 300
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Install and start a systemd user unit, or a launchd agent on macOS
+    Install {
+        /// Seconds between refreshes, defaults to `interval` in config.toml or 300
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Stop the service and remove it
+    Uninstall,
+}
 
-    f.read_to_string(&mut buf).unwrap();
-    let t = buf.parse::<Table>().unwrap();
+#[derive(Subcommand)]
+enum Auth {
+    /// Store a key, read from stdin
+    Set { provider: String },
+    /// Say whether a key is stored, without printing it
+    Status { provider: String },
+    /// Remove a stored key
+    Delete { provider: String },
+}
 
-    let mut accts = vec![];
+// --format json for the commands that have it, pretty printed
+fn emit_json<T: Serialize>(out: &mut dyn Write, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)
+}
 
-    for (name, val) in t.iter() {
-        let mut stocks = vec![];
+// Writes to stdout only fail once it's closed, which ends the command
+fn written(res: io::Result<()>) {
+    if let Err(e) = res {
+        eprintln!("failed to write output: {}", e);
+        std::process::exit(1);
+    }
+}
 
-        for (stock_name, info) in val.as_table().unwrap().iter() {
-            let amount = info.get("num").unwrap().as_float().unwrap();
-            let cost_basis = info.get("price").unwrap().as_float().unwrap();
+// Holdings and their quotes, exiting on errors and, with --strict, on any
+// failed quote
+async fn load_quoted(
+    path: &str,
+    all: bool,
+    strict: bool,
+    conf: &config::Config,
+) -> (Holdings, HashMap<String, Quote>) {
+    let holdings = match load_holdings(path, all) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    quote_holdings(holdings, strict, conf).await
+}
 
-            stocks.push(Stock {
-                symbol: stock_name.clone(),
-                amount,
-                cost_basis,
-            })
+async fn quote_holdings(
+    mut holdings: Holdings,
+    strict: bool,
+    conf: &config::Config,
+) -> (Holdings, HashMap<String, Quote>) {
+    lookup::resolve_identifiers(conf, &mut holdings).await;
+    crypto::resolve_wallets(&mut holdings.accounts).await;
+    let (stock_info, failed) = match quote_accounts(&holdings.accounts, conf).await {
+        Ok(q) => q,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
+    };
 
-        accts.push(Account {
-            stocks,
-            name: name.clone(),
-        });
+    for (symbol, e) in failed.iter() {
+        eprintln!("failed to fetch a quote for {}: {}", symbol, e);
+    }
+    // a symbol taken as delisted stands at its last recorded price, which is
+    // a guess --strict doesn't make
+    let mut guessed = stock_info
+        .iter()
+        .filter(|(_, q)| strict && q.status == Status::Delisted)
+        .map(|(symbol, _)| symbol)
+        .collect::<Vec<_>>();
+    guessed.sort();
+    for symbol in guessed.iter() {
+        eprintln!(
+            "failed to fetch a quote for {}: symbol not found, only its last recorded price",
+            symbol
+        );
+    }
+    if strict && (!failed.is_empty() || !guessed.is_empty()) {
+        std::process::exit(1);
     }
 
-    accts
+    (holdings, stock_info)
 }
 
-async fn update_stock_info(info: HashMap<String, (f64, f64)>) -> HashMap<String, (f64, f64)> {
-    let futures = info.into_iter().map(|(symbol, _)| {
-        tokio::spawn(async move {
-            match get_nasdaq_value(&symbol, "stocks").await {
-                Some(x) => (symbol, x),
-                None => (
-                    symbol.clone(),
-                    get_nasdaq_value(&symbol, "etf").await.unwrap_or_default(),
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    log::set_verbose(cli.verbose);
+    glyph::set_ascii(cli.ascii || glyph::detect());
+    profile::set(cli.profile.clone());
+
+    let mut conf = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if !cli.provider.is_empty() {
+        if let Err(e) = provider::check_names(&cli.provider) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        conf.providers = cli.provider.clone();
+    }
+    money::set_rounding(conf.rounding, conf.money_decimals);
+    time::set_zone(
+        conf.timezone
+            .as_deref()
+            .and_then(|z| time::parse_zone(z).ok()),
+    );
+
+    let mut file = None;
+    let mut demo = false;
+
+    match cli.command {
+        Some(Command::Ta { symbol }) => {
+            ta::run(&symbol.to_uppercase(), &mut render::stdout(cli.plain)).await;
+            return;
+        }
+        Some(Command::Greeks) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let res = greeks::run(
+                &shown(&holdings.accounts, cli.all),
+                &stock_info,
+                &conf,
+                &mut render::stdout(cli.plain),
+            )
+            .await;
+            written(res);
+            return;
+        }
+        Some(Command::Chart {
+            symbols,
+            portfolio,
+            benchmark,
+            range,
+            interval,
+            candles,
+            volume,
+            normalize,
+            closes,
+        }) => {
+            let opts = chart::ChartOpts {
+                range,
+                interval,
+                candles,
+                volume: volume || candles,
+                normalize,
+                closes: closes.unwrap_or(conf.closes),
+            };
+            if portfolio {
+                let benchmark = benchmark
+                    .or(conf.benchmark.clone())
+                    .map(|b| benchmark::resolve(&b, &conf));
+                chart::portfolio(benchmark.as_ref(), &opts, &mut render::stdout(cli.plain)).await;
+            } else {
+                let symbols = symbols.iter().map(|s| s.to_uppercase()).collect::<Vec<_>>();
+                chart::run(&symbols, &opts, &mut render::stdout(cli.plain)).await;
+            }
+            return;
+        }
+        Some(Command::Daemon { action }) => {
+            let res = match action {
+                Daemon::Run { interval } => {
+                    daemon::run(interval).await;
+                    return;
+                }
+                Daemon::Install { interval } => {
+                    daemon::install(interval).map(|p| format!("installed and started {}", p))
+                }
+                Daemon::Uninstall => daemon::uninstall().map(|p| format!("removed {}", p)),
+            };
+            match res {
+                Ok(msg) => println!("{}", msg),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Snapshot { quiet }) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let snap = snapshot::take(
+                &holdings.accounts,
+                &stock_info,
+                conf.base_currency.as_deref(),
+            );
+            if !snapshot::complete(&snap) {
+                eprintln!("snapshot not recorded, some quotes failed");
+                std::process::exit(1);
+            }
+            if let Err(e) = snapshot::record(&snap) {
+                eprintln!("failed to record snapshot: {}", e);
+                std::process::exit(1);
+            }
+            if let Err(e) = journal::record(&holdings.accounts, &stock_info, &conf) {
+                eprintln!("failed to write the journal: {}", e);
+            }
+            if quiet {
+                return;
+            }
+            if cli.format == "json" {
+                println!("{}", serde_json::to_string(&snap).unwrap());
+            } else {
+                println!(
+                    "{}  total {:.2}",
+                    time::format(&snap.time, "%Y-%m-%d %H:%M"),
+                    snap.total
+                );
+            }
+            return;
+        }
+        Some(Command::Watch { interval }) => {
+            watch::run(interval, cli.all, cli.tickers, cli.legs, cli.emoji).await;
+            return;
+        }
+        Some(Command::Quote { symbol }) => {
+            let symbol = symbol.to_uppercase();
+            // the home listing only comes from the holdings, a missing file is fine
+            let home = load_accounts(&holdings_path(), true)
+                .ok()
+                .and_then(|a| quote::find_home(&a, &symbol));
+            let d = match quote::detail(&conf, &symbol, home.as_ref()).await {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &d)
+            } else {
+                quote::render(&d, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Perf {
+            output: Some(path),
+            range,
+            benchmark,
+            closes,
+            image,
+            ..
+        }) => {
+            let today = time::today();
+            let from = match range.as_deref().map(|r| (r, chart::parse_range(r, today))) {
+                Some((_, Some(d))) => d,
+                Some((r, None)) => {
+                    eprintln!("invalid range {}, expected e.g. 5d, 3m, 1y or ytd", r);
+                    std::process::exit(1);
+                }
+                None => NaiveDate::MIN,
+            };
+            let totals = snapshot::daily_totals(from);
+            let mut series = vec![("portfolio".to_string(), totals.clone())];
+            if let (Some(name), Some((start, value))) =
+                (benchmark.or(conf.benchmark.clone()), totals.first())
+            {
+                let b = benchmark::resolve(&name, &conf);
+                let closes = closes.unwrap_or(conf.closes);
+                match benchmark::series(&b, *start, today, closes).await {
+                    Ok(s) => series.push((b.name, benchmark::rebase(&s, *start, *value))),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let title = match totals.last() {
+                Some((_, v)) => format!("Portfolio, ${:.2}", v),
+                None => "Portfolio".to_string(),
+            };
+            if let Err(e) = plot::lines(&path, &image.style(), &title, &series, &conf) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Perf { symbols, real, .. }) => {
+            let mut p = perf::build(&snapshot::load(), symbols, time::today());
+            if real {
+                match cpi::load(&conf).await {
+                    Ok(c) => perf::deflate(&mut p, &c),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &p)
+            } else {
+                perf::render(&p, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Calendar { ics }) => {
+            let accounts = match load_accounts(&holdings_path(), cli.all) {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let accounts = shown(&accounts, cli.all);
+            let dates = calendar::fetch(&calendar::symbols(&accounts)).await;
+            let events = calendar::build(&accounts, &dates, time::today());
+
+            let res = match ics.as_deref() {
+                Some("-") => {
+                    print!("{}", calendar::to_ics(&events, Utc::now()));
+                    Ok(())
+                }
+                Some(path) => {
+                    lock::write_atomic(path, calendar::to_ics(&events, Utc::now()).as_bytes())
+                }
+                None => {
+                    let out = &mut render::stdout(cli.plain);
+                    if cli.format == "json" {
+                        emit_json(out, &events)
+                    } else {
+                        calendar::render(&events, out)
+                    }
+                }
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Since { date }) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let now = snapshot::take(
+                &holdings.accounts,
+                &stock_info,
+                conf.base_currency.as_deref(),
+            );
+            let s = match since::build(&snapshot::load(), &now, &holdings.accounts, date) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &s)
+            } else {
+                since::render(&s, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Init) => {
+            if let Err(e) = init::run(&conf, &holdings_path()).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Add {
+            account,
+            symbol,
+            num,
+            price,
+        }) => {
+            let symbol = symbol.to_uppercase();
+            // nothing is written until the symbol is known to have a quote
+            if let Err(e) = lookup::check_symbol(&conf, &symbol).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            match portfolio::add_position(&holdings_path(), &account, &symbol, num, price) {
+                Ok(()) => println!("added {} {} at {} to {}", num, symbol, price, account),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Remove { account, symbol }) => {
+            let symbol = symbol.to_uppercase();
+            match portfolio::remove_position(&holdings_path(), &account, &symbol) {
+                Ok(()) => println!("removed {} from {}", symbol, account),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Accounts) => {
+            let accounts = match load_accounts(&holdings_path(), true) {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            for a in accounts.iter() {
+                let mut notes = vec![format!("{} positions", a.stocks.len())];
+                if a.hidden {
+                    notes.push("hidden".to_string());
+                }
+                if let Some(d) = a.closed {
+                    notes.push(format!("closed {}", d));
+                }
+                println!("{}\t{}", a.name, notes.join(", "));
+            }
+            return;
+        }
+        Some(Command::Ids { pin }) => {
+            if pin {
+                match portfolio::pin_ids(&holdings_path()) {
+                    Ok(n) => eprintln!("pinned {} ids", n),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let accounts = match load_accounts(&holdings_path(), true) {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            for a in accounts.iter() {
+                println!("{}\t{}", a.id, a.name);
+                for s in a.stocks.iter() {
+                    println!("{}\t{}.{}", s.id, a.name, s.symbol);
+                }
+            }
+            return;
+        }
+        Some(Command::CloseAccount { name }) => {
+            let today = time::today();
+            match portfolio::close_account(&holdings_path(), &name, today) {
+                Ok(()) => println!(
+                    "closed {}, its positions and trades are kept in the holdings file",
+                    name
                 ),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
             }
-        })
-    });
+            return;
+        }
+        Some(Command::SelfUpdate) => {
+            if let Err(e) = update::run().await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Check {
+            mut files,
+            quiet,
+            exit_code,
+        }) => {
+            if files.is_empty() {
+                files.push(holdings_path());
+            }
+            if !check::run(&files, &conf, quiet).await && exit_code {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Auth { action }) => {
+            let res = match action {
+                Auth::Set { provider } => auth::read_key(&provider)
+                    .and_then(|k| auth::store(&provider, &k))
+                    .map(|_| format!("stored the {} key in the keyring", provider)),
+                Auth::Status { provider } => auth::lookup(&provider).map(|k| match k {
+                    Some(_) => format!("{} has a key in the keyring", provider),
+                    None => format!("no key stored for {}", provider),
+                }),
+                Auth::Delete { provider } => {
+                    auth::delete(&provider).map(|_| format!("removed the {} key", provider))
+                }
+            };
+            match res {
+                Ok(msg) => println!("{}", msg),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::CheckUpstream) => {
+            if let Err(e) = upstream::run(&conf).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Import {
+            file,
+            account,
+            kind,
+            dry_run,
+        }) => {
+            // importers can name the account by an id that outlives renames
+            let account = load_accounts(&holdings_path(), true)
+                .ok()
+                .and_then(|a| a.into_iter().find(|a| a.id == account))
+                .map(|a| a.name)
+                .unwrap_or(account);
+            let res = import::action_table(&conf).and_then(|table| {
+                let rows = import::read(&file, kind.as_deref())?;
+                let (txns, skipped) = import::resolve_all(&rows, &table);
 
-    join_all(futures)
-        .await
-        .into_iter()
-        .map(|res| res.unwrap())
-        .collect()
-}
+                for (action, n) in skipped.iter() {
+                    eprintln!(
+                        "skipped {} rows with action {:?}, map it under import_actions",
+                        n, action
+                    );
+                }
+                let s = if dry_run {
+                    import::preview(&holdings_path(), &account, &txns)?
+                } else {
+                    import::apply(&holdings_path(), &account, &txns, "import")?
+                };
 
-async fn get_nasdaq_value(symbol: &str, class: &str) -> Option<(f64, f64)> {
-    let client = Client::new();
-    let url = format!(
-        "https://api.nasdaq.com/api/quote/{}/info?assetclass={}",
-        symbol, class
-    );
+                for t in s.duplicates.iter() {
+                    eprintln!(
+                        "skipped {} {:?} {} on {}, already in the ledger",
+                        t.symbol,
+                        t.action,
+                        t.num,
+                        t.date.format("%Y-%m-%d")
+                    );
+                }
+                println!(
+                    "{} {} buys, {} sells and {} dividends into {}",
+                    if dry_run { "would import" } else { "imported" },
+                    s.buys,
+                    s.sells,
+                    s.dividends,
+                    account
+                );
+                Ok(())
+            });
+            if let Err(e) = res {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Reconcile { file, account }) => {
+            let res = load_accounts(&holdings_path(), false).and_then(|accounts| {
+                let selected = accounts
+                    .iter()
+                    .filter(|a| account.as_ref().is_none_or(|n| &a.name == n || &a.id == n))
+                    .collect::<Vec<_>>();
+                if selected.is_empty() {
+                    return Err(format!("no account named {}", account.unwrap_or_default()));
+                }
+                reconcile::run(&file, &selected)
+            });
+            match res {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Networth { history }) => {
+            let out = &mut render::stdout(cli.plain);
+            let res = if history {
+                networth::render_history(&networth::daily(networth::load()), out)
+            } else {
+                let (holdings, stock_info) =
+                    load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+                let s = networth::statement(&holdings, cli.all, &stock_info, time::today());
+                if let Err(e) = networth::record(&s, &stock_info) {
+                    eprintln!("failed to record net worth: {}", e);
+                }
+                if cli.format == "json" {
+                    emit_json(out, &s)
+                } else {
+                    networth::render(&s, out)
+                }
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Income { since, year }) => {
+            let today = time::today();
+            let (since, until) = match year {
+                Some(y) => income::year(y),
+                None => (since.unwrap_or(today - Duration::days(364)), today),
+            };
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let accounts = shown(&holdings.accounts, cli.all);
+            let s = income::summary(&accounts, &stock_info, &conf, since, until);
 
-    let res = client.get(url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/85.0.4183.121 Safari/537.36")
-            .header("Accept", "*/*")
-            .header("Accept-Encoding", "gzip, deflate, br")
-            .header("Connection", "keep-alive")
-            .send().await.ok()?
-            .text().await.ok()?;
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &s)
+            } else {
+                income::render(&s, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Fees { years, growth }) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let accounts = shown(&holdings.accounts, cli.all);
+            let r = fees::project(&accounts, &stock_info, &conf, years, growth);
 
-    let v: Value = serde_json::from_str(&res).ok()?;
-    let price_str = v["data"]["primaryData"]["lastSalePrice"].as_str()?;
-    let change_str = v["data"]["primaryData"]["netChange"].as_str()?;
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &r)
+            } else {
+                fees::render(&r, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Lots { symbol }) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let accounts = shown(&holdings.accounts, cli.all);
+            let positions = lots::positions(&accounts, &stock_info, symbol.as_deref());
 
-    let price = price_str[1..].parse::<f64>().ok()?;
-    let change = change_str.parse::<f64>().ok()?;
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &positions)
+            } else if cli.format == "csv" {
+                lots::render_csv(&positions, out)
+            } else {
+                lots::render(&positions, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Alloc { by, output, image }) => {
+            if !["symbol", "category", "class"].contains(&by.as_str()) {
+                eprintln!("--by should be symbol, category or class, not {}", by);
+                std::process::exit(1);
+            }
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let accounts = shown(&holdings.accounts, cli.all);
+            let opts = report::Options::new(&conf, cli.tickers);
+            let report = report::build(&accounts, &stock_info, &opts);
+            let a = if by == "symbol" {
+                alloc::build(&report)
+            } else {
+                let categories = classify::load(&accounts, &conf).await;
+                alloc::build_by(&report, |r| {
+                    let category = categories
+                        .get(&r.symbol)
+                        .map_or(classify::UNCLASSIFIED, |c| c.as_str());
+                    let name = if by == "class" && category != classify::UNCLASSIFIED {
+                        classify::broad(category)
+                    } else {
+                        category
+                    };
+                    (name.to_string(), name.to_string())
+                })
+            };
+            if let Some(path) = output {
+                let title = format!("Allocation, {}", a.total);
+                if let Err(e) = plot::pie(&path, &image.style(), &title, &a.portfolio, &conf) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
 
-    Some((price, change))
-}
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &a)
+            } else {
+                alloc::render(&a, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Publish { dir, private }) => {
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let opts = report::Options::new(&conf, cli.tickers);
+            let report = report::build(&shown(&holdings.accounts, cli.all), &stock_info, &opts);
+            let p = publish::build(&report, private, Utc::now());
+            let slices = alloc::build(&report).portfolio;
+            let history = snapshot::daily_totals(NaiveDate::MIN);
+            match publish::write(&dir, &p, &slices, &history, &conf) {
+                Ok(files) => {
+                    for f in files.iter() {
+                        println!("wrote {}", f);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Attribution { range, by }) => {
+            if !["symbol", "account", "category", "class"].contains(&by.as_str()) {
+                eprintln!(
+                    "--by should be symbol, account, category or class, not {}",
+                    by
+                );
+                std::process::exit(1);
+            }
+            let Some(from) = chart::parse_range(&range, time::today()) else {
+                eprintln!("invalid range {}, expected e.g. 5d, 3m, 1y or ytd", range);
+                std::process::exit(1);
+            };
+            let accounts = match load_accounts(&holdings_path(), cli.all) {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let accounts = shown(&accounts, cli.all);
+            let categories = match by.as_str() {
+                "category" | "class" => classify::load(&accounts, &conf).await,
+                _ => HashMap::new(),
+            };
+            let res = attribution::build(&snapshot::load(), from, |symbol| match by.as_str() {
+                "account" => {
+                    // sold since isn't in any account now
+                    let held = accounts
+                        .iter()
+                        .map(|a| {
+                            let n = a.stocks.iter().filter(|s| s.symbol == symbol);
+                            (a.name.clone(), n.map(|s| s.amount).sum::<f64>())
+                        })
+                        .filter(|(_, n)| *n > 0.0)
+                        .collect::<Vec<_>>();
+                    let total = held.iter().map(|(_, n)| n).sum::<f64>();
+                    if total > 0.0 {
+                        held.into_iter().map(|(a, n)| (a, n / total)).collect()
+                    } else {
+                        vec![("No longer held".to_string(), 1.0)]
+                    }
+                }
+                "category" | "class" => {
+                    let category = categories
+                        .get(symbol)
+                        .map_or(classify::UNCLASSIFIED, |c| c.as_str());
+                    let name = if by == "class" && category != classify::UNCLASSIFIED {
+                        classify::broad(category)
+                    } else {
+                        category
+                    };
+                    vec![(name.to_string(), 1.0)]
+                }
+                _ => vec![(symbol.to_string(), 1.0)],
+            });
+            let a = match res {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
 
-fn clr(f: f64) -> String {
-    if f < 0.0 {
-        "\x1b[38;5;1m"
-    } else {
-        "\x1b[38;5;2m"
-    }
-    .to_string()
-}
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &a)
+            } else {
+                attribution::render(&a, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Log {
+            since,
+            account,
+            symbol,
+            limit,
+        }) => {
+            let q = events::Query {
+                since,
+                account,
+                symbol,
+                limit,
+            };
+            let found = events::query(events::load(), &q);
+
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &found)
+            } else {
+                events::render(&found, out)
+            };
+            written(res);
+            return;
+        }
+        Some(Command::Metrics { exit_code }) => {
+            let path = conf.script.as_deref().map(script::script_path);
+            if path.is_none() && conf.alerts.is_empty() {
+                eprintln!(
+                    "no script or alerts set, add `script = \"metrics.rhai\"` or `[alerts]` to {}",
+                    config::config_path()
+                );
+                std::process::exit(1);
+            }
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let opts = report::Options::new(&conf, cli.tickers);
+            let r = report::build(&shown(&holdings.accounts, cli.all), &stock_info, &opts);
+            let res = path
+                .as_deref()
+                .map_or(Ok(script::Metrics::default()), |p| script::run(p, &r))
+                .and_then(|mut m| {
+                    let mut raised = script::conditions(&conf.alerts, &r)?
+                        .iter()
+                        .map(|name| alerts::Alert::named(name))
+                        .collect::<Vec<_>>();
+                    raised.append(&mut m.alerts);
+                    m.alerts = raised;
+                    Ok(m)
+                });
+            let m = match res {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
 
-fn print(accounts: &Vec<Account>, stock_info: &HashMap<String, (f64, f64)>) {
-    for account in accounts {
-        println!("{}:", account.name);
-        println!("\x1b[1m\tSymbol\t  Price      Net     Net %      Total   Total %\x1b[0m");
-
-        for stock in account.stocks.iter() {
-            let name = &stock.symbol;
-            let (price, net) = stock_info[name];
-
-            let old = price + net;
-            let net_perc = (old - price) * 100.0 / old;
-
-            let total_net = (price - stock.cost_basis) * stock.amount;
-            let old = stock.cost_basis * stock.amount;
-            let new = price * stock.amount;
-
-            let total_perc = (new - old) * 100.0 / old;
-
-            let gain = net * stock.amount;
-
-            println!("\t{}\t${:>7.2}  {}${:>6.2}\x1b[0m  {}{:>6.2}%\x1b[0m  {}${:>9.2}\x1b[0m  {}{:>6.2}%\x1b[0m",
-                     name,
-                     price,
-                     clr(gain),
-                     gain,
-                     clr(net_perc),
-                     net_perc,
-                     clr(total_net),
-                     total_net,
-                     clr(total_perc),
-                     total_perc,
+            let out = &mut render::stdout(cli.plain);
+            let res = if cli.format == "json" {
+                emit_json(out, &m)
+            } else {
+                script::render(&m, out)
+            };
+            written(res);
+            if exit_code && !m.alerts.is_empty() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Plugins) => {
+            for name in plugin::list() {
+                println!("{}", name);
+            }
+            return;
+        }
+        Some(Command::External(args)) => {
+            let (name, args) = args.split_first().expect("clap passes the subcommand name");
+            let exe = match plugin::find(name) {
+                Some(e) => e,
+                None => {
+                    eprintln!("no command {} and no stocks-{} on PATH", name, name);
+                    std::process::exit(2);
+                }
+            };
+            let (holdings, stock_info) =
+                load_quoted(&holdings_path(), cli.all, cli.strict, &conf).await;
+            let opts = report::Options::new(&conf, cli.tickers);
+            let mut report = report::build(&shown(&holdings.accounts, cli.all), &stock_info, &opts);
+            report.add_other(
+                &holdings.other,
+                &holdings.liabilities,
+                time::today(),
+                cli.net_worth,
             );
+            let json = serde_json::to_vec_pretty(&report).expect("report serializes");
+
+            match plugin::run(&exe, args, &json) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Show { file: f }) => file = f,
+        Some(Command::Demo) => demo = true,
+        None => {}
+    }
+
+    let emoji = cli.emoji.then(|| render::Emoji::new(&conf));
+    let renderer: Box<dyn render::OutputRenderer> = match render::from_format(
+        &cli.format,
+        cli.template.as_deref(),
+        cli.legs,
+        &conf.highlight,
+        emoji,
+    ) {
+        Ok(_) if cli.summary => Box::new(render::Summary { emoji }),
+        Ok(_) if cli.accessible => Box::new(render::Accessible),
+        Ok(_) if cli.qr => Box::new(qr::Qr {
+            url: conf.publish_url.clone(),
+        }),
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // the first run on a terminal sets the holdings up instead of failing
+    if file.is_none()
+        && !demo
+        && !Path::new(&holdings_path()).exists()
+        && std::io::stdin().is_terminal()
+    {
+        if let Err(e) = init::run(&conf, &holdings_path()).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // scheduled buys only go into the profile's own holdings
+    if file.is_none() && !demo {
+        if let Err(e) = recurring::run(&conf, &holdings_path(), time::today()).await {
+            eprintln!("failed to record recurring buys: {}", e);
         }
     }
+
+    let path = file.clone().unwrap_or_else(holdings_path);
+    let (holdings, stock_info) = if demo {
+        quote_holdings(demo::holdings(), cli.strict, &conf).await
+    } else {
+        load_quoted(&path, cli.all, cli.strict, &conf).await
+    };
+    let accounts = holdings.accounts;
+
+    let opts = report::Options::new(&conf, cli.tickers);
+    let mut report = report::build(&shown(&accounts, cli.all), &stock_info, &opts);
+    report.add_other(
+        &holdings.other,
+        &holdings.liabilities,
+        time::today(),
+        cli.net_worth,
+    );
+    let out = &mut render::stdout(cli.plain || cli.accessible);
+    let res = renderer.render(&report, out).and_then(|_| {
+        // only the human readable formats get it, so the rest stay parseable
+        if !cli.footer
+            || cli.summary
+            || cli.qr
+            || cli.accessible
+            || !matches!(cli.format.as_str(), "table" | "bar")
+        {
+            return Ok(());
+        }
+        let (network, cached) = http::counts();
+        let f = provider::fetched();
+        writeln!(out, "{}", render::footer(&f, network, cached))
+    });
+    written(res);
+
+    // history tracks the profile's own holdings, not whatever was passed in
+    if file.is_some() || demo {
+        return;
+    }
+    if let Err(e) = snapshot::record(&snapshot::take(
+        &accounts,
+        &stock_info,
+        conf.base_currency.as_deref(),
+    )) {
+        eprintln!("failed to record snapshot: {}", e);
+        if cli.strict {
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = journal::record(&accounts, &stock_info, &conf) {
+        eprintln!("failed to write the journal: {}", e);
+    }
 }
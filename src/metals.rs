@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use serde_json::Value;
+
+use crate::http::fetch_json;
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::provider::{Quote, QuoteError, QuoteProvider, QuoteResult, Status};
+
+const GRAMS_PER_TROY_OUNCE: f64 = 31.1034768;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metal {
+    Gold,
+    Silver,
+    Platinum,
+    Palladium,
+}
+
+const METALS: [Metal; 4] = [
+    Metal::Gold,
+    Metal::Silver,
+    Metal::Platinum,
+    Metal::Palladium,
+];
+
+impl Metal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metal::Gold => "gold",
+            Metal::Silver => "silver",
+            Metal::Platinum => "platinum",
+            Metal::Palladium => "palladium",
+        }
+    }
+
+    // ISO 4217 codes, which is also what spot prices are quoted under
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Metal::Gold => "XAU",
+            Metal::Silver => "XAG",
+            Metal::Platinum => "XPT",
+            Metal::Palladium => "XPD",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<Metal> {
+        METALS.into_iter().find(|m| m.symbol() == symbol)
+    }
+}
+
+// Holdings keys look like "gold_oz" or "silver_g"; the weight is in troy
+// ounces, grams or kilograms and comes back as a factor to troy ounces
+pub fn parse_holding(key: &str) -> Option<(Metal, f64)> {
+    let (name, unit) = key.rsplit_once('_')?;
+    let metal = METALS.into_iter().find(|m| m.name() == name)?;
+
+    let per_unit = match unit {
+        "oz" | "ozt" => 1.0,
+        "g" => 1.0 / GRAMS_PER_TROY_OUNCE,
+        "kg" => 1000.0 / GRAMS_PER_TROY_OUNCE,
+        _ => return None,
+    };
+
+    Some((metal, per_unit))
+}
+
+pub async fn get_spot(metal: Metal) -> QuoteResult {
+    let url = format!("https://api.gold-api.com/price/{}", metal.symbol());
+
+    let v = fetch_json(&url).await.map_err(QuoteError::Network)?;
+    parse_spot(metal, &v)
+}
+
+// The spot feed has no previous close, so the day's change reads as zero
+pub fn parse_spot(metal: Metal, v: &Value) -> QuoteResult {
+    let price = v["price"].as_f64().ok_or_else(|| {
+        QuoteError::Schema(format!("spot response for {} has no price", metal.name()))
+    })?;
+    let currency = v["currency"].as_str().unwrap_or(DEFAULT_CURRENCY);
+    let time = v["updatedAt"]
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc));
+
+    Ok(Quote {
+        price: Money::new(price, currency),
+        change: Money::zero(currency),
+        open: None,
+        time,
+        status: Status::Trading,
+    })
+}
+
+// Spot prices with a dealer premium (or a negative discount) in percent on
+// top, since physical metal rarely sells back at spot
+pub struct Spot {
+    pub premiums: BTreeMap<String, f64>,
+}
+
+impl QuoteProvider for Spot {
+    fn name(&self) -> &'static str {
+        "spot"
+    }
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult> {
+        async move {
+            let metal = Metal::from_symbol(symbol).ok_or(QuoteError::NotFound)?;
+            let quote = get_spot(metal).await?;
+            let factor = 1.0 + self.premiums.get(metal.name()).unwrap_or(&0.0) / 100.0;
+
+            Ok(Quote {
+                price: quote.price * factor,
+                change: quote.change * factor,
+                ..quote
+            })
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holding_keys_convert_to_troy_ounces() {
+        assert_eq!(parse_holding("gold_oz"), Some((Metal::Gold, 1.0)));
+        let (metal, per_unit) = parse_holding("silver_kg").unwrap();
+        assert_eq!(metal, Metal::Silver);
+        assert!((per_unit - 32.1507).abs() < 1e-4);
+        assert_eq!(parse_holding("gold"), None);
+        assert_eq!(parse_holding("copper_oz"), None);
+        assert_eq!(parse_holding("gold_lb"), None);
+    }
+
+    #[test]
+    fn spot_price_parses() {
+        let v = serde_json::json!({
+            "currency": "USD",
+            "name": "Gold",
+            "price": 2034.5,
+            "symbol": "XAU",
+            "updatedAt": "2023-11-24T21:00:00Z",
+        });
+        let q = parse_spot(Metal::Gold, &v).unwrap();
+        assert_eq!(q.price, Money::usd(2034.5));
+        assert_eq!(q.change, Money::usd(0.0));
+        assert!(q.time.is_some());
+
+        assert!(matches!(
+            parse_spot(Metal::Gold, &serde_json::json!({})),
+            Err(QuoteError::Schema(_))
+        ));
+    }
+}
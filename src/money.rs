@@ -0,0 +1,225 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+// An amount tagged with its ISO 4217 currency code.
+//
+// Arithmetic between two different currencies has no meaningful answer
+// without an exchange rate, so instead of panicking it yields NaN, which the
+// renderers show as "n/a" the same way a failed float computation would be.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: String,
+}
+
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+// How a half cent goes: away from zero like most brokers, or to the even
+// neighbour (banker's rounding) so a long column of halves doesn't drift up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    #[default]
+    HalfUp,
+    #[serde(alias = "bankers")]
+    HalfEven,
+}
+
+static ROUNDING: OnceLock<(Rounding, Option<usize>)> = OnceLock::new();
+
+// Set once from the config; `decimals` overrides each currency's minor unit
+pub fn set_rounding(mode: Rounding, decimals: Option<usize>) {
+    let _ = ROUNDING.set((mode, decimals));
+}
+
+fn rounding() -> (Rounding, Option<usize>) {
+    ROUNDING.get().copied().unwrap_or_default()
+}
+
+// Products like 2.675 * 100 land a hair under the half they mean, so
+// anything this close to a half, relative to its size, counts as one
+const HALF_TOLERANCE: f64 = 1e-12;
+
+pub fn round_to(amount: f64, decimals: usize, mode: Rounding) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    let scaled = amount * scale;
+    let floor = scaled.floor();
+
+    let rounded = if (scaled - floor - 0.5).abs() > HALF_TOLERANCE * scaled.abs().max(1.0) {
+        scaled.round()
+    } else {
+        match mode {
+            Rounding::HalfUp if scaled < 0.0 => floor,
+            Rounding::HalfUp => floor + 1.0,
+            Rounding::HalfEven if floor % 2.0 == 0.0 => floor,
+            Rounding::HalfEven => floor + 1.0,
+        }
+    };
+
+    rounded / scale
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Money::new(0.0, DEFAULT_CURRENCY)
+    }
+}
+
+impl Money {
+    pub fn new(amount: f64, currency: &str) -> Self {
+        Money {
+            amount,
+            currency: currency.to_uppercase(),
+        }
+    }
+
+    pub fn usd(amount: f64) -> Self {
+        Money::new(amount, "USD")
+    }
+
+    pub fn zero(currency: &str) -> Self {
+        Money::new(0.0, currency)
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self.currency.as_str() {
+            "USD" => "$",
+            "EUR" => "€",
+            "GBP" => "£",
+            "JPY" => "¥",
+            "CAD" => "C$",
+            "AUD" => "A$",
+            "CHF" => "Fr",
+            "INR" => "₹",
+            c => c,
+        }
+    }
+
+    // digits after the decimal point in the currency's minor unit
+    pub fn decimals(&self) -> usize {
+        match self.currency.as_str() {
+            "JPY" | "KRW" => 0,
+            _ => 2,
+        }
+    }
+
+    // to the configured precision, or the currency's minor unit
+    pub fn round(&self) -> Self {
+        let (mode, decimals) = rounding();
+        let decimals = decimals.unwrap_or(self.decimals());
+        Money::new(round_to(self.amount, decimals, mode), &self.currency)
+    }
+
+    pub fn abs(&self) -> Self {
+        Money::new(self.amount.abs(), &self.currency)
+    }
+
+    pub fn is_nan(&self) -> bool {
+        self.amount.is_nan()
+    }
+
+    // percentage of `self` relative to `base`, e.g. a gain over the cost basis
+    pub fn pct_of(&self, base: &Money) -> f64 {
+        if self.currency != base.currency {
+            return f64::NAN;
+        }
+        self.amount * 100.0 / base.amount
+    }
+
+    fn combine(&self, other: &Money, f: impl Fn(f64, f64) -> f64) -> Money {
+        if self.currency == other.currency {
+            Money::new(f(self.amount, other.amount), &self.currency)
+        } else {
+            Money::new(f64::NAN, &self.currency)
+        }
+    }
+}
+
+// `{:>9}` pads the number, not the symbol, so columns read "$   12.50"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = self.symbol();
+
+        if self.amount.is_nan() {
+            let w = f
+                .width()
+                .unwrap_or(0)
+                .saturating_sub(symbol.chars().count());
+            return write!(f, "{}{:>w$}", symbol, "n/a");
+        }
+
+        let prec = f.precision().unwrap_or(self.decimals());
+        let w = f
+            .width()
+            .unwrap_or(0)
+            .saturating_sub(symbol.chars().count());
+        write!(f, "{}{:>w$.prec$}", symbol, self.amount)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        self.combine(&other, |a, b| a + b)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        *self = self.combine(&other, |a, b| a + b);
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        self.combine(&other, |a, b| a - b)
+    }
+}
+
+impl Mul<f64> for Money {
+    type Output = Money;
+
+    fn mul(self, n: f64) -> Money {
+        Money::new(self.amount * n, &self.currency)
+    }
+}
+
+impl Div<f64> for Money {
+    type Output = Money;
+
+    fn div(self, n: f64) -> Money {
+        Money::new(self.amount / n, &self.currency)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money::new(-self.amount, &self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halves_round_up_or_to_even() {
+        // 2.675 is stored as 2.67499999...
+        assert_eq!(round_to(2.675, 2, Rounding::HalfUp), 2.68);
+        assert_eq!(round_to(2.665, 2, Rounding::HalfUp), 2.67);
+        assert_eq!(round_to(-2.675, 2, Rounding::HalfUp), -2.68);
+        assert_eq!(round_to(2.675, 2, Rounding::HalfEven), 2.68);
+        assert_eq!(round_to(2.665, 2, Rounding::HalfEven), 2.66);
+        assert_eq!(round_to(-0.125, 2, Rounding::HalfEven), -0.12);
+        assert_eq!(round_to(2.6651, 2, Rounding::HalfEven), 2.67);
+        assert_eq!(round_to(1234.5, 0, Rounding::HalfEven), 1234.0);
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::America::New_York;
+use serde_json::Value;
+
+use crate::config::Closes;
+use crate::debug;
+use crate::http::{fetch_json, get_json};
+use crate::money::Money;
+use crate::provider::{Quote, QuoteError, QuoteResult, Status};
+use crate::yahoo::get_adjusted_history;
+use crate::{lock, profile, time};
+
+// Nasdaq needs to be asked for a symbol under the right asset class, and the
+// wrong one comes back empty. Most symbols are stocks; those found to be
+// funds are kept in assetclass.json so they're asked for as funds first
+// next time, rather than failing as stocks and being asked again.
+const DEFAULT_CLASS: &str = "stocks";
+const FUND_CLASS: &str = "etf";
+
+static CLASSES: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+pub struct Bar {
+    pub date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+// Known places the quote has lived in the info payload, newest layout first.
+// Each entry is (name, price path, change path).
+const QUOTE_SCHEMAS: [(&str, &[&str], &[&str]); 3] = [
+    (
+        "primaryData",
+        &["data", "primaryData", "lastSalePrice"],
+        &["data", "primaryData", "netChange"],
+    ),
+    (
+        "secondaryData",
+        &["data", "secondaryData", "lastSalePrice"],
+        &["data", "secondaryData", "netChange"],
+    ),
+    ("flat", &["data", "lastSalePrice"], &["data", "netChange"]),
+];
+
+fn classes_path() -> String {
+    format!("{}/assetclass.json", profile::data_dir())
+}
+
+fn read_classes() -> BTreeMap<String, String> {
+    fs::read_to_string(classes_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+// The class to ask for first and the one to fall back to
+pub fn classes(symbol: &str) -> [&'static str; 2] {
+    let mut known = CLASSES.lock().unwrap();
+    let class = known
+        .get_or_insert_with(read_classes)
+        .get(symbol)
+        .map(String::as_str);
+    if class == Some(FUND_CLASS) {
+        [FUND_CLASS, DEFAULT_CLASS]
+    } else {
+        [DEFAULT_CLASS, FUND_CLASS]
+    }
+}
+
+// Remembers the class a symbol was found under, writing only when that
+// changes what's asked for first
+fn learn(symbol: &str, class: &str) {
+    if classes(symbol)[0] == class {
+        return;
+    }
+    let path = classes_path();
+    let res = fs::create_dir_all(profile::data_dir())
+        .map_err(|e| e.to_string())
+        .and_then(|_| lock::lock(&path))
+        .and_then(|_lock| {
+            // another process may have learned others meanwhile
+            let mut known = read_classes();
+            if class == DEFAULT_CLASS {
+                known.remove(symbol);
+            } else {
+                known.insert(symbol.to_string(), class.to_string());
+            }
+            lock::write_atomic(
+                &path,
+                serde_json::to_string_pretty(&known).unwrap().as_bytes(),
+            )
+            .map_err(|e| e.to_string())?;
+            *CLASSES.lock().unwrap() = Some(known);
+            Ok(())
+        });
+    if let Err(e) = res {
+        debug!("nasdaq: could not save {}: {}", path, e);
+    }
+}
+
+// A quote under the symbol's known class, trying the other if that's wrong
+pub async fn get_quote(symbol: &str) -> QuoteResult {
+    let [first, other] = classes(symbol);
+    match get_nasdaq_value(symbol, first).await {
+        Err(QuoteError::NotFound) => {
+            let res = get_nasdaq_value(symbol, other).await;
+            if res.is_ok() {
+                learn(symbol, other);
+            }
+            res
+        }
+        res => res,
+    }
+}
+
+pub async fn get_nasdaq_value(symbol: &str, class: &str) -> QuoteResult {
+    let url = format!(
+        "https://api.nasdaq.com/api/quote/{}/info?assetclass={}",
+        symbol, class
+    );
+
+    let v = fetch_json(&url).await.map_err(QuoteError::Network)?;
+    parse_quote(symbol, &v)
+}
+
+pub fn parse_quote(symbol: &str, v: &Value) -> QuoteResult {
+    // unknown symbols (or the wrong asset class) come back with null data
+    if v["data"].is_null() {
+        return Err(QuoteError::NotFound);
+    }
+
+    for (name, price_path, change_path) in QUOTE_SCHEMAS.iter() {
+        let price = lookup(v, price_path).and_then(parse_number);
+        let change = lookup(v, change_path).and_then(parse_change);
+
+        if let (Some(price), Some(change)) = (price, change) {
+            debug!("nasdaq {}: matched {} schema", symbol, name);
+            // nasdaq only lists US markets
+            let time =
+                lookup(v, &["data", "primaryData", "lastTradeTimestamp"]).and_then(parse_timestamp);
+
+            return Ok(Quote {
+                price: Money::usd(price),
+                change: Money::usd(change),
+                open: None,
+                time,
+                status: if halted(v) {
+                    Status::Halted
+                } else {
+                    Status::Trading
+                },
+            });
+        }
+    }
+
+    Err(QuoteError::Schema(format!(
+        "nasdaq response for {} has no known price field",
+        symbol
+    )))
+}
+
+// A trading halt shows up as the market status or as a notification over the
+// quote, like "Trading Halted at 10:15 AM ET"
+fn halted(v: &Value) -> bool {
+    let halt = |s: &str| s.to_lowercase().contains("halt");
+    lookup(v, &["data", "marketStatus"]).is_some_and(halt)
+        || v["data"]["notifications"]
+            .as_array()
+            .is_some_and(|n| n.iter().any(|n| halt(&n.to_string())))
+}
+
+// "Closed at Nov 24, 2023 4:00 PM ET", "Nov 24, 2023 10:15 AM ET" or just
+// "Nov 24, 2023", always New York time
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim().trim_end_matches("ET").trim();
+    let words = s.split_whitespace().collect::<Vec<_>>();
+
+    for start in 0..words.len() {
+        let rest = words[start..].join(" ");
+
+        if let Ok(t) = NaiveDateTime::parse_from_str(&rest, "%b %d, %Y %I:%M %p") {
+            return time::from_exchange(t, New_York);
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(&rest, "%b %d, %Y") {
+            return time::from_exchange(d.and_hms_opt(16, 0, 0)?, New_York);
+        }
+    }
+
+    None
+}
+
+fn lookup<'a>(v: &'a Value, path: &[&str]) -> Option<&'a str> {
+    path.iter().try_fold(v, |v, k| v.get(k))?.as_str()
+}
+
+// "+1.23", "-0.50" and "UNCH" for no change
+fn parse_change(s: &str) -> Option<f64> {
+    if s.eq_ignore_ascii_case("unch") {
+        return Some(0.0);
+    }
+    parse_number(s.trim_start_matches('+'))
+}
+
+pub async fn get_nasdaq_history(
+    symbol: &str,
+    class: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Option<Vec<Bar>> {
+    let url = format!(
+        "https://api.nasdaq.com/api/quote/{}/historical?assetclass={}&fromdate={}&todate={}&limit=9999",
+        symbol,
+        class,
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d"),
+    );
+
+    parse_history(&get_json(&url).await?)
+}
+
+pub fn parse_history(v: &Value) -> Option<Vec<Bar>> {
+    let rows = v["data"]["tradesTable"]["rows"].as_array()?;
+
+    let mut bars = rows
+        .iter()
+        .filter_map(|row| {
+            let field = |k: &str| row[k].as_str().and_then(parse_number);
+            let close = field("close")?;
+
+            // funds report "N/A" for everything but the close
+            Some(Bar {
+                date: NaiveDate::parse_from_str(row["date"].as_str()?, "%m/%d/%Y").ok()?,
+                open: field("open").unwrap_or(close),
+                high: field("high").unwrap_or(close),
+                low: field("low").unwrap_or(close),
+                close,
+                volume: field("volume").unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // nasdaq returns the newest row first
+    bars.sort_by_key(|b| b.date);
+
+    if bars.is_empty() {
+        None
+    } else {
+        Some(bars)
+    }
+}
+
+pub async fn get_history(symbol: &str, from: NaiveDate, to: NaiveDate) -> Option<Vec<Bar>> {
+    let [first, other] = classes(symbol);
+    match get_nasdaq_history(symbol, first, from, to).await {
+        Some(x) => Some(x),
+        None => {
+            let bars = get_nasdaq_history(symbol, other, from, to).await?;
+            learn(symbol, other);
+            Some(bars)
+        }
+    }
+}
+
+// Closes as traded from nasdaq, or adjusted for splits and dividends from
+// yahoo, which nasdaq doesn't have
+pub async fn get_series(
+    symbol: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    closes: Closes,
+) -> Option<Vec<Bar>> {
+    match closes {
+        Closes::Raw => get_history(symbol, from, to).await,
+        Closes::Adjusted => get_adjusted_history(symbol, from, to).await,
+    }
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    s.trim_start_matches('$')
+        .replace(',', "")
+        .parse::<f64>()
+        .ok()
+}
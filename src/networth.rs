@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::money::Money;
+use crate::portfolio::{shown, Holdings};
+use crate::provider::Quote;
+use crate::render::clr;
+use crate::report::{self, sum};
+use crate::snapshot::history_dir;
+use crate::time;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    pub name: String,
+    pub value: Money,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    // market value of each account's positions
+    pub securities: Vec<Line>,
+    pub cash: Vec<Line>,
+    pub other: Vec<Line>,
+    // balances owed, as positive amounts
+    pub liabilities: Vec<Line>,
+    pub net_worth: Money,
+}
+
+impl Statement {
+    pub fn assets(&self) -> Money {
+        let lines = self.securities.iter().chain(&self.cash).chain(&self.other);
+        sum(lines.map(|l| l.value.clone()))
+    }
+
+    pub fn owed(&self) -> Money {
+        sum(self.liabilities.iter().map(|l| l.value.clone()))
+    }
+}
+
+// One recorded statement, summed as plain numbers like the snapshots
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: DateTime<Utc>,
+    pub securities: f64,
+    pub cash: f64,
+    pub other: f64,
+    pub liabilities: f64,
+    pub net_worth: f64,
+}
+
+fn history_path() -> String {
+    format!("{}/networth.jsonl", history_dir())
+}
+
+pub fn statement(
+    holdings: &Holdings,
+    all: bool,
+    stock_info: &HashMap<String, Quote>,
+    today: NaiveDate,
+) -> Statement {
+    let accounts = shown(&holdings.accounts, all);
+    let report = report::build(&accounts, stock_info, &report::Options::default());
+
+    let securities = report
+        .accounts
+        .iter()
+        .filter(|a| !a.rows.is_empty())
+        .map(|a| Line {
+            name: a.name.clone(),
+            value: a.value.clone(),
+        })
+        .collect();
+    let cash = accounts
+        .iter()
+        .filter_map(|a| {
+            Some(Line {
+                name: a.name.clone(),
+                value: a.cash.clone()?,
+            })
+        })
+        .collect();
+    let other = holdings
+        .other
+        .iter()
+        .map(|a| Line {
+            name: a.name.clone(),
+            value: a.value_on(today),
+        })
+        .collect();
+    let liabilities = holdings
+        .liabilities
+        .iter()
+        .map(|l| Line {
+            name: l.name.clone(),
+            value: l.balance_on(today),
+        })
+        .collect();
+
+    let mut s = Statement {
+        securities,
+        cash,
+        other,
+        liabilities,
+        net_worth: Money::default(),
+    };
+    s.net_worth = s.assets() - s.owed();
+    s
+}
+
+pub fn record(s: &Statement, stock_info: &HashMap<String, Quote>) -> io::Result<()> {
+    // same as the snapshots, a failed quote would read as a crash
+    if stock_info.values().any(|q| q.price.amount == 0.0) {
+        return Ok(());
+    }
+
+    let total = |lines: &[Line]| sum(lines.iter().map(|l| l.value.clone())).amount;
+    let entry = Entry {
+        time: Utc::now(),
+        securities: total(&s.securities),
+        cash: total(&s.cash),
+        other: total(&s.other),
+        liabilities: total(&s.liabilities),
+        net_worth: s.net_worth.amount,
+    };
+
+    fs::create_dir_all(history_dir())?;
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())?;
+
+    writeln!(f, "{}", serde_json::to_string(&entry)?)
+}
+
+pub fn load() -> Vec<Entry> {
+    let f = match File::open(history_path()) {
+        Ok(f) => f,
+        Err(_) => return vec![],
+    };
+
+    BufReader::new(f)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect()
+}
+
+// the last entry of each day
+pub fn daily(entries: Vec<Entry>) -> Vec<(NaiveDate, Entry)> {
+    let mut days = BTreeMap::new();
+    for e in entries {
+        days.insert(time::date(&e.time), e);
+    }
+    days.into_iter().collect()
+}
+
+pub fn render(s: &Statement, out: &mut dyn Write) -> io::Result<()> {
+    let w = [&s.securities, &s.cash, &s.other, &s.liabilities]
+        .iter()
+        .flat_map(|lines| lines.iter())
+        .map(|l| l.name.chars().count())
+        .fold(10, usize::max);
+
+    let sections = [
+        ("Securities", &s.securities),
+        ("Cash", &s.cash),
+        ("Other assets", &s.other),
+        ("Liabilities", &s.liabilities),
+    ];
+    for (title, lines) in sections {
+        if lines.is_empty() {
+            continue;
+        }
+        writeln!(out, "\x1b[1m{}\x1b[0m", title)?;
+        for l in lines.iter() {
+            writeln!(out, "\t{:<w$}  {:>12}", l.name, l.value)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "\t{:<w$}  {:>12}", "Assets", s.assets())?;
+    writeln!(out, "\t{:<w$}  {:>12}", "Owed", s.owed())?;
+    writeln!(
+        out,
+        "\x1b[1m\t{:<w$}  {:>12}\x1b[0m",
+        "Net worth", s.net_worth
+    )
+}
+
+pub fn render_history(days: &[(NaiveDate, Entry)], out: &mut dyn Write) -> io::Result<()> {
+    if days.is_empty() {
+        return writeln!(out, "no net worth history yet");
+    }
+
+    writeln!(
+        out,
+        "\x1b[1m\t{:<10}  {:>12}  {:>12}  {:>12}  {:>10}\x1b[0m",
+        "Date", "Assets", "Owed", "Net worth", "Change"
+    )?;
+
+    let mut prev: Option<f64> = None;
+    for (day, e) in days.iter() {
+        let assets = e.securities + e.cash + e.other;
+        let change = prev.map(|p| e.net_worth - p).unwrap_or_default();
+        writeln!(
+            out,
+            "\t{}  {:>12.2}  {:>12.2}  {:>12.2}  {}{:>+10.2}\x1b[0m",
+            day.format("%Y-%m-%d"),
+            assets,
+            e.liabilities,
+            e.net_worth,
+            clr(change),
+            change
+        )?;
+        prev = Some(e.net_worth);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{Account, Liability, OtherAsset, Stock};
+
+    #[test]
+    fn net_worth_is_assets_less_liabilities() {
+        let holdings = Holdings {
+            accounts: vec![Account {
+                cash: Some(Money::usd(500.0)),
+                ..Account::test(
+                    "Brokerage",
+                    vec![Stock {
+                        symbol: "AAPL".to_string(),
+                        amount: 10.0,
+                        cost_basis: Money::usd(150.0),
+                        ..Default::default()
+                    }],
+                )
+            }],
+            other: vec![OtherAsset {
+                name: "house".to_string(),
+                value: Money::usd(300000.0),
+                as_of: None,
+                appreciation: 3.0,
+            }],
+            liabilities: vec![Liability {
+                name: "mortgage".to_string(),
+                balance: Money::usd(200000.0),
+                rate: 0.0,
+                payment: None,
+                as_of: None,
+            }],
+        };
+        let quotes = HashMap::from([(
+            "AAPL".to_string(),
+            Quote {
+                price: Money::usd(200.0),
+                ..Default::default()
+            },
+        )]);
+        let today = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+
+        let s = statement(&holdings, false, &quotes, today);
+        assert_eq!(s.securities[0].value, Money::usd(2000.0));
+        assert_eq!(s.cash[0].value, Money::usd(500.0));
+        assert_eq!(s.assets(), Money::usd(302500.0));
+        assert_eq!(s.net_worth, Money::usd(102500.0));
+    }
+
+    #[test]
+    fn liabilities_accrue_and_pay_down_monthly() {
+        let loan = Liability {
+            name: "car loan".to_string(),
+            balance: Money::usd(10000.0),
+            rate: 12.0,
+            payment: Some(Money::usd(1100.0)),
+            as_of: NaiveDate::from_ymd_opt(2024, 1, 15),
+        };
+        let on = |y, m, d| loan.balance_on(NaiveDate::from_ymd_opt(y, m, d).unwrap());
+
+        assert_eq!(on(2024, 1, 1), Money::usd(10000.0));
+        assert_eq!(on(2024, 2, 14), Money::usd(10000.0));
+        // 1% interest then the payment
+        assert_eq!(on(2024, 2, 15), Money::usd(9000.0));
+        assert!((on(2024, 3, 15).amount - 7990.0).abs() < 1e-9);
+        // paid off, not owed back
+        assert_eq!(on(2025, 6, 1), Money::usd(0.0));
+    }
+}
@@ -0,0 +1,158 @@
+// Option positions are recognised by their OCC symbol, e.g.
+// AAPL231215C00190000: the underlying, expiry as yymmdd, C or P, and the
+// strike in thousandths of a dollar.
+
+use std::f64::consts::PI;
+
+use chrono::NaiveDate;
+
+// shares per equity option contract
+pub const MULTIPLIER: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Call,
+    Put,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contract {
+    pub underlying: String,
+    pub expiry: NaiveDate,
+    pub kind: Kind,
+    pub strike: f64,
+}
+
+pub fn parse(symbol: &str) -> Option<Contract> {
+    // the fixed width tail is 15 characters, the root takes 1 to 6
+    if symbol.len() < 16 || symbol.len() > 21 || !symbol.is_ascii() {
+        return None;
+    }
+    let (root, tail) = symbol.split_at(symbol.len() - 15);
+
+    let expiry = NaiveDate::parse_from_str(&tail[..6], "%y%m%d").ok()?;
+    let kind = match &tail[6..7] {
+        "C" => Kind::Call,
+        "P" => Kind::Put,
+        _ => return None,
+    };
+    if !tail[7..].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let strike = tail[7..].parse::<f64>().ok()? / 1000.0;
+
+    Some(Contract {
+        underlying: root.trim().to_string(),
+        expiry,
+        kind,
+        strike,
+    })
+}
+
+impl Contract {
+    pub fn in_the_money(&self, underlying: f64) -> bool {
+        match self.kind {
+            Kind::Call => underlying > self.strike,
+            Kind::Put => underlying < self.strike,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,
+    // change in the option's price per calendar day
+    pub theta: f64,
+    pub iv: f64,
+}
+
+// Abramowitz and Stegun 7.1.26, good to about 1e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+fn norm_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / 2f64.sqrt()))
+}
+
+fn norm_pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * PI).sqrt()
+}
+
+// Black-Scholes greeks from the implied volatility the provider reports,
+// with `years` to expiry and a continuously compounded risk free `rate`
+pub fn greeks(kind: Kind, spot: f64, strike: f64, years: f64, iv: f64, rate: f64) -> Greeks {
+    let sqrt_t = years.sqrt();
+    let d1 = ((spot / strike).ln() + (rate + iv * iv / 2.0) * years) / (iv * sqrt_t);
+    let d2 = d1 - iv * sqrt_t;
+    let discount = strike * (-rate * years).exp();
+    let decay = -spot * norm_pdf(d1) * iv / (2.0 * sqrt_t);
+
+    let (delta, theta) = match kind {
+        Kind::Call => (norm_cdf(d1), decay - rate * discount * norm_cdf(d2)),
+        Kind::Put => (norm_cdf(d1) - 1.0, decay + rate * discount * norm_cdf(-d2)),
+    };
+
+    Greeks {
+        delta,
+        theta: theta / 365.0,
+        iv,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_occ_symbols() {
+        let c = parse("AAPL231215C00190000").unwrap();
+        assert_eq!(c.underlying, "AAPL");
+        assert_eq!(c.expiry, NaiveDate::from_ymd_opt(2023, 12, 15).unwrap());
+        assert_eq!(c.kind, Kind::Call);
+        assert_eq!(c.strike, 190.0);
+
+        let p = parse("F240119P00012500").unwrap();
+        assert_eq!(p.underlying, "F");
+        assert_eq!(p.kind, Kind::Put);
+        assert_eq!(p.strike, 12.5);
+    }
+
+    #[test]
+    fn plain_tickers_are_not_options() {
+        assert_eq!(parse("AAPL"), None);
+        assert_eq!(parse("BRK.B"), None);
+        assert_eq!(parse("AAPL231215X00190000"), None);
+    }
+
+    #[test]
+    fn black_scholes_greeks() {
+        // at the money, a year out, 20% vol and 5% rates
+        let call = greeks(Kind::Call, 100.0, 100.0, 1.0, 0.2, 0.05);
+        assert!((call.delta - 0.6368).abs() < 1e-3);
+        assert!((call.theta * 365.0 - -6.414).abs() < 1e-2);
+
+        let put = greeks(Kind::Put, 100.0, 100.0, 1.0, 0.2, 0.05);
+        assert!((put.delta - -0.3632).abs() < 1e-3);
+        assert!((put.theta * 365.0 - -1.658).abs() < 1e-2);
+    }
+
+    #[test]
+    fn moneyness() {
+        let call = parse("AAPL231215C00190000").unwrap();
+        assert!(call.in_the_money(195.0));
+        assert!(!call.in_the_money(185.0));
+
+        let put = parse("AAPL231215P00190000").unwrap();
+        assert!(put.in_the_money(185.0));
+    }
+}
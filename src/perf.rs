@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::chart::parse_range;
+use crate::cpi::{real_pct, Cpi};
+use crate::glyph;
+use crate::render::clr;
+use crate::snapshot::Snapshot;
+use crate::time;
+
+pub const PERIODS: [&str; 6] = ["1d", "1w", "1m", "3m", "ytd", "1y"];
+// days of history in the trend column
+const TREND_DAYS: usize = 30;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_SPARKS: [char; 8] = ['_', '.', ',', '-', '~', '=', '*', '#'];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub amount: f64,
+    pub pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    pub name: String,
+    pub value: f64,
+    // one per period, none when the history doesn't go back that far
+    pub changes: Vec<Option<Change>>,
+    // the changes in percent less inflation, with --real
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub real: Vec<Option<f64>>,
+    pub trend: Vec<f64>,
+}
+
+// The total's change over a period split into money put in (or taken out)
+// and what the market did with the rest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breakdown {
+    pub contributions: f64,
+    pub growth: f64,
+    pub growth_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Perf {
+    pub as_of: NaiveDate,
+    pub periods: Vec<String>,
+    pub lines: Vec<Line>,
+    // one per period for the total, like its changes
+    pub breakdown: Vec<Option<Breakdown>>,
+}
+
+// The last value of each day, for days where `value` has one
+fn daily(snaps: &[Snapshot], value: impl Fn(&Snapshot) -> Option<f64>) -> Vec<(NaiveDate, f64)> {
+    let mut days = BTreeMap::new();
+    for s in snaps.iter() {
+        if let Some(v) = value(s) {
+            days.insert(time::date(&s.time), v);
+        }
+    }
+    days.into_iter().collect()
+}
+
+// Change since the last value recorded on or before `start`
+fn change(days: &[(NaiveDate, f64)], start: NaiveDate) -> Option<Change> {
+    let (_, now) = days.last()?;
+    let (_, then) = days.iter().rev().find(|(d, _)| *d <= start)?;
+
+    Some(Change {
+        amount: now - then,
+        pct: if *then != 0.0 {
+            (now - then) * 100.0 / then
+        } else {
+            0.0
+        },
+    })
+}
+
+// Shares that appear between two snapshots were bought at about the later
+// one's price, and ones that go were sold at it
+fn contributed(prev: &Snapshot, next: &Snapshot) -> f64 {
+    next.symbols
+        .iter()
+        .map(|(symbol, h)| {
+            let before = prev.symbols.get(symbol).map_or(0.0, |p| p.amount);
+            (h.amount - before) * h.price
+        })
+        .chain(
+            prev.symbols
+                .iter()
+                .filter(|(symbol, _)| !next.symbols.contains_key(*symbol))
+                .map(|(_, p)| -p.amount * p.price),
+        )
+        .sum()
+}
+
+fn breakdown(snaps: &[Snapshot], start: NaiveDate) -> Option<Breakdown> {
+    // the last snapshot of each day, like the changes use
+    let mut days: BTreeMap<NaiveDate, &Snapshot> = BTreeMap::new();
+    for s in snaps.iter() {
+        days.insert(time::date(&s.time), s);
+    }
+    let days = days.into_values().collect::<Vec<_>>();
+    let first = days.iter().rposition(|s| time::date(&s.time) <= start)?;
+    let then = days[first].total;
+    let now = days.last()?.total;
+
+    let contributions: f64 = days[first..]
+        .windows(2)
+        .map(|w| contributed(w[0], w[1]))
+        .sum();
+    let growth = now - then - contributions;
+    // money put in partway through counts as there for half the period
+    let base = then + contributions / 2.0;
+
+    Some(Breakdown {
+        contributions,
+        growth,
+        growth_pct: if base > 0.0 {
+            growth * 100.0 / base
+        } else {
+            0.0
+        },
+    })
+}
+
+fn line(name: &str, days: Vec<(NaiveDate, f64)>, today: NaiveDate) -> Option<Line> {
+    let value = days.last()?.1;
+    let changes = PERIODS
+        .iter()
+        .map(|p| parse_range(p, today).and_then(|start| change(&days, start)))
+        .collect();
+    let trend = days
+        .iter()
+        .skip(days.len().saturating_sub(TREND_DAYS))
+        .map(|(_, v)| *v)
+        .collect();
+
+    Some(Line {
+        name: name.to_string(),
+        value,
+        changes,
+        real: vec![],
+        trend,
+    })
+}
+
+// The portfolio total, then each account, then with `symbols` each position,
+// for whatever the latest snapshot holds
+pub fn build(snaps: &[Snapshot], symbols: bool, today: NaiveDate) -> Perf {
+    let mut lines = vec![];
+    lines.extend(line("Total", daily(snaps, |s| Some(s.total)), today));
+
+    let latest = snaps.last();
+    for name in latest.iter().flat_map(|s| s.accounts.keys()) {
+        lines.extend(line(
+            name,
+            daily(snaps, |s| s.accounts.get(name).copied()),
+            today,
+        ));
+    }
+
+    if symbols {
+        for symbol in latest.iter().flat_map(|s| s.symbols.keys()) {
+            let value = |s: &Snapshot| s.symbols.get(symbol).map(|h| h.amount * h.price);
+            lines.extend(line(symbol, daily(snaps, value), today));
+        }
+    }
+
+    let breakdown = PERIODS
+        .iter()
+        .map(|p| parse_range(p, today).and_then(|start| breakdown(snaps, start)))
+        .collect();
+
+    Perf {
+        as_of: today,
+        periods: PERIODS.iter().map(|p| p.to_string()).collect(),
+        lines,
+        breakdown,
+    }
+}
+
+// Takes inflation from each period's start up to `as_of` out of every change
+pub fn deflate(p: &mut Perf, cpi: &Cpi) {
+    let factors = p
+        .periods
+        .iter()
+        .map(|period| parse_range(period, p.as_of).and_then(|start| cpi.factor(start, p.as_of)))
+        .collect::<Vec<_>>();
+
+    for l in p.lines.iter_mut() {
+        l.real = l
+            .changes
+            .iter()
+            .zip(factors.iter())
+            .map(|(c, f)| Some(real_pct(c.as_ref()?.pct, (*f)?)))
+            .collect();
+    }
+}
+
+pub fn sparkline(values: &[f64]) -> String {
+    let sparks = glyph::pick(SPARKS, ASCII_SPARKS);
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+    values
+        .iter()
+        .map(|v| {
+            if max - min <= 0.0 {
+                return sparks[sparks.len() / 2];
+            }
+            let i = ((v - min) / (max - min) * (sparks.len() - 1) as f64).round() as usize;
+            sparks[i.min(sparks.len() - 1)]
+        })
+        .collect()
+}
+
+pub fn render(p: &Perf, out: &mut dyn Write) -> io::Result<()> {
+    if p.lines.is_empty() {
+        return writeln!(
+            out,
+            "no history yet, each run of `stocks` records a snapshot"
+        );
+    }
+
+    let w = p
+        .lines
+        .iter()
+        .map(|l| l.name.chars().count())
+        .fold(8, usize::max);
+
+    write!(out, "\x1b[1m\t{:<w$}  {:>12}", "", "Value")?;
+    for period in p.periods.iter() {
+        write!(out, "  {:>8}", period)?;
+    }
+    writeln!(out, "  Trend\x1b[0m")?;
+
+    for (i, l) in p.lines.iter().enumerate() {
+        write!(out, "\t{:<w$}  {:>12.2}", l.name, l.value)?;
+        for c in l.changes.iter() {
+            match c {
+                Some(c) => write!(out, "  {}{:>+7.2}%\x1b[0m", clr(c.amount), c.pct)?,
+                None => write!(out, "  {:>8}", "n/a")?,
+            }
+        }
+        writeln!(out, "  {}", sparkline(&l.trend))?;
+
+        if !l.real.is_empty() {
+            write!(out, "\t{:<w$}  {:>12}", "  real", "")?;
+            for r in l.real.iter() {
+                match r {
+                    Some(r) => write!(out, "  {}{:>+7.2}%\x1b[0m", clr(*r), r)?,
+                    None => write!(out, "  {:>8}", "n/a")?,
+                }
+            }
+            writeln!(out)?;
+        }
+
+        // right under the total, which is the first line
+        if i == 0 {
+            render_breakdown(p, out, w)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn render_breakdown(p: &Perf, out: &mut dyn Write, w: usize) -> io::Result<()> {
+    write!(out, "\t{:<w$}  {:>12}", "  put in", "")?;
+    for b in p.breakdown.iter() {
+        match b {
+            Some(b) => write!(out, "  {:>+8.0}", b.contributions)?,
+            None => write!(out, "  {:>8}", "n/a")?,
+        }
+    }
+    writeln!(out)?;
+
+    write!(out, "\t{:<w$}  {:>12}", "  growth", "")?;
+    for b in p.breakdown.iter() {
+        match b {
+            Some(b) => write!(out, "  {}{:>+7.2}%\x1b[0m", clr(b.growth), b.growth_pct)?,
+            None => write!(out, "  {:>8}", "n/a")?,
+        }
+    }
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Holding;
+    use chrono::{TimeZone, Utc};
+
+    fn snap(day: u32, total: f64) -> Snapshot {
+        Snapshot {
+            time: Utc.with_ymd_and_hms(2024, 3, day, 15, 0, 0).unwrap(),
+            total,
+            accounts: BTreeMap::from([("Brokerage".to_string(), total)]),
+            symbols: BTreeMap::new(),
+            currency: None,
+        }
+    }
+
+    #[test]
+    fn changes_against_the_value_at_each_period_start() {
+        let snaps = [
+            snap(1, 100.0),
+            snap(7, 110.0),
+            snap(13, 121.0),
+            snap(14, 99.0),
+        ];
+        let today = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        let p = build(&snaps, false, today);
+
+        assert_eq!(p.lines.len(), 2);
+        let total = &p.lines[0];
+        assert_eq!(total.value, 99.0);
+        // a day back is the 13th
+        assert_eq!(total.changes[0].as_ref().unwrap().amount, -22.0);
+        // a week back the 7th was the last run
+        assert!((total.changes[1].as_ref().unwrap().pct - -10.0).abs() < 1e-9);
+        // the history doesn't reach a month back
+        assert_eq!(total.changes[2], None);
+    }
+
+    #[test]
+    fn money_put_in_is_kept_apart_from_growth() {
+        let holding = |amount, price| Holding { amount, price };
+        let mut a = snap(6, 1000.0);
+        a.symbols = BTreeMap::from([("VTI".to_string(), holding(10.0, 100.0))]);
+        // five more shares bought at 110
+        let mut b = snap(10, 1650.0);
+        b.symbols = BTreeMap::from([("VTI".to_string(), holding(15.0, 110.0))]);
+        let mut c = snap(14, 1800.0);
+        c.symbols = BTreeMap::from([("VTI".to_string(), holding(15.0, 120.0))]);
+
+        let today = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        let p = build(&[a, b, c], false, today);
+
+        // 800 up over the week, 550 of it put in
+        let week = p.breakdown[1].as_ref().unwrap();
+        assert_eq!(week.contributions, 550.0);
+        assert_eq!(week.growth, 250.0);
+        // a day back nothing was bought
+        assert_eq!(p.breakdown[0].as_ref().unwrap().contributions, 0.0);
+        assert_eq!(p.breakdown[2], None);
+    }
+
+    #[test]
+    fn real_changes_leave_out_inflation() {
+        let snaps = [snap(1, 100.0), snap(14, 110.0)];
+        let today = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        let mut p = build(&snaps, false, today);
+        let cpi = Cpi(BTreeMap::from([
+            (NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(), 100.0),
+            (NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), 101.0),
+        ]));
+        deflate(&mut p, &cpi);
+
+        let total = &p.lines[0];
+        // back to the 7th is all March, so no inflation to take out
+        assert!((total.real[1].unwrap() - 10.0).abs() < 1e-9);
+        // the history doesn't reach a month back
+        assert_eq!(total.real[2], None);
+    }
+
+    #[test]
+    fn sparkline_spans_low_to_high() {
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0]), "▁▅█");
+        assert_eq!(sparkline(&[5.0, 5.0]), "▅▅");
+    }
+}
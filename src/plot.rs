@@ -0,0 +1,475 @@
+// Charts drawn to image files, for putting in a dashboard or notes; the kind
+// comes from the file's extension. SVG leaves the text to whatever shows it,
+// PNG draws it with a TrueType font: `font` in config, or the first of the
+// usual system ones found.
+
+use std::sync::OnceLock;
+
+use chrono::NaiveDate;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use crate::alloc::Slice;
+use crate::config::Config;
+
+// more than this and the rest are shown together as one slice
+const MAX_SLICES: usize = 12;
+
+const FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+const NO_FONT: &str =
+    "no font found for PNG text, set `font` in config to a .ttf file or write an .svg";
+
+const PALETTE: &[RGBColor] = &[
+    RGBColor(0x4e, 0x79, 0xa7),
+    RGBColor(0xf2, 0x8e, 0x2b),
+    RGBColor(0xe1, 0x57, 0x59),
+    RGBColor(0x76, 0xb7, 0xb2),
+    RGBColor(0x59, 0xa1, 0x4f),
+    RGBColor(0xed, 0xc9, 0x48),
+    RGBColor(0xb0, 0x7a, 0xa1),
+    RGBColor(0xff, 0x9d, 0xa7),
+    RGBColor(0x9c, 0x75, 0x5f),
+    RGBColor(0xba, 0xb0, 0xac),
+    RGBColor(0x2f, 0x4b, 0x7c),
+    RGBColor(0x8c, 0xd1, 0x7d),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub width: u32,
+    pub height: u32,
+    pub theme: Theme,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            width: 900,
+            height: 560,
+            theme: Theme::Light,
+        }
+    }
+}
+
+// background, text and grid lines
+fn colors(theme: Theme) -> (RGBColor, RGBColor, RGBColor) {
+    match theme {
+        Theme::Light => (WHITE, BLACK, RGBColor(0xdd, 0xdd, 0xdd)),
+        Theme::Dark => (
+            RGBColor(0x1e, 0x1e, 0x1e),
+            RGBColor(0xdd, 0xdd, 0xdd),
+            RGBColor(0x44, 0x44, 0x44),
+        ),
+    }
+}
+
+// `1200x600`
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let size = s
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+    match size {
+        Some((w, h)) if (200..=8000).contains(&w) && (150..=8000).contains(&h) => Ok((w, h)),
+        _ => Err(format!(
+            "size {} should be width x height in pixels, like 1200x600",
+            s
+        )),
+    }
+}
+
+pub fn parse_theme(s: &str) -> Result<Theme, String> {
+    match s.to_lowercase().as_str() {
+        "light" => Ok(Theme::Light),
+        "dark" => Ok(Theme::Dark),
+        _ => Err(format!("theme should be light or dark, not {}", s)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Svg,
+    Png,
+}
+
+pub fn kind(path: &str) -> Result<Kind, String> {
+    let ext = path.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+    match ext.as_deref() {
+        Some("svg") => Ok(Kind::Svg),
+        Some("png") => Ok(Kind::Png),
+        _ => Err(format!("{} should end in .svg or .png", path)),
+    }
+}
+
+// plotters keeps fonts for good once registered, so this only happens once
+fn load_font(conf: &Config) -> Result<(), String> {
+    static LOADED: OnceLock<Result<(), String>> = OnceLock::new();
+    LOADED
+        .get_or_init(|| {
+            let candidates = match &conf.font {
+                Some(f) => vec![f.as_str()],
+                None => FONTS.to_vec(),
+            };
+            let bytes = candidates
+                .iter()
+                .find_map(|p| std::fs::read(p).ok())
+                .ok_or_else(|| match &conf.font {
+                    Some(f) => format!("could not read the font {}", f),
+                    None => NO_FONT.to_string(),
+                })?;
+            let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+            plotters::style::register_font("sans-serif", FontStyle::Normal, bytes)
+                .map_err(|_| "the font is not a TrueType font plotters can read".to_string())
+        })
+        .clone()
+}
+
+// Largest first, with everything past the last there's a color for merged
+fn pie_slices(slices: &[Slice]) -> Vec<(String, f64)> {
+    let mut parts = slices
+        .iter()
+        .filter(|s| s.value.amount > 0.0)
+        .map(|s| (s.name.clone(), s.value.amount))
+        .collect::<Vec<_>>();
+    parts.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if parts.len() > MAX_SLICES {
+        let rest = parts.split_off(MAX_SLICES - 1);
+        parts.push(("Other".to_string(), rest.iter().map(|(_, v)| v).sum()));
+    }
+    parts
+}
+
+fn draw_pie<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    style: &Style,
+    title: &str,
+    parts: &[(String, f64)],
+) -> Result<(), String> {
+    let err = |e: DrawingAreaErrorKind<DB::ErrorType>| e.to_string();
+    let (background, text, _) = colors(style.theme);
+    root.fill(&background).map_err(err)?;
+    root.draw(&Text::new(
+        title.to_string(),
+        (24, 20),
+        ("sans-serif", 24).into_font().color(&text),
+    ))
+    .map_err(err)?;
+
+    let total: f64 = parts.iter().map(|(_, v)| v).sum();
+    let side = style.height.min(style.width * 2 / 3) as i32;
+    let (cx, cy) = (side / 2 + 10, style.height as i32 / 2 + 20);
+    let r = (side as f64 / 2.0) - 60.0;
+    // clockwise from the top
+    let mut angle = -std::f64::consts::FRAC_PI_2;
+    for (i, (name, value)) in parts.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let sweep = value / total * std::f64::consts::TAU;
+        let steps = ((sweep.to_degrees()).ceil() as usize).max(1);
+        let mut points = vec![(cx, cy)];
+        for s in 0..=steps {
+            let a = angle + sweep * s as f64 / steps as f64;
+            points.push((
+                cx + (r * a.cos()).round() as i32,
+                cy + (r * a.sin()).round() as i32,
+            ));
+        }
+        root.draw(&Polygon::new(points, color.filled()))
+            .map_err(err)?;
+        angle += sweep;
+
+        // the legend, beside the pie
+        let y = 80 + i as i32 * 34;
+        let x = cx + r as i32 + 60;
+        root.draw(&Rectangle::new([(x, y), (x + 20, y + 20)], color.filled()))
+            .map_err(err)?;
+        root.draw(&Text::new(
+            format!("{}  {:.1}%", name, value * 100.0 / total),
+            (x + 32, y + 10),
+            ("sans-serif", 18)
+                .into_font()
+                .color(&text)
+                .pos(Pos::new(HPos::Left, VPos::Center)),
+        ))
+        .map_err(err)?;
+    }
+    root.present().map_err(err)
+}
+
+// A pie of the slices, largest first, written to `path`
+pub fn pie(
+    path: &str,
+    style: &Style,
+    title: &str,
+    slices: &[Slice],
+    conf: &Config,
+) -> Result<(), String> {
+    let parts = pie_slices(slices);
+    if parts.is_empty() {
+        return Err("nothing to chart, every value is zero".to_string());
+    }
+    let size = (style.width, style.height);
+    let res = match kind(path)? {
+        Kind::Svg => draw_pie(
+            &SVGBackend::new(path, size).into_drawing_area(),
+            style,
+            title,
+            &parts,
+        ),
+        Kind::Png => {
+            load_font(conf)?;
+            draw_pie(
+                &BitMapBackend::new(path, size).into_drawing_area(),
+                style,
+                title,
+                &parts,
+            )
+        }
+    };
+    res.map_err(|e| format!("could not draw {}: {}", path, e))
+}
+
+type Series = (String, Vec<(NaiveDate, f64)>);
+
+// Even steps between `lo` and `hi` at a round size, for the value axis
+fn ticks(lo: f64, hi: f64) -> Vec<f64> {
+    let span = (hi - lo).max(1e-9);
+    let raw = span / 5.0;
+    let mag = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 2.5, 5.0, 10.0]
+        .iter()
+        .map(|m| m * mag)
+        .find(|s| *s >= raw)
+        .unwrap_or(10.0 * mag);
+    let mut t = (lo / step).ceil() * step;
+    let mut out = vec![];
+    while t <= hi + step * 1e-9 {
+        out.push(t);
+        t += step;
+    }
+    out
+}
+
+fn draw_lines<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    style: &Style,
+    title: &str,
+    series: &[Series],
+) -> Result<(), String> {
+    let err = |e: DrawingAreaErrorKind<DB::ErrorType>| e.to_string();
+    let (background, text, grid) = colors(style.theme);
+    root.fill(&background).map_err(err)?;
+    root.draw(&Text::new(
+        title.to_string(),
+        (24, 20),
+        ("sans-serif", 24).into_font().color(&text),
+    ))
+    .map_err(err)?;
+
+    let points = || series.iter().flat_map(|(_, s)| s.iter());
+    let first = points().map(|(d, _)| *d).min().unwrap();
+    let last = points().map(|(d, _)| *d).max().unwrap();
+    let lo = points().map(|(_, v)| *v).fold(f64::MAX, f64::min);
+    let hi = points().map(|(_, v)| *v).fold(f64::MIN, f64::max);
+    let pad = ((hi - lo) * 0.05).max(hi.abs() * 0.01).max(1.0);
+    let (lo, hi) = (lo - pad, hi + pad);
+
+    // the plot area, with room for the axis labels and the legend
+    let (left, right) = (90, style.width as i32 - 30);
+    let (top, bottom) = (70, style.height as i32 - 50);
+    let days = (last - first).num_days().max(1) as f64;
+    let x =
+        |d: NaiveDate| left + ((d - first).num_days() as f64 / days * (right - left) as f64) as i32;
+    let y = |v: f64| bottom - ((v - lo) / (hi - lo) * (bottom - top) as f64) as i32;
+    let label = |s: String, at: (i32, i32), pos: Pos| {
+        Text::new(s, at, ("sans-serif", 14).into_font().color(&text).pos(pos))
+    };
+
+    for t in ticks(lo, hi) {
+        root.draw(&PathElement::new(vec![(left, y(t)), (right, y(t))], grid))
+            .map_err(err)?;
+        root.draw(&label(
+            format!("{:.0}", t),
+            (left - 8, y(t)),
+            Pos::new(HPos::Right, VPos::Center),
+        ))
+        .map_err(err)?;
+    }
+    for i in 0..=4 {
+        let d = first + chrono::Duration::days((days * i as f64 / 4.0).round() as i64);
+        root.draw(&label(
+            d.format("%Y-%m-%d").to_string(),
+            (x(d), bottom + 10),
+            // the ends kept inside the image
+            Pos::new(
+                match i {
+                    0 => HPos::Left,
+                    4 => HPos::Right,
+                    _ => HPos::Center,
+                },
+                VPos::Top,
+            ),
+        ))
+        .map_err(err)?;
+    }
+
+    for (i, (name, s)) in series.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let line = s.iter().map(|(d, v)| (x(*d), y(*v))).collect::<Vec<_>>();
+        root.draw(&PathElement::new(line, color.stroke_width(2)))
+            .map_err(err)?;
+
+        // the legend, across the top right
+        let lx = right - 160 * (series.len() - i) as i32;
+        root.draw(&Rectangle::new([(lx, 30), (lx + 16, 46)], color.filled()))
+            .map_err(err)?;
+        root.draw(&label(
+            name.clone(),
+            (lx + 24, 38),
+            Pos::new(HPos::Left, VPos::Center),
+        ))
+        .map_err(err)?;
+    }
+    root.present().map_err(err)
+}
+
+// Each series as a line over the dates, written to `path`
+pub fn lines(
+    path: &str,
+    style: &Style,
+    title: &str,
+    series: &[Series],
+    conf: &Config,
+) -> Result<(), String> {
+    let series = series
+        .iter()
+        .filter(|(_, s)| !s.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+    if series.iter().all(|(_, s)| s.len() < 2) {
+        return Err("not enough history to chart yet".to_string());
+    }
+    let size = (style.width, style.height);
+    let res = match kind(path)? {
+        Kind::Svg => draw_lines(
+            &SVGBackend::new(path, size).into_drawing_area(),
+            style,
+            title,
+            &series,
+        ),
+        Kind::Png => {
+            load_font(conf)?;
+            draw_lines(
+                &BitMapBackend::new(path, size).into_drawing_area(),
+                style,
+                title,
+                &series,
+            )
+        }
+    };
+    res.map_err(|e| format!("could not draw {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn slice(name: &str, value: f64) -> Slice {
+        Slice {
+            name: name.to_string(),
+            value: Money::usd(value),
+            pct: 0.0,
+        }
+    }
+
+    #[test]
+    fn pies_are_drawn_to_svg_with_a_legend() {
+        assert_eq!(kind("a.SVG"), Ok(Kind::Svg));
+        assert_eq!(kind("a.png"), Ok(Kind::Png));
+        assert!(kind("allocation").is_err());
+
+        let many = (0..15)
+            .map(|i| slice(&format!("S{}", i), 100.0 - i as f64))
+            .collect::<Vec<_>>();
+        let parts = pie_slices(&many);
+        assert_eq!(parts.len(), MAX_SLICES);
+        assert_eq!(parts[MAX_SLICES - 1].0, "Other");
+        assert_eq!(parts[MAX_SLICES - 1].1, 89.0 + 88.0 + 87.0 + 86.0);
+
+        let path = std::env::temp_dir().join(format!("stocks-pie-{}.svg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let slices = [
+            slice("VTI", 600.0),
+            slice("BND", 300.0),
+            slice("Cash", 100.0),
+        ];
+        pie(
+            path,
+            &Style::default(),
+            "Allocation",
+            &slices,
+            &Config::default(),
+        )
+        .unwrap();
+        let svg = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(svg.contains("VTI  60.0%"), "{}", svg);
+        assert!(svg.contains("Cash  10.0%"));
+        assert_eq!(svg.matches("<polygon").count(), 3);
+    }
+
+    #[test]
+    fn value_history_is_drawn_with_axes_and_a_legend() {
+        assert_eq!(parse_size("1200x600"), Ok((1200, 600)));
+        assert!(parse_size("big").is_err());
+        assert_eq!(parse_theme("Dark"), Ok(Theme::Dark));
+        assert_eq!(ticks(0.0, 100.0), [0.0, 20.0, 40.0, 60.0, 80.0, 100.0]);
+        assert_eq!(ticks(9_100.0, 10_400.0), [9_500.0, 10_000.0]);
+
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let series = [
+            (
+                "portfolio".to_string(),
+                vec![(day(1), 10_000.0), (day(5), 10_400.0), (day(9), 10_200.0)],
+            ),
+            (
+                "VTI".to_string(),
+                vec![(day(1), 10_000.0), (day(9), 10_300.0)],
+            ),
+        ];
+        let path = std::env::temp_dir().join(format!("stocks-lines-{}.svg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let style = Style {
+            theme: Theme::Dark,
+            ..Default::default()
+        };
+        lines(path, &style, "Portfolio", &series, &Config::default()).unwrap();
+        let svg = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(
+            svg.contains("2024-03-01") && svg.contains("2024-03-09"),
+            "{}",
+            svg
+        );
+        assert!(svg.contains("portfolio") && svg.contains("VTI"));
+        assert!(svg.contains("#1E1E1E"));
+
+        let short = [("portfolio".to_string(), vec![(day(1), 1.0)])];
+        assert!(lines(path, &style, "Portfolio", &short, &Config::default()).is_err());
+    }
+}
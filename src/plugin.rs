@@ -0,0 +1,128 @@
+// Subcommands the crate doesn't ship, git style: `stocks foo --bar` runs the
+// first `stocks-foo` on PATH with `--bar`, the report on stdin as the same
+// JSON `--format json` prints, and where the holdings and data live in
+// STOCKS_HOLDINGS and STOCKS_DATA_DIR.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::profile;
+
+const PREFIX: &str = "stocks-";
+
+fn path_var() -> std::ffi::OsString {
+    std::env::var_os("PATH").unwrap_or_default()
+}
+
+pub fn find(name: &str) -> Option<PathBuf> {
+    find_in(&path_var(), name)
+}
+
+fn find_in(paths: &OsStr, name: &str) -> Option<PathBuf> {
+    std::env::split_paths(paths)
+        .map(|dir| {
+            dir.join(format!(
+                "{}{}{}",
+                PREFIX,
+                name,
+                std::env::consts::EXE_SUFFIX
+            ))
+        })
+        .find(|p| is_executable(p))
+}
+
+// Every extension on PATH by name, each once even if found in several places
+pub fn list() -> Vec<String> {
+    list_in(&path_var())
+}
+
+fn list_in(paths: &OsStr) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for dir in std::env::split_paths(paths) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for e in entries.flatten() {
+            let file = e.file_name().to_string_lossy().to_string();
+            let Some(name) = file.strip_prefix(PREFIX) else {
+                continue;
+            };
+            let name = name.trim_end_matches(std::env::consts::EXE_SUFFIX);
+            if !name.is_empty() && is_executable(&e.path()) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+#[cfg(unix)]
+fn is_executable(p: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    p.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(p: &Path) -> bool {
+    p.is_file()
+}
+
+// Runs the extension to the end and returns its exit code
+pub fn run(exe: &Path, args: &[String], input: &[u8]) -> Result<i32, String> {
+    let mut child = Command::new(exe)
+        .args(args)
+        .env("STOCKS_HOLDINGS", profile::holdings_path())
+        .env("STOCKS_DATA_DIR", profile::data_dir())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", exe.display(), e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // an extension that doesn't want the report may exit without reading it
+        match stdin.write_all(input) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                return Err(format!("could not write to {}: {}", exe.display(), e))
+            }
+            _ => {}
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("could not run {}: {}", exe.display(), e))?;
+    // killed by a signal has no code, which is a failure all the same
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn finds_executables_named_after_the_subcommand() {
+        let dir = std::env::temp_dir().join(format!("stocks-plugin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("stocks-hello");
+        std::fs::write(&script, "#!/bin/sh\ncat > /dev/null\nexit 3\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // not executable, so not an extension
+        std::fs::write(dir.join("stocks-notes"), "").unwrap();
+
+        let paths = std::env::join_paths([dir.clone()]).unwrap();
+        let found = find_in(&paths, "hello");
+        let names = list_in(&paths);
+        let missing = find_in(&paths, "notes");
+        let code = run(&script, &[], b"{}");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, Some(script));
+        assert_eq!(names, ["hello"]);
+        assert_eq!(missing, None);
+        assert_eq!(code, Ok(3));
+    }
+}
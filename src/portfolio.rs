@@ -0,0 +1,1012 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::crypto::{Chain, Wallet};
+use crate::events::{self, Event};
+use crate::exchange;
+use crate::lock;
+use crate::lots;
+use crate::metals;
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::options;
+use crate::profile;
+
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    pub name: String,
+    // kept through renames once pinned with `id = "..."`, otherwise the name
+    pub id: String,
+    pub hidden: bool,
+    pub closed: Option<NaiveDate>,
+    pub stocks: Vec<Stock>,
+    // uninvested balance, `cash = 1500`
+    pub cash: Option<Money>,
+    // paid to the account rather than a position, like lending fees
+    pub income: Vec<Income>,
+}
+
+#[cfg(test)]
+impl Account {
+    // An open account of just these positions, for the tests that build one
+    pub fn test(name: &str, stocks: Vec<Stock>) -> Self {
+        Account {
+            name: name.to_string(),
+            id: name.to_string(),
+            hidden: false,
+            closed: None,
+            stocks,
+            cash: None,
+            income: vec![],
+        }
+    }
+}
+
+// Everything in a holdings file
+#[derive(Debug, Default)]
+pub struct Holdings {
+    pub accounts: Vec<Account>,
+    pub other: Vec<OtherAsset>,
+    pub liabilities: Vec<Liability>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub enum Asset {
+    // shares, funds and options, all quoted by symbol
+    #[default]
+    Security,
+    // `kind = "future"`, quoted from yahoo, e.g. "ES=F" or "ESZ23.CME"
+    Future,
+    // `kind = "crypto"`, coins quoted in dollars, e.g. BTC
+    Crypto,
+    // physical metal held by weight, e.g. `gold_oz = 12.5`, at spot
+    Metal,
+}
+
+// Where a security trades, for the providers that only cover some markets.
+// `market = "otc"` (or "pink") for OTC and pink sheet tickers, which nasdaq
+// doesn't list under any asset class.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub enum Market {
+    #[default]
+    Listed,
+    Otc,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Stock {
+    // `id = "..."` on the position, otherwise "account/key"
+    pub id: String,
+    pub symbol: String,
+    pub alias: Option<String>,
+    // positions sharing a strategy name are reported together, e.g. a stock
+    // and the call written against it
+    pub strategy: Option<String>,
+    pub amount: f64,
+    pub cost_basis: Money,
+    pub trades: Vec<Trade>,
+    pub asset: Asset,
+    pub market: Market,
+    // units of the underlying per contract: 100 for equity options, the
+    // contract size for futures, otherwise 1
+    pub multiplier: f64,
+    // initial margin per contract
+    pub margin: Option<Money>,
+    pub expiry: Option<NaiveDate>,
+    pub dividends: Vec<Dividend>,
+    // staking or interest paid out in the coin itself
+    pub rewards: Vec<Reward>,
+    // grants still to vest, not yet counted in the amount held
+    pub vesting: Vec<Vest>,
+    // where an ADR or cross-listed share trades at home
+    pub home: Option<Home>,
+    // for coins, the address whose balance is the number held
+    pub wallet: Option<Wallet>,
+}
+
+// The home market listing of an ADR, e.g. 7203.T for TM
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Home {
+    pub symbol: String,
+    // home shares each ADR stands for
+    pub ratio: f64,
+}
+
+impl Default for Stock {
+    fn default() -> Self {
+        Stock {
+            id: String::new(),
+            symbol: String::new(),
+            alias: None,
+            strategy: None,
+            amount: 0.0,
+            cost_basis: Money::default(),
+            trades: vec![],
+            asset: Asset::Security,
+            market: Market::Listed,
+            multiplier: 1.0,
+            margin: None,
+            expiry: None,
+            dividends: vec![],
+            rewards: vec![],
+            vesting: vec![],
+            home: None,
+            wallet: None,
+        }
+    }
+}
+
+// Something held outside the brokerages, like a house or a car, valued by
+// hand under `[assets.<name>]` and grown by `appreciation` percent a year
+// since `as_of`
+#[derive(Debug, Clone)]
+pub struct OtherAsset {
+    pub name: String,
+    pub value: Money,
+    pub as_of: Option<NaiveDate>,
+    pub appreciation: f64,
+}
+
+impl OtherAsset {
+    pub fn value_on(&self, date: NaiveDate) -> Money {
+        let years = match self.as_of {
+            Some(d) => (date - d).num_days() as f64 / 365.25,
+            None => 0.0,
+        };
+        self.value.clone() * (1.0 + self.appreciation / 100.0).powf(years)
+    }
+}
+
+// Money owed, under `[liabilities.<name>]`. With `rate` (percent a year) and
+// a monthly `payment`, the balance as of `as_of` is carried forward a month
+// at a time: interest is added, then the payment comes off.
+#[derive(Debug, Clone)]
+pub struct Liability {
+    pub name: String,
+    pub balance: Money,
+    pub rate: f64,
+    pub payment: Option<Money>,
+    pub as_of: Option<NaiveDate>,
+}
+
+impl Liability {
+    pub fn balance_on(&self, date: NaiveDate) -> Money {
+        let months = match self.as_of {
+            Some(d) if date > d => months_between(d, date),
+            _ => 0,
+        };
+        let payment = self.payment.as_ref().map(|p| p.amount).unwrap_or_default();
+
+        let mut balance = self.balance.amount;
+        for _ in 0..months {
+            balance = (balance * (1.0 + self.rate / 1200.0) - payment).max(0.0);
+        }
+        Money::new(balance, &self.balance.currency)
+    }
+}
+
+// whole months from `from` to `to`, counting a month once its day comes round
+fn months_between(from: NaiveDate, to: NaiveDate) -> u32 {
+    let months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    let months = if to.day() < from.day() {
+        months - 1
+    } else {
+        months
+    };
+    months.max(0) as u32
+}
+
+// a recorded purchase (positive num) or sale (negative num), with any
+// commission paid on it
+#[derive(Debug, Clone, Deserialize)]
+pub struct Trade {
+    pub date: NaiveDate,
+    pub num: f64,
+    pub price: Money,
+    pub fees: Money,
+}
+
+// `income = [{ date = ..., amount = ..., kind = "lending" }]` on an account,
+// for securities lending, sweep interest and the like; "interest" if no kind
+#[derive(Debug, Clone, Deserialize)]
+pub struct Income {
+    pub date: NaiveDate,
+    pub amount: Money,
+    pub kind: String,
+}
+
+// cash paid out on a position, `dividends = [{ date = ..., amount = ... }]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dividend {
+    pub date: NaiveDate,
+    pub amount: Money,
+    // how much of it is qualified, `qualified = ...` as the broker reports it
+    pub qualified: Option<Money>,
+    // tax a foreign payer kept back, `withheld = ...`, out of the amount
+    // before withholding
+    pub withheld: Option<Money>,
+}
+
+// shares of a grant that vest on a day, `vesting = [{ date = ..., num = ... }]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Vest {
+    pub date: NaiveDate,
+    pub num: f64,
+}
+
+// units of a coin paid for staking or lending it,
+// `rewards = [{ date = ..., amount = ... }]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Reward {
+    pub date: NaiveDate,
+    pub amount: f64,
+}
+
+pub fn holdings_path() -> String {
+    profile::holdings_path()
+}
+
+pub fn load_accounts(path: &str, all: bool) -> Result<Vec<Account>, String> {
+    Ok(load_holdings(path, all)?.accounts)
+}
+
+// The whole holdings file, leaving out closed accounts unless `all`
+pub fn load_holdings(path: &str, all: bool) -> Result<Holdings, String> {
+    let mut holdings = parse_holdings(path)?;
+    holdings.accounts.retain(|a| all || a.closed.is_none());
+    Ok(holdings)
+}
+
+pub fn shown(accounts: &[Account], all: bool) -> Vec<&Account> {
+    accounts.iter().filter(|a| all || !a.hidden).collect()
+}
+
+// "-" reads the holdings from stdin instead of a file
+fn read_holdings(path: &str) -> Result<String, String> {
+    let mut buf = String::new();
+
+    if path == "-" {
+        io::stdin()
+            .read_to_string(&mut buf)
+            .map_err(|e| format!("could not read stdin: {}", e))?;
+        return Ok(buf);
+    }
+
+    let mut f = File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => {
+            format!("{} does not exist, run `stocks init` to set it up", path)
+        }
+        _ => format!("could not open {}: {}", path, e),
+    })?;
+    f.read_to_string(&mut buf)
+        .map_err(|e| format!("could not read {}: {}", path, e))?;
+    Ok(buf)
+}
+
+// The same layout as the TOML file works as a JSON object, which is easier
+// to produce from scripts
+fn parse_table(buf: &str) -> Result<Table, String> {
+    if buf.trim_start().starts_with('{') {
+        serde_json::from_str::<Table>(buf).map_err(|e| e.to_string())
+    } else {
+        buf.parse::<Table>().map_err(|e| e.to_string())
+    }
+}
+
+pub fn parse_accounts(path: &str) -> Result<Vec<Account>, String> {
+    Ok(parse_holdings(path)?.accounts)
+}
+
+pub fn parse_holdings(path: &str) -> Result<Holdings, String> {
+    let buf = read_holdings(path)?;
+    let name = if path == "-" { "stdin" } else { path };
+    parse_holdings_str(&buf, name)
+}
+
+// `name` is what errors call the source, usually its path
+pub fn parse_holdings_str(buf: &str, name: &str) -> Result<Holdings, String> {
+    let t = parse_table(buf).map_err(|e| format!("could not parse {}: {}", name, e))?;
+
+    let mut holdings = Holdings::default();
+
+    for (name, val) in t.iter() {
+        // the tables that aren't accounts
+        match name.as_str() {
+            ASSETS => {
+                holdings.other = parse_other(val)?;
+                continue;
+            }
+            LIABILITIES => {
+                holdings.liabilities = parse_liabilities(val)?;
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut stocks = vec![];
+        let mut hidden = false;
+        let mut closed = None;
+        let mut cash = None;
+        let mut income = vec![];
+        let mut id = name.clone();
+
+        let table = val
+            .as_table()
+            .ok_or_else(|| format!("{} should be a table of positions", name))?;
+
+        for (stock_name, info) in table.iter() {
+            // plain values are account settings, tables are positions
+            if !info.is_table() {
+                match stock_name.as_str() {
+                    "hidden" => {
+                        hidden = info
+                            .as_bool()
+                            .ok_or_else(|| format!("{}.hidden should be true or false", name))?
+                    }
+                    "id" => {
+                        id = info
+                            .as_str()
+                            .ok_or_else(|| format!("{}.id should be a string", name))?
+                            .to_string()
+                    }
+                    "closed" => closed = Some(parse_date(name, info)?),
+                    "cash" => {
+                        let amount = number(Some(info))
+                            .ok_or_else(|| format!("{}.cash should be a number", name))?;
+                        cash = Some(Money::new(amount, DEFAULT_CURRENCY));
+                    }
+                    "income" => {
+                        income = info
+                            .as_array()
+                            .ok_or_else(|| format!("{}.income should be a list", name))?
+                            .iter()
+                            .map(|i| parse_income(name, i))
+                            .collect::<Result<Vec<_>, _>>()?;
+                    }
+                    // no cost basis given, so the gain is unknown
+                    key => match (metals::parse_holding(key), number(Some(info))) {
+                        (Some((metal, per_unit)), Some(weight)) => stocks.push(Stock {
+                            id: format!("{}/{}", name, key),
+                            symbol: metal.symbol().to_string(),
+                            alias: Some(metal.name().to_string()),
+                            amount: weight * per_unit,
+                            cost_basis: Money::usd(f64::NAN),
+                            asset: Asset::Metal,
+                            ..Default::default()
+                        }),
+                        (Some(_), None) => {
+                            return Err(format!("{}.{} should be a weight", name, key))
+                        }
+                        // likely a typo, which would otherwise go unnoticed
+                        (None, _) => {
+                            return Err(format!(
+                                "{} has unknown setting {}, expected hidden, id, closed, \
+                                 cash, income or a metal like gold_oz",
+                                name, key
+                            ))
+                        }
+                    },
+                }
+                continue;
+            }
+
+            // a listing abroad is bought in its own currency unless told otherwise
+            let currency = info
+                .get("currency")
+                .and_then(|c| c.as_str())
+                .or(exchange::currency(stock_name))
+                .unwrap_or(DEFAULT_CURRENCY);
+            let stock_id = match info.get("id") {
+                Some(i) => i
+                    .as_str()
+                    .ok_or_else(|| format!("{}.{}.id should be a string", name, stock_name))?
+                    .to_string(),
+                None => format!("{}/{}", name, stock_name),
+            };
+
+            let trades = match info.get("trades").and_then(|t| t.as_array()) {
+                Some(t) => t
+                    .iter()
+                    .map(|t| parse_trade(stock_name, currency, t))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+
+            // the chain defaults to the coin, so BTC needs only an address
+            let wallet = match info.get("address").and_then(|a| a.as_str()) {
+                Some(address) => {
+                    let chain = match info.get("chain").and_then(|c| c.as_str()) {
+                        Some(c) => c.to_string(),
+                        None => stock_name.to_lowercase(),
+                    };
+                    Some(Wallet {
+                        chain: Chain::parse(&chain).ok_or_else(|| {
+                            format!(
+                                "{}.{} has unknown chain {}, expected bitcoin or ethereum",
+                                name, stock_name, chain
+                            )
+                        })?,
+                        address: address.to_string(),
+                    })
+                }
+                None => None,
+            };
+
+            // with only trades listed, the shares and average cost are
+            // whatever lots are still open; a wallet's balance comes later
+            let open = (!trades.is_empty()).then(|| lots::match_lots(&trades).0);
+            let amount = match (number(info.get("num")), &open) {
+                (Some(n), _) => n,
+                (None, Some(open)) => open.iter().map(|l| l.num).sum(),
+                (None, None) if wallet.is_some() => 0.0,
+                (None, None) => return Err(format!("{}.{} is missing num", name, stock_name)),
+            };
+            let cost_basis = match (number(info.get("price")), &open) {
+                (Some(p), _) => p,
+                (None, Some(open)) => lots::average_cost(open),
+                // coins sent in from elsewhere may have no known cost
+                (None, None) if wallet.is_some() => f64::NAN,
+                (None, None) => return Err(format!("{}.{} is missing price", name, stock_name)),
+            };
+
+            let dividends = match info.get("dividends").and_then(|d| d.as_array()) {
+                Some(d) => d
+                    .iter()
+                    .map(|d| parse_dividend(stock_name, currency, d))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+
+            let rewards = match info.get("rewards").and_then(|r| r.as_array()) {
+                Some(r) => r
+                    .iter()
+                    .map(|r| parse_reward(stock_name, r))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+
+            let vesting = match info.get("vesting").and_then(|v| v.as_array()) {
+                Some(v) => v
+                    .iter()
+                    .map(|v| parse_vest(stock_name, v))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+
+            let alias = info.get("alias").and_then(|a| a.as_str()).map(String::from);
+            let strategy = info
+                .get("strategy")
+                .and_then(|s| s.as_str())
+                .map(String::from);
+            let home = info.get("home").and_then(|h| h.as_str()).map(|h| Home {
+                symbol: h.to_string(),
+                ratio: number(info.get("ratio")).unwrap_or(1.0),
+            });
+
+            let owner = format!("{}.{}", name, stock_name);
+            let asset = match info.get("kind").and_then(|k| k.as_str()) {
+                None if wallet.is_some() => Asset::Crypto,
+                None | Some("stock") | Some("etf") | Some("fund") | Some("option") => {
+                    Asset::Security
+                }
+                Some("future") => Asset::Future,
+                Some("crypto") => Asset::Crypto,
+                Some(k) => return Err(format!("{} has unknown kind {}", owner, k)),
+            };
+            let market = match info.get("market").and_then(|m| m.as_str()) {
+                None => Market::Listed,
+                Some("otc") | Some("pink") => Market::Otc,
+                Some(m) => return Err(format!("{} has unknown market {}, expected otc", owner, m)),
+            };
+
+            let multiplier = match (number(info.get("multiplier")), asset) {
+                (Some(m), _) => m,
+                (None, Asset::Future) => {
+                    return Err(format!("{} is a future and needs a multiplier", owner))
+                }
+                (None, Asset::Security) if options::parse(stock_name).is_some() => {
+                    options::MULTIPLIER
+                }
+                (None, _) => 1.0,
+            };
+
+            let margin = number(info.get("margin")).map(|m| Money::new(m, currency));
+            let expiry = match info.get("expiry") {
+                Some(d) => Some(parse_date(&owner, d)?),
+                None => None,
+            };
+
+            // `[acct.gold_g]` with num and price per gram, held as troy ounces
+            if let Some((metal, per_unit)) = metals::parse_holding(stock_name) {
+                stocks.push(Stock {
+                    id: stock_id,
+                    symbol: metal.symbol().to_string(),
+                    alias: alias.or_else(|| Some(metal.name().to_string())),
+                    amount: amount * per_unit,
+                    cost_basis: Money::new(cost_basis / per_unit, currency),
+                    asset: Asset::Metal,
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            stocks.push(Stock {
+                id: stock_id,
+                symbol: stock_name.clone(),
+                alias,
+                strategy,
+                amount,
+                cost_basis: Money::new(cost_basis, currency),
+                trades,
+                asset,
+                market,
+                multiplier,
+                margin,
+                expiry,
+                dividends,
+                rewards,
+                vesting,
+                home,
+                wallet,
+            })
+        }
+
+        holdings.accounts.push(Account {
+            id,
+            stocks,
+            hidden,
+            closed,
+            cash,
+            income,
+            name: name.clone(),
+        });
+    }
+
+    check_ids(&holdings.accounts)?;
+    Ok(holdings)
+}
+
+// an id is only any use to other tools if it names one thing
+fn check_ids(accounts: &[Account]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    let ids = accounts
+        .iter()
+        .map(|a| &a.id)
+        .chain(accounts.iter().flat_map(|a| a.stocks.iter().map(|s| &s.id)));
+    for id in ids {
+        if !seen.insert(id) {
+            return Err(format!("id {} is used more than once", id));
+        }
+    }
+    Ok(())
+}
+
+const ASSETS: &str = "assets";
+const LIABILITIES: &str = "liabilities";
+
+fn parse_liabilities(val: &Value) -> Result<Vec<Liability>, String> {
+    let table = val
+        .as_table()
+        .ok_or_else(|| format!("{} should be a table of debts", LIABILITIES))?;
+
+    table
+        .iter()
+        .map(|(name, info)| {
+            let owner = format!("{}.{}", LIABILITIES, name);
+            let balance = number(info.get("balance"))
+                .ok_or_else(|| format!("{} is missing balance", owner))?;
+            let currency = info
+                .get("currency")
+                .and_then(|c| c.as_str())
+                .unwrap_or(DEFAULT_CURRENCY);
+
+            let as_of = match info.get("as_of") {
+                Some(d) => Some(parse_date(&owner, d)?),
+                None => None,
+            };
+
+            Ok(Liability {
+                name: name.clone(),
+                balance: Money::new(balance, currency),
+                rate: number(info.get("rate")).unwrap_or_default(),
+                payment: number(info.get("payment")).map(|p| Money::new(p, currency)),
+                as_of,
+            })
+        })
+        .collect()
+}
+
+fn parse_other(val: &Value) -> Result<Vec<OtherAsset>, String> {
+    let table = val
+        .as_table()
+        .ok_or_else(|| format!("{} should be a table of assets", ASSETS))?;
+
+    table
+        .iter()
+        .map(|(name, info)| {
+            let owner = format!("{}.{}", ASSETS, name);
+            let value =
+                number(info.get("value")).ok_or_else(|| format!("{} is missing value", owner))?;
+            let currency = info
+                .get("currency")
+                .and_then(|c| c.as_str())
+                .unwrap_or(DEFAULT_CURRENCY);
+            let as_of = match info.get("as_of") {
+                Some(d) => Some(parse_date(&owner, d)?),
+                None => None,
+            };
+
+            Ok(OtherAsset {
+                name: name.clone(),
+                value: Money::new(value, currency),
+                as_of,
+                appreciation: number(info.get("appreciation")).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+pub fn parse_trade(symbol: &str, currency: &str, t: &Value) -> Result<Trade, String> {
+    let date = match t.get("date") {
+        Some(d) => parse_date(symbol, d)?,
+        None => return Err(format!("trade for {} is missing a date", symbol)),
+    };
+
+    Ok(Trade {
+        date,
+        num: number(t.get("num")).ok_or_else(|| format!("trade for {} is missing num", symbol))?,
+        price: Money::new(
+            number(t.get("price"))
+                .ok_or_else(|| format!("trade for {} is missing price", symbol))?,
+            currency,
+        ),
+        fees: Money::new(number(t.get("fees")).unwrap_or_default(), currency),
+    })
+}
+
+fn parse_dividend(symbol: &str, currency: &str, d: &Value) -> Result<Dividend, String> {
+    let date = match d.get("date") {
+        Some(date) => parse_date(symbol, date)?,
+        None => return Err(format!("dividend for {} is missing a date", symbol)),
+    };
+    let amount = number(d.get("amount"))
+        .ok_or_else(|| format!("dividend for {} is missing amount", symbol))?;
+    let part = |key: &str, what: &str| match d.get(key) {
+        Some(v) => number(Some(v))
+            .map(Some)
+            .ok_or_else(|| format!("{} of a dividend for {} should be a number", what, symbol)),
+        None => Ok(None),
+    };
+    let qualified = part("qualified", "qualified part")?;
+    let withheld = part("withheld", "tax withheld")?;
+
+    Ok(Dividend {
+        date,
+        amount: Money::new(amount, currency),
+        qualified: qualified.map(|q| Money::new(q.min(amount), currency)),
+        withheld: withheld.map(|w| Money::new(w, currency)),
+    })
+}
+
+fn parse_reward(symbol: &str, r: &Value) -> Result<Reward, String> {
+    let date = match r.get("date") {
+        Some(date) => parse_date(symbol, date)?,
+        None => return Err(format!("reward for {} is missing a date", symbol)),
+    };
+    let amount = number(r.get("amount"))
+        .ok_or_else(|| format!("reward for {} is missing amount", symbol))?;
+
+    Ok(Reward { date, amount })
+}
+
+fn parse_vest(symbol: &str, v: &Value) -> Result<Vest, String> {
+    let date = match v.get("date") {
+        Some(date) => parse_date(symbol, date)?,
+        None => return Err(format!("vesting for {} is missing a date", symbol)),
+    };
+    let num =
+        number(v.get("num")).ok_or_else(|| format!("vesting for {} is missing num", symbol))?;
+
+    Ok(Vest { date, num })
+}
+
+fn parse_income(account: &str, i: &Value) -> Result<Income, String> {
+    let date = match i.get("date") {
+        Some(d) => parse_date(account, d)?,
+        None => return Err(format!("income for {} is missing a date", account)),
+    };
+    let amount = number(i.get("amount"))
+        .ok_or_else(|| format!("income for {} is missing amount", account))?;
+    let currency = i
+        .get("currency")
+        .and_then(|c| c.as_str())
+        .unwrap_or(DEFAULT_CURRENCY);
+
+    Ok(Income {
+        date,
+        amount: Money::new(amount, currency),
+        kind: i
+            .get("kind")
+            .and_then(|k| k.as_str())
+            .unwrap_or("interest")
+            .to_string(),
+    })
+}
+
+// toml keeps integers and floats apart, but `num = 10` should just work
+pub fn number(v: Option<&Value>) -> Option<f64> {
+    match v? {
+        Value::Float(f) => Some(*f),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+// dates can be written as toml dates or as strings
+fn parse_date(owner: &str, v: &Value) -> Result<NaiveDate, String> {
+    let date = match v {
+        Value::Datetime(d) => d.to_string(),
+        Value::String(s) => s.clone(),
+        _ => return Err(format!("expected a date for {}", owner)),
+    };
+
+    NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date {} for {}", date, owner))
+}
+
+// Apply `f` to the holdings file, keeping comments and formatting intact. The
+// file stays locked from reading it to replacing it, so two commands editing
+// at once can't lose each other's changes.
+pub fn edit_holdings<F>(path: &str, f: F) -> Result<(), String>
+where
+    F: FnOnce(&mut toml_edit::Document) -> Result<(), String>,
+{
+    let _lock = lock::lock(path)?;
+    let buf = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut doc = buf
+        .parse::<toml_edit::Document>()
+        .map_err(|e| format!("could not parse {}: {}", path, e))?;
+
+    f(&mut doc)?;
+
+    lock::write_atomic(path, doc.to_string().as_bytes())
+        .map_err(|e| format!("could not write {}: {}", path, e))
+}
+
+// Adds `symbol = { num, price }` to an account, creating the account (and
+// the file) if they're new. Buying more of a position already held adds to
+// its shares at the averaged price.
+pub fn add_position(
+    path: &str,
+    account: &str,
+    symbol: &str,
+    num: f64,
+    price: f64,
+) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", path, e))?;
+        }
+        fs::write(path, "").map_err(|e| format!("could not create {}: {}", path, e))?;
+    }
+
+    edit_holdings(path, |doc| {
+        if doc.get(account).is_none() {
+            doc.insert(account, toml_edit::Item::Table(toml_edit::Table::new()));
+        }
+        let acct = doc
+            .get_mut(account)
+            .and_then(|a| a.as_table_like_mut())
+            .ok_or_else(|| format!("{} is not an account", account))?;
+
+        let position = match acct.get_mut(symbol).and_then(|p| p.as_table_like_mut()) {
+            Some(p) => p,
+            None => {
+                let mut position = toml_edit::InlineTable::new();
+                position.insert("num", num.into());
+                position.insert("price", price.into());
+                acct.insert(symbol, toml_edit::value(position));
+                return Ok(());
+            }
+        };
+
+        let held = position.get("num").and_then(edit_number);
+        let paid = position.get("price").and_then(edit_number);
+        let (held, paid) = match (held, paid) {
+            (Some(h), Some(p)) => (h, p),
+            // positions worked out from their trades take another trade instead
+            _ => {
+                return Err(format!(
+                    "{}.{} has no num and price to add to, record a trade instead",
+                    account, symbol
+                ))
+            }
+        };
+
+        let total = held + num;
+        let avg = if total == 0.0 {
+            0.0
+        } else {
+            (held * paid + num * price) / total
+        };
+        position.insert("num", toml_edit::value(total));
+        position.insert("price", toml_edit::value(avg));
+
+        Ok(())
+    })?;
+
+    events::record(&[Event {
+        account: Some(account.to_string()),
+        symbol: Some(symbol.to_string()),
+        num: Some(num),
+        price: Some(price),
+        ..Event::new("add")
+    }]);
+    Ok(())
+}
+
+// Takes a position out of an account, keeping the rest of the file as it was
+pub fn remove_position(path: &str, account: &str, symbol: &str) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Err(format!("{} does not exist", path));
+    }
+
+    edit_holdings(path, |doc| {
+        let acct = doc
+            .get_mut(account)
+            .and_then(|a| a.as_table_like_mut())
+            .ok_or_else(|| format!("no account named {}", account))?;
+
+        match acct.remove(symbol) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} holds no {}", account, symbol)),
+        }
+    })?;
+
+    events::record(&[Event {
+        account: Some(account.to_string()),
+        symbol: Some(symbol.to_string()),
+        ..Event::new("remove")
+    }]);
+    Ok(())
+}
+
+fn edit_number(item: &toml_edit::Item) -> Option<f64> {
+    item.as_float()
+        .or_else(|| item.as_integer().map(|i| i as f64))
+}
+
+pub fn close_account(path: &str, name: &str, date: NaiveDate) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Err(format!("{} does not exist", path));
+    }
+
+    edit_holdings(path, |doc| {
+        let acct = doc
+            .get_mut(name)
+            .and_then(|a| a.as_table_like_mut())
+            .ok_or_else(|| format!("no account named {}", name))?;
+
+        if acct.contains_key("closed") {
+            return Err(format!("{} is already closed", name));
+        }
+
+        let date = date
+            .format("%Y-%m-%d")
+            .to_string()
+            .parse::<toml_edit::Datetime>()
+            .unwrap();
+        acct.insert("closed", toml_edit::value(date));
+
+        Ok(())
+    })?;
+
+    events::record(&[Event {
+        account: Some(name.to_string()),
+        detail: Some(format!("on {}", date.format("%Y-%m-%d"))),
+        ..Event::new("close")
+    }]);
+    Ok(())
+}
+
+// Writes a generated `id` into every account and position that has none, so
+// it stays the same when they're renamed or moved, and returns how many.
+// Metals held as a plain `gold_oz = 12.5` have nowhere to keep one.
+pub fn pin_ids(path: &str) -> Result<usize, String> {
+    if !Path::new(path).exists() {
+        return Err(format!("{} does not exist", path));
+    }
+
+    let mut pinned = 0;
+    edit_holdings(path, |doc| {
+        let mut taken = HashSet::new();
+        let mut tables = vec![];
+        for (name, item) in doc.iter_mut() {
+            if name == ASSETS || name == LIABILITIES {
+                continue;
+            }
+            if let Some(acct) = item.as_table_like_mut() {
+                tables.push(acct);
+            }
+        }
+
+        for acct in tables.iter() {
+            if let Some(id) = acct.get("id").and_then(|i| i.as_str()) {
+                taken.insert(id.to_string());
+            }
+            for (_, p) in acct.iter() {
+                if let Some(id) = p.as_table_like().and_then(|p| p.get("id")) {
+                    taken.insert(id.as_str().unwrap_or_default().to_string());
+                }
+            }
+        }
+
+        for acct in tables.iter_mut() {
+            if !acct.contains_key("id") {
+                acct.insert("id", toml_edit::value(new_id(&mut taken)));
+                pinned += 1;
+            }
+            for (_, p) in acct.iter_mut() {
+                let Some(t) = p.as_table_like_mut() else {
+                    continue;
+                };
+                if t.contains_key("id") {
+                    continue;
+                }
+                t.insert("id", toml_edit::value(new_id(&mut taken)));
+                pinned += 1;
+                // keep `{ num = 10, price = 150, id = "..." }` evenly spaced
+                if let Some(inline) = p.as_inline_table_mut() {
+                    inline.fmt();
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if pinned > 0 {
+        events::record(&[Event {
+            detail: Some(format!("{} ids", pinned)),
+            ..Event::new("pin ids")
+        }]);
+    }
+    Ok(pinned)
+}
+
+// random enough to never clash within one file, checked against it anyway
+fn new_id(taken: &mut HashSet<String>) -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    loop {
+        let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+        h.write_usize(taken.len());
+        let id = format!("{:012x}", h.finish() & 0xffff_ffff_ffff);
+        if taken.insert(id.clone()) {
+            return id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_settings_are_checked() {
+        let holdings = parse_holdings_str("[a]\nhidden = true\ngold_oz = 2", "h").unwrap();
+        assert!(holdings.accounts[0].hidden);
+        assert_eq!(holdings.accounts[0].stocks[0].amount, 2.0);
+
+        let err = parse_holdings_str("[a]\nhiden = true", "h").unwrap_err();
+        assert!(err.starts_with("a has unknown setting hiden"), "{}", err);
+        let err = parse_holdings_str("[a]\nhidden = \"yes\"", "h").unwrap_err();
+        assert_eq!(err, "a.hidden should be true or false");
+        let err = parse_holdings_str("[a]\ngold_oz = \"2\"", "h").unwrap_err();
+        assert_eq!(err, "a.gold_oz should be a weight");
+    }
+}
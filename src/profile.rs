@@ -0,0 +1,59 @@
+// Everything stocks keeps lives under a data and a config directory. A named
+// profile (--profile club) gets its own pair below those, so it has separate
+// holdings, config, provider stats and history from the default one.
+
+use std::env;
+use std::sync::OnceLock;
+
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn parse_name(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(name.to_string())
+    } else {
+        Err(format!(
+            "invalid profile name {}, use letters, digits, - and _",
+            name
+        ))
+    }
+}
+
+// set once at startup, later calls are ignored
+pub fn set(name: Option<String>) {
+    let _ = PROFILE.set(name);
+}
+
+pub fn name() -> Option<&'static str> {
+    PROFILE.get().and_then(|p| p.as_deref())
+}
+
+fn home() -> String {
+    env::var("HOME").unwrap()
+}
+
+pub fn data_dir() -> String {
+    match name() {
+        Some(p) => format!("{}/.local/share/stocks/profiles/{}", home(), p),
+        None => format!("{}/.local/share/stocks", home()),
+    }
+}
+
+pub fn config_dir() -> String {
+    match name() {
+        Some(p) => format!("{}/.config/stocks/profiles/{}", home(), p),
+        None => format!("{}/.config/stocks", home()),
+    }
+}
+
+// the default holdings file predates the data directory and sits beside it
+pub fn holdings_path() -> String {
+    match name() {
+        Some(_) => format!("{}/stocks.toml", data_dir()),
+        None => format!("{}/.local/share/stocks.toml", home()),
+    }
+}
@@ -0,0 +1,665 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures::future::{select_all, BoxFuture, FutureExt};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, Order};
+use crate::crypto::Coins;
+use crate::exchange;
+use crate::finnhub::Finnhub;
+use crate::fx;
+use crate::lock;
+use crate::metals::Spot;
+use crate::money::Money;
+use crate::nasdaq;
+use crate::options;
+use crate::portfolio::{Account, Asset, Market};
+use crate::profile;
+use crate::snapshot;
+use crate::yahoo::get_yahoo_value;
+use crate::{auth, debug};
+
+#[derive(Debug, Clone)]
+pub enum QuoteError {
+    Network(String),
+    NotFound,
+    // the provider answered but not in any shape we know how to read
+    Schema(String),
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuoteError::Network(e) => write!(f, "request failed: {}", e),
+            QuoteError::NotFound => write!(f, "symbol not found"),
+            QuoteError::Schema(e) => write!(f, "provider schema changed: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Quote {
+    pub price: Money,
+    // net change since the previous close
+    pub change: Money,
+    // today's opening price, not every provider has it
+    pub open: Option<Money>,
+    // when the price was last traded, if the provider says
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub status: Status,
+}
+
+// Whether the price is live. A halted symbol's price is its last trade before
+// the halt; a delisted one is no longer known to any provider and its price is
+// the last one recorded in a snapshot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Trading,
+    Halted,
+    Delisted,
+}
+
+pub type QuoteResult = Result<Quote, QuoteError>;
+
+pub trait QuoteProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult>;
+}
+
+pub struct Nasdaq;
+
+impl QuoteProvider for Nasdaq {
+    fn name(&self) -> &'static str {
+        "nasdaq"
+    }
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult> {
+        nasdaq::get_quote(symbol).boxed()
+    }
+}
+
+pub struct Yahoo;
+
+impl QuoteProvider for Yahoo {
+    fn name(&self) -> &'static str {
+        "yahoo"
+    }
+
+    fn quote<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, QuoteResult> {
+        get_yahoo_value(symbol).boxed()
+    }
+}
+
+pub type Providers = Arc<Vec<Box<dyn QuoteProvider>>>;
+
+pub const NAMES: [&str; 3] = ["nasdaq", "yahoo", "finnhub"];
+
+pub fn check_names(names: &[String]) -> Result<(), String> {
+    match names.iter().find(|n| !NAMES.contains(&n.as_str())) {
+        Some(n) => Err(format!(
+            "unknown quote provider {}, expected one of {}",
+            n,
+            NAMES.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+// Keys come from `api_keys` in config (which may be "${VAR}"), then the
+// provider's own environment variable, then the keyring
+fn key(conf: &Config, provider: &str, var: &str) -> Option<String> {
+    conf.api_keys
+        .get(provider)
+        .cloned()
+        .or_else(|| std::env::var(var).ok().filter(|k| !k.is_empty()))
+        .or_else(|| auth::api_key(conf, provider))
+}
+
+pub fn from_names(names: &[String], conf: &Config) -> Result<Providers, String> {
+    check_names(names)?;
+    let mut providers: Vec<Box<dyn QuoteProvider>> = vec![];
+
+    for name in names {
+        match name.as_str() {
+            "nasdaq" => providers.push(Box::new(Nasdaq)),
+            "yahoo" => providers.push(Box::new(Yahoo)),
+            _ => providers.push(Box::new(Finnhub {
+                key: key(conf, "finnhub", "FINNHUB_API_KEY"),
+            })),
+        }
+    }
+
+    if providers.is_empty() {
+        providers.push(Box::new(Nasdaq));
+    }
+
+    Ok(Arc::new(providers))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Latency {
+    pub ok: u64,
+    pub failed: u64,
+    // dropped from a race that another provider won first
+    #[serde(default)]
+    pub lost: u64,
+    pub total_ms: u64,
+}
+
+impl Latency {
+    // a lost race counts as long as the winner took, which is the least it
+    // would have, so a provider isn't only timed on the races it wins
+    pub fn mean_ms(&self) -> Option<u64> {
+        self.total_ms.checked_div(self.ok + self.lost)
+    }
+}
+
+// Per-provider response times, kept across runs so the fastest source can be
+// tried first when `order = "latency"` is set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Stats(pub BTreeMap<String, Latency>);
+
+fn stats_path() -> String {
+    format!("{}/providers.json", profile::data_dir())
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        fs::read_to_string(stats_path())
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = stats_path();
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        lock::write_atomic(&path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    fn record(&mut self, provider: &str, ms: u64, ok: bool) {
+        let l = self.0.entry(provider.to_string()).or_default();
+        if ok {
+            l.ok += 1;
+            l.total_ms += ms;
+        } else {
+            l.failed += 1;
+        }
+    }
+
+    fn record_lost(&mut self, provider: &str, ms: u64) {
+        let l = self.0.entry(provider.to_string()).or_default();
+        l.lost += 1;
+        l.total_ms += ms;
+    }
+
+    // provider indices, fastest mean latency first, unmeasured ones last
+    fn order(&self, providers: &[Box<dyn QuoteProvider>]) -> Vec<usize> {
+        let mut idx = (0..providers.len()).collect::<Vec<_>>();
+        idx.sort_by_key(|i| {
+            self.0
+                .get(providers[*i].name())
+                .and_then(|l| l.mean_ms())
+                .unwrap_or(u64::MAX)
+        });
+        idx
+    }
+}
+
+// Quotes from recent runs, reused for `quote_ttl` seconds so running the
+// command a few times in a row doesn't ask the providers again each time.
+// Each is kept under the providers asked as well as the symbol, so another
+// --provider doesn't get what the usual ones said.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuoteCache(pub BTreeMap<String, Cached>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cached {
+    pub quote: Quote,
+    pub fetched: DateTime<Utc>,
+}
+
+fn quote_cache_path() -> String {
+    format!("{}/quotes.json", profile::data_dir())
+}
+
+// e.g. "finnhub,yahoo/AAPL"
+fn cache_key(providers: &[Box<dyn QuoteProvider>], symbol: &str) -> String {
+    let names = providers.iter().map(|p| p.name()).collect::<Vec<_>>();
+    format!("{}/{}", names.join(","), symbol)
+}
+
+impl QuoteCache {
+    pub fn load() -> Self {
+        fs::read_to_string(quote_cache_path())
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = quote_cache_path();
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        lock::write_atomic(&path, serde_json::to_string(self)?.as_bytes())
+    }
+
+    pub fn fresh(
+        &self,
+        providers: &[Box<dyn QuoteProvider>],
+        symbol: &str,
+        now: DateTime<Utc>,
+        ttl: chrono::Duration,
+    ) -> Option<Quote> {
+        self.0
+            .get(&cache_key(providers, symbol))
+            .filter(|c| now - c.fetched < ttl)
+            .map(|c| c.quote.clone())
+    }
+
+    // only what is still fresh is kept, so the file stays small
+    fn store(
+        &mut self,
+        providers: &[Box<dyn QuoteProvider>],
+        quotes: &HashMap<String, Quote>,
+        now: DateTime<Utc>,
+        ttl: chrono::Duration,
+    ) {
+        self.0.retain(|_, c| now - c.fetched < ttl);
+        for (symbol, quote) in quotes.iter() {
+            self.0.insert(
+                cache_key(providers, symbol),
+                Cached {
+                    quote: quote.clone(),
+                    fetched: now,
+                },
+            );
+        }
+    }
+}
+
+// What this run's lookups came to, for the footer under the table
+#[derive(Debug, Default, Clone)]
+pub struct Fetched {
+    // quotes answered by each provider
+    pub answered: BTreeMap<String, usize>,
+    pub failed: Vec<String>,
+    pub elapsed: Duration,
+}
+
+static FETCHED: Mutex<Fetched> = Mutex::new(Fetched {
+    answered: BTreeMap::new(),
+    failed: vec![],
+    elapsed: Duration::ZERO,
+});
+
+pub fn fetched() -> Fetched {
+    FETCHED.lock().unwrap().clone()
+}
+
+async fn timed(p: &dyn QuoteProvider, symbol: &str, stats: &Mutex<Stats>) -> QuoteResult {
+    let start = Instant::now();
+    let res = p.quote(symbol).await;
+    let ms = start.elapsed().as_millis() as u64;
+
+    match &res {
+        Ok(_) => {
+            *FETCHED
+                .lock()
+                .unwrap()
+                .answered
+                .entry(p.name().to_string())
+                .or_default() += 1
+        }
+        Err(e) => debug!("{} {}: {}", p.name(), symbol, e),
+    }
+    stats.lock().unwrap().record(p.name(), ms, res.is_ok());
+    res
+}
+
+// With several providers a schema change is the most useful thing to report,
+// since "not found" from a fallback usually just means it lacks the symbol.
+fn worst(a: QuoteError, b: QuoteError) -> QuoteError {
+    match (&a, &b) {
+        (QuoteError::Schema(_), _) => a,
+        (_, QuoteError::Schema(_)) => b,
+        (QuoteError::Network(_), _) => a,
+        _ => b,
+    }
+}
+
+pub async fn fetch(
+    providers: &[Box<dyn QuoteProvider>],
+    symbol: &str,
+    race: bool,
+    by_latency: bool,
+    stats: &Mutex<Stats>,
+) -> QuoteResult {
+    let mut err = QuoteError::NotFound;
+
+    if race && providers.len() > 1 {
+        let start = Instant::now();
+        let mut pending = providers
+            .iter()
+            .map(|p| timed(p.as_ref(), symbol, stats).boxed())
+            .collect::<Vec<_>>();
+        let mut racing = providers.iter().map(|p| p.name()).collect::<Vec<_>>();
+
+        // first valid answer wins, the slower requests are dropped
+        while !pending.is_empty() {
+            let (res, i, rest) = select_all(pending).await;
+            racing.swap_remove(i);
+            match res {
+                Ok(x) => {
+                    let ms = start.elapsed().as_millis() as u64;
+                    let mut stats = stats.lock().unwrap();
+                    for name in racing {
+                        stats.record_lost(name, ms);
+                    }
+                    return Ok(x);
+                }
+                Err(e) => err = worst(err, e),
+            }
+            pending = rest;
+        }
+
+        return Err(err);
+    }
+
+    let order = if by_latency {
+        stats.lock().unwrap().order(providers)
+    } else {
+        (0..providers.len()).collect()
+    };
+
+    for i in order {
+        match timed(providers[i].as_ref(), symbol, stats).await {
+            Ok(x) => return Ok(x),
+            Err(e) => err = worst(err, e),
+        }
+    }
+
+    Err(err)
+}
+
+// The configured providers that quote OTC tickers, which is all but nasdaq,
+// or yahoo when nasdaq is the only one
+fn otc_providers(names: &[String]) -> Vec<String> {
+    let covering = names
+        .iter()
+        .filter(|n| n.as_str() != "nasdaq")
+        .cloned()
+        .collect::<Vec<_>>();
+    if covering.is_empty() {
+        vec!["yahoo".to_string()]
+    } else {
+        covering
+    }
+}
+
+pub async fn quote_accounts(
+    accounts: &[Account],
+    conf: &Config,
+) -> Result<(HashMap<String, Quote>, Vec<(String, QuoteError)>), String> {
+    let stocks = || accounts.iter().flat_map(|a| a.stocks.iter());
+
+    // options also need their underlying, to tell whether they are in the money
+    let mut symbols = stocks()
+        .filter(|s| s.asset == Asset::Security && s.market == Market::Listed)
+        .flat_map(|s| {
+            let underlying = options::parse(&s.symbol).map(|c| c.underlying);
+            std::iter::once(s.symbol.clone()).chain(underlying)
+        })
+        .collect::<Vec<_>>();
+    symbols.sort();
+    symbols.dedup();
+
+    // nasdaq has no futures quotes, so those always go to yahoo
+    let mut futures = stocks()
+        .filter(|s| s.asset == Asset::Future)
+        .map(|s| s.symbol.clone())
+        .collect::<Vec<_>>();
+    futures.sort();
+    futures.dedup();
+
+    let mut otc = stocks()
+        .filter(|s| s.asset == Asset::Security && s.market == Market::Otc)
+        .map(|s| s.symbol.clone())
+        .collect::<Vec<_>>();
+    otc.sort();
+    otc.dedup();
+    symbols.retain(|s| !otc.contains(s));
+
+    // listings outside the US are only on yahoo
+    let mut foreign = symbols.clone();
+    foreign.retain(|s| exchange::foreign(s));
+    symbols.retain(|s| !exchange::foreign(s));
+
+    let providers = from_names(&conf.providers, conf)?;
+    let (mut info, mut failed) = update_stock_info(symbols, providers, conf).await;
+
+    if !foreign.is_empty() {
+        let yahoo = from_names(&["yahoo".to_string()], conf)?;
+        let (f_info, f_failed) = update_stock_info(foreign, yahoo, conf).await;
+        info.extend(f_info);
+        failed.extend(f_failed);
+    }
+
+    if !otc.is_empty() {
+        let covering = from_names(&otc_providers(&conf.providers), conf)?;
+        let (o_info, o_failed) = update_stock_info(otc, covering, conf).await;
+        info.extend(o_info);
+        failed.extend(o_failed);
+    }
+
+    let mut metals = stocks()
+        .filter(|s| s.asset == Asset::Metal)
+        .map(|s| s.symbol.clone())
+        .collect::<Vec<_>>();
+    metals.sort();
+    metals.dedup();
+
+    if !futures.is_empty() {
+        let yahoo = from_names(&["yahoo".to_string()], conf)?;
+        let (f_info, f_failed) = update_stock_info(futures, yahoo, conf).await;
+        info.extend(f_info);
+        failed.extend(f_failed);
+    }
+
+    let mut coin_symbols = stocks()
+        .filter(|s| s.asset == Asset::Crypto)
+        .map(|s| s.symbol.clone())
+        .collect::<Vec<_>>();
+    coin_symbols.sort();
+    coin_symbols.dedup();
+
+    if !coin_symbols.is_empty() {
+        let coins: Providers = Arc::new(vec![Box::new(Coins)]);
+        let (c_info, c_failed) = update_stock_info(coin_symbols, coins, conf).await;
+        info.extend(c_info);
+        failed.extend(c_failed);
+    }
+
+    if !metals.is_empty() {
+        let spot: Providers = Arc::new(vec![Box::new(Spot {
+            premiums: conf.metal_premiums.clone(),
+        })]);
+        let (m_info, m_failed) = update_stock_info(metals, spot, conf).await;
+        info.extend(m_info);
+        failed.extend(m_failed);
+    }
+
+    // totals are in one currency, at rates quoted like any other symbol
+    let base = fx::base(conf.base_currency.as_deref(), accounts.iter());
+    let mut held = fx::currencies(accounts.iter());
+    held.extend(info.values().map(|q| q.price.currency.clone()));
+    let rates = held
+        .iter()
+        .filter(|c| **c != base)
+        .map(|c| fx::pair(c, &base))
+        .collect::<Vec<_>>();
+    if !rates.is_empty() {
+        let yahoo = from_names(&["yahoo".to_string()], conf)?;
+        let (r_info, r_failed) = update_stock_info(rates, yahoo, conf).await;
+        info.extend(r_info);
+        failed.extend(r_failed);
+    }
+
+    // a security none of the providers know any more was most likely
+    // delisted, so it stands at its last recorded price rather than at nothing
+    if failed
+        .iter()
+        .any(|(_, e)| matches!(e, QuoteError::NotFound))
+    {
+        let last = snapshot::last_prices();
+        failed.retain(|(symbol, e)| {
+            let (QuoteError::NotFound, Some((time, price, recorded))) = (e, last.get(symbol))
+            else {
+                return true;
+            };
+            let currency = stocks()
+                .find(|s| &s.symbol == symbol)
+                .map_or("USD", |s| &s.cost_basis.currency);
+            // snapshots are in the base currency, back to the listing's
+            let price = match recorded {
+                Some(c) => fx::convert(&Money::new(*price, c), currency, &info),
+                None => Money::new(*price, currency),
+            };
+            info.insert(
+                symbol.clone(),
+                Quote {
+                    price,
+                    change: Money::new(0.0, currency),
+                    open: None,
+                    time: Some(*time),
+                    status: Status::Delisted,
+                },
+            );
+            false
+        });
+    }
+
+    failed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok((info, failed))
+}
+
+// Symbols looked up at once, to stay under the providers' rate limits
+const CONCURRENCY: usize = 8;
+const QUOTE_TTL: i64 = 60;
+
+// Failed lookups are left out of the quotes and come back with the reason
+pub async fn update_stock_info(
+    symbols: Vec<String>,
+    providers: Providers,
+    conf: &Config,
+) -> (HashMap<String, Quote>, Vec<(String, QuoteError)>) {
+    let stats = Arc::new(Mutex::new(Stats::load()));
+    let start = Instant::now();
+    let race = conf.race;
+    let by_latency = conf.order == Order::Latency;
+
+    let now = Utc::now();
+    let ttl = chrono::Duration::seconds(conf.quote_ttl.map_or(QUOTE_TTL, |t| t as i64));
+    let mut cache = QuoteCache::load();
+    let mut info = HashMap::new();
+    let symbols = symbols
+        .into_iter()
+        .filter(|s| match cache.fresh(&providers, s, now, ttl) {
+            Some(q) => {
+                info.insert(s.clone(), q);
+                false
+            }
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    if !info.is_empty() {
+        *FETCHED
+            .lock()
+            .unwrap()
+            .answered
+            .entry("cache".to_string())
+            .or_default() += info.len();
+    }
+
+    // spawned as the stream is polled, so no more than CONCURRENCY run at once
+    let results = stream::iter(symbols)
+        .map(|symbol| {
+            let providers = providers.clone();
+            let stats = stats.clone();
+
+            tokio::spawn(async move {
+                let x = fetch(&providers, &symbol, race, by_latency, &stats).await;
+                (symbol, x)
+            })
+        })
+        .buffer_unordered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut failed = vec![];
+    let mut fetched = HashMap::new();
+
+    for res in results {
+        match res {
+            Ok((symbol, Ok(x))) => {
+                fetched.insert(symbol, x);
+            }
+            Ok((symbol, Err(e))) => failed.push((symbol, e)),
+            Err(e) => eprintln!("quote lookup stopped: {}", e),
+        }
+    }
+
+    if let Err(e) = stats.lock().unwrap().save() {
+        eprintln!("failed to save provider stats: {}", e);
+    }
+    if ttl > chrono::Duration::zero() && !fetched.is_empty() {
+        cache.store(&providers, &fetched, now, ttl);
+        if let Err(e) = cache.save() {
+            eprintln!("failed to cache quotes: {}", e);
+        }
+    }
+    info.extend(fetched);
+
+    failed.sort_by(|a, b| a.0.cmp(&b.0));
+    {
+        let mut f = FETCHED.lock().unwrap();
+        f.elapsed += start.elapsed();
+        f.failed.extend(failed.iter().map(|(s, _)| s.clone()));
+    }
+    (info, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_quotes_are_kept_per_provider_set() {
+        let usual: Vec<Box<dyn QuoteProvider>> = vec![Box::new(Nasdaq), Box::new(Yahoo)];
+        let yahoo: Vec<Box<dyn QuoteProvider>> = vec![Box::new(Yahoo)];
+        let now = Utc::now();
+        let ttl = chrono::Duration::seconds(60);
+
+        let mut cache = QuoteCache::default();
+        let quotes = HashMap::from([("AAPL".to_string(), Quote::default())]);
+        cache.store(&usual, &quotes, now, ttl);
+
+        assert!(cache.fresh(&usual, "AAPL", now, ttl).is_some());
+        assert!(cache.fresh(&yahoo, "AAPL", now, ttl).is_none());
+        assert!(cache.fresh(&usual, "AAPL", now + ttl, ttl).is_none());
+    }
+}
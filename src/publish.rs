@@ -0,0 +1,351 @@
+// `stocks publish --dir ./site` writes the report as a static page with its
+// charts and the same numbers as JSON, to copy onto any static host and look
+// at from anywhere. Nothing in it updates by itself, so it's read-only by
+// nature; running it again from cron keeps it current. With --private every
+// amount and share count is left out and only percentages are published.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::alloc::{self, Slice};
+use crate::benchmark;
+use crate::config::Config;
+use crate::lock;
+use crate::money::Money;
+use crate::plot::{self, Style};
+use crate::report::Report;
+use crate::time;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Position {
+    pub symbol: String,
+    pub label: String,
+    // of the whole portfolio
+    pub weight: f64,
+    pub day_pct: f64,
+    pub gain_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Money>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PublicAccount {
+    pub name: String,
+    pub weight: f64,
+    pub day_pct: f64,
+    pub gain_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<Money>,
+    pub positions: Vec<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Share {
+    pub name: String,
+    pub pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Published {
+    pub generated: DateTime<Utc>,
+    pub as_of: Option<DateTime<Utc>>,
+    pub private: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<Money>,
+    pub day_pct: f64,
+    pub gain_pct: f64,
+    pub accounts: Vec<PublicAccount>,
+    pub allocation: Vec<Share>,
+}
+
+pub fn build(report: &Report, private: bool, now: DateTime<Utc>) -> Published {
+    let total = &report.totals.total;
+    let amount = |m: &Money| (!private).then(|| m.clone());
+    let accounts = report
+        .accounts
+        .iter()
+        .map(|a| PublicAccount {
+            name: a.name.clone(),
+            weight: a.total().pct_of(total),
+            day_pct: a.net_pct,
+            gain_pct: a.gain_pct,
+            total: amount(&a.total()),
+            positions: a
+                .rows
+                .iter()
+                .filter(|r| !r.failed)
+                .map(|r| Position {
+                    symbol: r.symbol.clone(),
+                    label: r.label.clone(),
+                    weight: r.portfolio_weight,
+                    day_pct: r.net_pct,
+                    gain_pct: r.total_pct,
+                    shares: (!private).then_some(r.shares),
+                    value: amount(&r.value),
+                })
+                .collect(),
+        })
+        .collect();
+    let allocation = alloc::build(report)
+        .portfolio
+        .into_iter()
+        .map(|s| Share {
+            name: s.name,
+            pct: s.pct,
+        })
+        .collect();
+
+    Published {
+        generated: now,
+        as_of: report.as_of,
+        private,
+        total: amount(total),
+        day_pct: report.totals.net_pct,
+        gain_pct: report.totals.gain_pct,
+        accounts,
+        allocation,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn pct(p: f64) -> String {
+    if !p.is_finite() {
+        return "<td class=\"num\">n/a</td>".to_string();
+    }
+    let class = if p > 0.0 {
+        "num up"
+    } else if p < 0.0 {
+        "num down"
+    } else {
+        "num"
+    };
+    format!("<td class=\"{}\">{:+.2}%</td>", class, p)
+}
+
+fn weight(p: f64) -> String {
+    if p.is_finite() {
+        format!("<td class=\"num\">{:.2}%</td>", p)
+    } else {
+        "<td class=\"num\">n/a</td>".to_string()
+    }
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:60rem;margin:2rem auto;padding:0 1rem;color:#222}\
+h1{margin-bottom:0}.as-of{color:#777;margin-top:.25rem}\
+table{border-collapse:collapse;width:100%;margin:1rem 0}\
+th,td{padding:.3rem .6rem;border-bottom:1px solid #ddd;text-align:left}\
+.num{text-align:right;font-variant-numeric:tabular-nums}.up{color:#2a7d2a}.down{color:#b22}\
+img{max-width:100%}\
+@media (prefers-color-scheme:dark){body{background:#1e1e1e;color:#ddd}th,td{border-color:#444}.as-of{color:#999}}";
+
+// The page itself, showing `charts` (file and description) under the totals
+pub fn html(p: &Published, charts: &[(&str, &str)]) -> String {
+    let mut h = String::new();
+    h.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    h.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    h.push_str("<meta name=\"robots\" content=\"noindex\">\n<title>Portfolio</title>\n");
+    h.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+
+    let headline = match &p.total {
+        Some(t) => format!("Portfolio, {}", escape(&t.to_string())),
+        None => "Portfolio".to_string(),
+    };
+    h.push_str(&format!("<h1>{}</h1>\n", headline));
+    let as_of = p.as_of.unwrap_or(p.generated);
+    h.push_str(&format!(
+        "<p class=\"as-of\">Prices as of {}</p>\n",
+        escape(&time::format(&as_of, "%Y-%m-%d %H:%M %Z"))
+    ));
+    h.push_str(&format!(
+        "<table>\n<tr><th>Today</th><th>Gain</th></tr>\n<tr>{}{}</tr>\n</table>\n",
+        pct(p.day_pct),
+        pct(p.gain_pct)
+    ));
+
+    for (file, alt) in charts.iter() {
+        h.push_str(&format!(
+            "<p><img src=\"{}\" alt=\"{}\"></p>\n",
+            escape(file),
+            escape(alt)
+        ));
+    }
+
+    for a in p.accounts.iter() {
+        let total = match &a.total {
+            Some(t) => format!(", {}", escape(&t.to_string())),
+            None => String::new(),
+        };
+        h.push_str(&format!(
+            "<h2>{}{}</h2>\n<table>\n<tr><th>Position</th>",
+            escape(&a.name),
+            total
+        ));
+        if !p.private {
+            h.push_str("<th class=\"num\">Shares</th><th class=\"num\">Value</th>");
+        }
+        h.push_str("<th class=\"num\">Weight</th><th class=\"num\">Today</th><th class=\"num\">Gain</th></tr>\n");
+        for r in a.positions.iter() {
+            h.push_str(&format!("<tr><td>{}</td>", escape(&r.label)));
+            if let (Some(shares), Some(value)) = (r.shares, &r.value) {
+                h.push_str(&format!(
+                    "<td class=\"num\">{}</td><td class=\"num\">{}</td>",
+                    shares,
+                    escape(&value.to_string())
+                ));
+            }
+            h.push_str(&format!(
+                "{}{}{}</tr>\n",
+                weight(r.weight),
+                pct(r.day_pct),
+                pct(r.gain_pct)
+            ));
+        }
+        h.push_str(&format!(
+            "<tr><th>Total</th>{}{}{}{}</tr>\n</table>\n",
+            if p.private { "" } else { "<td></td><td></td>" },
+            weight(a.weight),
+            pct(a.day_pct),
+            pct(a.gain_pct)
+        ));
+    }
+
+    h.push_str(&format!(
+        "<p class=\"as-of\">Published {} with stocks.</p>\n</body>\n</html>\n",
+        escape(&time::format(&p.generated, "%Y-%m-%d %H:%M %Z"))
+    ));
+    h
+}
+
+// Writes index.html, report.json, the allocation pie and, with enough
+// history, the value over time into `dir`, returning the files written.
+// Privately the history is indexed to start at 100 rather than in dollars.
+pub fn write(
+    dir: &str,
+    p: &Published,
+    slices: &[Slice],
+    history: &[(NaiveDate, f64)],
+    conf: &Config,
+) -> Result<Vec<String>, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir, e))?;
+    let path = |f: &str| Path::new(dir).join(f).to_string_lossy().to_string();
+    let style = Style::default();
+    let mut charts = vec![];
+    if plot::pie(&path("allocation.svg"), &style, "Allocation", slices, conf).is_ok() {
+        charts.push(("allocation.svg", "Allocation"));
+    }
+    let history = match (p.private, history.first()) {
+        (true, Some((start, _))) => benchmark::rebase(history, *start, 100.0),
+        _ => history.to_vec(),
+    };
+    if history.len() >= 2 {
+        let title = if p.private {
+            "Value, indexed to 100"
+        } else {
+            "Value"
+        };
+        let series = [("portfolio".to_string(), history)];
+        plot::lines(&path("history.svg"), &style, title, &series, conf)?;
+        charts.push(("history.svg", "Value over time"));
+    }
+
+    let json = serde_json::to_string_pretty(p).map_err(|e| e.to_string())?;
+    let mut written = charts.iter().map(|(f, _)| path(f)).collect::<Vec<_>>();
+    for (file, contents) in [
+        ("report.json", json + "\n"),
+        ("index.html", html(p, &charts)),
+    ] {
+        lock::write_atomic(&path(file), contents.as_bytes())
+            .map_err(|e| format!("could not write {}: {}", path(file), e))?;
+        written.push(path(file));
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::Status;
+    use crate::report::{AccountReport, Row, Totals};
+    use chrono::TimeZone;
+
+    fn row(symbol: &str, shares: f64, value: f64) -> Row {
+        Row {
+            id: symbol.to_string(),
+            symbol: symbol.to_string(),
+            label: format!("<{}>", symbol),
+            shares,
+            cost: Money::usd(1.0),
+            price: Money::usd(value / shares),
+            invested: Money::usd(shares),
+            value: Money::usd(value),
+            account_weight: 50.0,
+            portfolio_weight: 50.0,
+            net: Money::usd(1.0),
+            net_pct: 1.5,
+            total: Money::usd(value - shares),
+            total_pct: -2.0,
+            time: None,
+            stale: false,
+            strategy: None,
+            margin: None,
+            failed: false,
+            status: Status::Trading,
+            cost_known: true,
+        }
+    }
+
+    #[test]
+    fn private_pages_publish_only_percentages() {
+        let accounts = vec![AccountReport {
+            id: "Brokerage".to_string(),
+            name: "Brokerage".to_string(),
+            closed: None,
+            rows: vec![row("VTI", 10.0, 2500.0), row("BND", 20.0, 2500.0)],
+            invested: Money::usd(30.0),
+            value: Money::usd(5000.0),
+            cash: None,
+            net: Money::usd(2.0),
+            net_pct: 1.5,
+            gain: Money::usd(4970.0),
+            gain_pct: 12.0,
+            strategies: vec![],
+        }];
+        let report = Report {
+            totals: Totals::of(&accounts),
+            accounts,
+            as_of: None,
+            warnings: vec![],
+            other: vec![],
+            net_worth: None,
+        };
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+
+        let open = build(&report, false, now);
+        assert_eq!(open.accounts[0].positions[0].shares, Some(10.0));
+        assert!(html(&open, &[]).contains("Portfolio, $5000.00"));
+
+        let p = build(&report, true, now);
+        assert_eq!(p.total, None);
+        assert_eq!(p.accounts[0].weight, 100.0);
+        assert_eq!(p.allocation[0].pct, 50.0);
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(!json.contains("value") && !json.contains("shares") && !json.contains("5000"));
+
+        let page = html(&p, &[("allocation.svg", "Allocation")]);
+        assert!(!page.contains("5000") && !page.contains("Shares"));
+        assert!(page.contains("<td>&lt;VTI&gt;</td><td class=\"num\">50.00%</td>"));
+        assert!(page.contains("<td class=\"num up\">+1.50%</td>"));
+        assert!(page.contains("<img src=\"allocation.svg\" alt=\"Allocation\">"));
+    }
+}
@@ -0,0 +1,117 @@
+// --qr prints a QR code in place of the table, to hand the numbers to a phone
+// by pointing its camera at the terminal: the summary as compact JSON, or
+// `publish_url` when the report is published somewhere.
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+use qrcode::{Color, EcLevel, QrCode};
+use serde::Serialize;
+
+use crate::glyph;
+use crate::render::OutputRenderer;
+use crate::report::Report;
+
+// blank modules around the code, which scanners need to find its edges
+const QUIET: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub total: f64,
+    pub currency: String,
+    pub change: f64,
+    pub change_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+pub fn summary(report: &Report) -> Summary {
+    let t = &report.totals;
+    Summary {
+        total: t.total.round().amount,
+        currency: t.total.currency.clone(),
+        change: t.net.round().amount,
+        change_pct: (t.net_pct * 100.0).round() / 100.0,
+        as_of: report.as_of,
+    }
+}
+
+// Two rows of modules to a line with half blocks, or one of #s with --ascii.
+// Light modules are the filled ones, since most terminals draw light text on
+// a dark background.
+pub fn draw(data: &str) -> Result<String, String> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::L)
+        .map_err(|e| format!("can't fit that in a QR code: {}", e))?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let light = |x: usize, y: usize| {
+        x < QUIET
+            || y < QUIET
+            || x >= width + QUIET
+            || y >= width + QUIET
+            || colors[(y - QUIET) * width + x - QUIET] == Color::Light
+    };
+
+    let size = width + 2 * QUIET;
+    let mut out = String::new();
+    // without half blocks a module is two # wide, about as wide as it's tall
+    if glyph::ascii() {
+        for y in 0..size {
+            for x in 0..size {
+                out.push_str(if light(x, y) { "##" } else { "  " });
+            }
+            out.push('\n');
+        }
+        return Ok(out);
+    }
+    for y in (0..size).step_by(2) {
+        for x in 0..size {
+            // past the last row is more quiet zone
+            let bottom = y + 1 >= size || light(x, y + 1);
+            out.push(match (light(x, y), bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+pub struct Qr {
+    // encoded instead of the summary when set
+    pub url: Option<String>,
+}
+
+impl OutputRenderer for Qr {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        let data = match &self.url {
+            Some(u) => u.clone(),
+            None => serde_json::to_string(&summary(report)).map_err(io::Error::from)?,
+        };
+        let code = draw(&data).map_err(io::Error::other)?;
+        write!(out, "{}", code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_draw_two_module_rows_per_line() {
+        let code = draw("https://example.com/stocks/").unwrap();
+        let lines = code.lines().collect::<Vec<_>>();
+        // version 2 is 25 modules, plus the quiet zone on each side
+        let size = 25 + 2 * QUIET;
+        assert_eq!(lines.len(), size.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == size));
+        assert!(lines[0].chars().all(|c| c == '█'));
+        // the top left finder pattern starts with a dark row of 7 under the zone
+        assert!(lines[1].starts_with("██ ▄▄▄▄▄ █"));
+
+        assert!(draw(&"x".repeat(5000)).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::lookup::check_symbol;
+use crate::money::Money;
+use crate::portfolio::{Account, Home};
+use crate::provider::Quote;
+use crate::render::clr;
+use crate::time;
+use crate::yahoo::get_yahoo_value;
+
+// An ADR's home listing next to it, for seeing how far the two have drifted
+#[derive(Debug, Clone, Serialize)]
+pub struct Listing {
+    pub symbol: String,
+    pub ratio: f64,
+    pub price: Money,
+    // the home shares one ADR stands for, in the home currency
+    pub per_adr: Money,
+    // ADR currency per unit of the home currency that the two prices imply
+    pub implied_fx: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Detail {
+    pub symbol: String,
+    pub quote: Quote,
+    pub home: Option<Listing>,
+}
+
+// The home listing given for `symbol` in any account, `home = "7203.T"`
+pub fn find_home(accounts: &[Account], symbol: &str) -> Option<Home> {
+    accounts
+        .iter()
+        .flat_map(|a| a.stocks.iter())
+        .filter(|s| s.symbol.eq_ignore_ascii_case(symbol))
+        .find_map(|s| s.home.clone())
+}
+
+pub fn listing(adr: &Money, home: &Home, price: Money) -> Listing {
+    let per_adr = price.clone() * home.ratio;
+    Listing {
+        symbol: home.symbol.clone(),
+        ratio: home.ratio,
+        implied_fx: adr.amount / per_adr.amount,
+        per_adr,
+        price,
+    }
+}
+
+pub async fn detail(conf: &Config, symbol: &str, home: Option<&Home>) -> Result<Detail, String> {
+    let quote = check_symbol(conf, symbol).await?;
+
+    // foreign listings are only on yahoo
+    let home = match home {
+        Some(h) => {
+            let q = get_yahoo_value(&h.symbol)
+                .await
+                .map_err(|e| format!("no quote for home listing {}: {}", h.symbol, e))?;
+            Some(listing(&quote.price, h, q.price))
+        }
+        None => None,
+    };
+
+    Ok(Detail {
+        symbol: symbol.to_string(),
+        quote,
+        home,
+    })
+}
+
+pub fn render(d: &Detail, out: &mut dyn Write) -> io::Result<()> {
+    let q = &d.quote;
+    write!(
+        out,
+        "\x1b[1m{}\x1b[0m  {}  {}{} ({:.2}%)\x1b[0m",
+        d.symbol,
+        q.price,
+        clr(q.change.amount),
+        q.change,
+        q.change.pct_of(&(q.price.clone() - q.change.clone()))
+    )?;
+    match &q.time {
+        Some(t) => writeln!(out, "  at {}", time::stamp(t))?,
+        None => writeln!(out)?,
+    }
+
+    if let Some(h) = &d.home {
+        writeln!(
+            out,
+            "\thome listing {}  {}  x {} = {} per ADR",
+            h.symbol, h.price, h.ratio, h.per_adr
+        )?;
+        writeln!(
+            out,
+            "\timplied FX   1 {} = {:.6} {}  ({}/{} {:.4})",
+            h.price.currency,
+            h.implied_fx,
+            q.price.currency,
+            q.price.currency,
+            h.price.currency,
+            1.0 / h.implied_fx
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implied_fx_from_adr_and_home_prices() {
+        let home = Home {
+            symbol: "7203.T".to_string(),
+            ratio: 10.0,
+        };
+        let l = listing(&Money::usd(190.0), &home, Money::new(2800.0, "JPY"));
+
+        assert_eq!(l.per_adr, Money::new(28000.0, "JPY"));
+        assert!((l.implied_fx - 190.0 / 28000.0).abs() < 1e-12);
+        assert!((1.0 / l.implied_fx - 147.368).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::import::parse_number;
+use crate::portfolio::Account;
+
+// share counts closer than this are the same, brokers round fractional shares
+const SHARE_TOLERANCE: f64 = 1e-4;
+// and average costs within half a cent
+const COST_TOLERANCE: f64 = 0.005;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub num: f64,
+    // average cost per share, when the export has one
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Shares { ours: f64, broker: f64 },
+    Cost { ours: f64, broker: f64 },
+    NotInHoldings(Position),
+    NotAtBroker(Position),
+}
+
+const SYMBOL_COLUMNS: [&str; 3] = ["symbol", "ticker", "security"];
+const NUM_COLUMNS: [&str; 4] = ["quantity", "qty", "shares", "units"];
+// per share cost, or the whole position's
+const COST_COLUMNS: [&str; 5] = [
+    "average cost",
+    "average cost basis",
+    "avg cost",
+    "cost/share",
+    "unit cost",
+];
+const TOTAL_COST_COLUMNS: [&str; 4] = ["cost basis", "cost basis total", "total cost", "cost"];
+
+// Rows without a share count, like cash sweeps and footers, are left out
+pub fn parse_positions(buf: &str) -> Result<BTreeMap<String, Position>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(buf.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("could not read csv header: {}", e))?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect::<Vec<_>>();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let symbol = column(&SYMBOL_COLUMNS).ok_or("csv has no symbol column")?;
+    let num = column(&NUM_COLUMNS).ok_or("csv has no quantity column")?;
+    let cost = column(&COST_COLUMNS);
+    let total_cost = column(&TOTAL_COST_COLUMNS);
+
+    let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("could not read csv: {}", e))?;
+        let get = |i: Option<usize>| i.and_then(|i| record.get(i)).and_then(parse_number);
+
+        let name = record.get(symbol).unwrap_or_default().to_uppercase();
+        let n = match get(Some(num)) {
+            Some(n) if !name.is_empty() => n,
+            _ => continue,
+        };
+        let c = get(cost).or_else(|| get(total_cost).map(|t| t / n));
+
+        // the same symbol over several lots adds up
+        let p = positions.entry(name).or_insert(Position {
+            num: 0.0,
+            cost: Some(0.0),
+        });
+        p.cost = match (p.cost, c) {
+            (Some(old), Some(c)) => Some((old * p.num + c * n) / (p.num + n)),
+            _ => None,
+        };
+        p.num += n;
+    }
+
+    Ok(positions)
+}
+
+pub fn holdings_positions(accounts: &[&Account]) -> BTreeMap<String, Position> {
+    let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+
+    for stock in accounts.iter().flat_map(|a| a.stocks.iter()) {
+        let p = positions.entry(stock.symbol.clone()).or_insert(Position {
+            num: 0.0,
+            cost: Some(0.0),
+        });
+        let total = p.num + stock.amount;
+        let cost = p.cost.unwrap_or_default();
+        p.cost = Some(if total != 0.0 {
+            (cost * p.num + stock.cost_basis.amount * stock.amount) / total
+        } else {
+            cost
+        });
+        p.num = total;
+    }
+
+    positions
+}
+
+pub fn compare(
+    ours: &BTreeMap<String, Position>,
+    broker: &BTreeMap<String, Position>,
+) -> Vec<(String, Mismatch)> {
+    let mut out = vec![];
+
+    for (symbol, b) in broker.iter() {
+        let o = match ours.get(symbol) {
+            Some(o) => o,
+            None => {
+                out.push((symbol.clone(), Mismatch::NotInHoldings(b.clone())));
+                continue;
+            }
+        };
+
+        if (o.num - b.num).abs() > SHARE_TOLERANCE {
+            out.push((
+                symbol.clone(),
+                Mismatch::Shares {
+                    ours: o.num,
+                    broker: b.num,
+                },
+            ));
+        }
+        if let (Some(oc), Some(bc)) = (o.cost, b.cost) {
+            if (oc - bc).abs() > COST_TOLERANCE {
+                out.push((
+                    symbol.clone(),
+                    Mismatch::Cost {
+                        ours: oc,
+                        broker: bc,
+                    },
+                ));
+            }
+        }
+    }
+
+    for (symbol, o) in ours.iter() {
+        if !broker.contains_key(symbol) {
+            out.push((symbol.clone(), Mismatch::NotAtBroker(o.clone())));
+        }
+    }
+
+    out
+}
+
+// Prints each mismatch and returns whether there were none
+pub fn run(path: &str, accounts: &[&Account]) -> Result<bool, String> {
+    let buf = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let broker = parse_positions(&buf).map_err(|e| format!("{}: {}", path, e))?;
+    let mismatches = compare(&holdings_positions(accounts), &broker);
+
+    for (symbol, m) in mismatches.iter() {
+        match m {
+            Mismatch::Shares { ours, broker } => println!(
+                "{}: holdings have {} shares, the broker {}",
+                symbol, ours, broker
+            ),
+            Mismatch::Cost { ours, broker } => println!(
+                "{}: average cost is {:.2} in the holdings, {:.2} at the broker",
+                symbol, ours, broker
+            ),
+            Mismatch::NotInHoldings(p) => {
+                println!(
+                    "{}: {} shares at the broker, not in the holdings",
+                    symbol, p.num
+                )
+            }
+            Mismatch::NotAtBroker(p) => {
+                println!(
+                    "{}: {} shares in the holdings, not at the broker",
+                    symbol, p.num
+                )
+            }
+        }
+    }
+
+    if mismatches.is_empty() {
+        println!("holdings match {} ({} positions)", path, broker.len());
+    }
+
+    Ok(mismatches.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::portfolio::Stock;
+
+    fn stock(symbol: &str, amount: f64, cost: f64) -> Stock {
+        Stock {
+            symbol: symbol.to_string(),
+            amount,
+            cost_basis: Money::usd(cost),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn broker_lots_combine_and_cash_is_skipped() {
+        let csv = "Symbol,Description,Quantity,Cost Basis\n\
+                   AAPL,Apple,10,\"$1,500.00\"\n\
+                   AAPL,Apple,10,\"$1,900.00\"\n\
+                   SPAXX**,Money market,,\n\
+                   \n\
+                   \"Data as of 11/24/2023\"\n";
+        let p = parse_positions(csv).unwrap();
+
+        assert_eq!(p.len(), 1);
+        assert_eq!(p["AAPL"].num, 20.0);
+        assert_eq!(p["AAPL"].cost, Some(170.0));
+    }
+
+    #[test]
+    fn mismatches_in_both_directions() {
+        let account = Account::test(
+            "a",
+            vec![
+                stock("AAPL", 10.0, 150.0),
+                stock("VTI", 5.0, 200.0),
+                stock("GOOG", 1.0, 100.0),
+            ],
+        );
+        let csv = "Symbol,Quantity,Average Cost\nAAPL,10,150.004\nVTI,6,210\nMSFT,2,300\n";
+        let broker = parse_positions(csv).unwrap();
+
+        let m = compare(&holdings_positions(&[&account]), &broker);
+        let symbols = m.iter().map(|(s, _)| s.as_str()).collect::<Vec<_>>();
+
+        // AAPL is within rounding
+        assert_eq!(symbols, ["MSFT", "VTI", "VTI", "GOOG"]);
+        assert!(matches!(m[0].1, Mismatch::NotInHoldings(_)));
+        assert_eq!(
+            m[1].1,
+            Mismatch::Shares {
+                ours: 5.0,
+                broker: 6.0
+            }
+        );
+        assert!(matches!(m[3].1, Mismatch::NotAtBroker(_)));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::config::{Config, Recurring};
+use crate::import::{self, Action, Transaction};
+use crate::lock;
+use crate::nasdaq::get_history;
+use crate::profile;
+
+fn state_path() -> String {
+    format!("{}/recurring.json", profile::data_dir())
+}
+
+// The last scheduled date handled for each recurring buy
+fn load_state() -> BTreeMap<String, NaiveDate> {
+    fs::read_to_string(state_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &BTreeMap<String, NaiveDate>) -> io::Result<()> {
+    fs::create_dir_all(profile::data_dir())?;
+    lock::write_atomic(
+        &state_path(),
+        serde_json::to_string_pretty(state)?.as_bytes(),
+    )
+}
+
+fn key(r: &Recurring) -> String {
+    format!("{}|{}|{}|{}", r.account, r.symbol, r.amount, r.day)
+}
+
+// `day` in the given month, pulled back to the month's last day
+fn in_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..=day.clamp(1, 31))
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+}
+
+// Scheduled dates after `last` up to and including `today`
+pub fn due(day: u32, last: NaiveDate, today: NaiveDate) -> Vec<NaiveDate> {
+    let (mut year, mut month) = (last.year(), last.month());
+    let mut dates = vec![];
+
+    while let Some(date) = in_month(year, month, day) {
+        if date > today {
+            break;
+        }
+        if date > last {
+            dates.push(date);
+        }
+        (year, month) = if month == 12 {
+            (year + 1, 1)
+        } else {
+            (year, month + 1)
+        };
+    }
+
+    dates
+}
+
+// the close on the scheduled day, or the next trading day when it falls on
+// a weekend or holiday
+async fn price_on(symbol: &str, date: NaiveDate) -> Option<f64> {
+    let bars = get_history(symbol, date, date + Duration::days(7)).await?;
+    bars.into_iter().find(|b| b.date >= date).map(|b| b.close)
+}
+
+fn confirmed(r: &Recurring, date: NaiveDate, num: f64, price: f64) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    eprint!(
+        "record {:.2} {} at {:.2} (${:.2}) in {} for {}? [y/N] ",
+        num,
+        r.symbol,
+        price,
+        r.amount,
+        r.account,
+        date.format("%Y-%m-%d")
+    );
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+// Records every recurring buy whose date has come since the last run. A date
+// without a price yet (today, before the close) is tried again next time; one
+// that is declined is passed over for good.
+pub async fn run(conf: &Config, holdings: &str, today: NaiveDate) -> Result<(), String> {
+    if conf.recurring.is_empty() {
+        return Ok(());
+    }
+
+    let mut state = load_state();
+
+    for r in conf.recurring.iter() {
+        let start = r
+            .start
+            .as_ref()
+            .and_then(|d| NaiveDate::parse_from_str(&d.to_string(), "%Y-%m-%d").ok());
+        let last = match (state.get(&key(r)), start) {
+            (Some(last), _) => *last,
+            (None, Some(start)) => start - Duration::days(1),
+            // nothing to catch up on the first run
+            (None, None) => {
+                state.insert(key(r), today);
+                continue;
+            }
+        };
+
+        let mut txns = vec![];
+        let mut handled = last;
+        for date in due(r.day, last, today) {
+            let price = match price_on(&r.symbol, date).await {
+                Some(p) if p > 0.0 => p,
+                _ => break,
+            };
+            let num = r.amount / price;
+            handled = date;
+
+            if r.confirm && !confirmed(r, date, num, price) {
+                continue;
+            }
+            txns.push(Transaction {
+                date,
+                symbol: r.symbol.to_uppercase(),
+                action: Action::Buy,
+                num,
+                price,
+                amount: r.amount,
+            });
+        }
+
+        if !txns.is_empty() {
+            import::apply(holdings, &r.account, &txns, "recurring")?;
+            for t in txns.iter() {
+                println!(
+                    "recorded {:.4} {} at {:.2} in {} for {}",
+                    t.num,
+                    t.symbol,
+                    t.price,
+                    r.account,
+                    t.date.format("%Y-%m-%d")
+                );
+            }
+        }
+        state.insert(key(r), handled);
+    }
+
+    save_state(&state).map_err(|e| format!("could not save {}: {}", state_path(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn due_dates_since_the_last_run() {
+        assert_eq!(
+            due(1, date(2024, 1, 1), date(2024, 3, 15)),
+            [date(2024, 2, 1), date(2024, 3, 1)]
+        );
+        assert!(due(1, date(2024, 3, 1), date(2024, 3, 31)).is_empty());
+        // the 31st lands on the last day of short months
+        assert_eq!(
+            due(31, date(2024, 1, 31), date(2024, 4, 30)),
+            [date(2024, 2, 29), date(2024, 3, 31), date(2024, 4, 30)]
+        );
+        // across a year end, and the day itself counts
+        assert_eq!(
+            due(15, date(2023, 12, 1), date(2024, 1, 15)),
+            [date(2023, 12, 15), date(2024, 1, 15)]
+        );
+    }
+}
@@ -0,0 +1,1197 @@
+use std::io::{self, IsTerminal, Write};
+
+use crate::chart::size;
+use crate::config::{Config, Highlight};
+use crate::glyph;
+use crate::money::Money;
+use crate::provider::{Fetched, Status};
+use crate::report::{AccountReport, Report, Row, Strategy, Totals};
+use crate::time;
+
+const DIM: &str = "\x1b[38;5;8m";
+
+pub fn clr(f: f64) -> String {
+    if f < 0.0 {
+        "\x1b[38;5;1m"
+    } else {
+        "\x1b[38;5;2m"
+    }
+    .to_string()
+}
+
+// Drops ANSI escape sequences on the way through, for output that is piped
+// or redirected rather than shown on a terminal.
+pub struct Plain<W: Write> {
+    inner: W,
+    state: Escape,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    Start,
+    Csi,
+}
+
+impl<W: Write> Plain<W> {
+    pub fn new(inner: W) -> Self {
+        Plain {
+            inner,
+            state: Escape::None,
+        }
+    }
+}
+
+impl<W: Write> Write for Plain<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut kept = Vec::with_capacity(buf.len());
+
+        // sequences can be split across writes, so the state is kept between calls
+        for &b in buf {
+            self.state = match (self.state, b) {
+                (Escape::None, 0x1b) => Escape::Start,
+                (Escape::None, _) => {
+                    kept.push(b);
+                    Escape::None
+                }
+                (Escape::Start, b'[') => Escape::Csi,
+                (Escape::Start, _) => Escape::None,
+                (Escape::Csi, 0x40..=0x7e) => Escape::None,
+                (Escape::Csi, _) => Escape::Csi,
+            };
+        }
+
+        self.inner.write_all(&kept)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Plain output when asked for, or when stdout is not a terminal
+pub fn stdout(plain: bool) -> Box<dyn Write> {
+    let out = io::stdout();
+    if plain || !out.is_terminal() {
+        Box::new(Plain::new(out.lock()))
+    } else {
+        Box::new(out.lock())
+    }
+}
+
+// Turns a computed report into output. Implement this to add a format; the
+// built in ones are picked with --format.
+pub trait OutputRenderer {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()>;
+}
+
+pub const FORMATS: [&str; 7] = ["table", "json", "csv", "tsv", "markdown", "bar", "template"];
+
+pub fn from_format(
+    format: &str,
+    template: Option<&str>,
+    legs: bool,
+    highlight: &[Highlight],
+    emoji: Option<Emoji>,
+) -> Result<Box<dyn OutputRenderer>, String> {
+    match format {
+        "table" => Ok(Box::new(Table {
+            legs,
+            highlight: highlight.to_vec(),
+            emoji,
+        })),
+        "json" => Ok(Box::new(Json)),
+        "csv" => Ok(Box::new(Csv)),
+        "tsv" => Ok(Box::new(Tsv)),
+        "markdown" | "md" => Ok(Box::new(Markdown)),
+        "bar" => Ok(Box::new(Bar)),
+        "template" => match template {
+            Some(t) => Ok(Box::new(Template::new(t)?)),
+            None => Err("--format template needs --template".to_string()),
+        },
+        _ => Err(format!(
+            "unknown format {}, expected one of {}",
+            format,
+            FORMATS.join(", ")
+        )),
+    }
+}
+
+fn header(name: &str, closed: Option<chrono::NaiveDate>) -> String {
+    match closed {
+        Some(d) => format!("{} (closed {})", name, d.format("%Y-%m-%d")),
+        None => name.to_string(),
+    }
+}
+
+// "+$321.40 (+0.80%) today"
+fn day_move(account: &AccountReport) -> String {
+    let sign = if account.net.amount < 0.0 { "-" } else { "+" };
+    format!(
+        "{}{}{} ({:+.2}%) today\x1b[0m",
+        clr(account.net.amount),
+        sign,
+        account.net.abs(),
+        account.net_pct
+    )
+}
+
+// With `legs`, each strategy's positions are listed under its combined line.
+// `highlight` rules from config restyle cells or rows by their value, and
+// with `emoji` each position leads with its icon.
+pub struct Table {
+    pub legs: bool,
+    pub highlight: Vec<Highlight>,
+    pub emoji: Option<Emoji>,
+}
+
+// --emoji marks each position 🟢, 🔴 or ⚪ by today's move and the summary 🚀
+// or 📉 on a big day, for pasting into chat topics and status bars. Within
+// `flat` percent either way is flat; `big` is how far a big day moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emoji {
+    pub flat: f64,
+    pub big: f64,
+}
+
+impl Emoji {
+    pub fn new(conf: &Config) -> Self {
+        Emoji {
+            flat: conf.emoji_flat_pct.unwrap_or(0.1),
+            big: conf.emoji_big_pct.unwrap_or(1.0),
+        }
+    }
+
+    // unknown moves, like a failed quote's, are flat
+    pub fn row(&self, pct: f64) -> &'static str {
+        if pct > self.flat {
+            glyph::pick("🟢", "+ ")
+        } else if pct < -self.flat {
+            glyph::pick("🔴", "- ")
+        } else {
+            glyph::pick("⚪", "  ")
+        }
+    }
+
+    pub fn summary(&self, pct: f64) -> Option<&'static str> {
+        if pct >= self.big {
+            Some(glyph::pick("🚀", "++"))
+        } else if pct <= -self.big {
+            Some(glyph::pick("📉", "--"))
+        } else {
+            None
+        }
+    }
+}
+
+// Puts `prefix` after the line's leading tab, where the emoji column goes
+fn lead(line: String, prefix: &str) -> String {
+    line.replacen('\t', &format!("\t{}", prefix), 1)
+}
+
+const COLORS: [(&str, &str); 9] = [
+    ("black", "\x1b[38;5;0m"),
+    ("red", "\x1b[38;5;1m"),
+    ("green", "\x1b[38;5;2m"),
+    ("yellow", "\x1b[38;5;3m"),
+    ("blue", "\x1b[38;5;4m"),
+    ("magenta", "\x1b[38;5;5m"),
+    ("cyan", "\x1b[38;5;6m"),
+    ("white", "\x1b[38;5;7m"),
+    ("grey", "\x1b[38;5;8m"),
+];
+
+// the columns a rule can test, all of them numbers
+const NUMERIC: [&str; 12] = [
+    "shares",
+    "cost",
+    "price",
+    "invested",
+    "value",
+    "account_weight",
+    "portfolio_weight",
+    "net",
+    "net_pct",
+    "total",
+    "total_pct",
+    "margin",
+];
+
+pub fn check_highlights(rules: &[Highlight]) -> Result<(), String> {
+    for h in rules.iter() {
+        if !NUMERIC.contains(&h.column.as_str()) {
+            return Err(format!(
+                "highlight column {} isn't one of {}",
+                h.column,
+                NUMERIC.join(", ")
+            ));
+        }
+        if let Some(c) = h.color.as_deref() {
+            if !COLORS.iter().any(|(name, _)| *name == c) {
+                return Err(format!("highlight color {} isn't a color name", c));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn number(r: &Row, column: &str) -> Option<f64> {
+    let v = match column {
+        "shares" => r.shares,
+        "cost" => r.cost.amount,
+        "price" => r.price.amount,
+        "invested" => r.invested.amount,
+        "value" => r.value.amount,
+        "account_weight" => r.account_weight,
+        "portfolio_weight" => r.portfolio_weight,
+        "net" => r.net.amount,
+        "net_pct" => r.net_pct,
+        "total" => r.total.amount,
+        "total_pct" => r.total_pct,
+        "margin" => r.margin.as_ref()?.amount,
+        _ => return None,
+    };
+    v.is_finite().then_some(v)
+}
+
+fn matches(h: &Highlight, v: f64) -> bool {
+    h.above.is_none_or(|a| v > a)
+        && h.below.is_none_or(|b| v < b)
+        && h.between.is_none_or(|[lo, hi]| lo <= v && v <= hi)
+}
+
+// The escapes for `column` of the row from the first rule that applies to
+// it, or `default`
+fn styled(rules: &[Highlight], r: &Row, column: &str, default: &str) -> String {
+    let rule = rules.iter().find(|h| {
+        (h.row || h.column == column) && number(r, &h.column).is_some_and(|v| matches(h, v))
+    });
+    let Some(h) = rule else {
+        return default.to_string();
+    };
+    let color = h
+        .color
+        .as_deref()
+        .and_then(|c| COLORS.iter().find(|(name, _)| *name == c))
+        .map_or(default, |(_, code)| *code);
+    if h.bold {
+        format!("\x1b[1m{}", color)
+    } else {
+        color.to_string()
+    }
+}
+
+fn table_row(r: &Row, label: &str, w: usize, rules: &[Highlight]) -> String {
+    if r.failed {
+        return format!(
+            "\t{:<w$}  {:>10}  {:>8}  \x1b[38;5;1m{:>8}  fetch failed\x1b[0m",
+            label, r.shares, r.cost, "N/A"
+        );
+    }
+    // old quotes are greyed out and say how old they are, as are ones that
+    // aren't live at all
+    let (dim, note) = match (r.status, r.stale, &r.time) {
+        (Status::Delisted, _, t) => (
+            DIM,
+            format!(
+                "  \x1b[38;5;3mdelisted?{}\x1b[0m",
+                t.map(|t| format!(" last {}", time::stamp(&t)))
+                    .unwrap_or_default()
+            ),
+        ),
+        (Status::Halted, _, _) => (DIM, "  \x1b[38;5;3mhalted\x1b[0m".to_string()),
+        (_, true, Some(t)) => (DIM, format!("  {}stale, {}\x1b[0m", DIM, time::stamp(t))),
+        _ => ("", String::new()),
+    };
+
+    let cell = |column: &str, default: &str| styled(rules, r, column, default);
+
+    format!("\t{}{:<w$}\x1b[0m  {}{:>10}\x1b[0m  {}{:>8}\x1b[0m  {}{:>8}\x1b[0m  {}{:>10}\x1b[0m  {}{:>10}\x1b[0m  {}{:>6.2}%\x1b[0m  {}{:>6.2}%\x1b[0m  {}{:>7}\x1b[0m  {}{:>6.2}%\x1b[0m  {}{:>10}\x1b[0m  {}{:>6.2}%\x1b[0m{}",
+            cell("label", ""),
+            label,
+            cell("shares", ""),
+            r.shares,
+            cell("cost", ""),
+            r.cost,
+            cell("price", dim),
+            r.price,
+            cell("invested", ""),
+            r.invested,
+            cell("value", ""),
+            r.value,
+            cell("account_weight", ""),
+            r.account_weight,
+            cell("portfolio_weight", ""),
+            r.portfolio_weight,
+            cell("net", &clr(r.net.amount)),
+            r.net,
+            cell("net_pct", &clr(r.net_pct)),
+            r.net_pct,
+            cell("total", &clr(r.total.amount)),
+            r.total,
+            cell("total_pct", &clr(r.total_pct)),
+            r.total_pct,
+            note,
+    )
+}
+
+// A subtotal under the rows: cost, value, today's change and the gain, with
+// the weight columns left empty
+fn total_row(label: &str, t: &Totals, w: usize) -> String {
+    format!("\t\x1b[1m{:<w$}  {:>10}  {:>8}  {:>8}  {:>10}  {:>10}\x1b[0m  {:>7}  {:>7}  {}{:>7}\x1b[0m  {}{:>6.2}%\x1b[0m  {}{:>10}\x1b[0m  {}{:>6.2}%\x1b[0m",
+            label,
+            "",
+            "",
+            "",
+            t.invested,
+            t.total,
+            "",
+            "",
+            clr(t.net.amount),
+            t.net,
+            clr(t.net_pct),
+            t.net_pct,
+            clr(t.gain.amount),
+            t.gain,
+            clr(t.gain_pct),
+            t.gain_pct,
+    )
+}
+
+fn strategy_row(s: &Strategy, w: usize) -> String {
+    format!("\t\x1b[1m{:<w$}\x1b[0m  {:>10}  {:>8}  {:>8}  {:>10}  {:>10}  {:>6.2}%  {:>6.2}%  {}{:>7}\x1b[0m  {}{:>6.2}%\x1b[0m  {}{:>10}\x1b[0m  {}{:>6.2}%\x1b[0m",
+            s.name,
+            "",
+            "",
+            "",
+            s.invested,
+            s.value,
+            s.account_weight,
+            s.portfolio_weight,
+            clr(s.net.amount),
+            s.net,
+            clr(s.net_pct),
+            s.net_pct,
+            clr(s.total.amount),
+            s.total,
+            clr(s.total_pct),
+            s.total_pct,
+    )
+}
+
+impl OutputRenderer for Table {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        let labels = report
+            .accounts
+            .iter()
+            .flat_map(|a| a.rows.iter())
+            .map(|r| r.label.chars().count() + if r.strategy.is_some() { 2 } else { 0 });
+        let names = report
+            .accounts
+            .iter()
+            .flat_map(|a| a.strategies.iter())
+            .map(|s| s.name.chars().count());
+        let other = report.other.iter().map(|o| o.name.chars().count());
+        let w = labels.chain(names).chain(other).fold(6, usize::max);
+        // an emoji is two columns wide, and a space after it
+        let pad = if self.emoji.is_some() { "   " } else { "" };
+        let icon = |r: &Row| match self.emoji {
+            Some(e) => format!("{} ", e.row(r.net_pct)),
+            None => String::new(),
+        };
+
+        for account in report.accounts.iter() {
+            if account.rows.is_empty() {
+                writeln!(out, "{}:", header(&account.name, account.closed))?;
+            } else {
+                writeln!(
+                    out,
+                    "{}: {}",
+                    header(&account.name, account.closed),
+                    day_move(account)
+                )?;
+            }
+            writeln!(
+                out,
+                "\x1b[1m\t{pad}{:<w$}  {:>10}  {:>8}  {:>8}  {:>10}  {:>10}  {:>7}  {:>7}  {:>7}  {:>7}  {:>10}  {:>7}\x1b[0m",
+                "Symbol", "Shares", "Cost", "Price", "Invested", "Value", "Acct %", "Port %", "Net", "Net %", "Total", "Total %"
+            )?;
+
+            for (i, r) in account.rows.iter().enumerate() {
+                let strategy = match &r.strategy {
+                    Some(name) => account.strategies.iter().find(|s| &s.name == name),
+                    None => None,
+                };
+
+                match strategy {
+                    None => writeln!(
+                        out,
+                        "{}",
+                        lead(table_row(r, &r.label, w, &self.highlight), &icon(r))
+                    )?,
+                    // a strategy is printed where its first leg would be
+                    Some(s) if s.legs[0] == i => {
+                        writeln!(out, "{}", lead(strategy_row(s, w), pad))?;
+                        if self.legs {
+                            for &leg in s.legs.iter() {
+                                let r = &account.rows[leg];
+                                writeln!(
+                                    out,
+                                    "{}",
+                                    lead(
+                                        table_row(r, &format!("  {}", r.label), w, &self.highlight),
+                                        &icon(r)
+                                    )
+                                )?;
+                            }
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if let Some(cash) = &account.cash {
+                writeln!(
+                    out,
+                    "\t{pad}{:<w$}  {:>10}  {:>8}  {:>8}  {:>10}  {:>10}  {:>6.2}%  {:>6.2}%",
+                    "Cash",
+                    "",
+                    "",
+                    "",
+                    "",
+                    cash,
+                    cash.pct_of(&account.total()),
+                    cash.pct_of(&report.totals.total)
+                )?;
+            }
+            // a lone position is its own total
+            if account.rows.len() + account.cash.is_some() as usize > 1 {
+                writeln!(
+                    out,
+                    "{}",
+                    lead(
+                        total_row("Total", &Totals::of(std::slice::from_ref(account)), w),
+                        pad
+                    )
+                )?;
+            }
+        }
+
+        if report.accounts.len() > 1 {
+            writeln!(
+                out,
+                "{}",
+                lead(total_row("Portfolio", &report.totals, w), pad)
+            )?;
+        }
+
+        if !report.other.is_empty() {
+            writeln!(out, "Other assets:")?;
+            writeln!(
+                out,
+                "\x1b[1m\t{pad}{:<w$}  {:>10}  {:>8}\x1b[0m",
+                "Asset", "Value", "Per year"
+            )?;
+            for o in report.other.iter() {
+                writeln!(
+                    out,
+                    "\t{pad}{:<w$}  {:>10}  {}{:>+7.1}%\x1b[0m",
+                    o.name,
+                    o.value,
+                    clr(o.appreciation),
+                    o.appreciation
+                )?;
+            }
+        }
+        if let Some(n) = &report.net_worth {
+            writeln!(out, "\x1b[1mNet worth: {}\x1b[0m", n)?;
+        }
+
+        if let Some(t) = &report.as_of {
+            writeln!(out, "{}as of {}\x1b[0m", DIM, time::stamp(t))?;
+        }
+        for w in report.warnings.iter() {
+            writeln!(out, "\x1b[38;5;3m! {}\x1b[0m", w)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Json;
+
+impl OutputRenderer for Json {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)
+    }
+}
+
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// Just the portfolio's value and today's change on one line, for status
+// bars like polybar or waybar
+pub struct Summary {
+    pub emoji: Option<Emoji>,
+}
+
+impl OutputRenderer for Summary {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        let t = &report.totals;
+        if let Some(icon) = self.emoji.and_then(|e| e.summary(t.net_pct)) {
+            write!(out, "{} ", icon)?;
+        }
+        writeln!(out, "{} {} ({:+.2}%)", t.total, t.net, t.net_pct)
+    }
+}
+
+// --accessible reads well with a screen reader: a labelled sentence per
+// value, signs as words and no table to navigate
+pub struct Accessible;
+
+fn spoken_pct(p: f64) -> String {
+    if !p.is_finite() {
+        return "unknown".to_string();
+    }
+    match p {
+        p if p < 0.0 => format!("minus {:.2} percent", -p),
+        p if p > 0.0 => format!("plus {:.2} percent", p),
+        _ => "0 percent".to_string(),
+    }
+}
+
+fn spoken_money(m: &Money) -> String {
+    let unit = match m.currency.as_str() {
+        "USD" => "dollars",
+        "EUR" => "euros",
+        "GBP" => "pounds",
+        c => c,
+    };
+    let amount = m.round().amount;
+    if amount < 0.0 {
+        format!("minus {:.2} {}", -amount, unit)
+    } else {
+        format!("{:.2} {}", amount, unit)
+    }
+}
+
+fn spoken_row(r: &Row) -> String {
+    if r.failed {
+        return format!(
+            "{}, {} shares, no price, the quote failed",
+            r.label, r.shares
+        );
+    }
+    let mut line = format!(
+        "{} price {}, day change {}, {} shares worth {}, gain {}, {}",
+        r.label,
+        spoken_money(&r.price),
+        spoken_pct(r.net_pct),
+        r.shares,
+        spoken_money(&r.value),
+        spoken_money(&r.total),
+        spoken_pct(r.total_pct)
+    );
+    match (r.status, r.stale, &r.time) {
+        (Status::Halted, _, _) => line.push_str(", trading halted"),
+        (Status::Delisted, _, _) => line.push_str(", possibly delisted, at its last known price"),
+        (_, true, Some(t)) => line.push_str(&format!(", quote from {}", time::stamp(t))),
+        _ => {}
+    }
+    line
+}
+
+impl OutputRenderer for Accessible {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        for account in report.accounts.iter() {
+            let closed = match account.closed {
+                Some(d) => format!(", closed {}", d.format("%Y-%m-%d")),
+                None => String::new(),
+            };
+            writeln!(
+                out,
+                "Account {}{}, value {}, day change {}, {}.",
+                account.name,
+                closed,
+                spoken_money(&account.total()),
+                spoken_money(&account.net),
+                spoken_pct(account.net_pct)
+            )?;
+            for r in account.rows.iter() {
+                writeln!(out, "{}.", spoken_row(r))?;
+            }
+            if let Some(cash) = &account.cash {
+                writeln!(out, "Cash {}.", spoken_money(cash))?;
+            }
+        }
+
+        let t = &report.totals;
+        writeln!(
+            out,
+            "Portfolio value {}, day change {}, {}, gain {}, {}.",
+            spoken_money(&t.total),
+            spoken_money(&t.net),
+            spoken_pct(t.net_pct),
+            spoken_money(&t.gain),
+            spoken_pct(t.gain_pct)
+        )?;
+        for o in report.other.iter() {
+            writeln!(
+                out,
+                "Other asset {}, value {}, {} a year.",
+                o.name,
+                spoken_money(&o.value),
+                spoken_pct(o.appreciation)
+            )?;
+        }
+        if let Some(n) = &report.net_worth {
+            writeln!(out, "Net worth {}.", spoken_money(n))?;
+        }
+        if let Some(t) = &report.as_of {
+            writeln!(out, "Prices as of {}.", time::stamp(t))?;
+        }
+        for w in report.warnings.iter() {
+            writeln!(out, "Warning: {}.", w)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Csv;
+
+impl OutputRenderer for Csv {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", FIELDS.join(","))?;
+
+        for account in report.accounts.iter() {
+            for r in account.rows.iter() {
+                let line = FIELDS
+                    .iter()
+                    .map(|f| csv_field(&field(&account.name, r, f)))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", line.join(","))?;
+            }
+            let t = Totals::of(std::slice::from_ref(account));
+            let line = FIELDS
+                .iter()
+                .map(|f| csv_field(&total_field(&account.name, &t, f)))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", line.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Tab separated with a header row, for cut and awk. The columns are the
+// template fields in order; new ones are only ever added at the end. Each
+// account's positions are followed by a line of its own with no symbol.
+pub struct Tsv;
+
+impl OutputRenderer for Tsv {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", FIELDS.join("\t"))?;
+
+        for account in report.accounts.iter() {
+            for r in account.rows.iter() {
+                let line = FIELDS
+                    .iter()
+                    .map(|f| field(&account.name, r, f).replace(['\t', '\n'], " "))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", line.join("\t"))?;
+            }
+            let t = Totals::of(std::slice::from_ref(account));
+            let line = FIELDS
+                .iter()
+                .map(|f| total_field(&account.name, &t, f).replace(['\t', '\n'], " "))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", line.join("\t"))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Markdown;
+
+impl OutputRenderer for Markdown {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        for (i, account) in report.accounts.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "### {}\n", header(&account.name, account.closed))?;
+            writeln!(
+                out,
+                "| Symbol | Shares | Cost | Price | Invested | Value | Acct % | Port % | Net | Net % | Total | Total % |"
+            )?;
+            writeln!(
+                out,
+                "|:-------|-------:|-----:|------:|---------:|------:|-------:|-------:|----:|------:|------:|--------:|"
+            )?;
+
+            for r in account.rows.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} | {} | {:.2}% | {:.2}% | {} | {:.2}% | {} | {:.2}% |",
+                    r.label.replace('|', "\\|"),
+                    r.shares,
+                    r.cost,
+                    r.price,
+                    r.invested,
+                    r.value,
+                    r.account_weight,
+                    r.portfolio_weight,
+                    r.net,
+                    r.net_pct,
+                    r.total,
+                    r.total_pct
+                )?;
+            }
+            writeln!(
+                out,
+                "| | | | | **{}** | **{}** | | | | | | |",
+                account.invested, account.value
+            )?;
+        }
+
+        if !report.other.is_empty() {
+            writeln!(out, "\n### Other assets\n")?;
+            writeln!(out, "| Asset | Value | Per year |")?;
+            writeln!(out, "|:------|------:|---------:|")?;
+            for o in report.other.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {:+.1}% |",
+                    o.name.replace('|', "\\|"),
+                    o.value,
+                    o.appreciation
+                )?;
+            }
+        }
+        if let Some(n) = &report.net_worth {
+            writeln!(out, "\n**Net worth:** {}", n)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Horizontal bars of each position's total gain, scaled to the terminal
+pub struct Bar;
+
+impl OutputRenderer for Bar {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        let w = report
+            .accounts
+            .iter()
+            .flat_map(|a| a.rows.iter())
+            .map(|r| r.label.chars().count())
+            .fold(6, usize::max);
+
+        let max = report
+            .accounts
+            .iter()
+            .flat_map(|a| a.rows.iter())
+            .map(|r| r.total_pct.abs())
+            .filter(|p| p.is_finite())
+            .fold(0.0, f64::max);
+
+        let (cols, _) = size();
+        let room = cols.saturating_sub(w + 14).max(10) as f64;
+
+        for account in report.accounts.iter() {
+            writeln!(out, "{}:", header(&account.name, account.closed))?;
+
+            for r in account.rows.iter() {
+                let len = if max > 0.0 && r.total_pct.is_finite() {
+                    (r.total_pct.abs() / max * room).round() as usize
+                } else {
+                    0
+                };
+
+                writeln!(
+                    out,
+                    "\t{:<w$} {}{:>7.2}% {}\x1b[0m",
+                    r.label,
+                    clr(r.total_pct),
+                    r.total_pct,
+                    glyph::pick("█", "#").repeat(len)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const FIELDS: [&str; 20] = [
+    "account",
+    "symbol",
+    "label",
+    "shares",
+    "cost",
+    "price",
+    "invested",
+    "value",
+    "account_weight",
+    "portfolio_weight",
+    "net",
+    "net_pct",
+    "total",
+    "total_pct",
+    "currency",
+    "time",
+    "stale",
+    "strategy",
+    "margin",
+    "status",
+];
+
+// One row value as text, shared by the template, csv and tsv output
+fn field(account: &str, r: &Row, name: &str) -> String {
+    match name {
+        "account" => account.to_string(),
+        "symbol" => r.symbol.clone(),
+        "label" => r.label.clone(),
+        "shares" => r.shares.to_string(),
+        "cost" => format!("{:.2}", r.cost.amount),
+        "price" => format!("{:.2}", r.price.amount),
+        "invested" => format!("{:.2}", r.invested.amount),
+        "value" => format!("{:.2}", r.value.amount),
+        "account_weight" => format!("{:.2}", r.account_weight),
+        "portfolio_weight" => format!("{:.2}", r.portfolio_weight),
+        "net" => format!("{:.2}", r.net.amount),
+        "net_pct" => format!("{:.2}", r.net_pct),
+        "total" => format!("{:.2}", r.total.amount),
+        "total_pct" => format!("{:.2}", r.total_pct),
+        "currency" => r.price.currency.clone(),
+        "time" => r.time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        "stale" => r.stale.to_string(),
+        "status" => format!("{:?}", r.status).to_lowercase(),
+        "strategy" => r.strategy.clone().unwrap_or_default(),
+        "margin" => r
+            .margin
+            .as_ref()
+            .map(|m| format!("{:.2}", m.amount))
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+// An account's line in csv and tsv, under its positions: no symbol, and the
+// totals the table's Total row shows, cash included in the value
+fn total_field(account: &str, t: &Totals, name: &str) -> String {
+    match name {
+        "account" => account.to_string(),
+        "label" => "Total".to_string(),
+        "invested" => format!("{:.2}", t.invested.amount),
+        "value" => format!("{:.2}", t.total.amount),
+        "net" => format!("{:.2}", t.net.amount),
+        "net_pct" => format!("{:.2}", t.net_pct),
+        "total" => format!("{:.2}", t.gain.amount),
+        "total_pct" => format!("{:.2}", t.gain_pct),
+        "currency" => t.total.currency.clone(),
+        _ => String::new(),
+    }
+}
+
+// One line per position with `{field}` placeholders filled in, e.g.
+// --template '{symbol} {price} ({net_pct}%)'
+pub struct Template {
+    template: String,
+}
+
+impl Template {
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed {{ in template {}", template))?;
+            let field = &rest[start + 1..start + end];
+            if !FIELDS.contains(&field) {
+                return Err(format!(
+                    "unknown template field {}, expected one of {}",
+                    field,
+                    FIELDS.join(", ")
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(Template {
+            template: template.replace("\\t", "\t"),
+        })
+    }
+
+    fn fill(&self, account: &str, r: &Row) -> String {
+        FIELDS.iter().fold(self.template.clone(), |line, f| {
+            line.replace(&format!("{{{}}}", f), &field(account, r, f))
+        })
+    }
+}
+
+impl OutputRenderer for Template {
+    fn render(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        for account in report.accounts.iter() {
+            for r in account.rows.iter() {
+                writeln!(out, "{}", self.fill(&account.name, r))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// One dim line under the table saying where the numbers came from
+pub fn footer(f: &Fetched, network: usize, cached: usize) -> String {
+    let mut parts = vec![];
+    if f.answered.is_empty() {
+        parts.push("no quotes fetched".to_string());
+    } else {
+        let from = f
+            .answered
+            .iter()
+            .map(|(p, n)| format!("{} ({})", p, n))
+            .collect::<Vec<_>>();
+        parts.push(format!("quotes from {}", from.join(", ")));
+    }
+    parts.push(format!(
+        "{} requests over the network, {} not modified",
+        network, cached
+    ));
+    parts.push(format!("fetched in {:.2}s", f.elapsed.as_secs_f64()));
+    if !f.failed.is_empty() {
+        parts.push(format!(
+            "{} failed ({})",
+            f.failed.len(),
+            f.failed.join(", ")
+        ));
+    }
+
+    format!("{}{}\x1b[0m", DIM, parts.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::money::Money;
+
+    #[test]
+    fn plain_strips_escapes() {
+        let mut out = Plain::new(Vec::new());
+        write!(out, "\x1b[1mAAPL\x1b[0m\t{}$1.00\x1b[0m", clr(-1.0)).unwrap();
+        assert_eq!(String::from_utf8(out.inner).unwrap(), "AAPL\t$1.00");
+    }
+
+    #[test]
+    fn plain_handles_split_sequences() {
+        let mut out = Plain::new(Vec::new());
+        out.write_all(b"a\x1b").unwrap();
+        out.write_all(b"[38;5").unwrap();
+        out.write_all(b";1mb").unwrap();
+        assert_eq!(out.inner, b"ab");
+    }
+
+    #[test]
+    fn summary_is_one_plain_line() {
+        let report = Report {
+            accounts: vec![],
+            as_of: None,
+            warnings: vec![],
+            other: vec![],
+            net_worth: None,
+            totals: Totals {
+                total: Money::usd(6235.68),
+                net: Money::usd(-6.7),
+                net_pct: -0.1073,
+                ..Default::default()
+            },
+        };
+
+        let mut out = Vec::new();
+        Summary { emoji: None }.render(&report, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "$6235.68 $-6.70 (-0.11%)\n"
+        );
+    }
+
+    #[test]
+    fn emoji_by_thresholds() {
+        let e = Emoji {
+            flat: 0.1,
+            big: 1.0,
+        };
+        assert_eq!((e.row(0.5), e.row(-0.5), e.row(0.05)), ("🟢", "🔴", "⚪"));
+        assert_eq!(e.row(f64::NAN), "⚪");
+        assert_eq!((e.summary(1.2), e.summary(-1.0)), (Some("🚀"), Some("📉")));
+        assert_eq!(e.summary(0.5), None);
+
+        // the icon takes the place of the padding after the tab
+        assert_eq!(lead("\tVTI  1".to_string(), "🟢 "), "\t🟢 VTI  1");
+    }
+
+    fn row() -> Row {
+        Row {
+            id: "VTI".to_string(),
+            symbol: "VTI".to_string(),
+            label: "VTI".to_string(),
+            shares: 50.0,
+            cost: Money::usd(200.0),
+            price: Money::usd(250.0),
+            invested: Money::usd(10000.0),
+            value: Money::usd(12500.0),
+            account_weight: 100.0,
+            portfolio_weight: 100.0,
+            net: Money::usd(25.0),
+            net_pct: 0.2,
+            total: Money::usd(2500.0),
+            total_pct: 25.0,
+            time: None,
+            stale: false,
+            strategy: None,
+            margin: None,
+            failed: false,
+            status: Status::Trading,
+            cost_known: true,
+        }
+    }
+
+    #[test]
+    fn highlights_style_cells_or_rows_by_value() {
+        let row = row();
+        let flat = Highlight {
+            column: "net_pct".to_string(),
+            between: Some([-1.0, 1.0]),
+            color: Some("yellow".to_string()),
+            ..Default::default()
+        };
+        let big = Highlight {
+            column: "value".to_string(),
+            above: Some(10000.0),
+            bold: true,
+            row: true,
+            ..Default::default()
+        };
+        let green = clr(1.0);
+
+        let rules = [flat.clone()];
+        assert_eq!(styled(&rules, &row, "net_pct", &green), "\x1b[38;5;3m");
+        assert_eq!(styled(&rules, &row, "total_pct", &green), green);
+
+        // the first rule that applies wins, a row rule applies to every cell
+        let rules = [big, flat];
+        assert_eq!(styled(&rules, &row, "label", ""), "\x1b[1m");
+        assert_eq!(
+            styled(&rules, &row, "net_pct", &green),
+            format!("\x1b[1m{}", green)
+        );
+
+        assert!(check_highlights(&rules).is_ok());
+        let label = Highlight {
+            column: "label".to_string(),
+            ..Default::default()
+        };
+        assert!(check_highlights(&[label]).is_err());
+    }
+
+    #[test]
+    fn accessible_lines_say_each_value() {
+        let mut r = row();
+        r.net_pct = -1.2;
+        assert_eq!(
+            spoken_row(&r),
+            "VTI price 250.00 dollars, day change minus 1.20 percent, 50 shares worth \
+             12500.00 dollars, gain 2500.00 dollars, plus 25.00 percent"
+        );
+        r.failed = true;
+        assert_eq!(spoken_row(&r), "VTI, 50 shares, no price, the quote failed");
+        assert_eq!(spoken_money(&Money::new(-3.5, "CAD")), "minus 3.50 CAD");
+    }
+
+    #[test]
+    fn footer_lists_sources_and_failures() {
+        let f = Fetched {
+            answered: [("nasdaq".to_string(), 2), ("yahoo".to_string(), 1)].into(),
+            failed: vec!["MSFT".to_string()],
+            elapsed: Duration::from_millis(410),
+        };
+
+        let mut out = Plain::new(Vec::new());
+        write!(out, "{}", footer(&f, 2, 1)).unwrap();
+        assert_eq!(
+            String::from_utf8(out.inner).unwrap(),
+            "quotes from nasdaq (2), yahoo (1); 2 requests over the network, 1 not modified; \
+             fetched in 0.41s; 1 failed (MSFT)"
+        );
+    }
+
+    #[test]
+    fn tsv_header_keeps_its_column_order() {
+        let report = Report {
+            accounts: vec![],
+            as_of: None,
+            warnings: vec![],
+            other: vec![],
+            net_worth: None,
+            totals: Totals::default(),
+        };
+
+        let mut out = Vec::new();
+        Tsv.render(&report, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "account\tsymbol\tlabel\tshares\tcost\tprice\tinvested\tvalue\taccount_weight\t\
+             portfolio_weight\tnet\tnet_pct\ttotal\ttotal_pct\tcurrency\ttime\tstale\tstrategy\t\
+             margin\tstatus\n"
+        );
+    }
+
+    #[test]
+    fn csv_ends_each_account_with_its_totals() {
+        let accounts = vec![AccountReport {
+            id: "Brokerage".to_string(),
+            name: "Brokerage".to_string(),
+            closed: None,
+            rows: vec![row()],
+            invested: Money::usd(10000.0),
+            value: Money::usd(12500.0),
+            cash: Some(Money::usd(500.0)),
+            net: Money::usd(25.0),
+            net_pct: 0.2,
+            gain: Money::usd(2500.0),
+            gain_pct: 25.0,
+            strategies: vec![],
+        }];
+        let report = Report {
+            totals: Totals::of(&accounts),
+            accounts,
+            as_of: None,
+            warnings: vec![],
+            other: vec![],
+            net_worth: None,
+        };
+
+        let mut out = Vec::new();
+        Csv.render(&report, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("Brokerage,VTI,VTI,50,"));
+        assert_eq!(
+            lines[2],
+            "Brokerage,,Total,,,,10000.00,13000.00,,,25.00,0.20,2500.00,25.00,USD,,,,,"
+        );
+    }
+}
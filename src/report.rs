@@ -0,0 +1,942 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::config::{ChangeBasis, Config};
+use crate::fx;
+use crate::money::Money;
+use crate::options;
+use crate::portfolio::{Account, Liability, OtherAsset, Stock};
+use crate::provider::{Quote, Status};
+use crate::time;
+
+// One computed table row. Renderers only ever see these numbers, never the
+// raw holdings and quotes they came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct Row {
+    pub id: String,
+    pub symbol: String,
+    pub label: String,
+    pub shares: f64,
+    // average price paid per share
+    pub cost: Money,
+    pub price: Money,
+    // what was paid for the whole position, cost times shares
+    pub invested: Money,
+    // what the position is worth now, price times shares
+    pub value: Money,
+    // share of the account's and of the whole report's market value
+    pub account_weight: f64,
+    pub portfolio_weight: f64,
+    // today's change for the whole position
+    pub net: Money,
+    pub net_pct: f64,
+    // gain over the cost basis
+    pub total: Money,
+    pub total_pct: f64,
+    // when the quote was last traded, and whether that is too long ago
+    pub time: Option<DateTime<Utc>>,
+    pub stale: bool,
+    pub strategy: Option<String>,
+    // margin posted for futures, per contract times contracts held
+    pub margin: Option<Money>,
+    // no quote could be fetched, so the price and everything from it is
+    // unknown and left out of the account's totals
+    pub failed: bool,
+    // halted or delisted, when the price isn't a live one
+    pub status: Status,
+    // false for metal or a wallet's coins with no price paid given, whose
+    // invested and gain are unknown and left out of the totals that sum them
+    pub cost_known: bool,
+}
+
+// Positions grouped under one `strategy` name, with their combined numbers
+#[derive(Debug, Clone, Serialize)]
+pub struct Strategy {
+    pub name: String,
+    // indices into the account's rows
+    pub legs: Vec<usize>,
+    pub invested: Money,
+    pub value: Money,
+    pub account_weight: f64,
+    pub portfolio_weight: f64,
+    pub net: Money,
+    pub net_pct: f64,
+    pub total: Money,
+    pub total_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountReport {
+    pub id: String,
+    pub name: String,
+    pub closed: Option<NaiveDate>,
+    pub rows: Vec<Row>,
+    // cost and market value of every row together
+    pub invested: Money,
+    pub value: Money,
+    // uninvested, from `cash = ...` on the account
+    pub cash: Option<Money>,
+    // today's change across the account, against its value at the reference
+    pub net: Money,
+    pub net_pct: f64,
+    // market value over cost
+    pub gain: Money,
+    pub gain_pct: f64,
+    pub strategies: Vec<Strategy>,
+}
+
+impl AccountReport {
+    // the positions and the cash together
+    pub fn total(&self) -> Money {
+        match &self.cash {
+            Some(c) => sum([self.value.clone(), c.clone()].into_iter()),
+            None => self.value.clone(),
+        }
+    }
+}
+
+// An asset valued by hand, at its estimate for today
+#[derive(Debug, Clone, Serialize)]
+pub struct OtherRow {
+    pub name: String,
+    pub value: Money,
+    // assumed percent a year
+    pub appreciation: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub accounts: Vec<AccountReport>,
+    // the most recent quote time across all rows
+    pub as_of: Option<DateTime<Utc>>,
+    // things that need attention, like options about to expire
+    pub warnings: Vec<String>,
+    pub other: Vec<OtherRow>,
+    // every account and other asset less what is owed, when asked for
+    pub net_worth: Option<Money>,
+    pub totals: Totals,
+}
+
+// Every shown account together
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+    pub invested: Money,
+    // of the positions
+    pub value: Money,
+    pub cash: Money,
+    // positions and cash
+    pub total: Money,
+    // today's change
+    pub net: Money,
+    pub net_pct: f64,
+    // over what was paid
+    pub gain: Money,
+    pub gain_pct: f64,
+}
+
+impl Totals {
+    pub fn of(accounts: &[AccountReport]) -> Self {
+        let invested = sum(accounts.iter().map(|a| a.invested.clone()));
+        let value = sum(accounts.iter().map(|a| a.value.clone()));
+        let cash = sum(accounts.iter().filter_map(|a| a.cash.clone()));
+        let net = sum(accounts.iter().map(|a| a.net.clone()));
+        // over the positions with a known cost, as each account has it
+        let gain = sum(accounts.iter().map(|a| a.gain.clone())).round();
+
+        Totals {
+            net_pct: net.pct_of(&(value.clone() - net.clone())),
+            gain_pct: gain.pct_of(&invested),
+            total: sum(accounts.iter().map(|a| a.total())),
+            invested,
+            value,
+            cash,
+            net,
+            gain,
+        }
+    }
+}
+
+impl Report {
+    pub fn add_other(
+        &mut self,
+        assets: &[OtherAsset],
+        liabilities: &[Liability],
+        today: NaiveDate,
+        net_worth: bool,
+    ) {
+        self.other = assets
+            .iter()
+            .map(|a| OtherRow {
+                name: a.name.clone(),
+                value: a.value_on(today),
+                appreciation: a.appreciation,
+            })
+            .collect();
+
+        if net_worth {
+            let accounts = self.accounts.iter().map(|a| a.total());
+            let other = self.other.iter().map(|o| o.value.clone());
+            let owed = liabilities.iter().map(|l| -l.balance_on(today));
+            self.net_worth = Some(sum(accounts.chain(other).chain(owed)));
+        }
+    }
+}
+
+pub fn label(stock: &Stock, tickers: bool) -> String {
+    match &stock.alias {
+        Some(alias) if tickers => format!("{} ({})", alias, stock.symbol),
+        Some(alias) => alias.clone(),
+        None => stock.symbol.clone(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    // show "alias (TICKER)" rather than just the alias
+    pub tickers: bool,
+    pub change_basis: ChangeBasis,
+    pub share_decimals: Option<usize>,
+    pub stale_after: Duration,
+    pub expiry_warning: Duration,
+    // what the account and portfolio totals are in, see fx
+    pub base_currency: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            tickers: false,
+            change_basis: ChangeBasis::default(),
+            share_decimals: None,
+            stale_after: Duration::minutes(30),
+            expiry_warning: Duration::days(7),
+            base_currency: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn new(conf: &Config, tickers: bool) -> Self {
+        Options {
+            tickers,
+            change_basis: conf.change_basis,
+            share_decimals: conf.share_decimals,
+            stale_after: conf
+                .stale_after
+                .map(Duration::minutes)
+                .unwrap_or(Options::default().stale_after),
+            expiry_warning: conf
+                .expiry_warning_days
+                .map(Duration::days)
+                .unwrap_or(Options::default().expiry_warning),
+            base_currency: conf.base_currency.clone(),
+        }
+    }
+}
+
+fn round_shares(shares: f64, decimals: Option<usize>) -> f64 {
+    match decimals {
+        Some(d) => {
+            let scale = 10f64.powi(d as i32);
+            (shares * scale).round() / scale
+        }
+        None => shares,
+    }
+}
+
+// Today's move per share and as a percentage. Against the previous close,
+// which is price - change, or against today's open when asked and the
+// provider sent one. A rise is always positive.
+pub fn day_change(quote: &Quote, basis: ChangeBasis) -> (Money, f64) {
+    let reference = match (basis, &quote.open) {
+        (ChangeBasis::Open, Some(open)) => open.clone(),
+        _ => quote.price.clone() - quote.change.clone(),
+    };
+
+    let diff = quote.price.clone() - reference.clone();
+    let pct = diff.pct_of(&reference);
+
+    (diff, pct)
+}
+
+// Adds up amounts that should share a currency; a mix comes out as NaN. The
+// total is rounded like each part so float error doesn't show up as cents.
+pub fn sum(amounts: impl Iterator<Item = Money>) -> Money {
+    amounts
+        .reduce(|a, b| a + b)
+        .map(|m| m.round())
+        .unwrap_or_default()
+}
+
+// Quotes without a time can't be judged and are taken as current
+pub fn is_stale(time: Option<DateTime<Utc>>, now: DateTime<Utc>, max: Duration) -> bool {
+    time.is_some_and(|t| now - t > max)
+}
+
+// In order of each strategy's first leg
+fn strategies(rows: &[Row]) -> Vec<Strategy> {
+    let mut names: Vec<&str> = vec![];
+    for name in rows.iter().filter_map(|r| r.strategy.as_deref()) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let legs = (0..rows.len())
+                .filter(|&i| rows[i].strategy.as_deref() == Some(name))
+                .collect::<Vec<_>>();
+            let leg_rows = || legs.iter().map(|&i| &rows[i]);
+            let costed = || leg_rows().filter(|r| r.cost_known);
+
+            let invested = sum(costed().map(|r| r.invested.clone()));
+            let value = sum(leg_rows().map(|r| r.value.clone()));
+            let net = sum(leg_rows().map(|r| r.net.clone()));
+            let total = sum(costed().map(|r| r.total.clone()));
+
+            Strategy {
+                name: name.to_string(),
+                account_weight: leg_rows().map(|r| r.account_weight).sum(),
+                portfolio_weight: leg_rows().map(|r| r.portfolio_weight).sum(),
+                net_pct: net.pct_of(&(value.clone() - net.clone())),
+                total_pct: total.pct_of(&invested.abs()),
+                legs,
+                invested,
+                value,
+                net,
+                total,
+            }
+        })
+        .collect()
+}
+
+// Expiry coming up or passed, and short options in the money, which can be
+// assigned at any time
+pub fn expiry_warnings(
+    account: &str,
+    stock: &Stock,
+    underlying: Option<f64>,
+    today: NaiveDate,
+    within: Duration,
+) -> Vec<String> {
+    // options carry their expiry in the symbol, futures in the holdings file
+    let contract = options::parse(&stock.symbol);
+    let expiry = match stock.expiry.or(contract.as_ref().map(|c| c.expiry)) {
+        Some(e) => e,
+        None => return vec![],
+    };
+    let mut warnings = vec![];
+    let name = format!("{} {}", account, label(stock, true));
+
+    let left = expiry - today;
+    if left < Duration::zero() {
+        warnings.push(format!("{} expired on {}", name, expiry.format("%Y-%m-%d")));
+        return warnings;
+    }
+    if left <= within {
+        warnings.push(format!(
+            "{} expires in {} days ({})",
+            name,
+            left.num_days(),
+            expiry.format("%Y-%m-%d")
+        ));
+    }
+
+    if let (Some(contract), Some(price)) = (contract, underlying.filter(|p| *p > 0.0)) {
+        if stock.amount < 0.0 && contract.in_the_money(price) {
+            warnings.push(format!(
+                "{} is short and in the money ({} at {:.2}, strike {:.2}), assignment risk",
+                name, contract.underlying, price, contract.strike
+            ));
+        }
+    }
+
+    warnings
+}
+
+// Say so when a price isn't live, rather than let it pass for today's
+fn status_warning(
+    account: &str,
+    stock: &Stock,
+    stock_info: &HashMap<String, Quote>,
+) -> Option<String> {
+    let quote = stock_info.get(&stock.symbol)?;
+    let name = format!("{} {}", account, label(stock, true));
+    match quote.status {
+        Status::Trading => None,
+        Status::Halted => Some(format!(
+            "{} is halted, its price is the last trade before the halt",
+            name
+        )),
+        Status::Delisted => Some(format!(
+            "{} isn't listed by any provider any more, so it's at its last price{}; if it was delisted, `stocks remove \"{}\" {}` takes it out and keeps its history",
+            name,
+            quote
+                .time
+                .map(|t| format!(" from {}", time::date(&t).format("%Y-%m-%d")))
+                .unwrap_or_default(),
+            account,
+            stock.symbol
+        )),
+    }
+}
+
+pub fn build(accounts: &[&Account], stock_info: &HashMap<String, Quote>, opts: &Options) -> Report {
+    let now = Utc::now();
+    let today = time::today();
+    let base = fx::base(opts.base_currency.as_deref(), accounts.iter().copied());
+    let mut warnings = vec![];
+    let mut accounts = accounts
+        .iter()
+        .map(|account| {
+            for stock in account.stocks.iter() {
+                let underlying = options::parse(&stock.symbol)
+                    .and_then(|c| stock_info.get(&c.underlying))
+                    .map(|q| q.price.amount);
+                warnings.extend(expiry_warnings(
+                    &account.name,
+                    stock,
+                    underlying,
+                    today,
+                    opts.expiry_warning,
+                ));
+                if let Some(w) = status_warning(&account.name, stock, stock_info) {
+                    warnings.push(w);
+                }
+            }
+
+            let rows = account
+                .stocks
+                .iter()
+                .map(|stock| {
+                    let failed = !stock_info.contains_key(&stock.symbol);
+                    let quote = stock_info
+                        .get(&stock.symbol)
+                        .cloned()
+                        .unwrap_or_else(|| Quote {
+                            price: Money::new(f64::NAN, &stock.cost_basis.currency),
+                            change: Money::new(f64::NAN, &stock.cost_basis.currency),
+                            ..Default::default()
+                        });
+                    let (change, net_perc) = day_change(&quote, opts.change_basis);
+                    let price = quote.price;
+
+                    // contracts move by the multiplier, plain shares by one
+                    let units = stock.amount * stock.multiplier;
+                    // each position rounds to cents first, the way statements do
+                    let total_net = ((price.clone() - stock.cost_basis.clone()) * units).round();
+                    let invested = (stock.cost_basis.clone() * units).round();
+
+                    // against the size of the position, so a short that gains is positive
+                    let total_perc = total_net.pct_of(&invested.abs());
+
+                    Row {
+                        id: stock.id.clone(),
+                        symbol: stock.symbol.clone(),
+                        label: label(stock, opts.tickers),
+                        shares: round_shares(stock.amount, opts.share_decimals),
+                        cost: stock.cost_basis.clone(),
+                        invested,
+                        value: (price.clone() * units).round(),
+                        account_weight: 0.0,
+                        portfolio_weight: 0.0,
+                        price,
+                        net: (change * units).round(),
+                        net_pct: net_perc,
+                        total: total_net,
+                        total_pct: total_perc,
+                        time: quote.time,
+                        stale: is_stale(quote.time, now, opts.stale_after),
+                        strategy: stock.strategy.clone(),
+                        margin: stock.margin.clone().map(|m| m * stock.amount.abs()),
+                        failed,
+                        status: quote.status,
+                        cost_known: !stock.cost_basis.is_nan(),
+                    }
+                })
+                .collect::<Vec<Row>>();
+
+            // rows stay in their listing's currency, the sums are in the base
+            let to_base = |m: &Money| fx::convert(m, &base, stock_info);
+            let priced = || rows.iter().filter(|r| !r.failed);
+            let costed = || priced().filter(|r| r.cost_known);
+            let value = sum(priced().map(|r| to_base(&r.value)));
+            let invested = sum(costed().map(|r| to_base(&r.invested)));
+            let net = sum(priced().map(|r| to_base(&r.net)));
+            let gain = sum(costed().map(|r| to_base(&r.total)));
+
+            AccountReport {
+                id: account.id.clone(),
+                name: account.name.clone(),
+                closed: account.closed,
+                net_pct: net.pct_of(&(value.clone() - net.clone())),
+                gain_pct: gain.pct_of(&invested.abs()),
+                invested,
+                value,
+                cash: account.cash.as_ref().map(to_base),
+                net,
+                gain,
+                rows,
+                strategies: vec![],
+            }
+        })
+        .collect::<Vec<AccountReport>>();
+
+    // weights need every price, so they come once all rows are built; cash
+    // counts, so they show what a rebalance would work with
+    let portfolio = sum(accounts.iter().map(|a| a.total()));
+    for account in accounts.iter_mut() {
+        let total = account.total();
+        for row in account.rows.iter_mut() {
+            let value = fx::convert(&row.value, &base, stock_info);
+            row.account_weight = value.pct_of(&total);
+            row.portfolio_weight = value.pct_of(&portfolio);
+        }
+        account.strategies = strategies(&account.rows);
+    }
+
+    // the rows' quotes, not the exchange rates, which trade around the clock
+    let as_of = accounts
+        .iter()
+        .flat_map(|a| a.rows.iter())
+        .filter_map(|r| r.time)
+        .max();
+
+    Report {
+        totals: Totals::of(&accounts),
+        accounts,
+        as_of,
+        warnings,
+        other: vec![],
+        net_worth: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Asset;
+    use chrono::TimeZone;
+
+    fn quote(price: f64, change: f64, open: Option<f64>) -> Quote {
+        Quote {
+            price: Money::usd(price),
+            change: Money::usd(change),
+            open: open.map(Money::usd),
+            time: None,
+            status: Status::Trading,
+        }
+    }
+
+    #[test]
+    fn change_against_previous_close() {
+        // closed at 90 yesterday, 100 now
+        let (diff, pct) = day_change(&quote(100.0, 10.0, None), ChangeBasis::Close);
+        assert_eq!(diff.amount, 10.0);
+        assert!((pct - 11.111).abs() < 1e-3);
+
+        let (diff, pct) = day_change(&quote(90.0, -10.0, None), ChangeBasis::Close);
+        assert_eq!(diff.amount, -10.0);
+        assert!((pct - -10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn change_against_open() {
+        let (diff, pct) = day_change(&quote(100.0, 10.0, Some(95.0)), ChangeBasis::Open);
+        assert_eq!(diff.amount, 5.0);
+        assert!((pct - 5.263).abs() < 1e-3);
+    }
+
+    #[test]
+    fn open_falls_back_to_close_when_missing() {
+        let (diff, _) = day_change(&quote(100.0, 10.0, None), ChangeBasis::Open);
+        assert_eq!(diff.amount, 10.0);
+    }
+
+    #[test]
+    fn unchanged_is_zero() {
+        let (diff, pct) = day_change(&quote(50.0, 0.0, None), ChangeBasis::Close);
+        assert_eq!(diff.amount, 0.0);
+        assert_eq!(pct, 0.0);
+    }
+
+    fn stock(symbol: &str, amount: f64, cost: f64) -> Stock {
+        let multiplier = if options::parse(symbol).is_some() {
+            options::MULTIPLIER
+        } else {
+            1.0
+        };
+        Stock {
+            symbol: symbol.to_string(),
+            amount,
+            cost_basis: Money::usd(cost),
+            multiplier,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn value_is_price_times_shares() {
+        let account = Account::test(
+            "Brokerage",
+            vec![stock("AAPL", 10.0, 150.0), stock("VTI", 2.5, 200.0)],
+        );
+        let quotes = HashMap::from([
+            ("AAPL".to_string(), quote(190.0, 1.0, None)),
+            ("VTI".to_string(), quote(220.0, -2.0, None)),
+        ]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let rows = &report.accounts[0].rows;
+
+        assert_eq!(rows[0].value, Money::usd(1900.0));
+        assert_eq!(rows[1].value, Money::usd(550.0));
+        assert_eq!(report.accounts[0].value, Money::usd(2450.0));
+        assert_eq!(rows[1].invested, Money::usd(500.0));
+        assert_eq!(report.accounts[0].invested, Money::usd(2000.0));
+        // 10*1 - 2.5*2 today, against 1890 + 555 yesterday
+        assert_eq!(report.accounts[0].net, Money::usd(5.0));
+        assert!((report.accounts[0].net_pct - 5.0 * 100.0 / 2445.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cash_counts_towards_totals_and_weights() {
+        let brokerage = Account {
+            cash: Some(Money::usd(500.0)),
+            ..Account::test("Brokerage", vec![stock("AAPL", 10.0, 150.0)])
+        };
+        let ira = Account::test("IRA", vec![stock("AAPL", 5.0, 100.0)]);
+        let quotes = HashMap::from([("AAPL".to_string(), quote(150.0, 1.0, None))]);
+
+        let report = build(&[&brokerage, &ira], &quotes, &Options::default());
+        let a = &report.accounts[0];
+        assert_eq!(a.total(), Money::usd(2000.0));
+        assert_eq!(a.rows[0].account_weight, 75.0);
+        assert!((a.rows[0].portfolio_weight - 1500.0 * 100.0 / 2750.0).abs() < 1e-9);
+        assert_eq!(report.accounts[1].gain, Money::usd(250.0));
+
+        let t = &report.totals;
+        assert_eq!(t.value, Money::usd(2250.0));
+        assert_eq!(t.cash, Money::usd(500.0));
+        assert_eq!(t.total, Money::usd(2750.0));
+        assert_eq!(t.net, Money::usd(15.0));
+        assert_eq!(t.gain, Money::usd(250.0));
+        assert!((t.gain_pct - 12.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn failed_quotes_are_marked_and_left_out_of_totals() {
+        let account = Account::test(
+            "Brokerage",
+            vec![stock("AAPL", 10.0, 150.0), stock("NOPE", 5.0, 10.0)],
+        );
+        let quotes = HashMap::from([("AAPL".to_string(), quote(190.0, 1.0, None))]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let rows = &report.accounts[0].rows;
+
+        assert!(!rows[0].failed);
+        assert!(rows[1].failed);
+        assert!(rows[1].value.is_nan());
+        assert_eq!(report.accounts[0].value, Money::usd(1900.0));
+        assert_eq!(report.accounts[0].invested, Money::usd(1500.0));
+        assert_eq!(report.totals.total, Money::usd(1900.0));
+    }
+
+    #[test]
+    fn unknown_costs_are_left_out_of_invested_and_gain() {
+        let account = Account::test(
+            "Brokerage",
+            vec![
+                stock("AAPL", 10.0, 150.0),
+                Stock {
+                    asset: Asset::Metal,
+                    ..stock("XAU", 12.5, f64::NAN)
+                },
+            ],
+        );
+        let quotes = HashMap::from([
+            ("AAPL".to_string(), quote(190.0, 1.0, None)),
+            ("XAU".to_string(), quote(2000.0, 0.0, None)),
+        ]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let a = &report.accounts[0];
+        assert!(!a.rows[1].cost_known);
+        assert!(a.rows[1].total.is_nan());
+        assert_eq!(a.value, Money::usd(26900.0));
+        assert_eq!(a.invested, Money::usd(1500.0));
+        assert_eq!(a.gain, Money::usd(400.0));
+        assert_eq!(report.totals.gain, Money::usd(400.0));
+        assert!((report.totals.gain_pct - 26.667).abs() < 1e-3);
+
+        // coins in a wallet with no price given are the same
+        let wallet = Account {
+            stocks: vec![
+                stock("AAPL", 10.0, 150.0),
+                Stock {
+                    asset: Asset::Crypto,
+                    ..stock("BTC", 0.5, f64::NAN)
+                },
+            ],
+            ..account
+        };
+        let quotes = HashMap::from([
+            ("AAPL".to_string(), quote(190.0, 1.0, None)),
+            ("BTC".to_string(), quote(40000.0, 0.0, None)),
+        ]);
+        let report = build(&[&wallet], &quotes, &Options::default());
+        assert_eq!(report.accounts[0].value, Money::usd(21900.0));
+        assert_eq!(report.totals.invested, Money::usd(1500.0));
+        assert_eq!(report.totals.gain, Money::usd(400.0));
+    }
+
+    #[test]
+    fn listings_abroad_are_totalled_at_the_exchange_rate() {
+        let account = Account {
+            cash: Some(Money::usd(100.0)),
+            ..Account::test(
+                "Brokerage",
+                vec![
+                    stock("AAPL", 10.0, 150.0),
+                    Stock {
+                        cost_basis: Money::new(50.0, "CAD"),
+                        ..stock("SHOP.TO", 20.0, 0.0)
+                    },
+                ],
+            )
+        };
+        let mut quotes = HashMap::from([
+            ("AAPL".to_string(), quote(190.0, 1.0, None)),
+            (
+                "SHOP.TO".to_string(),
+                Quote {
+                    price: Money::new(100.0, "CAD"),
+                    change: Money::new(2.0, "CAD"),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        // with no rate the totals can't be known
+        let report = build(&[&account], &quotes, &Options::default());
+        assert!(report.totals.value.is_nan());
+
+        quotes.insert(fx::pair("CAD", "USD"), quote(0.75, 0.0, None));
+        let report = build(&[&account], &quotes, &Options::default());
+        let a = &report.accounts[0];
+        assert_eq!(a.rows[1].value, Money::new(2000.0, "CAD"));
+        assert_eq!(a.value, Money::usd(3400.0));
+        assert_eq!(a.invested, Money::usd(2250.0));
+        assert_eq!(a.gain, Money::usd(1150.0));
+        assert_eq!(a.net, Money::usd(40.0));
+        assert_eq!(report.totals.total, Money::usd(3500.0));
+        assert!((a.rows[1].account_weight - 1500.0 / 35.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn delisted_and_halted_count_at_their_last_price() {
+        let account = Account::test(
+            "Brokerage",
+            vec![stock("GONE", 10.0, 15.0), stock("HALT", 5.0, 10.0)],
+        );
+        let last = Utc.with_ymd_and_hms(2024, 2, 9, 21, 0, 0).unwrap();
+        let quotes = HashMap::from([
+            (
+                "GONE".to_string(),
+                Quote {
+                    time: Some(last),
+                    status: Status::Delisted,
+                    ..quote(12.0, 0.0, None)
+                },
+            ),
+            (
+                "HALT".to_string(),
+                Quote {
+                    status: Status::Halted,
+                    ..quote(8.0, -1.0, None)
+                },
+            ),
+        ]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let rows = &report.accounts[0].rows;
+        assert_eq!(rows[0].status, Status::Delisted);
+        assert!(!rows[0].failed);
+        assert_eq!(report.totals.total, Money::usd(160.0));
+        assert_eq!(
+            report.warnings,
+            [
+                "Brokerage GONE isn't listed by any provider any more, so it's at its last price from 2024-02-09; if it was delisted, `stocks remove \"Brokerage\" GONE` takes it out and keeps its history",
+                "Brokerage HALT is halted, its price is the last trade before the halt",
+            ]
+        );
+    }
+
+    #[test]
+    fn futures_move_by_their_multiplier() {
+        let es = Stock {
+            asset: Asset::Future,
+            multiplier: 50.0,
+            margin: Some(Money::usd(12000.0)),
+            expiry: Some(NaiveDate::from_ymd_opt(2023, 12, 15).unwrap()),
+            ..stock("ESZ23.CME", -2.0, 4500.0)
+        };
+        let account = Account::test("a", vec![es]);
+        let quotes = HashMap::from([("ESZ23.CME".to_string(), quote(4510.0, 4.0, None))]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let row = &report.accounts[0].rows[0];
+
+        // short two contracts at $50 a point
+        assert_eq!(row.value, Money::usd(-451000.0));
+        assert_eq!(row.total, Money::usd(-1000.0));
+        assert_eq!(row.net, Money::usd(-400.0));
+        assert_eq!(row.margin, Some(Money::usd(24000.0)));
+
+        let today = NaiveDate::from_ymd_opt(2023, 12, 12).unwrap();
+        let w = expiry_warnings("a", &account.stocks[0], None, today, Duration::days(7));
+        assert_eq!(w.len(), 1);
+        assert!(w[0].contains("expires in 3 days"));
+    }
+
+    #[test]
+    fn weights_cover_account_and_portfolio() {
+        let a = Account::test("a", vec![stock("AAPL", 1.0, 1.0), stock("VTI", 3.0, 1.0)]);
+        let b = Account::test("b", vec![stock("AAPL", 4.0, 1.0)]);
+        let quotes = HashMap::from([
+            ("AAPL".to_string(), quote(10.0, 0.0, None)),
+            ("VTI".to_string(), quote(10.0, 0.0, None)),
+        ]);
+
+        let report = build(&[&a, &b], &quotes, &Options::default());
+        let rows = &report.accounts[0].rows;
+
+        assert_eq!(rows[0].account_weight, 25.0);
+        assert_eq!(rows[1].account_weight, 75.0);
+        assert_eq!(rows[1].portfolio_weight, 37.5);
+        assert_eq!(report.accounts[1].rows[0].account_weight, 100.0);
+        assert_eq!(report.accounts[1].rows[0].portfolio_weight, 50.0);
+    }
+
+    #[test]
+    fn other_assets_appreciate_into_net_worth() {
+        let account = Account::test("a", vec![stock("AAPL", 10.0, 150.0)]);
+        let quotes = HashMap::from([("AAPL".to_string(), quote(200.0, 0.0, None))]);
+        let house = OtherAsset {
+            name: "house".to_string(),
+            value: Money::usd(400000.0),
+            as_of: NaiveDate::from_ymd_opt(2021, 1, 1),
+            appreciation: 5.0,
+        };
+        let today = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+
+        let mut report = build(&[&account], &quotes, &Options::default());
+        report.add_other(&[house], &[], today, false);
+        // two years at 5%, give or take the leap day
+        assert!((report.other[0].value.amount - 441000.0).abs() < 50.0);
+        assert!(report.net_worth.is_none());
+
+        // without an as_of date the value is taken as current
+        let car = OtherAsset {
+            name: "car".to_string(),
+            value: Money::usd(20000.0),
+            as_of: None,
+            appreciation: -15.0,
+        };
+        let loan = Liability {
+            name: "loan".to_string(),
+            balance: Money::usd(5000.0),
+            rate: 0.0,
+            payment: None,
+            as_of: None,
+        };
+        report.add_other(&[car], &[loan], today, true);
+        assert_eq!(report.net_worth, Some(Money::usd(17000.0)));
+    }
+
+    #[test]
+    fn strategy_combines_its_legs() {
+        let mut call = stock("AAPL231215C00190000", -1.0, 3.0);
+        call.strategy = Some("covered call".to_string());
+        let mut shares = stock("AAPL", 100.0, 150.0);
+        shares.strategy = Some("covered call".to_string());
+
+        let account = Account::test("a", vec![shares, stock("VTI", 1.0, 100.0), call]);
+        let quotes = HashMap::from([
+            ("AAPL".to_string(), quote(190.0, 0.0, None)),
+            ("VTI".to_string(), quote(100.0, 0.0, None)),
+            ("AAPL231215C00190000".to_string(), quote(5.0, 0.0, None)),
+        ]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let strategies = &report.accounts[0].strategies;
+
+        assert_eq!(strategies.len(), 1);
+        assert_eq!(strategies[0].legs, [0, 2]);
+        // +4000 on the shares, -200 on the short call's 100 shares
+        assert_eq!(strategies[0].total, Money::usd(3800.0));
+        assert_eq!(strategies[0].invested, Money::usd(14700.0));
+    }
+
+    #[test]
+    fn warns_about_expiring_and_assignable_options() {
+        let today = NaiveDate::from_ymd_opt(2023, 12, 11).unwrap();
+        let week = Duration::days(7);
+        let short_call = stock("AAPL231215C00190000", -1.0, 3.0);
+
+        let w = expiry_warnings("a", &short_call, Some(195.0), today, week);
+        assert_eq!(w.len(), 2);
+        assert!(w[0].contains("expires in 4 days"));
+        assert!(w[1].contains("assignment risk"));
+
+        // out of the money and far away is fine
+        let later = NaiveDate::from_ymd_opt(2023, 11, 1).unwrap();
+        assert!(expiry_warnings("a", &short_call, Some(180.0), later, week).is_empty());
+
+        // a long call in the money can't be assigned
+        let long_call = stock("AAPL231215C00190000", 1.0, 3.0);
+        assert!(expiry_warnings("a", &long_call, Some(195.0), later, week).is_empty());
+
+        let past = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        assert!(expiry_warnings("a", &long_call, None, past, week)[0].contains("expired"));
+        assert!(expiry_warnings("a", &stock("AAPL", 1.0, 1.0), None, today, week).is_empty());
+    }
+
+    #[test]
+    fn stale_after_threshold() {
+        let now = Utc::now();
+        let max = Duration::minutes(30);
+
+        assert!(!is_stale(None, now, max));
+        assert!(!is_stale(Some(now - Duration::minutes(5)), now, max));
+        assert!(is_stale(Some(now - Duration::hours(20)), now, max));
+    }
+
+    #[test]
+    fn shares_round_only_when_asked() {
+        assert_eq!(round_shares(3.51234, None), 3.51234);
+        assert_eq!(round_shares(3.51234, Some(2)), 3.51);
+        assert_eq!(round_shares(3.5, Some(0)), 4.0);
+    }
+
+    #[test]
+    fn totals_add_up_rounded_positions() {
+        let account = Account::test("a", vec![stock("AAA", 3.0, 1.0), stock("BBB", 3.0, 1.0)]);
+        let quotes = HashMap::from([
+            ("AAA".to_string(), quote(0.1, 0.0, None)),
+            ("BBB".to_string(), quote(33.335, 0.0, None)),
+        ]);
+
+        let report = build(&[&account], &quotes, &Options::default());
+        let a = &report.accounts[0];
+        assert_eq!(a.rows[0].value, Money::usd(0.3));
+        assert_eq!(a.rows[1].value, Money::usd(100.01));
+        // not 100.30500000000001
+        assert_eq!(a.value, Money::usd(100.31));
+    }
+}
@@ -0,0 +1,451 @@
+// Metrics and alerts too particular for the built-in reports, from a Rhai
+// script named by `script` in config. It can define either of
+//
+//     fn position(p) { #{ upside: (250.0 - p.price) / p.price * 100.0 } }
+//     fn portfolio(r) { if r.gain_pct < -10.0 { alert("down", "down " + r.gain_pct + "%") } }
+//
+// `position` is called for every row and `portfolio` once with the totals
+// and each account's rows. Both return a map of metric names to values, or
+// nothing, and `alert(id, message)` flags something for `stocks metrics`.
+// Watch and the daemon tell about an alert once by its id, so the message can
+// carry the latest numbers; `alert(message)` is its own id.
+//
+// Simpler alerts need no script: `alerts` in config names conditions in the
+// same language, like "VTI.net_pct < -2 && portfolio.net_pct < -1.5", where
+// each symbol held is a variable (or `symbols["BRK.B"]`) and `portfolio` has
+// the totals.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::alerts::Alert;
+use crate::config::Config;
+use crate::profile;
+use crate::report::{AccountReport, Report, Row};
+
+// enough for any sensible script, and stops one stuck in a loop
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionMetrics {
+    pub account: String,
+    pub symbol: String,
+    pub values: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Metrics {
+    pub positions: Vec<PositionMetrics>,
+    pub portfolio: BTreeMap<String, Value>,
+    pub alerts: Vec<Alert>,
+}
+
+// `script` as given in config, relative paths from the config directory
+pub fn script_path(script: &str) -> String {
+    if Path::new(script).is_absolute() {
+        script.to_string()
+    } else {
+        format!("{}/{}", profile::config_dir(), script)
+    }
+}
+
+pub fn run(path: &str, report: &Report) -> Result<Metrics, String> {
+    let source =
+        std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    run_source(&source, report).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn run_source(source: &str, report: &Report) -> Result<Metrics, String> {
+    let alerts = Rc::new(RefCell::new(vec![]));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let sink = alerts.clone();
+    engine.register_fn("alert", move |msg: &str| {
+        sink.borrow_mut().push(Alert::named(msg))
+    });
+    let sink = alerts.clone();
+    engine.register_fn("alert", move |id: &str, msg: &str| {
+        sink.borrow_mut().push(Alert {
+            id: id.to_string(),
+            message: msg.to_string(),
+        })
+    });
+
+    let ast = engine.compile(source).map_err(|e| e.to_string())?;
+    // the top level runs before each call, so constants it sets up are there
+    // for the functions as `global::NAME`
+    let mut scope = Scope::new();
+
+    let mut metrics = Metrics::default();
+
+    if defines(&ast, "position") {
+        for a in report.accounts.iter() {
+            for r in a.rows.iter() {
+                let out = engine
+                    .call_fn::<Dynamic>(&mut scope, &ast, "position", (row(a, r),))
+                    .map_err(|e| format!("position() on {}: {}", r.symbol, e))?;
+                let values = values(out, "position")?;
+                if !values.is_empty() {
+                    metrics.positions.push(PositionMetrics {
+                        account: a.name.clone(),
+                        symbol: r.symbol.clone(),
+                        values,
+                    });
+                }
+            }
+        }
+    }
+
+    if defines(&ast, "portfolio") {
+        let out = engine
+            .call_fn::<Dynamic>(&mut scope, &ast, "portfolio", (portfolio(report),))
+            .map_err(|e| format!("portfolio(): {}", e))?;
+        metrics.portfolio = values(out, "portfolio")?;
+    }
+
+    metrics.alerts = alerts.take();
+    Ok(metrics)
+}
+
+// The names of the `alerts` conditions that hold for the report
+pub fn conditions(
+    alerts: &BTreeMap<String, String>,
+    report: &Report,
+) -> Result<Vec<String>, String> {
+    if alerts.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let held = held(report);
+    let mut scope = Scope::new();
+    scope.push_constant("portfolio", portfolio(report));
+    // tickers are upper case, so they can't take the place of a keyword
+    for (symbol, m) in held.iter() {
+        let mut chars = symbol.chars();
+        let name = chars.next().is_some_and(|c| c.is_ascii_uppercase())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if name {
+            scope.push_constant(symbol.as_str(), m.clone());
+        }
+    }
+    scope.push_constant(
+        "symbols",
+        held.into_iter()
+            .map(|(s, m)| (s.into(), m.into()))
+            .collect::<Map>(),
+    );
+
+    let mut raised = vec![];
+    for (name, expr) in alerts.iter() {
+        let holds = engine
+            .eval_expression_with_scope::<Dynamic>(&mut scope, expr)
+            .map_err(|e| format!("alert {}: {}", name, e))?
+            .as_bool()
+            .map_err(|_| format!("alert {}: should be true or false", name))?;
+        if holds {
+            raised.push(name.clone());
+        }
+    }
+    Ok(raised)
+}
+
+// Every symbol across the accounts, counted together where it's in several
+fn held(report: &Report) -> BTreeMap<String, Map> {
+    let mut rows: BTreeMap<String, Vec<&Row>> = BTreeMap::new();
+    for r in report.accounts.iter().flat_map(|a| a.rows.iter()) {
+        rows.entry(r.symbol.clone()).or_default().push(r);
+    }
+    rows.into_iter()
+        .map(|(symbol, rows)| {
+            let total = |f: fn(&Row) -> f64| rows.iter().map(|r| f(r)).sum::<f64>();
+            let (value, net, invested) = (
+                total(|r| r.value.amount),
+                total(|r| r.net.amount),
+                total(|r| r.invested.amount),
+            );
+            let mut m = Map::new();
+            m.insert("symbol".into(), symbol.clone().into());
+            m.insert("price".into(), rows[0].price.amount.into());
+            m.insert("shares".into(), total(|r| r.shares).into());
+            m.insert("value".into(), value.into());
+            m.insert("net".into(), net.into());
+            m.insert("net_pct".into(), (net * 100.0 / (value - net)).into());
+            m.insert("gain".into(), (value - invested).into());
+            m.insert(
+                "gain_pct".into(),
+                ((value - invested) * 100.0 / invested).into(),
+            );
+            m.insert(
+                "portfolio_weight".into(),
+                total(|r| r.portfolio_weight).into(),
+            );
+            m.insert("failed".into(), rows.iter().any(|r| r.failed).into());
+            (symbol, m)
+        })
+        .collect()
+}
+
+// What watch and the daemon alert on: the conditions and the script's alerts
+pub fn alerts(conf: &Config, report: &Report) -> Result<Vec<Alert>, String> {
+    let mut raised = conditions(&conf.alerts, report)?
+        .iter()
+        .map(|name| Alert::named(name))
+        .collect::<Vec<_>>();
+    if let Some(path) = conf.script.as_deref().map(script_path) {
+        raised.extend(run(&path, report)?.alerts);
+    }
+    Ok(raised)
+}
+
+fn defines(ast: &AST, name: &str) -> bool {
+    ast.iter_functions()
+        .any(|f| f.name == name && f.params.len() == 1)
+}
+
+fn row(a: &AccountReport, r: &Row) -> Map {
+    let mut m = Map::new();
+    m.insert("account".into(), a.name.clone().into());
+    m.insert("id".into(), r.id.clone().into());
+    m.insert("symbol".into(), r.symbol.clone().into());
+    m.insert("label".into(), r.label.clone().into());
+    m.insert("shares".into(), r.shares.into());
+    m.insert("cost".into(), r.cost.amount.into());
+    m.insert("price".into(), r.price.amount.into());
+    m.insert("invested".into(), r.invested.amount.into());
+    m.insert("value".into(), r.value.amount.into());
+    m.insert("net".into(), r.net.amount.into());
+    m.insert("net_pct".into(), r.net_pct.into());
+    m.insert("gain".into(), r.total.amount.into());
+    m.insert("gain_pct".into(), r.total_pct.into());
+    m.insert("account_weight".into(), r.account_weight.into());
+    m.insert("portfolio_weight".into(), r.portfolio_weight.into());
+    m.insert("failed".into(), r.failed.into());
+    m
+}
+
+fn portfolio(report: &Report) -> Map {
+    let t = &report.totals;
+    let mut m = Map::new();
+    m.insert("invested".into(), t.invested.amount.into());
+    m.insert("value".into(), t.value.amount.into());
+    m.insert("cash".into(), t.cash.amount.into());
+    m.insert("total".into(), t.total.amount.into());
+    m.insert("net".into(), t.net.amount.into());
+    m.insert("net_pct".into(), t.net_pct.into());
+    m.insert("gain".into(), t.gain.amount.into());
+    m.insert("gain_pct".into(), t.gain_pct.into());
+
+    let accounts = report
+        .accounts
+        .iter()
+        .map(|a| {
+            let mut acct = Map::new();
+            acct.insert("name".into(), a.name.clone().into());
+            acct.insert("id".into(), a.id.clone().into());
+            acct.insert("value".into(), a.value.amount.into());
+            acct.insert("total".into(), a.total().amount.into());
+            acct.insert("gain".into(), a.gain.amount.into());
+            acct.insert("gain_pct".into(), a.gain_pct.into());
+            acct.insert("net".into(), a.net.amount.into());
+            acct.insert("net_pct".into(), a.net_pct.into());
+            let rows = a
+                .rows
+                .iter()
+                .map(|r| Dynamic::from_map(row(a, r)))
+                .collect::<Array>();
+            acct.insert("positions".into(), rows.into());
+            Dynamic::from_map(acct)
+        })
+        .collect::<Array>();
+    m.insert("accounts".into(), accounts.into());
+    m
+}
+
+fn values(out: Dynamic, function: &str) -> Result<BTreeMap<String, Value>, String> {
+    if out.is_unit() {
+        return Ok(BTreeMap::new());
+    }
+    let map = out
+        .try_cast::<Map>()
+        .ok_or_else(|| format!("{}() should return a map of metrics", function))?;
+    Ok(map
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), json(v)))
+        .collect())
+}
+
+fn json(v: Dynamic) -> Value {
+    if let Ok(f) = v.as_float() {
+        return serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or(Value::Null);
+    }
+    if let Ok(i) = v.as_int() {
+        return Value::from(i);
+    }
+    if let Ok(b) = v.as_bool() {
+        return Value::Bool(b);
+    }
+    if v.is_unit() {
+        return Value::Null;
+    }
+    Value::String(v.to_string())
+}
+
+fn text(v: &Value) -> String {
+    match v {
+        Value::Number(n) if n.is_f64() => format!("{:.2}", n.as_f64().unwrap_or_default()),
+        Value::String(s) => s.clone(),
+        Value::Null => "n/a".to_string(),
+        v => v.to_string(),
+    }
+}
+
+fn pairs(values: &BTreeMap<String, Value>) -> String {
+    values
+        .iter()
+        .map(|(k, v)| format!("{} {}", k, text(v)))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+pub fn render(m: &Metrics, out: &mut dyn Write) -> io::Result<()> {
+    let mut account = None;
+    for p in m.positions.iter() {
+        if account != Some(&p.account) {
+            writeln!(out, "\x1b[1m{}\x1b[0m", p.account)?;
+            account = Some(&p.account);
+        }
+        writeln!(out, "\t{:<8}  {}", p.symbol, pairs(&p.values))?;
+    }
+    if !m.portfolio.is_empty() {
+        writeln!(out, "\x1b[1mPortfolio\x1b[0m\t{}", pairs(&m.portfolio))?;
+    }
+    for a in m.alerts.iter() {
+        writeln!(out, "\x1b[31malert\x1b[0m  {}", a.message)?;
+    }
+    if m.positions.is_empty() && m.portfolio.is_empty() && m.alerts.is_empty() {
+        writeln!(out, "the script returned no metrics")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::portfolio::{Account, Stock};
+    use crate::provider::Quote;
+    use crate::report;
+    use std::collections::HashMap;
+
+    #[test]
+    fn scripts_compute_metrics_and_raise_alerts() {
+        let account = Account {
+            id: "brk".to_string(),
+            ..Account::test(
+                "Brokerage",
+                vec![Stock {
+                    symbol: "AAPL".to_string(),
+                    amount: 10.0,
+                    cost_basis: Money::usd(200.0),
+                    ..Default::default()
+                }],
+            )
+        };
+        let quotes = HashMap::from([(
+            "AAPL".to_string(),
+            Quote {
+                price: Money::usd(160.0),
+                ..Default::default()
+            },
+        )]);
+        let r = report::build(&[&account], &quotes, &report::Options::default());
+
+        let m = run_source(
+            r#"
+            const TARGET = 240.0;
+            fn position(p) {
+                #{ upside: (global::TARGET - p.price) / p.price * 100.0, note: p.account }
+            }
+            fn portfolio(r) {
+                if r.gain_pct < -10.0 { alert("down", "down " + r.gain_pct + "%"); }
+                if r.gain_pct < -15.0 { alert("way down"); }
+                #{ positions: r.accounts[0].positions.len() }
+            }
+            "#,
+            &r,
+        )
+        .unwrap();
+
+        assert_eq!(m.positions[0].symbol, "AAPL");
+        assert_eq!(m.positions[0].values["upside"], Value::from(50.0));
+        assert_eq!(m.positions[0].values["note"], Value::from("Brokerage"));
+        assert_eq!(m.portfolio["positions"], Value::from(1));
+        assert_eq!(
+            m.alerts,
+            [
+                Alert {
+                    id: "down".to_string(),
+                    message: "down -20.0%".to_string()
+                },
+                Alert::named("way down")
+            ]
+        );
+
+        let err = run_source("fn position(p) { 1 }", &r).unwrap_err();
+        assert!(err.contains("should return a map"), "{}", err);
+    }
+
+    #[test]
+    fn conditions_combine_symbols_and_totals() {
+        let stock = |symbol: &str, cost: f64| Stock {
+            symbol: symbol.to_string(),
+            amount: 10.0,
+            cost_basis: Money::usd(cost),
+            ..Default::default()
+        };
+        let account = |name: &str, stocks| Account {
+            cash: Some(Money::usd(6000.0)),
+            ..Account::test(name, stocks)
+        };
+        let ira = account("IRA", vec![stock("VTI", 200.0), stock("BRK.B", 400.0)]);
+        let taxable = account("Taxable", vec![stock("VTI", 300.0)]);
+        let quote = |price: f64, prev: f64| Quote {
+            price: Money::usd(price),
+            change: Money::usd(price - prev),
+            ..Default::default()
+        };
+        let quotes = HashMap::from([
+            ("VTI".to_string(), quote(245.0, 250.0)),
+            ("BRK.B".to_string(), quote(410.0, 400.0)),
+        ]);
+        let r = report::build(&[&ira, &taxable], &quotes, &report::Options::default());
+
+        let alerts = BTreeMap::from([
+            (
+                "VTI drop".to_string(),
+                "VTI.net_pct < -1.5 && VTI.shares == 20.0".to_string(),
+            ),
+            ("idle cash".to_string(), "portfolio.cash > 5000".to_string()),
+            (
+                "BRK up".to_string(),
+                r#"symbols["BRK.B"].net_pct > 5"#.to_string(),
+            ),
+        ]);
+        assert_eq!(conditions(&alerts, &r).unwrap(), ["VTI drop", "idle cash"]);
+
+        let bad = BTreeMap::from([("x".to_string(), "VTI.price".to_string())]);
+        let err = conditions(&bad, &r).unwrap_err();
+        assert!(err.contains("should be true or false"), "{}", err);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::portfolio::Account;
+use crate::render::clr;
+use crate::snapshot::{Holding, Snapshot};
+use crate::time;
+
+// How one symbol, or everything together, moved since the snapshot. `flows`
+// is what was put in (or taken out, negative) by trading, and `growth` the
+// rest of the change, which is what the market did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub name: String,
+    pub then: f64,
+    pub now: f64,
+    pub change: f64,
+    pub flows: f64,
+    pub growth: f64,
+    pub growth_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Since {
+    // the day asked for, and the day of the snapshot standing in for it
+    pub date: NaiveDate,
+    pub from: NaiveDate,
+    pub positions: Vec<Change>,
+    pub total: Change,
+}
+
+impl Change {
+    fn new(name: &str, then: f64, now: f64, flows: f64) -> Self {
+        let growth = now - then - flows;
+        // against what was at work on average, counting money put in partway
+        // through as there for half the time
+        let base = then + flows / 2.0;
+        Change {
+            name: name.to_string(),
+            then,
+            now,
+            change: now - then,
+            flows,
+            growth,
+            growth_pct: if base > 0.0 {
+                growth * 100.0 / base
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+// Money traded into each symbol after `from`, from the trades recorded on
+// the positions
+fn traded(accounts: &[Account], from: NaiveDate, until: NaiveDate) -> BTreeMap<String, f64> {
+    let mut flows = BTreeMap::new();
+    for s in accounts.iter().flat_map(|a| a.stocks.iter()) {
+        for t in s.trades.iter().filter(|t| t.date > from && t.date <= until) {
+            *flows.entry(s.symbol.clone()).or_default() += t.num * s.multiplier * t.price.amount;
+        }
+    }
+    flows
+}
+
+// Compares `now`, a snapshot of the holdings at today's quotes, against the
+// last one recorded on or before `date`. Positions without trades recorded
+// count any change in shares as bought or sold at today's price.
+pub fn build(
+    snaps: &[Snapshot],
+    now: &Snapshot,
+    accounts: &[Account],
+    date: NaiveDate,
+) -> Result<Since, String> {
+    let then = snaps
+        .iter()
+        .rev()
+        .find(|s| time::date(&s.time) <= date)
+        .ok_or_else(|| match snaps.first() {
+            Some(s) => format!(
+                "no snapshot on or before {}, history starts {}",
+                date,
+                time::date(&s.time)
+            ),
+            None => "no history yet, each run of `stocks` records a snapshot".to_string(),
+        })?;
+    let from = time::date(&then.time);
+    let traded = traded(accounts, from, time::date(&now.time));
+
+    let symbols = then
+        .symbols
+        .keys()
+        .chain(now.symbols.keys())
+        .collect::<BTreeSet<_>>();
+    let positions = symbols
+        .into_iter()
+        .map(|symbol| {
+            let before = then.symbols.get(symbol);
+            let after = now.symbols.get(symbol);
+            let value = |h: Option<&Holding>| h.map_or(0.0, |h| h.amount * h.price);
+            let flows = match traded.get(symbol) {
+                Some(f) => *f,
+                None => {
+                    let held = |h: Option<&Holding>| h.map_or(0.0, |h| h.amount);
+                    let price = after.or(before).map_or(0.0, |h| h.price);
+                    (held(after) - held(before)) * price
+                }
+            };
+            Change::new(symbol, value(before), value(after), flows)
+        })
+        .filter(|c| c.then != 0.0 || c.now != 0.0)
+        .collect::<Vec<_>>();
+
+    let sum = |f: fn(&Change) -> f64| positions.iter().map(f).sum::<f64>();
+    let total = Change::new("Total", sum(|c| c.then), sum(|c| c.now), sum(|c| c.flows));
+
+    Ok(Since {
+        date,
+        from,
+        positions,
+        total,
+    })
+}
+
+fn row(out: &mut dyn Write, c: &Change, w: usize) -> io::Result<()> {
+    writeln!(
+        out,
+        "\t{:<w$}  {:>12.2}  {:>12.2}  {:>12.2}  {}{:>12.2}  {:>+7.2}%\x1b[0m",
+        c.name,
+        c.then,
+        c.now,
+        c.flows,
+        clr(c.growth),
+        c.growth,
+        c.growth_pct
+    )
+}
+
+pub fn render(s: &Since, out: &mut dyn Write) -> io::Result<()> {
+    if s.from == s.date {
+        writeln!(out, "since {}", s.date.format("%Y-%m-%d"))?;
+    } else {
+        writeln!(
+            out,
+            "since {}, the last snapshot before {}",
+            s.from.format("%Y-%m-%d"),
+            s.date.format("%Y-%m-%d")
+        )?;
+    }
+
+    let w = s
+        .positions
+        .iter()
+        .map(|c| c.name.chars().count())
+        .fold(8, usize::max);
+    writeln!(
+        out,
+        "\x1b[1m\t{:<w$}  {:>12}  {:>12}  {:>12}  {:>12}  {:>8}\x1b[0m",
+        "", "Then", "Now", "Put in", "Growth", ""
+    )?;
+    for c in s.positions.iter() {
+        row(out, c, w)?;
+    }
+    write!(out, "\x1b[1m")?;
+    row(out, &s.total, w)?;
+    write!(out, "\x1b[0m")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::portfolio::{Stock, Trade};
+    use chrono::{TimeZone, Utc};
+
+    fn snap(day: u32, symbols: &[(&str, f64, f64)]) -> Snapshot {
+        Snapshot {
+            time: Utc.with_ymd_and_hms(2024, 3, day, 15, 0, 0).unwrap(),
+            total: symbols.iter().map(|(_, n, p)| n * p).sum(),
+            accounts: BTreeMap::new(),
+            symbols: symbols
+                .iter()
+                .map(|(s, n, p)| {
+                    (
+                        s.to_string(),
+                        Holding {
+                            amount: *n,
+                            price: *p,
+                        },
+                    )
+                })
+                .collect(),
+            currency: None,
+        }
+    }
+
+    #[test]
+    fn buying_more_is_not_counted_as_growth() {
+        let history = [
+            snap(1, &[("AAPL", 10.0, 100.0), ("VTI", 5.0, 200.0)]),
+            snap(10, &[("AAPL", 10.0, 105.0), ("VTI", 5.0, 210.0)]),
+        ];
+        let now = snap(20, &[("AAPL", 15.0, 120.0), ("VTI", 8.0, 220.0)]);
+        let accounts = [Account::test(
+            "Brokerage",
+            vec![Stock {
+                symbol: "AAPL".to_string(),
+                amount: 15.0,
+                trades: vec![Trade {
+                    date: NaiveDate::from_ymd_opt(2024, 3, 12).unwrap(),
+                    num: 5.0,
+                    price: Money::usd(110.0),
+                    fees: Money::usd(0.0),
+                }],
+                ..Default::default()
+            }],
+        )];
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let s = build(&history, &now, &accounts, date).unwrap();
+        assert_eq!(s.from, NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+
+        // 1050 then, 1800 now, 550 of it bought
+        let aapl = &s.positions[0];
+        assert_eq!(
+            (aapl.change, aapl.flows, aapl.growth),
+            (750.0, 550.0, 200.0)
+        );
+        // no trades, so the 3 new shares count at today's 220, leaving 1050 to 1760
+        // up 50
+        let vti = &s.positions[1];
+        assert_eq!((vti.flows, vti.growth), (660.0, 50.0));
+        assert_eq!(s.total.growth, 250.0);
+
+        let early = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        assert!(build(&history, &now, &accounts, early).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::fx;
+use crate::portfolio::Account;
+use crate::profile;
+use crate::provider::Quote;
+use crate::time;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Holding {
+    // in units the price is quoted per, so 100 a contract for options and
+    // the contract size for futures, and the value is always amount times price
+    pub amount: f64,
+    pub price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub time: DateTime<Utc>,
+    pub total: f64,
+    pub accounts: BTreeMap<String, f64>,
+    pub symbols: BTreeMap<String, Holding>,
+    // what the values and prices are in; older snapshots have none, and each
+    // price there is in its listing's own currency
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+pub fn history_dir() -> String {
+    format!("{}/history", profile::data_dir())
+}
+
+fn snapshot_path() -> String {
+    format!("{}/snapshots.jsonl", history_dir())
+}
+
+// Values and prices are in the base currency, as the report's totals are, so
+// the history adds up to what `stocks` shows. Cash counts toward its account.
+pub fn take(
+    accounts: &[Account],
+    stock_info: &HashMap<String, Quote>,
+    base_currency: Option<&str>,
+) -> Snapshot {
+    let base = fx::base(base_currency, accounts.iter());
+    let mut snap = Snapshot {
+        time: Utc::now(),
+        total: 0.0,
+        accounts: BTreeMap::new(),
+        symbols: BTreeMap::new(),
+        currency: Some(base.clone()),
+    };
+
+    for account in accounts.iter().filter(|a| a.closed.is_none()) {
+        let mut value = account
+            .cash
+            .as_ref()
+            .map_or(0.0, |c| fx::convert(c, &base, stock_info).amount);
+
+        for stock in account.stocks.iter() {
+            let price = stock_info
+                .get(&stock.symbol)
+                .map(|q| fx::convert(&q.price, &base, stock_info).amount)
+                .unwrap_or_default();
+            value += price * stock.amount * stock.multiplier;
+
+            let holding = snap
+                .symbols
+                .entry(stock.symbol.clone())
+                .or_insert(Holding { amount: 0.0, price });
+            holding.amount += stock.amount * stock.multiplier;
+        }
+
+        // hidden accounts are kept for their own history but stay out of the total
+        if !account.hidden {
+            snap.total += value;
+        }
+        snap.accounts.insert(account.name.clone(), value);
+    }
+
+    snap
+}
+
+// A failed quote shows up as a zero price, which would look like a crash,
+// and a missing exchange rate as NaN
+pub fn complete(snap: &Snapshot) -> bool {
+    !snap
+        .symbols
+        .values()
+        .any(|h| h.price == 0.0 || h.price.is_nan())
+}
+
+pub fn record(snap: &Snapshot) -> std::io::Result<()> {
+    if !complete(snap) {
+        return Ok(());
+    }
+
+    fs::create_dir_all(history_dir())?;
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(snapshot_path())?;
+
+    writeln!(f, "{}", serde_json::to_string(snap)?)
+}
+
+pub fn load() -> Vec<Snapshot> {
+    let f = match File::open(snapshot_path()) {
+        Ok(f) => f,
+        Err(_) => return vec![],
+    };
+
+    BufReader::new(f)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect()
+}
+
+// Each symbol's last recorded price, the currency of the snapshot it's from,
+// and when it was first recorded at that price. A delisted symbol keeps being recorded at its last price, so the
+// time is when it last moved rather than the latest snapshot.
+pub fn last_prices() -> HashMap<String, (DateTime<Utc>, f64, Option<String>)> {
+    let mut last: HashMap<String, (DateTime<Utc>, f64, Option<String>)> = HashMap::new();
+    for snap in load() {
+        for (symbol, h) in snap.symbols {
+            if h.price <= 0.0 {
+                continue;
+            }
+            match last.get(&symbol) {
+                Some((_, p, _)) if *p == h.price => {}
+                _ => {
+                    last.insert(symbol, (snap.time, h.price, snap.currency.clone()));
+                }
+            }
+        }
+    }
+    last
+}
+
+// the last recorded portfolio value for each day on or after `from`
+pub fn daily_totals(from: NaiveDate) -> Vec<(NaiveDate, f64)> {
+    let mut days = BTreeMap::new();
+
+    for snap in load() {
+        let day = time::date(&snap.time);
+        if day >= from {
+            days.insert(day, snap.total);
+        }
+    }
+
+    days.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::portfolio::Stock;
+
+    #[test]
+    fn holdings_count_contracts_by_their_multiplier() {
+        let account = Account::test(
+            "Brokerage",
+            vec![Stock {
+                symbol: "AAPL231215C00190000".to_string(),
+                amount: 2.0,
+                multiplier: 100.0,
+                cost_basis: Money::usd(3.0),
+                ..Default::default()
+            }],
+        );
+        let quotes = HashMap::from([(
+            "AAPL231215C00190000".to_string(),
+            Quote {
+                price: Money::usd(4.5),
+                ..Default::default()
+            },
+        )]);
+
+        let snap = take(&[account], &quotes, None);
+        let h = &snap.symbols["AAPL231215C00190000"];
+        assert_eq!(h.amount * h.price, snap.total);
+        assert_eq!(snap.total, 900.0);
+    }
+
+    #[test]
+    fn values_are_in_the_base_currency_with_cash() {
+        let stock = |symbol: &str, currency: &str| Stock {
+            symbol: symbol.to_string(),
+            amount: 10.0,
+            cost_basis: Money::new(1.0, currency),
+            ..Default::default()
+        };
+        let account = Account {
+            cash: Some(Money::usd(500.0)),
+            ..Account::test(
+                "Brokerage",
+                vec![stock("AAPL", "USD"), stock("VOD.L", "GBP")],
+            )
+        };
+        let quote = |price: Money| Quote {
+            price,
+            ..Default::default()
+        };
+        let mut quotes = HashMap::from([
+            ("AAPL".to_string(), quote(Money::usd(190.0))),
+            ("VOD.L".to_string(), quote(Money::new(0.7, "GBP"))),
+        ]);
+
+        // without the rate the pound listing can't be counted
+        assert!(!complete(&take(
+            std::slice::from_ref(&account),
+            &quotes,
+            None
+        )));
+
+        quotes.insert(fx::pair("GBP", "USD"), quote(Money::usd(1.25)));
+        let snap = take(&[account], &quotes, None);
+        assert_eq!(snap.currency.as_deref(), Some("USD"));
+        assert!((snap.symbols["VOD.L"].price - 0.875).abs() < 1e-9);
+        assert!((snap.total - (1900.0 + 8.75 + 500.0)).abs() < 1e-9);
+        assert_eq!(snap.accounts["Brokerage"], snap.total);
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::{self, Write};
+
+use chrono::Duration;
+
+use crate::nasdaq::{get_history, Bar};
+use crate::render::clr;
+use crate::time;
+
+pub async fn run(symbol: &str, out: &mut dyn Write) {
+    let to = time::today();
+    let from = to - Duration::days(365);
+
+    let bars = match get_history(symbol, from, to).await {
+        Some(b) => b,
+        None => {
+            eprintln!("no historical data for {}", symbol);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = summary(symbol, &bars, out) {
+        eprintln!("failed to write output: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn summary(symbol: &str, bars: &[Bar], out: &mut dyn Write) -> io::Result<()> {
+    let closes = bars.iter().map(|b| b.close).collect::<Vec<_>>();
+    let last = bars.last().unwrap();
+
+    writeln!(
+        out,
+        "\x1b[1m{}\x1b[0m  ${:.2}  ({})",
+        symbol,
+        last.close,
+        last.date.format("%Y-%m-%d")
+    )?;
+
+    match rsi(&closes, 14) {
+        Some(r) => {
+            let label = if r >= 70.0 {
+                "\x1b[38;5;1moverbought"
+            } else if r <= 30.0 {
+                "\x1b[38;5;2moversold"
+            } else {
+                "neutral"
+            };
+            writeln!(out, "\tRSI(14)    {:>7.2}  {}\x1b[0m", r, label)?;
+        }
+        None => writeln!(out, "\tRSI(14)        n/a")?,
+    }
+
+    match macd(&closes, 12, 26, 9) {
+        Some((line, signal)) => {
+            let hist = line - signal;
+            let label = if hist >= 0.0 { "bullish" } else { "bearish" };
+            writeln!(
+                out,
+                "\tMACD       {:>7.2}  signal {:.2}  hist {}{:+.2} {}\x1b[0m",
+                line,
+                signal,
+                clr(hist),
+                hist,
+                label
+            )?;
+        }
+        None => writeln!(out, "\tMACD           n/a")?,
+    }
+
+    match bollinger(&closes, 20, 2.0) {
+        Some((lower, mid, upper)) => {
+            let pct_b = percent_b(last.close, lower, upper);
+            writeln!(
+                out,
+                "\tBollinger  {:>7.2}  %B  (lower ${:.2}  mid ${:.2}  upper ${:.2})",
+                pct_b, lower, mid, upper
+            )?;
+        }
+        None => writeln!(out, "\tBollinger      n/a")?,
+    }
+
+    Ok(())
+}
+
+pub fn ema(values: &[f64], period: usize) -> Vec<f64> {
+    if values.len() < period || period == 0 {
+        return vec![];
+    }
+
+    let k = 2.0 / (period as f64 + 1.0);
+    let seed = values[..period].iter().sum::<f64>() / period as f64;
+
+    let mut out = vec![seed];
+    for v in values[period..].iter() {
+        let prev = *out.last().unwrap();
+        out.push(v * k + prev * (1.0 - k));
+    }
+
+    out
+}
+
+// Wilder's smoothed RSI
+pub fn rsi(closes: &[f64], period: usize) -> Option<f64> {
+    if closes.len() <= period {
+        return None;
+    }
+
+    let changes = closes.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+
+    let mut gain = changes[..period].iter().filter(|c| **c > 0.0).sum::<f64>() / period as f64;
+    let mut loss = -changes[..period].iter().filter(|c| **c < 0.0).sum::<f64>() / period as f64;
+
+    for c in changes[period..].iter() {
+        gain = (gain * (period - 1) as f64 + c.max(0.0)) / period as f64;
+        loss = (loss * (period - 1) as f64 + (-c).max(0.0)) / period as f64;
+    }
+
+    // a flat window neither gained nor lost, which is neutral rather than
+    // as overbought as can be
+    match (gain == 0.0, loss == 0.0) {
+        (true, true) => return Some(50.0),
+        (false, true) => return Some(100.0),
+        _ => {}
+    }
+
+    Some(100.0 - 100.0 / (1.0 + gain / loss))
+}
+
+pub fn macd(closes: &[f64], fast: usize, slow: usize, signal: usize) -> Option<(f64, f64)> {
+    let fast = ema(closes, fast);
+    let slow = ema(closes, slow);
+
+    if slow.is_empty() {
+        return None;
+    }
+
+    // align the fast ema with the shorter slow ema
+    let offset = fast.len() - slow.len();
+    let line = slow
+        .iter()
+        .enumerate()
+        .map(|(i, s)| fast[i + offset] - s)
+        .collect::<Vec<_>>();
+
+    let signal = ema(&line, signal);
+
+    Some((*line.last()?, *signal.last()?))
+}
+
+pub fn bollinger(closes: &[f64], period: usize, width: f64) -> Option<(f64, f64, f64)> {
+    if closes.len() < period {
+        return None;
+    }
+
+    let window = &closes[closes.len() - period..];
+    let mean = window.iter().sum::<f64>() / period as f64;
+    let var = window.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / period as f64;
+    let sd = var.sqrt();
+
+    Some((mean - width * sd, mean, mean + width * sd))
+}
+
+// Where the close sits between the bands, 0 at the lower and 1 at the upper.
+// A flat window, like a halted symbol's, has no width and puts it in the middle.
+pub fn percent_b(close: f64, lower: f64, upper: f64) -> f64 {
+    if upper == lower {
+        return 0.5;
+    }
+    (close - lower) / (upper - lower)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_windows_are_neutral() {
+        let flat = [100.0; 30];
+        assert_eq!(rsi(&flat, 14), Some(50.0));
+        let rising = (0..30).map(|i| 100.0 + i as f64).collect::<Vec<_>>();
+        assert_eq!(rsi(&rising, 14), Some(100.0));
+
+        let (lower, _, upper) = bollinger(&flat, 20, 2.0).unwrap();
+        assert_eq!(percent_b(100.0, lower, upper), 0.5);
+        assert_eq!(percent_b(105.0, 95.0, 115.0), 0.5);
+        assert_eq!(percent_b(95.0, 95.0, 115.0), 0.0);
+    }
+}
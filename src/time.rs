@@ -0,0 +1,58 @@
+// Timestamps are kept in UTC everywhere and only converted for display, into
+// the `timezone` from config.toml or the system's local zone.
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+static ZONE: OnceLock<Option<Tz>> = OnceLock::new();
+
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|_| format!("unknown time zone {}, expected e.g. Europe/Berlin", name))
+}
+
+// set once at startup, later calls are ignored
+pub fn set_zone(zone: Option<Tz>) {
+    let _ = ZONE.set(zone);
+}
+
+fn zone() -> Option<Tz> {
+    ZONE.get().cloned().flatten()
+}
+
+pub fn format(t: &DateTime<Utc>, fmt: &str) -> String {
+    match zone() {
+        Some(tz) => t.with_timezone(&tz).format(fmt).to_string(),
+        None => t.with_timezone(&Local).format(fmt).to_string(),
+    }
+}
+
+// "2023-11-24 16:00 EST", or a bare offset when the zone has no abbreviation
+pub fn stamp(t: &DateTime<Utc>) -> String {
+    format(t, "%Y-%m-%d %H:%M %Z")
+}
+
+pub fn date(t: &DateTime<Utc>) -> NaiveDate {
+    match zone() {
+        Some(tz) => t.with_timezone(&tz).date_naive(),
+        None => t.with_timezone(&Local).date_naive(),
+    }
+}
+
+pub fn today() -> NaiveDate {
+    date(&Utc::now())
+}
+
+pub fn now_stamp(fmt: &str) -> String {
+    format(&Utc::now(), fmt)
+}
+
+// a wall clock time in an exchange's zone, e.g. New York for US markets
+pub fn from_exchange(local: NaiveDateTime, exchange: Tz) -> Option<DateTime<Utc>> {
+    exchange
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
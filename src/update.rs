@@ -0,0 +1,141 @@
+use std::env;
+use std::fs;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::http::{fetch_bytes, fetch_json};
+
+const RELEASES: &str = "https://api.github.com/repos/devinvs/stocks/releases/latest";
+
+// Release binaries are published as stocks-<arch>-<os>, next to a SHA256SUMS
+// file in the usual `sha256sum` format.
+pub fn asset_name() -> String {
+    let ext = if cfg!(windows) { ".exe" } else { "" };
+    format!("stocks-{}-{}{}", env::consts::ARCH, env::consts::OS, ext)
+}
+
+// "v1.2.0" and "1.2" both parse, anything after a '-' is ignored
+fn version(s: &str) -> Vec<u64> {
+    s.trim_start_matches('v')
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|p| p.parse().unwrap_or(0))
+        .collect()
+}
+
+pub fn is_newer(tag: &str, current: &str) -> bool {
+    version(tag) > version(current)
+}
+
+pub fn checksum_for(sums: &str, name: &str) -> Option<String> {
+    sums.lines().find_map(|l| {
+        let mut parts = l.split_whitespace();
+        let sum = parts.next()?;
+        let file = parts.next()?.trim_start_matches('*');
+        (file == name).then(|| sum.to_lowercase())
+    })
+}
+
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn asset_url(release: &Value, name: &str) -> Option<String> {
+    release["assets"]
+        .as_array()?
+        .iter()
+        .find(|a| a["name"].as_str() == Some(name))?["browser_download_url"]
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+pub async fn run() -> Result<(), String> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = fetch_json(RELEASES).await?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or("no tag in the latest release")?;
+
+    if !is_newer(tag, current) {
+        println!("stocks {} is up to date", current);
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let url = asset_url(&release, &name)
+        .ok_or_else(|| format!("release {} has no build for {}", tag, name))?;
+    let sums_url = asset_url(&release, "SHA256SUMS")
+        .ok_or_else(|| format!("release {} has no SHA256SUMS", tag))?;
+
+    let sums = String::from_utf8(fetch_bytes(&sums_url).await?)
+        .map_err(|_| "SHA256SUMS is not text".to_string())?;
+    let expected =
+        checksum_for(&sums, &name).ok_or_else(|| format!("SHA256SUMS does not list {}", name))?;
+
+    println!("downloading {} {}", name, tag);
+    let binary = fetch_bytes(&url).await?;
+    if sha256(&binary) != expected {
+        return Err(format!("checksum mismatch for {}, not installing", name));
+    }
+
+    replace_exe(&binary)?;
+    println!("updated stocks {} -> {}", current, tag);
+    Ok(())
+}
+
+// Written beside the running binary and renamed over it, so a failed
+// download never leaves a half written executable behind.
+fn replace_exe(binary: &[u8]) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let tmp = exe.with_extension("new");
+
+    fs::write(&tmp, binary).map_err(|e| format!("failed to write {}: {}", tmp.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+    }
+
+    fs::rename(&tmp, &exe).map_err(|e| format!("failed to replace {}: {}", exe.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert!(is_newer("v0.2.0", "0.1.0"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("v0.1.0-rc1", "0.1.0"));
+    }
+
+    #[test]
+    fn finds_checksum_line() {
+        let sums = "\
+aaaa  stocks-x86_64-linux
+BBBB *stocks-aarch64-macos
+";
+        assert_eq!(
+            checksum_for(sums, "stocks-aarch64-macos").as_deref(),
+            Some("bbbb")
+        );
+        assert_eq!(checksum_for(sums, "stocks-x86_64-windows.exe"), None);
+    }
+
+    #[test]
+    fn hashes_hex() {
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
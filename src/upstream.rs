@@ -0,0 +1,125 @@
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::http::fetch_json;
+use crate::update::is_newer;
+
+// A static file kept in the repository, so the check is a plain GET of a
+// public file and sends nothing about the user beyond the request itself.
+const STATUS: &str = "https://raw.githubusercontent.com/devinvs/stocks/master/status.json";
+
+#[derive(Debug, Deserialize)]
+pub struct Status {
+    #[serde(default)]
+    pub notices: Vec<Notice>,
+}
+
+// A provider endpoint known to be broken or changed, e.g.
+// {"provider": "nasdaq", "endpoint": "historical", "problem": "...",
+//  "workaround": "...", "fixed_in": "0.2.0"}
+#[derive(Debug, Deserialize)]
+pub struct Notice {
+    pub provider: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub problem: String,
+    #[serde(default)]
+    pub workaround: Option<String>,
+    // first release that copes with it; everything older is affected
+    #[serde(default)]
+    pub fixed_in: Option<String>,
+}
+
+impl Notice {
+    pub fn affects(&self, providers: &[String], version: &str) -> bool {
+        providers.contains(&self.provider)
+            && self
+                .fixed_in
+                .as_deref()
+                .is_none_or(|f| is_newer(f, version))
+    }
+}
+
+fn providers(conf: &Config) -> Vec<String> {
+    if conf.providers.is_empty() {
+        vec!["nasdaq".to_string()]
+    } else {
+        conf.providers.clone()
+    }
+}
+
+pub async fn run(conf: &Config) -> Result<(), String> {
+    let version = env!("CARGO_PKG_VERSION");
+    let v = fetch_json(STATUS)
+        .await
+        .map_err(|e| format!("could not fetch {}: {}", STATUS, e))?;
+    let status: Status =
+        serde_json::from_value(v).map_err(|e| format!("could not parse {}: {}", STATUS, e))?;
+
+    let providers = providers(conf);
+    let notices = status
+        .notices
+        .iter()
+        .filter(|n| n.affects(&providers, version))
+        .collect::<Vec<_>>();
+
+    if notices.is_empty() {
+        println!(
+            "no known problems with {} for stocks {}",
+            providers.join(", "),
+            version
+        );
+        return Ok(());
+    }
+
+    for n in notices {
+        match &n.endpoint {
+            Some(e) => println!("\x1b[1m{} ({})\x1b[0m: {}", n.provider, e, n.problem),
+            None => println!("\x1b[1m{}\x1b[0m: {}", n.provider, n.problem),
+        }
+        if let Some(w) = &n.workaround {
+            println!("\tworkaround: {}", w);
+        }
+        if let Some(f) = &n.fixed_in {
+            println!("\tfixed in {}, try `stocks self-update`", f);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_match_provider_and_version() {
+        let status: Status = serde_json::from_str(
+            r#"{"notices": [
+                {"provider": "nasdaq", "problem": "info moved", "fixed_in": "0.2.0"},
+                {"provider": "nasdaq", "problem": "history is empty"},
+                {"provider": "yahoo", "problem": "rate limited"}
+            ]}"#,
+        )
+        .unwrap();
+        let used = vec!["nasdaq".to_string()];
+
+        let hits = |v| {
+            status
+                .notices
+                .iter()
+                .filter(|n| n.affects(&used, v))
+                .map(|n| n.problem.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(hits("0.1.0"), ["info moved", "history is empty"]);
+        assert_eq!(hits("0.2.0"), ["history is empty"]);
+    }
+
+    #[test]
+    fn repo_status_file_parses() {
+        let status: Status = serde_json::from_str(include_str!("../status.json")).unwrap();
+        assert!(status.notices.iter().all(|n| !n.problem.is_empty()));
+    }
+}
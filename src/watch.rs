@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{stdout, Write};
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+
+use crate::config::{self, config_path, Config};
+use crate::portfolio::{holdings_path, load_accounts, shown};
+use crate::provider::{quote_accounts, Quote};
+use crate::render::{clr, Emoji, OutputRenderer, Table};
+use crate::report::Report;
+use crate::{alerts, crypto, fx, glyph, journal, report, script, snapshot, time};
+
+// Switches to the alternate screen and hides the cursor for as long as it
+// lives, so the terminal is restored on every exit path including panics.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Self {
+        print!("\x1b[?1049h\x1b[?25l");
+        stdout().flush().unwrap();
+        Screen
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout().flush();
+    }
+}
+
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut term = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Tracks the holdings and config files between refreshes so edits are picked
+// up without restarting. A file that fails to parse keeps the last good state.
+struct Reloader {
+    stamps: (Option<SystemTime>, Option<SystemTime>),
+    error: Option<String>,
+}
+
+impl Reloader {
+    fn new() -> Self {
+        Reloader {
+            stamps: (modified(&holdings_path()), modified(&config_path())),
+            error: None,
+        }
+    }
+
+    fn changed(&mut self) -> bool {
+        let stamps = (modified(&holdings_path()), modified(&config_path()));
+        if stamps == self.stamps {
+            return false;
+        }
+
+        self.stamps = stamps;
+        true
+    }
+}
+
+// The whole portfolio on one line: its value, today's change and the gain
+// over what was paid
+fn summary(report: &Report) -> String {
+    let t = &report.totals;
+    format!(
+        "\x1b[1mportfolio {}\x1b[0m  today {}{} ({:.2}%)\x1b[0m  gain {}{} ({:.2}%)\x1b[0m",
+        t.total,
+        clr(t.net.amount),
+        t.net,
+        t.net_pct,
+        clr(t.gain.amount),
+        t.gain,
+        t.gain_pct
+    )
+}
+
+// Symbols whose price moved since the previous refresh, and by how much
+fn moves(prev: &HashMap<String, Quote>, now: &HashMap<String, Quote>) -> Vec<(String, f64)> {
+    let mut moved = now
+        .iter()
+        .filter(|(symbol, _)| !fx::is_pair(symbol))
+        .filter_map(|(symbol, q)| {
+            let before = prev.get(symbol)?;
+            let delta = q.price.amount - before.price.amount;
+            (delta.abs() > 1e-9).then(|| (symbol.clone(), delta))
+        })
+        .collect::<Vec<_>>();
+    moved.sort_by(|a, b| a.0.cmp(&b.0));
+    moved
+}
+
+fn render_moves(moved: &[(String, f64)]) -> String {
+    if moved.is_empty() {
+        return "\x1b[38;5;8mno prices changed since the last refresh\x1b[0m".to_string();
+    }
+
+    let moved = moved
+        .iter()
+        .map(|(symbol, delta)| {
+            let arrow = if *delta < 0.0 {
+                glyph::pick("▼", "v")
+            } else {
+                glyph::pick("▲", "^")
+            };
+            format!(
+                "{}{} {}{:.2}\x1b[0m",
+                clr(*delta),
+                symbol,
+                arrow,
+                delta.abs()
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("since the last refresh: {}", moved.join("  "))
+}
+
+pub async fn run(interval: Option<u64>, all: bool, tickers: bool, legs: bool, emoji: bool) {
+    let (mut accounts, mut conf) = match (load_accounts(&holdings_path(), all), config::load()) {
+        (Ok(a), Ok(c)) => (a, c),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // each refresh is there to get new prices, not ones cached by a recent run
+    conf.quote_ttl = Some(0);
+    let mut reloader = Reloader::new();
+    let mut last = None;
+
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+
+    let screen = Screen::enter();
+
+    loop {
+        if reloader.changed() {
+            reloader.error = match (load_accounts(&holdings_path(), all), config::load()) {
+                (Ok(a), Ok(c)) => {
+                    accounts = a;
+                    conf = Config {
+                        quote_ttl: Some(0),
+                        ..c
+                    };
+                    None
+                }
+                (Err(e), _) | (_, Err(e)) => Some(e),
+            };
+        }
+
+        let every = interval.or(conf.interval).unwrap_or(60);
+        crypto::resolve_wallets(&mut accounts).await;
+
+        let (stock_info, failed) = tokio::select! {
+            res = quote_accounts(&accounts, &conf) => match res {
+                Ok(q) => q,
+                Err(e) => {
+                    drop(screen);
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+            _ = &mut stop => break,
+        };
+
+        print!("\x1b[H\x1b[2J");
+        let opts = report::Options::new(&conf, tickers);
+        let report = report::build(&shown(&accounts, all), &stock_info, &opts);
+        Table {
+            legs,
+            highlight: conf.highlight.clone(),
+            emoji: emoji.then(|| Emoji::new(&conf)),
+        }
+        .render(&report, &mut stdout())
+        .unwrap();
+        println!();
+        println!("{}", summary(&report));
+        if let Some(prev) = &last {
+            println!("{}", render_moves(&moves(prev, &stock_info)));
+        }
+        for (symbol, e) in failed.iter() {
+            println!("\x1b[38;5;1mfailed to fetch {}: {}\x1b[0m", symbol, e);
+        }
+        if conf.script.is_some() || !conf.alerts.is_empty() {
+            let res = script::alerts(&conf, &report).and_then(|raised| {
+                let fresh = alerts::fresh(&raised, &conf, Utc::now())?;
+                Ok((raised, fresh))
+            });
+            match res {
+                Ok((raised, fresh)) => {
+                    for a in raised.iter() {
+                        println!("\x1b[38;5;1malert\x1b[0m  {}", a.message);
+                    }
+                    // the bell only for those not told about before
+                    if !fresh.is_empty() {
+                        print!("\x07");
+                    }
+                }
+                Err(e) => println!("\x1b[38;5;1m{}\x1b[0m", e),
+            }
+        }
+        if let Some(e) = &reloader.error {
+            println!("\x1b[38;5;1mnot reloaded: {}\x1b[0m", e);
+        }
+        println!(
+            "\x1b[38;5;8mupdated {}, refreshing every {}s, ctrl-c to quit\x1b[0m",
+            time::now_stamp("%H:%M:%S %Z"),
+            every
+        );
+        stdout().flush().unwrap();
+
+        last = Some(stock_info);
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(every)) => {}
+            _ = &mut stop => break,
+        }
+    }
+
+    drop(screen);
+
+    if let Some(stock_info) = last {
+        if let Err(e) = snapshot::record(&snapshot::take(
+            &accounts,
+            &stock_info,
+            conf.base_currency.as_deref(),
+        )) {
+            eprintln!("failed to record snapshot: {}", e);
+        }
+        if let Err(e) = journal::record(&accounts, &stock_info, &conf) {
+            eprintln!("failed to write the journal: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn quotes(prices: &[(&str, f64)]) -> HashMap<String, Quote> {
+        prices
+            .iter()
+            .map(|(s, p)| {
+                let q = Quote {
+                    price: Money::usd(*p),
+                    ..Default::default()
+                };
+                (s.to_string(), q)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_changed_prices_are_flagged() {
+        let prev = quotes(&[("AAPL", 189.97), ("MSFT", 370.0), ("VTI", 230.0)]);
+        let now = quotes(&[
+            ("AAPL", 190.1),
+            ("MSFT", 370.0),
+            ("VTI", 229.5),
+            ("NEW", 1.0),
+        ]);
+
+        let moved = moves(&prev, &now);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(moved[0].0, "AAPL");
+        assert!((moved[0].1 - 0.13).abs() < 1e-9);
+        assert_eq!(moved[1].0, "VTI");
+        assert_eq!(moved[1].1, -0.5);
+    }
+}
@@ -0,0 +1,331 @@
+use chrono::{DateTime, Duration, NaiveDate};
+use serde_json::Value;
+
+use crate::debug;
+use crate::exchange;
+use crate::http::{self, fetch_json};
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::nasdaq::Bar;
+use crate::options::Contract;
+use crate::provider::{Quote, QuoteError, QuoteResult, Status};
+
+const API: &str = "https://query1.finance.yahoo.com";
+
+// An api url for one symbol, which is escaped as the last path segment like
+// the parameters are in the query, so a symbol or search with a space, `&`
+// or `/` in it can't change what is asked for
+fn url(path: &str, symbol: &str, params: &[(&str, &str)]) -> String {
+    let mut url = reqwest::Url::parse_with_params(&format!("{}/{}", API, path), params)
+        .expect("yahoo urls are valid");
+    url.path_segments_mut()
+        .expect("yahoo urls have a path")
+        .push(symbol);
+    url.to_string()
+}
+
+pub async fn get_yahoo_value(symbol: &str) -> QuoteResult {
+    let url = url(
+        "v8/finance/chart",
+        symbol,
+        &[("range", "1d"), ("interval", "1d")],
+    );
+
+    let v = match fetch_json(&url).await {
+        Ok(v) => v,
+        Err(e) if http::not_found(&e) => return Err(QuoteError::NotFound),
+        Err(e) => return Err(QuoteError::Network(e)),
+    };
+
+    if !v["chart"]["error"].is_null() {
+        return Err(QuoteError::NotFound);
+    }
+
+    let meta = &v["chart"]["result"][0]["meta"];
+    if meta.is_null() {
+        return Err(QuoteError::Schema(format!(
+            "yahoo response for {} has no chart meta",
+            symbol
+        )));
+    }
+
+    let price = meta["regularMarketPrice"]
+        .as_f64()
+        .ok_or_else(|| QuoteError::Schema(format!("yahoo response for {} has no price", symbol)))?;
+
+    let prev = match (
+        meta["chartPreviousClose"].as_f64(),
+        meta["previousClose"].as_f64(),
+    ) {
+        (Some(p), _) => {
+            debug!("yahoo {}: matched chartPreviousClose schema", symbol);
+            p
+        }
+        (None, Some(p)) => {
+            debug!("yahoo {}: matched previousClose schema", symbol);
+            p
+        }
+        (None, None) => {
+            return Err(QuoteError::Schema(format!(
+                "yahoo response for {} has no previous close",
+                symbol
+            )))
+        }
+    };
+
+    // London quotes in pence, which would otherwise read as pounds
+    let given = meta["currency"]
+        .as_str()
+        .or(exchange::currency(symbol))
+        .unwrap_or(DEFAULT_CURRENCY);
+    let (currency, minor) = exchange::major_unit(given);
+    let (price, prev) = (price / minor, prev / minor);
+    let currency = currency.as_str();
+
+    let time = meta["regularMarketTime"]
+        .as_i64()
+        .and_then(|t| DateTime::from_timestamp(t, 0));
+
+    let open = v["chart"]["result"][0]["indicators"]["quote"][0]["open"]
+        .as_array()
+        .and_then(|o| o.last())
+        .and_then(|o| o.as_f64())
+        .map(|o| Money::new(o / minor, currency));
+
+    Ok(Quote {
+        price: Money::new(price, currency),
+        change: Money::new(price - prev, currency),
+        open,
+        time,
+        status: Status::Trading,
+    })
+}
+
+// Implied volatility for one contract, from the chain for its expiry
+pub async fn get_implied_vol(symbol: &str, contract: &Contract) -> Result<f64, String> {
+    let expiry = contract.expiry.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let url = url(
+        "v7/finance/options",
+        &contract.underlying,
+        &[("date", &expiry.timestamp().to_string())],
+    );
+
+    let v = fetch_json(&url).await?;
+    parse_implied_vol(&v, symbol).ok_or_else(|| format!("no implied volatility for {}", symbol))
+}
+
+pub fn parse_implied_vol(v: &Value, symbol: &str) -> Option<f64> {
+    let chain = &v["optionChain"]["result"][0]["options"][0];
+
+    ["calls", "puts"]
+        .iter()
+        .filter_map(|side| chain[side].as_array())
+        .flatten()
+        .find(|c| c["contractSymbol"].as_str() == Some(symbol))?["impliedVolatility"]
+        .as_f64()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub symbol: String,
+    pub name: String,
+}
+
+// Tickers whose symbol or name looks like `query`, best first
+pub async fn search(query: &str) -> Result<Vec<Match>, String> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/v1/finance/search", API),
+        &[("q", query), ("quotesCount", "5"), ("newsCount", "0")],
+    )
+    .expect("yahoo urls are valid");
+
+    let v = fetch_json(url.as_str()).await?;
+    Ok(parse_search(&v))
+}
+
+pub fn parse_search(v: &Value) -> Vec<Match> {
+    v["quotes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|q| {
+            let symbol = q["symbol"].as_str()?;
+            let name = q["shortname"].as_str().or(q["longname"].as_str());
+            Some(Match {
+                symbol: symbol.to_string(),
+                name: name.unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+// What kind of holding this is: a fund's category, like "Large Blend" or
+// "Intermediate Core Bond", or a stock's sector
+pub async fn get_category(symbol: &str) -> Result<Option<String>, String> {
+    let url = url(
+        "v10/finance/quoteSummary",
+        symbol,
+        &[("modules", "quoteType,fundProfile,assetProfile")],
+    );
+
+    let v = fetch_json(&url).await?;
+    Ok(parse_category(&v))
+}
+
+pub fn parse_category(v: &Value) -> Option<String> {
+    let result = &v["quoteSummary"]["result"][0];
+    let category = match result["quoteType"]["quoteType"].as_str() {
+        Some("EQUITY") => result["assetProfile"]["sector"].as_str(),
+        Some("CRYPTOCURRENCY") => Some("Digital Assets"),
+        _ => result["fundProfile"]["categoryName"].as_str(),
+    };
+    category.filter(|c| !c.is_empty()).map(String::from)
+}
+
+// Dates coming up for a stock, where Yahoo knows them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dates {
+    pub earnings: Option<NaiveDate>,
+    pub ex_dividend: Option<NaiveDate>,
+    pub dividend: Option<NaiveDate>,
+}
+
+pub async fn get_dates(symbol: &str) -> Result<Dates, String> {
+    let url = url(
+        "v10/finance/quoteSummary",
+        symbol,
+        &[("modules", "calendarEvents")],
+    );
+
+    let v = fetch_json(&url).await?;
+    Ok(parse_dates(&v))
+}
+
+pub fn parse_dates(v: &Value) -> Dates {
+    let events = &v["quoteSummary"]["result"][0]["calendarEvents"];
+    let date = |d: &Value| {
+        d["raw"]
+            .as_i64()
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map(|t| t.date_naive())
+    };
+    Dates {
+        // sometimes a window of two days, the first is soonest
+        earnings: events["earnings"]["earningsDate"]
+            .as_array()
+            .and_then(|d| d.first())
+            .and_then(date),
+        ex_dividend: date(&events["exDividendDate"]),
+        dividend: date(&events["dividendDate"]),
+    }
+}
+
+// Daily bars with every price scaled so splits and dividends don't show up as
+// jumps, from yahoo's adjusted closes. Nasdaq only has the closes traded at.
+pub async fn get_adjusted_history(
+    symbol: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Option<Vec<Bar>> {
+    let start = from.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+    // the end is exclusive, so the day after to take in `to`
+    let end = (to + Duration::days(1))
+        .and_hms_opt(0, 0, 0)?
+        .and_utc()
+        .timestamp();
+    let url = url(
+        "v8/finance/chart",
+        symbol,
+        &[
+            ("period1", &start.to_string()),
+            ("period2", &end.to_string()),
+            ("interval", "1d"),
+            ("events", "div,split"),
+        ],
+    );
+
+    parse_adjusted_history(&fetch_json(&url).await.ok()?)
+}
+
+pub fn parse_adjusted_history(v: &Value) -> Option<Vec<Bar>> {
+    let result = &v["chart"]["result"][0];
+    let times = result["timestamp"].as_array()?;
+    // bars are stamped at the open, which is the day before in UTC in Sydney
+    let offset = result["meta"]["gmtoffset"].as_i64().unwrap_or_default();
+    let quote = &result["indicators"]["quote"][0];
+    let adjusted = &result["indicators"]["adjclose"][0]["adjclose"];
+    // London quotes in pence like the live price
+    let (_, minor) = exchange::major_unit(result["meta"]["currency"].as_str().unwrap_or_default());
+
+    let bars = times
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            let field = |k: &str| quote[k][i].as_f64();
+            let close = field("close")?;
+            let adj = adjusted[i].as_f64()?;
+            if close == 0.0 {
+                return None;
+            }
+            let factor = adj / close / minor;
+            Some(Bar {
+                date: DateTime::from_timestamp(t.as_i64()? + offset, 0)?.date_naive(),
+                open: field("open").unwrap_or(close) * factor,
+                high: field("high").unwrap_or(close) * factor,
+                low: field("low").unwrap_or(close) * factor,
+                close: adj / minor,
+                volume: field("volume").unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (!bars.is_empty()).then_some(bars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn adjusted_bars_scale_by_the_adjusted_close() {
+        // 2024-03-01 and 03-04 at 14:30 UTC, the New York open
+        let v = json!({"chart": {"result": [{
+            "meta": {"currency": "USD", "gmtoffset": -18000},
+            "timestamp": [1709303400, 1709562600],
+            "indicators": {
+                "quote": [{
+                    "open": [200.0, 101.0],
+                    "high": [210.0, 102.0],
+                    "low": [190.0, 99.0],
+                    "close": [204.0, 100.0],
+                    "volume": [1000, 2000]
+                }],
+                // split two for one in between
+                "adjclose": [{"adjclose": [102.0, 100.0]}]
+            }
+        }]}});
+        let bars = parse_adjusted_history(&v).unwrap();
+        assert_eq!(bars[0].date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(bars[1].date, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+        assert_eq!((bars[0].open, bars[0].close), (100.0, 102.0));
+        assert_eq!(bars[1].close, 100.0);
+
+        assert!(parse_adjusted_history(&json!({"chart": {"result": null}})).is_none());
+    }
+
+    #[test]
+    fn symbols_are_escaped_in_urls() {
+        assert_eq!(
+            url(
+                "v8/finance/chart",
+                "AAPL",
+                &[("range", "1d"), ("interval", "1d")]
+            ),
+            "https://query1.finance.yahoo.com/v8/finance/chart/AAPL?range=1d&interval=1d"
+        );
+        assert_eq!(
+            url("v10/finance/quoteSummary", "BRK/B&x", &[("modules", "a,b")]),
+            "https://query1.finance.yahoo.com/v10/finance/quoteSummary/BRK%2FB&x?modules=a%2Cb"
+        );
+    }
+}